@@ -0,0 +1,149 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::ServerEvent};
+use serde::{Serialize, Deserialize};
+
+use crate::{Player, PlayerInput, PlayerMovement, MoveDirection, SERVER_ID, has_local_player};
+
+/// Default for `--afk-timeout`
+pub const DEFAULT_AFK_TIMEOUT: f32 = 10.0;
+/// How often clients send `PlayerInput::None` so an idle but responsive client isn't mistaken for a frozen one
+const KEEPALIVE_INTERVAL: f32 = 1.0;
+
+/// Stops players whose client has stopped sending inputs (frozen or suspended), separate from netcode's disconnect timeout
+pub struct AfkPlugin;
+
+impl Plugin for AfkPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InputActivity>()
+            .replicate::<Afk>()
+            .add_systems(Update, afk_tracking_system.run_if(resource_exists::<AfkTimeout>()))
+            .add_systems(Update, keepalive_system.run_if(has_local_player));
+    }
+}
+
+/// Replicated marker for players whose client hasn't sent any input within the `AfkTimeout`
+#[derive(Component, Serialize, Deserialize)]
+pub struct Afk;
+
+/// Seconds without any input (including keepalives) before a player is marked `Afk`
+#[derive(Resource)]
+pub struct AfkTimeout(pub f32);
+
+/// Server side, the elapsed time each player's client last sent an input
+#[derive(Resource, Default)]
+pub struct InputActivity(HashMap<u64, f32>);
+
+fn afk_tracking_system(
+    mut commands: Commands,
+    mut activity: ResMut<InputActivity>,
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    mut movement_reader: EventReader<FromClient<PlayerMovement>>,
+    mut server_events: EventReader<ServerEvent>,
+    mut players: Query<(Entity, &Player, &mut MoveDirection, Option<&Afk>)>,
+    timeout: Res<AfkTimeout>,
+    time: Res<Time>,
+) {
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            activity.0.remove(&client_id.raw());
+        }
+    }
+
+    let now = time.elapsed_seconds();
+    for FromClient { client_id, .. } in input_reader.read()
+    {
+        activity.0.insert(client_id.raw(), now);
+    }
+    for FromClient { client_id, .. } in movement_reader.read()
+    {
+        activity.0.insert(client_id.raw(), now);
+    }
+
+    for (player_entity, player, mut direction, afk) in &mut players
+    {
+        // The host's own player is played in the server's window, it can't freeze without the server freezing too
+        if player.0 == SERVER_ID.raw()
+        {
+            continue;
+        }
+
+        // Players that have never sent anything start counting from when they were first seen
+        let last_input = *activity.0.entry(player.0).or_insert(now);
+        let inactive = now - last_input > timeout.0;
+
+        if inactive && afk.is_none()
+        {
+            info!("Server: Player '{}' stopped sending inputs, marking AFK", player.0);
+            direction.0 = Vec2::ZERO;
+            commands.entity(player_entity).insert(Afk);
+        }
+        else if !inactive && afk.is_some()
+        {
+            info!("Server: Player '{}' is sending inputs again", player.0);
+            commands.entity(player_entity).remove::<Afk>();
+        }
+    }
+}
+
+fn keepalive_system(
+    mut input_writer: EventWriter<PlayerInput>,
+    mut since_keepalive: Local<f32>,
+    time: Res<Time>,
+) {
+    *since_keepalive += time.delta_seconds();
+    if *since_keepalive < KEEPALIVE_INTERVAL
+    {
+        return;
+    }
+
+    *since_keepalive = 0.0;
+    input_writer.send(PlayerInput::None);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use clap::Parser;
+
+    use crate::{Cli, bench::{memory_session, step_session, tests::connected_session}};
+
+    fn is_afk(server_app: &mut App, id: u64) -> bool {
+        server_app.world.query::<(&Player, Option<&Afk>)>().iter(&server_app.world)
+            .any(|(player, afk)| player.0 == id && afk.is_some())
+    }
+
+    #[test]
+    fn player_whose_client_goes_quiet_is_marked_afk() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        server_app.world.insert_resource(AfkTimeout(2.0));
+
+        // Client 1 is frozen, so not even its keepalives arrive
+        for _ in 0..180
+        {
+            step_session(&mut server_app, &mut client_apps[1..]);
+        }
+        assert!(is_afk(&mut server_app, 1));
+        assert!(!is_afk(&mut server_app, 2), "a client sending keepalives was marked AFK");
+
+        // Its next keepalive clears it
+        for _ in 0..90
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        assert!(!is_afk(&mut server_app, 1));
+    }
+
+    #[test]
+    fn afk_timeout_must_be_a_finite_duration() {
+        for timeout in ["--afk-timeout=-1", "--afk-timeout=NaN", "--afk-timeout=inf"]
+        {
+            let cli = Cli::parse_from(["replicon_test_1", "bench", timeout]);
+            assert!(memory_session(&cli, 0, 0).is_err(), "{timeout} was accepted");
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{LocalPlayerId, MoveDirection, Player, is_headless, player_state::PlayerState};
+
+/// Seconds a player stays in an animation state before the server lets it change again
+const MIN_HOLD: f32 = 0.1;
+/// Pulses per second of a walking player's sprite
+const WALK_PULSE_RATE: f32 = 4.0;
+const WALK_PULSE_SCALE: f32 = 0.1;
+const DEAD_SCALE: f32 = 0.7;
+
+/// What each player is doing, decided by the server and sent as an enum in `congestion::Cosmetics`, shown client side
+/// as a scale pulse
+pub struct AnimStatePlugin;
+
+impl Plugin for AnimStatePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, update_anim_state_system.run_if(has_authority()))
+            .add_systems(Update, show_anim_state_system.run_if(not(is_headless)));
+    }
+}
+
+/// There's no `Dashing` yet, it comes with the dash
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AnimState
+{
+    #[default]
+    Idle,
+    Walking,
+    Dead,
+}
+
+impl AnimState
+{
+    fn of(state: &PlayerState, direction: Option<&MoveDirection>) -> Self {
+        if !state.is_alive()
+        {
+            return AnimState::Dead;
+        }
+
+        match direction
+        {
+            Some(direction) if direction.0 != Vec2::ZERO => AnimState::Walking,
+            _ => AnimState::Idle,
+        }
+    }
+}
+
+/// Server side, elapsed seconds when the player's `AnimState` last changed
+#[derive(Component, Default)]
+pub struct AnimHold(f32);
+
+/// Server side, follows each player's movement and health, holding every state for at least `MIN_HOLD`
+///
+/// Tapping a key would otherwise flip the state, and resend it, every few ticks.
+fn update_anim_state_system(
+    mut players: Query<(&mut AnimState, &mut AnimHold, &PlayerState, Option<&MoveDirection>)>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (mut anim_state, mut hold, state, direction) in &mut players
+    {
+        let wanted = AnimState::of(state, direction);
+        if *anim_state == wanted || now - hold.0 < MIN_HOLD
+        {
+            continue;
+        }
+
+        *anim_state = wanted;
+        hold.0 = now;
+    }
+}
+
+/// Scales each player's sprite for its state, the local player walks as soon as a key is held instead of a round trip later
+fn show_anim_state_system(
+    mut players: Query<(&Player, &AnimState, &PlayerState, Option<&MoveDirection>, &mut Transform)>,
+    local_player: Option<Res<LocalPlayerId>>,
+    time: Res<Time>,
+) {
+    let local_id = local_player.map(|local_player| local_player.0);
+    for (player, anim_state, state, direction, mut transform) in &mut players
+    {
+        let shown = if Some(player.0) == local_id { AnimState::of(state, direction) } else { *anim_state };
+        let scale = match shown
+        {
+            AnimState::Idle => 1.0,
+            AnimState::Walking => 1.0 + WALK_PULSE_SCALE * (time.elapsed_seconds() * WALK_PULSE_RATE * std::f32::consts::TAU).sin(),
+            AnimState::Dead => DEAD_SCALE,
+        };
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::is_headless;
+
+/// Skin textures, indexed by `Appearance`, relative to the `assets/` folder
+pub const SKIN_FILES: [&str; 4] = [
+    "skins/skin_0.png",
+    "skins/skin_1.png",
+    "skins/skin_2.png",
+    "skins/skin_3.png",
+];
+pub const PROJECTILE_TEXTURE: &str = "skins/projectile.png";
+
+/// Skin selection, the server assigns these and clients only look them up
+///
+/// Skins reach clients through `congestion::Cosmetics` rather than replication, so one can arrive after the player's
+/// sprite has been attached, and is put on it then.
+pub struct AppearancePlugin;
+
+impl Plugin for AppearancePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AppearanceCycle>()
+            .add_systems(Startup, load_skins_system.run_if(not(is_headless)))
+            .add_systems(Update, apply_skin_system.run_if(resource_exists::<SkinAssets>()));
+    }
+}
+
+/// Index into `SKIN_FILES`
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Appearance(pub u8);
+
+/// The color a sprite is drawn with when nothing else is tinting it
+#[derive(Component, Clone, Copy)]
+pub struct BaseColor(pub Color);
+
+/// Server side, hands out skins to new players in order
+#[derive(Resource, Default)]
+pub struct AppearanceCycle(u8);
+
+impl AppearanceCycle
+{
+    pub fn next_appearance(&mut self) -> Appearance {
+        let appearance = Appearance(self.0);
+        self.0 = (self.0 + 1) % SKIN_FILES.len() as u8;
+        appearance
+    }
+}
+
+/// Pre-loaded textures, `None` for any file this build doesn't have
+#[derive(Resource)]
+pub struct SkinAssets
+{
+    skins: Vec<Option<Handle<Image>>>,
+    projectile: Option<Handle<Image>>,
+}
+
+impl SkinAssets
+{
+    /// The texture for a skin, `None` for unknown or missing skins so callers fall back to a colored square
+    pub fn skin(&self, appearance: Appearance) -> Option<Handle<Image>> {
+        self.skins.get(appearance.0 as usize).cloned().flatten()
+    }
+
+    pub fn projectile(&self) -> Option<Handle<Image>> {
+        self.projectile.clone()
+    }
+}
+
+fn load_skins_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let load = |file: &str| {
+        if !Path::new("assets").join(file).exists()
+        {
+            warn!("Texture 'assets/{file}' is missing, falling back to a colored square");
+            return None;
+        }
+        Some(asset_server.load(file.to_string()))
+    };
+
+    commands.insert_resource(SkinAssets {
+        skins: SKIN_FILES.iter().map(|file| load(file)).collect(),
+        projectile: load(PROJECTILE_TEXTURE),
+    });
+}
+
+/// Swaps the texture of a player whose skin changed or arrived after its sprite, keeping the colored square for
+/// unknown or missing skins
+fn apply_skin_system(
+    mut players: Query<(&Appearance, &mut Handle<Image>, &mut Sprite, &mut BaseColor), Changed<Appearance>>,
+    skins: Res<SkinAssets>,
+) {
+    for (appearance, mut texture, mut sprite, mut base_color) in &mut players
+    {
+        let Some(skin) = skins.skin(*appearance) else { continue; };
+        *texture = skin;
+        sprite.color = Color::WHITE;
+        base_color.0 = Color::WHITE;
+    }
+}
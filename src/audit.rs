@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+
+use crate::{Cli, Player, PlayerSpawnedComponent, Position, PredictedSpawn, RandomComponent, RandomOtherComponent};
+
+/// Seconds between audits
+const AUDIT_INTERVAL: f32 = 1.0;
+
+/// Invariant checks for chasing ghost entities, enabled with `--audit`
+pub struct AuditPlugin;
+
+impl Plugin for AuditPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AuditFailures>()
+            .add_systems(Last, audit_system.run_if(audit_enabled));
+    }
+}
+
+/// Total invariant violations found since startup
+#[derive(Resource, Default)]
+pub struct AuditFailures(pub u64);
+
+fn audit_enabled(cli: Res<Cli>) -> bool {
+    cli.audit_enabled()
+}
+
+/// Every `AUDIT_INTERVAL`, checks the world for entities that shouldn't exist in the state they're in
+///
+/// Exclusive so violations can be logged with every component the entity has.
+fn audit_system(
+    world: &mut World,
+    mut since_audit: Local<f32>,
+) {
+    *since_audit += world.resource::<Time>().delta_seconds();
+    if *since_audit < AUDIT_INTERVAL
+    {
+        return;
+    }
+    *since_audit = 0.0;
+
+    let mut spawned = world.query_filtered::<(Entity, Has<PredictedSpawn>), (With<PlayerSpawnedComponent>, With<Replication>)>();
+    let mut trackers = world.query_filtered::<Entity, (With<RandomOtherComponent>, Without<RandomComponent>)>();
+    let mut players = world.query_filtered::<Entity, (With<Player>, Without<Position>)>();
+
+    let mut violations: Vec<(Entity, &str)> = Vec::new();
+
+    // Only clients have a server entity map, every replicated spawn there came from the server or is waiting to be confirmed by it
+    if let Some(entity_map) = world.get_resource::<ServerEntityMap>()
+    {
+        let mapped = entity_map.to_server();
+        for (entity, predicted) in spawned.iter(world)
+        {
+            if !predicted && !mapped.contains_key(&entity)
+            {
+                violations.push((entity, "replicated spawn that is neither mapped to a server entity nor awaiting confirmation"));
+            }
+        }
+    }
+
+    violations.extend(trackers.iter(world).map(|entity| (entity, "has RandomOtherComponent but not RandomComponent")));
+    violations.extend(players.iter(world).map(|entity| (entity, "Player without a Position")));
+
+    let mut failures = violations.len() as u64;
+
+    if let Some(client_map) = world.get_resource::<ClientEntityMap>()
+    {
+        for (client_id, mappings) in client_map.iter()
+        {
+            for mapping in mappings
+            {
+                if world.get_entity(mapping.server_entity).is_none()
+                {
+                    error!("Audit: Mapping for client '{client_id}' points at despawned server entity {:?}", mapping.server_entity);
+                    failures += 1;
+                }
+            }
+        }
+    }
+
+    for (entity, problem) in violations
+    {
+        let components: Vec<&str> = world.inspect_entity(entity).iter().map(|info| info.name()).collect();
+        error!("Audit: {entity:?} {problem}, components: {components:?}");
+    }
+
+    if failures > 0
+    {
+        world.resource_mut::<AuditFailures>().0 += failures;
+    }
+}
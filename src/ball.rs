@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{EntityKind, Player, Position, MoveDirection, pause::SimulationSet, player_state::PlayerState, room::{self, Room}};
+
+/// How close a player has to be to the ball to grab (and keep holding) it
+pub const BALL_GRAB_RANGE: f32 = 25.0;
+pub const BALL_SPEED: f32 = 60.0;
+
+/// A single shared entity that any player can take control of, to test routing one client's inputs to different server entities
+pub struct BallPlugin;
+
+impl Plugin for BallPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate::<Ball>()
+            .replicate::<Velocity>()
+            .replicate::<ControlledBy>()
+            .add_systems(Update, ball_control_system.run_if(has_authority()))
+            .add_systems(FixedUpdate, move_ball_system.in_set(SimulationSet).run_if(has_authority()))
+            .add_systems(Update,
+                (
+                    attach_extras_to_ball,
+                    tint_ball_system,
+                )
+            );
+    }
+}
+
+#[derive(Component, Serialize, Deserialize)]
+pub struct Ball;
+
+#[derive(Component, Serialize, Deserialize, Default)]
+pub struct Velocity(pub Vec2);
+
+/// The id of the player currently steering the ball
+#[derive(Component, Serialize, Deserialize)]
+pub struct ControlledBy(pub u64);
+
+/// Server-side marker for players currently holding the grab key
+#[derive(Component)]
+pub struct GrabIntent;
+
+/// Server side, where the ball was when it was grabbed, its controller is stopped there while steering it
+///
+/// Holding range is measured from here rather than from the ball, which the controller is driving away from them.
+#[derive(Component)]
+struct GrabPoint(Vec2);
+
+pub fn spawn_ball(commands: &mut Commands) {
+    commands.spawn((Ball, EntityKind::Ball, Position(Vec2::new(100.0, 0.0)), Velocity::default(), Room::default(), Replication));
+}
+
+/// A stable color per player id, used to show who controls the ball
+pub fn player_color(player_id: u64) -> Color {
+    Color::hsl((player_id.wrapping_mul(47) % 360) as f32, 0.7, 0.6)
+}
+
+/// Server side, hands control of the ball to a grabbing player in range and releases it when they let go, die or are
+/// moved out of range of where they grabbed it, or leave its room
+fn ball_control_system(
+    mut commands: Commands,
+    mut balls: Query<(Entity, &Position, &mut Velocity, Option<&ControlledBy>, Option<&GrabPoint>, Option<&Room>), With<Ball>>,
+    mut grabbers: Query<(&Player, &Position, &PlayerState, Option<&mut MoveDirection>, Option<&Room>), With<GrabIntent>>,
+) {
+    for (ball_entity, ball_pos, mut velocity, controlled_by, grab_point, ball_room) in &mut balls
+    {
+        let in_room = |room: Option<&Room>| room::room_of(room) == room::room_of(ball_room);
+        if let Some(controlled_by) = controlled_by
+        {
+            let grab_point = grab_point.map_or(ball_pos.0, |grab_point| grab_point.0);
+            let still_holding = grabbers.iter()
+                .any(|(player, pos, state, _, room)| player.0 == controlled_by.0 && state.is_alive() && in_room(room) && pos.0.distance(grab_point) <= BALL_GRAB_RANGE);
+
+            if !still_holding
+            {
+                info!("Server: Player '{}' released the ball", controlled_by.0);
+                commands.entity(ball_entity).remove::<(ControlledBy, GrabPoint)>();
+                velocity.0 = Vec2::ZERO;
+            }
+            continue;
+        }
+
+        // Lowest player id wins, so two grabs landing in the same tick always resolve to the same winner
+        let winner = grabbers.iter_mut()
+            .filter(|(_, pos, state, _, room)| state.is_alive() && in_room(*room) && pos.0.distance(ball_pos.0) <= BALL_GRAB_RANGE)
+            .min_by_key(|(player, ..)| player.0);
+
+        let Some((player, _, _, move_direction, _)) = winner else { continue; };
+
+        info!("Server: Player '{}' grabbed the ball", player.0);
+        commands.entity(ball_entity).insert((ControlledBy(player.0), GrabPoint(ball_pos.0)));
+
+        // The grabber's movement inputs now steer the ball, so stop their player where it is
+        if let Some(mut move_direction) = move_direction
+        {
+            move_direction.0 = Vec2::ZERO;
+        }
+    }
+}
+
+fn move_ball_system(
+    mut balls: Query<(&mut Position, &Velocity), With<Ball>>,
+    time: Res<Time>,
+) {
+    for (mut pos, velocity) in &mut balls
+    {
+        pos.0 += velocity.0 * time.delta_seconds();
+    }
+}
+
+pub fn attach_extras_to_ball(
+    mut commands: Commands,
+    balls: Query<(Entity, &EntityKind, &Position), Added<Replication>>,
+) {
+    for (ball_entity, kind, pos) in &balls
+    {
+        if *kind != EntityKind::Ball
+        {
+            continue;
+        }
+
+        commands.entity(ball_entity).insert(SpriteBundle
+        {
+            sprite: Sprite
+            {
+                custom_size: Some(Vec2::new(10.0, 10.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(pos.0.extend(1.0)),
+            ..default()
+        });
+    }
+}
+
+/// Tints the ball with its controller's color, or white when nobody holds it
+fn tint_ball_system(
+    mut balls: Query<(&mut Sprite, Option<&ControlledBy>), With<Ball>>,
+) {
+    for (mut sprite, controlled_by) in &mut balls
+    {
+        sprite.color = controlled_by.map_or(Color::WHITE, |controlled_by| player_color(controlled_by.0));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn holds_the_ball_as_it_is_steered_out_of_grab_range() {
+        let mut app = App::new();
+        app.add_systems(Update, ball_control_system);
+        let ball = app.world.spawn((Ball, Position(Vec2::ZERO), Velocity::default())).id();
+        let player = app.world.spawn((Player(1), Position(Vec2::new(10.0, 0.0)), PlayerState::Alive, MoveDirection::default(), GrabIntent)).id();
+
+        app.update();
+        assert_eq!(app.world.get::<ControlledBy>(ball).unwrap().0, 1);
+
+        app.world.get_mut::<Position>(ball).unwrap().0 = Vec2::new(-BALL_GRAB_RANGE * 4.0, 0.0);
+        app.update();
+        assert!(app.world.get::<ControlledBy>(ball).is_some());
+
+        // Knocked away from where they grabbed it
+        app.world.get_mut::<Position>(player).unwrap().0 = Vec2::new(BALL_GRAB_RANGE * 2.0, 0.0);
+        app.update();
+        assert!(app.world.get::<ControlledBy>(ball).is_none());
+    }
+}
@@ -0,0 +1,327 @@
+use std::{error::Error, f32::consts::TAU, fs::File, io::{BufWriter, Write}, time::Instant};
+
+use bevy::{prelude::*, ecs::system::CommandQueue};
+use bevy_replicon::{prelude::*, renet::ClientId};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{Cli, EntityKind, LocalPlayerId, PlayerInput, PlayerMovement, PlayerSpawnedComponent, PredictedSpawn, SERVER_ID, SIMULATION_RATE, audit::AuditFailures, build_app, insert_server_state, memory_transport::{LinkConditions, MemoryServerTransport}, system_timings::{SystemTimings, TimedSystem}, weapon::WeaponKind};
+
+/// Scripted client behaviour and measurements for the `bench` subcommand
+pub struct BenchPlugin;
+
+impl Plugin for BenchPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(PreUpdate, start_apply_timer_system.before(ClientSet::Receive).run_if(resource_exists::<BenchScript>()))
+            .add_systems(PreUpdate, stop_apply_timer_system.after(ClientSet::Receive).run_if(resource_exists::<BenchScript>()))
+            .add_systems(Update, (scripted_client_system, shot_latency_system).run_if(resource_exists::<BenchScript>()));
+    }
+}
+
+/// Drives one scripted client and collects what it measured since the last sample
+#[derive(Resource)]
+struct BenchScript
+{
+    rng: ChaCha8Rng,
+    shoot_rate: f32,
+    move_rate: f32,
+    /// Sequence of the last movement sent, the server ignores any that aren't newer
+    movement_sequence: u32,
+    apply_started: Option<Instant>,
+    /// Wall-clock microseconds spent applying each frame's replication
+    apply_micros: Vec<f32>,
+    /// Simulated milliseconds from sending a shot to the server's confirmation arriving
+    latency_millis: Vec<f32>,
+}
+
+/// A scripted shot waiting for the server to confirm it, with the elapsed time it was sent at
+#[derive(Component)]
+struct BenchShot(f32);
+
+/// Runs the benchmark described by a `Cli::Bench`, returning the process exit code
+pub fn run(cli: Cli) -> i32 {
+    match run_bench(cli)
+    {
+        Ok(0) => 0,
+        Ok(failures) =>
+        {
+            error!("Bench: Clients reported {failures} audit failures, the run desynced");
+            1
+        }
+        Err(e) =>
+        {
+            error!("Bench: {e}");
+            2
+        }
+    }
+}
+
+/// Returns the total audit failures across every client
+fn run_bench(mut cli: Cli) -> Result<u64, Box<dyn Error>> {
+    let Cli::Bench { clients, duration, shoot_rate, move_rate, bench_seed, ref output, ref mut server_args } = cli else {
+        return Err("not a bench command".into());
+    };
+    server_args.seed = server_args.seed.or(Some(bench_seed));
+    let output = output.clone();
+
+    let (mut server_app, mut client_apps) = memory_session(&cli, clients, bench_seed)?;
+    for (index, client_app) in (1..).zip(&mut client_apps)
+    {
+        client_app.world.insert_resource(BenchScript {
+            rng: ChaCha8Rng::seed_from_u64(bench_seed.wrapping_add(index)),
+            shoot_rate,
+            move_rate,
+            movement_sequence: 0,
+            apply_started: None,
+            apply_micros: Vec::new(),
+            latency_millis: Vec::new(),
+        });
+    }
+
+    let mut csv = BufWriter::new(File::create(&output)?);
+    let timing_columns: String = TimedSystem::ALL.into_iter().map(|system| format!(",{}_max_us", system.name())).collect();
+    writeln!(csv, "second,replicated_entities,bytes_per_second,apply_mean_us,apply_p95_us,latency_mean_ms{timing_columns}")?;
+
+    let steps_per_second = SIMULATION_RATE as u64;
+    let steps = (duration as f64 * SIMULATION_RATE).ceil() as u64;
+    info!("Bench: {clients} clients for {duration}s, writing samples to {}", output.display());
+
+    for step in 1..=steps
+    {
+        step_session(&mut server_app, &mut client_apps);
+
+        if step % steps_per_second != 0
+        {
+            continue;
+        }
+
+        let replicated_entities = server_app.world.query_filtered::<(), With<Replication>>().iter(&server_app.world).count();
+        let server = server_app.world.resource::<RenetServer>();
+        let bytes_per_second: f64 = server.clients_id().into_iter()
+            .filter_map(|client_id| server.network_info(client_id).ok())
+            .map(|info| info.bytes_sent_per_second)
+            .sum();
+
+        let mut apply_micros = Vec::new();
+        let mut latency_millis = Vec::new();
+        // Slowest frame of each reaction system on any client
+        let mut timing_max = [0.0f32; TimedSystem::ALL.len()];
+        for client_app in &mut client_apps
+        {
+            let mut script = client_app.world.resource_mut::<BenchScript>();
+            apply_micros.append(&mut script.apply_micros);
+            latency_millis.append(&mut script.latency_millis);
+
+            let mut timings = client_app.world.resource_mut::<SystemTimings>();
+            for (system, max) in TimedSystem::ALL.into_iter().zip(&mut timing_max)
+            {
+                *max = max.max(timings.take_sample_max(system).as_secs_f32() * 1_000_000.0);
+            }
+        }
+        apply_micros.sort_by(f32::total_cmp);
+        let p95 = apply_micros.get((apply_micros.len() * 95 / 100).min(apply_micros.len().saturating_sub(1))).copied().unwrap_or_default();
+
+        let timing_values: String = timing_max.iter().map(|max| format!(",{max:.1}")).collect();
+        writeln!(csv, "{},{replicated_entities},{bytes_per_second:.0},{:.1},{p95:.1},{:.1}{timing_values}",
+            step / steps_per_second, mean(&apply_micros), mean(&latency_millis))?;
+    }
+    csv.flush()?;
+
+    let failures = client_apps.iter().map(|client_app| client_app.world.resource::<AuditFailures>().0).sum();
+    info!("Bench: Finished, {failures} audit failures");
+    Ok(failures)
+}
+
+/// A server app and `clients` client apps all built from `cli`, connected over a `MemoryServerTransport` and ready to step
+///
+/// Clients get ids counting up from 1, and each link's loss rolls are seeded from `seed`.
+pub fn memory_session(cli: &Cli, clients: u64, seed: u64) -> Result<(App, Vec<App>), Box<dyn Error>> {
+    let mut cli = cli.clone();
+    let server_args = cli.server_args_mut().ok_or("not a server command")?.clone();
+
+    let mut server_app = build_app(cli.clone());
+    server_app.finish();
+    server_app.cleanup();
+
+    let connection_config = server_args.connection_args.connection_config(server_app.world.resource::<NetworkChannels>());
+    server_app.world.insert_resource(RenetServer::new(connection_config.clone()));
+    server_app.world.insert_resource(LocalPlayerId(SERVER_ID.raw()));
+    let mut queue = CommandQueue::default();
+    insert_server_state(&mut Commands::new(&mut queue, &server_app.world), &server_args)?;
+    queue.apply(&mut server_app.world);
+
+    let mut transport = MemoryServerTransport::default();
+    let mut client_apps = Vec::new();
+    for index in 1..=clients
+    {
+        let client_id = ClientId::from_raw(index);
+        let mut client_app = build_app(cli.clone());
+        client_app.finish();
+        client_app.cleanup();
+
+        client_app.world.insert_resource(RenetClient::new(connection_config.clone()));
+        client_app.world.insert_resource(transport.connect(client_id, LinkConditions { seed: seed ^ index, ..default() }));
+        client_app.world.insert_resource(LocalPlayerId(client_id.raw()));
+        client_apps.push(client_app);
+    }
+    server_app.world.insert_resource(transport);
+
+    Ok((server_app, client_apps))
+}
+
+/// One frame of the server, then one of every client
+pub fn step_session(server_app: &mut App, client_apps: &mut [App]) {
+    server_app.update();
+    for client_app in client_apps
+    {
+        client_app.update();
+    }
+}
+
+fn mean(samples: &[f32]) -> f32 {
+    if samples.is_empty()
+    {
+        return 0.0;
+    }
+    samples.iter().sum::<f32>() / samples.len() as f32
+}
+
+/// Moves and shoots at random, at the configured average rates
+fn scripted_client_system(
+    mut commands: Commands,
+    mut script: ResMut<BenchScript>,
+    mut input_writer: EventWriter<PlayerInput>,
+    mut movement_writer: EventWriter<PlayerMovement>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    let move_chance = script.move_rate * delta;
+    if script.rng.gen::<f32>() < move_chance
+    {
+        let angle = script.rng.gen_range(0.0..TAU);
+        script.movement_sequence += 1;
+        movement_writer.send(PlayerMovement { direction: Vec2::from_angle(angle), sequence: script.movement_sequence });
+    }
+
+    let shoot_chance = script.shoot_rate * delta;
+    if script.rng.gen::<f32>() < shoot_chance
+    {
+        let shot = commands.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, Replication, PredictedSpawn(0.0), BenchShot(time.elapsed_seconds()))).id();
+        input_writer.send(PlayerInput::Shoot { weapon: WeaponKind::Pistol, projectiles: vec![shot] });
+    }
+}
+
+/// Same confirmation check as `confirm_predicted_spawns_system`, timing how long each shot took
+fn shot_latency_system(
+    mut commands: Commands,
+    mut script: ResMut<BenchScript>,
+    shots: Query<(Entity, &BenchShot, Ref<PlayerSpawnedComponent>)>,
+    time: Res<Time>,
+) {
+    for (entity, shot, component) in &shots
+    {
+        if component.is_changed() && !component.is_added()
+        {
+            script.latency_millis.push((time.elapsed_seconds() - shot.0) * 1000.0);
+            commands.entity(entity).remove::<BenchShot>();
+        }
+    }
+}
+
+fn start_apply_timer_system(
+    mut script: ResMut<BenchScript>,
+) {
+    script.apply_started = Some(Instant::now());
+}
+
+fn stop_apply_timer_system(
+    mut script: ResMut<BenchScript>,
+) {
+    let Some(started) = script.apply_started.take() else { return; };
+    script.apply_micros.push(started.elapsed().as_secs_f32() * 1_000_000.0);
+}
+
+#[cfg(test)]
+pub mod tests
+{
+    use clap::Parser;
+
+    use super::*;
+    use crate::{Player, Position};
+
+    /// Frames a session gets to connect before a test gives up on it
+    const CONNECT_FRAMES: usize = 600;
+
+    /// A bench session with default settings and no scripted clients, stepped until every client has its own player
+    pub fn connected_session(clients: u64) -> (App, Vec<App>) {
+        connected_session_with(&Cli::parse_from(["replicon_test_1", "bench"]), clients)
+    }
+
+    /// `connected_session` with the server and connection flags of `cli`, a `bench` command
+    pub fn connected_session_with(cli: &Cli, clients: u64) -> (App, Vec<App>) {
+        let (mut server_app, mut client_apps) = memory_session(cli, clients, 0).unwrap();
+        for _ in 0..CONNECT_FRAMES
+        {
+            step_session(&mut server_app, &mut client_apps);
+            if client_apps.iter_mut().all(has_own_player)
+            {
+                return (server_app, client_apps);
+            }
+        }
+        panic!("Clients didn't get their players within {CONNECT_FRAMES} frames");
+    }
+
+    /// A client joining a running session as `client_id`, it still has to be stepped to connect
+    pub fn join_session(server_app: &mut App, client_id: u64) -> App {
+        let cli = Cli::parse_from(["replicon_test_1", "bench"]);
+        let mut client_app = build_app(cli.clone());
+        client_app.finish();
+        client_app.cleanup();
+
+        let connection_config = cli.connection_args().connection_config(client_app.world.resource::<NetworkChannels>());
+        let transport = server_app.world.resource_mut::<MemoryServerTransport>().connect(ClientId::from_raw(client_id), LinkConditions::default());
+        client_app.world.insert_resource(RenetClient::new(connection_config));
+        client_app.world.insert_resource(transport);
+        client_app.world.insert_resource(LocalPlayerId(client_id));
+        client_app
+    }
+
+    fn has_own_player(client_app: &mut App) -> bool {
+        let local_player = client_app.world.resource::<LocalPlayerId>().0;
+        client_app.world.query::<&Player>().iter(&client_app.world).any(|player| player.0 == local_player)
+    }
+
+    #[test]
+    fn clients_connect_over_memory_transport() {
+        let (server_app, _) = connected_session(2);
+        let server = server_app.world.resource::<RenetServer>();
+        assert_eq!(server.clients_id().len(), 2);
+    }
+
+    #[test]
+    fn raised_channel_memory_takes_500_entities_spawned_in_one_tick() {
+        const SPAWNED: usize = 500;
+        let cli = Cli::parse_from(["replicon_test_1", "bench", "--channel-memory", "50000000", "--available-bytes-per-tick", "1000000"]);
+        let (mut server_app, mut client_apps) = connected_session_with(&cli, 2);
+        // Obstacles and the like have a `Position` of their own
+        let count = |client_app: &mut App| client_app.world.query_filtered::<(), (With<Position>, Without<Player>)>().iter(&client_app.world).count();
+        let before: Vec<usize> = client_apps.iter_mut().map(count).collect();
+
+        server_app.world.spawn_batch((0..SPAWNED).map(|index| (Replication, Position(Vec2::new(index as f32 * 0.25, 0.0)))));
+        for _ in 0..120
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let server = server_app.world.resource::<RenetServer>();
+        assert_eq!(server.clients_id().len(), 2, "a client was disconnected");
+        for (client_app, before) in client_apps.iter_mut().zip(before)
+        {
+            assert!(client_app.world.resource::<RenetClient>().is_connected());
+            assert_eq!(count(client_app) - before, SPAWNED);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::process::{Command, Stdio};
+
+use bevy::{prelude::*, app::AppExit, input::InputSystem};
+use bevy_replicon::prelude::*;
+use rand::Rng;
+
+use crate::{Cli, ListenPort, LocalPlayerId, Player, console::ConsoleCommand, embed::EmbeddedServer, game_phase::{self, GamePhase, Ready}, input_map::{Action, InputBindings}};
+
+/// Shortest and longest a bot keeps heading one way, in seconds
+const TURN_SECONDS: (f32, f32) = (0.5, 2.0);
+/// Shots a bot fires per second on average
+const SHOTS_PER_SECOND: f32 = 1.5;
+/// Seconds between presses of the ready key while not ready, long enough for the last press to have come back
+const READY_RETRY_SECONDS: f32 = 1.0;
+
+/// `client --bot` plays by itself, for filling a `dev` session without a keyboard per window
+///
+/// The bot presses the bound keys in `Input<KeyCode>` right after bevy has read the real keyboard, so every
+/// input system, prediction included, treats it exactly like a player. It wanders in one of eight directions,
+/// taps the fire key now and then and readies up whenever it's in the lobby and not ready. `spawn bot` on a server
+/// starts one as a separate process, which quits once it loses its connection so it doesn't outlive the server.
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, start_bot_system)
+            .add_systems(PreUpdate, bot_input_system.after(InputSystem).run_if(resource_exists::<Bot>()).run_if(resource_exists::<RenetClient>()))
+            .add_systems(Update, quit_when_disconnected_system.run_if(resource_exists::<Bot>()).run_if(resource_exists::<RenetClient>()))
+            .add_systems(Update, spawn_bot_console_system.run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// The keys a bot is holding and when it next does something, only present on a `--bot` client
+#[derive(Resource, Default)]
+pub struct Bot
+{
+    held: Vec<KeyCode>,
+    turn_at: f32,
+    ready_at: f32,
+    /// Whether it has ever connected, the first connection attempt isn't a lost connection
+    connected: bool,
+}
+
+fn start_bot_system(
+    mut commands: Commands,
+    cli: Res<Cli>,
+) {
+    if cli.is_bot()
+    {
+        info!("Client: Playing as a bot");
+        commands.init_resource::<Bot>();
+    }
+}
+
+fn bot_input_system(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut bot: ResMut<Bot>,
+    bindings: Res<InputBindings>,
+    client: Res<RenetClient>,
+    players: Query<(&Player, &Ready)>,
+    phases: Query<&GamePhase>,
+    local_player: Res<LocalPlayerId>,
+    time: Res<Time>,
+) {
+    // Shooting and readying are taps, released the frame after
+    keys.release(bindings.get(Action::Shoot).key);
+    keys.release(bindings.get(Action::Ready).key);
+    if !client.is_connected()
+    {
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    let mut rng = rand::thread_rng();
+    if now >= bot.turn_at
+    {
+        for key in bot.held.drain(..)
+        {
+            keys.release(key);
+        }
+
+        let horizontal = [None, Some(Action::MoveLeft), Some(Action::MoveRight)][rng.gen_range(0..3)];
+        let vertical = [None, Some(Action::MoveDown), Some(Action::MoveUp)][rng.gen_range(0..3)];
+        for action in [horizontal, vertical].into_iter().flatten()
+        {
+            let key = bindings.get(action).key;
+            keys.press(key);
+            bot.held.push(key);
+        }
+        bot.turn_at = now + rng.gen_range(TURN_SECONDS.0..TURN_SECONDS.1);
+    }
+
+    if rng.gen::<f32>() < SHOTS_PER_SECOND * time.delta_seconds()
+    {
+        keys.press(bindings.get(Action::Shoot).key);
+    }
+
+    let unready = players.iter().any(|(player, ready)| player.0 == local_player.0 && !ready.0);
+    if unready && game_phase::current_phase(&phases) == GamePhase::Lobby && now >= bot.ready_at
+    {
+        keys.press(bindings.get(Action::Ready).key);
+        bot.ready_at = now + READY_RETRY_SECONDS;
+    }
+}
+
+/// A bot with nobody to play with quits, unless it was asked to keep reconnecting
+fn quit_when_disconnected_system(
+    mut bot: ResMut<Bot>,
+    mut exit: EventWriter<AppExit>,
+    client: Res<RenetClient>,
+    cli: Res<Cli>,
+) {
+    if client.is_connected()
+    {
+        bot.connected = true;
+    }
+    else if bot.connected && client.is_disconnected() && !matches!(*cli, Cli::Client { auto_reconnect: true, .. })
+    {
+        info!("Client: Bot lost its connection, quitting");
+        exit.send(AppExit);
+    }
+}
+
+/// `spawn bot` starts a bot client connected to this server, with its own identity and this server's connection flags
+fn spawn_bot_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut spawned: Local<u32>,
+    port: Option<Res<ListenPort>>,
+    embedded: Option<Res<EmbeddedServer>>,
+    cli: Res<Cli>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "spawn"
+        {
+            continue;
+        }
+        if command.args.get(1).map(String::as_str) != Some("bot")
+        {
+            warn!("Server: Usage: spawn bot");
+            continue;
+        }
+        // An embedded server's executable is some other program, and one only reachable in memory has no port anyway
+        let Some(port) = port.as_ref().filter(|_| embedded.is_none()) else {
+            warn!("Server: Only a standalone server or host listening on a port can start bots");
+            continue;
+        };
+
+        *spawned += 1;
+        match start_bot_process(port.0, *spawned, &cli)
+        {
+            Ok(pid) => info!("Server: Started bot {} as process {pid}", *spawned),
+            Err(e) => warn!("Server: Couldn't start a bot: {e}"),
+        }
+    }
+}
+
+/// Starts this executable as `client --bot`, its output discarded since it would drown out the server's
+fn start_bot_process(port: u16, index: u32, cli: &Cli) -> std::io::Result<u32> {
+    let child = Command::new(std::env::current_exe()?)
+        .args(["client", "--bot", "--port", port.to_string().as_str(), "--identity-file", format!("bot_identity_{index}.txt").as_str()])
+        .args(cli.connection_args().to_flags())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(child.id())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{Position, bench::{step_session, tests::connected_session}};
+
+    #[test]
+    fn bot_moves_and_readies_up() {
+        // A round needs at least two ready players to start counting down
+        let (mut server_app, mut client_apps) = connected_session(2);
+        let start = server_app.world.query::<(&Player, &Position)>().iter(&server_app.world).find(|(player, _)| player.0 == 1).unwrap().1.0;
+        for client_app in &mut client_apps
+        {
+            client_app.world.init_resource::<Bot>();
+        }
+
+        let (mut moved, mut readied) = (false, false);
+        for _ in 0..300
+        {
+            step_session(&mut server_app, &mut client_apps);
+            let position = server_app.world.query::<(&Player, &Position)>().iter(&server_app.world).find(|(player, _)| player.0 == 1).unwrap().1.0;
+            moved |= position != start;
+            // Everyone being ready starts the countdown, which may clear it again
+            readied |= server_app.world.query::<&GamePhase>().iter(&server_app.world).any(|phase| *phase != GamePhase::Lobby);
+        }
+
+        assert!(moved);
+        assert!(readied);
+    }
+}
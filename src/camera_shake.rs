@@ -0,0 +1,150 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, transform::TransformSystem};
+use rand::Rng;
+
+use crate::{Cli, LocalPlayerId, Player, damage::{DamageDealt, HitConfirmed}, has_local_player, is_headless};
+
+/// World units of shake per point of damage taken
+const SHAKE_PER_DAMAGE: f32 = 0.6;
+/// Landing a hit kicks the camera this fraction as hard as taking the same damage
+const HIT_CONFIRMED_FRACTION: f32 = 0.25;
+const MAX_SHAKE: f32 = 20.0;
+/// Per second, fast enough that a shake is down to about 1% after 0.3 seconds
+const SHAKE_DECAY_RATE: f32 = 15.0;
+/// Frames the local player's sprite is drawn white after taking damage
+const FLASH_FRAMES: u8 = 2;
+
+/// Client side hit feedback for the local player: the camera shakes with the damage taken, and their sprite flashes white
+///
+/// Only ever moves the camera's transform, never a replicated `Position`. `--no-camera-shake` turns the shake off.
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CameraShake>()
+            .add_systems(PreUpdate, unshake_camera_system.run_if(camera_shake_enabled))
+            .add_systems(Update, (add_shake_system.run_if(camera_shake_enabled), start_hit_flash_system).run_if(has_local_player).run_if(not(is_headless)))
+            .add_systems(PostUpdate,
+                (
+                    shake_camera_system.run_if(camera_shake_enabled),
+                    hit_flash_system,
+                )
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(has_local_player)
+                    .run_if(not(is_headless))
+            );
+    }
+}
+
+/// How hard the camera is shaking, and the offset currently added to it
+#[derive(Resource, Default)]
+pub struct CameraShake
+{
+    pub amplitude: f32,
+    applied: Vec2,
+}
+
+/// Frames the sprite has left drawn white
+#[derive(Component)]
+pub struct HitFlash(u8);
+
+fn camera_shake_enabled(cli: Res<Cli>) -> bool {
+    match *cli
+    {
+        Cli::Client { no_camera_shake, .. } | Cli::Host { no_camera_shake, .. } => !no_camera_shake,
+        _ => false,
+    }
+}
+
+fn add_shake_system(
+    mut shake: ResMut<CameraShake>,
+    mut damage_events: EventReader<DamageDealt>,
+    mut confirm_events: EventReader<HitConfirmed>,
+    players: Query<&Player>,
+    local_player: Res<LocalPlayerId>,
+) {
+    let taken: u32 = damage_events.read()
+        .filter(|damage| players.get(damage.target).is_ok_and(|player| player.0 == local_player.0))
+        .map(|damage| damage.amount)
+        .sum();
+    let dealt: u32 = confirm_events.read().map(|confirmed| confirmed.damage).sum();
+
+    let kick = (taken as f32 + dealt as f32 * HIT_CONFIRMED_FRACTION) * SHAKE_PER_DAMAGE;
+    shake.amplitude = (shake.amplitude + kick).min(MAX_SHAKE);
+}
+
+/// Takes last frame's offset back off, so camera following and free-fly always work from the unshaken position
+fn unshake_camera_system(
+    mut shake: ResMut<CameraShake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    let applied = std::mem::take(&mut shake.applied);
+    if applied == Vec2::ZERO
+    {
+        return;
+    }
+
+    for mut camera in &mut cameras
+    {
+        camera.translation -= applied.extend(0.0);
+    }
+}
+
+fn shake_camera_system(
+    mut shake: ResMut<CameraShake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+    time: Res<Time>,
+) {
+    shake.amplitude *= (-SHAKE_DECAY_RATE * time.delta_seconds()).exp();
+    if shake.amplitude < 0.01
+    {
+        shake.amplitude = 0.0;
+        return;
+    }
+
+    // Purely visual, so it doesn't need the seeded game rng
+    let angle = rand::thread_rng().gen_range(0.0..TAU);
+    let offset = Vec2::from_angle(angle) * shake.amplitude;
+    for mut camera in &mut cameras
+    {
+        camera.translation += offset.extend(0.0);
+    }
+    shake.applied = offset;
+}
+
+fn start_hit_flash_system(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageDealt>,
+    players: Query<&Player>,
+    local_player: Res<LocalPlayerId>,
+) {
+    for damage in damage_events.read()
+    {
+        if players.get(damage.target).is_ok_and(|player| player.0 == local_player.0)
+        {
+            commands.entity(damage.target).insert(HitFlash(FLASH_FRAMES));
+        }
+    }
+}
+
+/// Runs after every `Update` system that tints players, so the white wins for the flash's frames
+fn hit_flash_system(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut HitFlash, &mut Sprite)>,
+) {
+    for (player_entity, mut flash, mut sprite) in &mut players
+    {
+        // `grey_out_inactive_players` puts the usual colour back on the next frame
+        if flash.0 == 0
+        {
+            commands.entity(player_entity).remove::<HitFlash>();
+            continue;
+        }
+
+        sprite.color = Color::WHITE;
+        flash.0 -= 1;
+    }
+}
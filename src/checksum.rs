@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use bevy_replicon::{prelude::*, renet::SendType};
+use serde::{Serialize, Deserialize};
+
+use crate::{Cli, LocalPlayerId, Player, Position, audit::AuditFailures, damage::Health, serialization};
+
+/// Seconds between the server's state checksums
+const CHECKSUM_INTERVAL: f32 = 5.0;
+
+/// Every `CHECKSUM_INTERVAL` the server hashes what it means every client to see of each player, and clients running
+/// `--audit` hash their own view the same way and count any difference as an audit failure
+///
+/// Only players are covered, they're replicated to everyone. `Score` is left out because its changes come over their
+/// own reliable channel, so a kill just before a checksum can land a tick apart from it and look like a divergence.
+pub struct ChecksumPlugin;
+
+impl Plugin for ChecksumPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            // Another one follows shortly, so a lost one isn't worth resending
+            .add_server_event::<StateChecksum>(SendType::Unreliable)
+            .add_systems(Update, send_checksum_system.run_if(resource_exists::<RenetServer>()))
+            .add_systems(Update, check_checksum_system.run_if(resource_exists::<RenetClient>()).run_if(checksum_enabled));
+    }
+}
+
+/// The server's per-player hashes at `tick`, sorted by player id, and one hash over all of them
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct StateChecksum
+{
+    pub tick: u32,
+    pub hash: u64,
+    pub players: Vec<(u64, u64)>,
+}
+
+fn checksum_enabled(cli: Res<Cli>) -> bool {
+    cli.audit_enabled()
+}
+
+/// FNV-1a, written out so every build and platform agrees on it, unlike std's hasher
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+/// Hashes `Position` as it goes over the wire, so rounding can't cause a mismatch
+fn hash_player(position: &Position, health: &Health) -> u64 {
+    let [x, y] = serialization::wire_position(position.0);
+    let bytes = x.to_le_bytes().into_iter()
+        .chain(y.to_le_bytes())
+        .chain(health.current.to_le_bytes())
+        .chain(health.max.to_le_bytes());
+    fnv1a(bytes)
+}
+
+fn player_hashes(players: &Query<(&Player, &Position, &Health)>) -> Vec<(u64, u64)> {
+    let mut hashes: Vec<(u64, u64)> = players.iter().map(|(player, position, health)| (player.0, hash_player(position, health))).collect();
+    hashes.sort_unstable();
+    hashes
+}
+
+fn combined_hash(players: &[(u64, u64)]) -> u64 {
+    fnv1a(players.iter().flat_map(|(id, hash)| id.to_le_bytes().into_iter().chain(hash.to_le_bytes())))
+}
+
+fn send_checksum_system(
+    mut checksums: EventWriter<ToClients<StateChecksum>>,
+    mut since_checksum: Local<f32>,
+    players: Query<(&Player, &Position, &Health)>,
+    tick: Res<RepliconTick>,
+    time: Res<Time>,
+) {
+    *since_checksum += time.delta_seconds();
+    if *since_checksum < CHECKSUM_INTERVAL
+    {
+        return;
+    }
+    *since_checksum = 0.0;
+
+    let players = player_hashes(&players);
+    let hash = combined_hash(&players);
+    checksums.send(ToClients { mode: SendMode::Broadcast, event: StateChecksum { tick: tick.get(), hash, players } });
+}
+
+/// Compares each checksum against this client's view, if it's at that tick give or take `--checksum-tolerance`
+///
+/// The local player is taken from the server's side, this client predicts its own movement so it's never comparable.
+fn check_checksum_system(
+    mut checksums: EventReader<StateChecksum>,
+    mut failures: ResMut<AuditFailures>,
+    players: Query<(&Player, &Position, &Health)>,
+    local_player: Option<Res<LocalPlayerId>>,
+    tick: Res<RepliconTick>,
+    cli: Res<Cli>,
+) {
+    let tolerance = match *cli
+    {
+        Cli::Client { checksum_tolerance, .. } => checksum_tolerance,
+        _ => 0,
+    };
+    let local_id = local_player.map(|local_player| local_player.0);
+
+    for checksum in checksums.read()
+    {
+        let offset = tick.get().wrapping_sub(checksum.tick) as i32;
+        if offset.unsigned_abs() > tolerance
+        {
+            debug!("Client: Skipping state checksum for tick {}, at tick {} which is outside the tolerance", checksum.tick, tick.get());
+            continue;
+        }
+
+        let mut ours = player_hashes(&players);
+        for (id, hash) in &mut ours
+        {
+            if Some(*id) == local_id
+            {
+                let theirs = checksum.players.iter().find(|(their_id, _)| their_id == id);
+                *hash = theirs.map_or(*hash, |(_, their_hash)| *their_hash);
+            }
+        }
+        if combined_hash(&ours) == checksum.hash
+        {
+            continue;
+        }
+
+        // Players only one side has, and players both have but with different state
+        let missing = checksum.players.iter().filter(|(id, _)| !ours.iter().any(|(our_id, _)| our_id == id)).map(|(id, _)| *id);
+        let extra = ours.iter().filter(|(id, _)| !checksum.players.iter().any(|(their_id, _)| their_id == id)).map(|(id, _)| *id);
+        let differing = ours.iter().filter(|(id, hash)| checksum.players.iter().any(|(their_id, their_hash)| their_id == id && their_hash != hash)).map(|(id, _)| *id);
+        error!(
+            "Audit: State checksum for tick {} doesn't match at tick {}, missing players {:?}, unexpected players {:?}, differing players {:?}",
+            checksum.tick, tick.get(), missing.collect::<Vec<_>>(), extra.collect::<Vec<_>>(), differing.collect::<Vec<_>>(),
+        );
+        failures.0 += 1;
+    }
+}
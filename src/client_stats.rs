@@ -0,0 +1,169 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::{ClientId, ServerEvent}};
+
+use crate::{PlayerInput, PlayerMovement, congestion::ClientBackoff, focus::BackgroundClients, is_headless};
+
+/// Per connected client network and input stats, shown in a panel on a windowed server
+pub struct ClientStatsPlugin;
+
+impl Plugin for ClientStatsPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ClientInputStats>()
+            .add_systems(Update,
+                count_client_inputs_system.run_if(resource_exists::<RenetServer>())
+            )
+            .add_systems(Startup, spawn_client_stats_panel.run_if(not(is_headless)))
+            .add_systems(Update,
+                (
+                    client_stats_rows_system,
+                    update_client_stats_panel,
+                ).chain().run_if(resource_exists::<RenetServer>()).run_if(not(is_headless))
+            );
+    }
+}
+
+#[derive(Default)]
+pub struct InputCounters
+{
+    /// Inputs received since the panel last refreshed
+    pub recent: u32,
+    /// Inputs refused since the client connected (dead, countdown, invalid)
+    pub rejected: u32,
+    /// Pre-spawned entities sent again after the server had already mapped them, a buggy or cheating client
+    pub reused: u32,
+}
+
+/// Server side input counters per client
+#[derive(Resource, Default)]
+pub struct ClientInputStats(HashMap<ClientId, InputCounters>);
+
+impl ClientInputStats
+{
+    pub fn record_rejected(&mut self, client_id: ClientId) {
+        self.0.entry(client_id).or_default().rejected += 1;
+    }
+
+    pub fn record_reused(&mut self, client_id: ClientId, entities: usize) {
+        self.0.entry(client_id).or_default().reused += entities as u32;
+    }
+}
+
+#[derive(Component)]
+pub struct ClientStatsPanel;
+
+#[derive(Component)]
+pub struct ClientStatsRow(pub ClientId);
+
+fn count_client_inputs_system(
+    mut stats: ResMut<ClientInputStats>,
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    mut movement_reader: EventReader<FromClient<PlayerMovement>>,
+    mut server_events: EventReader<ServerEvent>,
+) {
+    for FromClient { client_id, .. } in input_reader.read()
+    {
+        stats.0.entry(*client_id).or_default().recent += 1;
+    }
+    for FromClient { client_id, .. } in movement_reader.read()
+    {
+        stats.0.entry(*client_id).or_default().recent += 1;
+    }
+
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            stats.0.remove(client_id);
+        }
+    }
+}
+
+fn spawn_client_stats_panel(
+    mut commands: Commands,
+) {
+    commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(60.0),
+            right: Val::Px(5.0),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    }, ClientStatsPanel));
+}
+
+/// Adds a row per connected client and removes rows for clients that are gone
+///
+/// Rows are reconciled against the server's client list rather than only connect/disconnect events,
+/// so rapid churn can't leave a stale row behind.
+fn client_stats_rows_system(
+    mut commands: Commands,
+    panels: Query<Entity, With<ClientStatsPanel>>,
+    rows: Query<(Entity, &ClientStatsRow)>,
+    server: Res<RenetServer>,
+) {
+    let Ok(panel) = panels.get_single() else { return; };
+
+    let connected = server.clients_id();
+    for (row_entity, row) in &rows
+    {
+        if !connected.contains(&row.0)
+        {
+            commands.entity(row_entity).despawn_recursive();
+        }
+    }
+
+    for client_id in connected
+    {
+        if rows.iter().any(|(_, row)| row.0 == client_id)
+        {
+            continue;
+        }
+
+        let row = commands.spawn((TextBundle::from_section(
+            format!("{client_id}: waiting for stats"),
+            TextStyle { font_size: 16.0, color: Color::WHITE, ..default() }
+        ), ClientStatsRow(client_id))).id();
+        commands.entity(panel).add_child(row);
+    }
+}
+
+/// Refreshes every row once per second
+fn update_client_stats_panel(
+    mut rows: Query<(&ClientStatsRow, &mut Text)>,
+    mut stats: ResMut<ClientInputStats>,
+    mut since_update: Local<f32>,
+    background: Res<BackgroundClients>,
+    backoff: Res<ClientBackoff>,
+    server: Res<RenetServer>,
+    time: Res<Time>,
+) {
+    *since_update += time.delta_seconds();
+    if *since_update < 1.0
+    {
+        return;
+    }
+
+    for (row, mut text) in &mut rows
+    {
+        let Ok(info) = server.network_info(row.0) else { continue; };
+        let counters = stats.0.entry(row.0).or_default();
+        let inputs_per_sec = counters.recent as f32 / *since_update;
+        counters.recent = 0;
+        let backoff = backoff.get(row.0)
+            .filter(|backoff| backoff.episodes > 0)
+            .map_or(String::new(), |backoff| format!(", backoff {} ({} episodes)", backoff.level, backoff.episodes));
+        let reused = if counters.reused > 0 { format!(", {} reused entities", counters.reused) } else { String::new() };
+
+        text.sections[0].value = format!(
+            "{}: rtt {:.0}ms, {:.0} B/s out, {:.0} B/s in, {inputs_per_sec:.1} inputs/s, {} rejected{reused}{backoff}{}",
+            row.0, info.rtt, info.bytes_sent_per_second, info.bytes_received_per_second, counters.rejected,
+            if background.contains(row.0) { ", in background" } else { "" },
+        );
+    }
+
+    *since_update = 0.0;
+}
@@ -0,0 +1,288 @@
+use std::{error::Error, ffi::OsString, fs, path::{Path, PathBuf}, time::SystemTime};
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, parser::ValueSource};
+use serde::Deserialize;
+
+use crate::{Cli, DEFAULT_MAX_ENTITIES, ServerArgs, afk::DEFAULT_AFK_TIMEOUT, console::ConsoleCommand, input_map::{BindingsTable, InputBindings}, settings::{GameSettings, GameTuning}, transport};
+
+/// Seconds between checks of the `--config` file for changes
+const POLL_INTERVAL: f32 = 1.0;
+
+/// Reloads the `[tuning]` table of the server's `--config` file whenever the file is saved
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, reload_tuning_system.run_if(resource_exists::<ConfigWatch>()).run_if(resource_exists::<RenetServer>()))
+            .add_systems(Update, set_tuning_console_system.run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// Server settings loadable from a TOML file with `--config`, any flag given on the command line wins over the file
+///
+/// ```toml
+/// replication_rate = 20.0
+/// world_half_extent = 300.0
+///
+/// # Reloaded live while the server runs
+/// [tuning]
+/// move_speed = 60.0
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config
+{
+    pub replication_rate: f32,
+    pub afk_timeout: f32,
+    pub seed: Option<u64>,
+    pub max_entities: usize,
+    pub world_half_extent: f32,
+    pub max_projectiles: usize,
+    pub stats_file: PathBuf,
+    pub tuning: GameTuning,
+    /// Read by the input map at startup, checked here so a bad binding fails the load like any other value
+    pub bindings: BindingsTable,
+}
+
+impl Default for Config
+{
+    fn default() -> Self {
+        let settings = GameSettings::default();
+        Self {
+            replication_rate: settings.replication_rate,
+            afk_timeout: DEFAULT_AFK_TIMEOUT,
+            seed: None,
+            max_entities: DEFAULT_MAX_ENTITIES,
+            world_half_extent: settings.world_half_extent,
+            max_projectiles: settings.max_projectiles,
+            stats_file: PathBuf::from("stats.csv"),
+            tuning: GameTuning::default(),
+            bindings: BindingsTable::default(),
+        }
+    }
+}
+
+impl Config
+{
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {e}", path.display()))?;
+        // toml's errors already name the offending key and line
+        let config: Config = toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        config.validate().map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.replication_rate <= 0.0
+        {
+            return Err("`replication_rate` must be greater than zero".to_string());
+        }
+        if self.afk_timeout <= 0.0
+        {
+            return Err("`afk_timeout` must be greater than zero".to_string());
+        }
+        if self.max_entities == 0
+        {
+            return Err("`max_entities` must be at least one".to_string());
+        }
+        if self.world_half_extent <= 0.0
+        {
+            return Err("`world_half_extent` must be greater than zero".to_string());
+        }
+        if self.max_projectiles == 0
+        {
+            return Err("`max_projectiles` must be at least one".to_string());
+        }
+
+        InputBindings::from_table(&self.bindings)?;
+        self.tuning.validate()
+    }
+
+    /// Copies every value whose flag wasn't given on the command line into `server_args`
+    pub fn apply(&self, server_args: &mut ServerArgs, matches: &ArgMatches) {
+        let from_file = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+        if from_file("replication_rate")
+        {
+            server_args.replication_rate = self.replication_rate;
+        }
+        if from_file("afk_timeout")
+        {
+            server_args.afk_timeout = self.afk_timeout;
+        }
+        if from_file("seed")
+        {
+            server_args.seed = self.seed;
+        }
+        if from_file("max_entities")
+        {
+            server_args.max_entities = self.max_entities;
+        }
+        if from_file("world_half_extent")
+        {
+            server_args.world_half_extent = self.world_half_extent;
+        }
+        if from_file("max_projectiles")
+        {
+            server_args.max_projectiles = self.max_projectiles;
+        }
+        if from_file("stats_file")
+        {
+            server_args.stats_file = self.stats_file.clone();
+        }
+        // Tuning has no flags, it only ever comes from the file
+        server_args.tuning = self.tuning;
+    }
+}
+
+/// Parses the command line, then fills in anything it left out from the `--config` file if there is one
+///
+/// In a browser the command line is made up from the page's query string, see `transport::command_line`.
+pub fn parse_cli() -> Result<Cli, Box<dyn Error>> {
+    apply_config_file(&Cli::command().get_matches_from(transport::command_line()))
+}
+
+/// `parse_cli` for arguments that aren't the process's own, the first being the program name
+pub fn parse_cli_from(args: impl IntoIterator<Item = impl Into<OsString> + Clone>) -> Result<Cli, Box<dyn Error>> {
+    apply_config_file(&Cli::command().try_get_matches_from(args)?)
+}
+
+fn apply_config_file(matches: &ArgMatches) -> Result<Cli, Box<dyn Error>> {
+    let mut cli = Cli::from_arg_matches(matches)?;
+
+    let (Some((_, sub_matches)), Some(server_args)) = (matches.subcommand(), cli.server_args_mut()) else { return Ok(cli); };
+    let Some(path) = server_args.config.clone() else { return Ok(cli); };
+
+    Config::load(&path)?.apply(server_args, sub_matches);
+    Ok(cli)
+}
+
+/// The server's `--config` file and when it was last seen modified
+#[derive(Resource)]
+pub struct ConfigWatch
+{
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    since_poll: f32,
+}
+
+impl ConfigWatch
+{
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified_time(&path);
+        Self { path, modified, since_poll: 0.0 }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Polls the config file's modification time, applying its `[tuning]` when it changes
+///
+/// A file that fails to parse or validate is ignored with a warning, keeping every old value.
+/// Only tuning is applied live, the rest of the file takes effect on the next start.
+fn reload_tuning_system(
+    mut watch: ResMut<ConfigWatch>,
+    mut tuning: ResMut<GameTuning>,
+    mut settings: Query<&mut GameSettings>,
+    time: Res<Time>,
+) {
+    watch.since_poll += time.delta_seconds();
+    if watch.since_poll < POLL_INTERVAL
+    {
+        return;
+    }
+    watch.since_poll = 0.0;
+
+    let modified = modified_time(&watch.path);
+    if modified == watch.modified
+    {
+        return;
+    }
+    watch.modified = modified;
+
+    let config = match Config::load(&watch.path)
+    {
+        Ok(config) => config,
+        Err(e) =>
+        {
+            warn!("Server: Not reloading tuning, keeping the old values: {e}");
+            return;
+        }
+    };
+
+    if config.tuning == *tuning
+    {
+        return;
+    }
+
+    info!("Server: Reloaded tuning from {}: {:?}", watch.path.display(), config.tuning);
+    *tuning = config.tuning;
+    if let Ok(mut settings) = settings.get_single_mut()
+    {
+        tuning.mirror_into(&mut settings);
+    }
+}
+
+/// `set <tuning value> <new value>`, e.g. `set move_speed 80`, until the config file next changes
+fn set_tuning_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut tuning: ResMut<GameTuning>,
+    mut settings: Query<&mut GameSettings>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "set"
+        {
+            continue;
+        }
+
+        let [_, field, value] = command.args.as_slice() else
+        {
+            warn!("Server: Usage: set <tuning value> <new value>");
+            continue;
+        };
+
+        let mut changed = *tuning;
+        if let Err(e) = changed.set(field, value).and_then(|()| changed.validate())
+        {
+            warn!("Server: Not setting {field}: {e}");
+            continue;
+        }
+
+        info!("Server: Set {field} to {value}");
+        *tuning = changed;
+        if let Ok(mut settings) = settings.get_single_mut()
+        {
+            tuning.mirror_into(&mut settings);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn file_values_are_used_unless_a_flag_overrides_them() {
+        let path = std::env::temp_dir().join(format!("replicon_test_config_{}.toml", std::process::id()));
+        fs::write(&path, "replication_rate = 20.0\nworld_half_extent = 300.0\nmax_projectiles = 50\n\n[tuning]\nmove_speed = 60.0\n").unwrap();
+
+        let cli = parse_cli_from(["replicon_test_1", "bench", "--config", path.to_str().unwrap(), "--replication-rate", "40"]);
+        let _ = fs::remove_file(&path);
+        let Cli::Bench { server_args, .. } = cli.unwrap() else { panic!("parsed as another command"); };
+
+        assert_eq!(server_args.replication_rate, 40.0);
+        assert_eq!(server_args.world_half_extent, 300.0);
+        assert_eq!(server_args.max_projectiles, 50);
+        assert_eq!(server_args.tuning.move_speed, 60.0);
+        // In neither, so the default
+        assert_eq!(server_args.afk_timeout, DEFAULT_AFK_TIMEOUT);
+    }
+}
@@ -0,0 +1,310 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::{prelude::*, renet::{ClientId, SendType, ServerEvent}, server::ServerSet, RenetSend};
+use serde::{Serialize, Deserialize};
+
+use crate::{Player, anim_state::AnimState, appearance::Appearance, focus::BackgroundClients, shutdown::{ServerAnnouncement, Severity}, web_transport::web_server_send_system};
+
+/// Round trip in milliseconds above which a client's link counts as congested
+const CONGESTED_RTT_MS: f64 = 250.0;
+/// Fraction of packets lost above which a client's link counts as congested
+const CONGESTED_PACKET_LOSS: f64 = 0.1;
+/// Steps of backoff, each congested second takes one more
+const MAX_BACKOFF: u8 = 3;
+/// Clear seconds in a row before backing off one step less
+const RECOVERY_SECONDS: u32 = 5;
+/// Seconds between the frames a backed off client is sent anything, per step of backoff
+const SEND_INTERVAL_PER_LEVEL: f32 = 0.1;
+
+/// Server side, spots clients whose link is struggling from renet's network info and sends them less in steps,
+/// recovering one step per `RECOVERY_SECONDS` of good conditions
+///
+/// A backed off client's packets are dropped on all but one frame every `SEND_INTERVAL_PER_LEVEL` times its level,
+/// the way `FocusPlugin` throttles background clients, and replicon and renet resend whatever it missed. It's sent
+/// no `Appearance` or `AnimState` at all meanwhile: those go to each client as a `Cosmetics` event rather than being
+/// replicated, since replicon builds one update for every client, and a client that recovers is sent them all again.
+pub struct CongestionPlugin;
+
+impl Plugin for CongestionPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ClientBackoff>()
+            .add_server_event::<Cosmetics>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_systems(Update, update_backoff_system.run_if(resource_exists::<RenetServer>()))
+            .add_systems(Update, receive_cosmetics_system.run_if(resource_exists::<RenetClient>()))
+            .add_systems(PostUpdate, send_cosmetics_system.before(ServerSet::Send).run_if(resource_exists::<RenetServer>()))
+            // After replicon has sent this frame's messages, before any transport takes the packets
+            .add_systems(PostUpdate, throttle_congested_system.after(ServerSet::Send).before(web_server_send_system).before(RenetSend).run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+#[derive(Default)]
+pub struct Backoff
+{
+    /// 0 while the link is fine, up to `MAX_BACKOFF`
+    pub level: u8,
+    /// Times the level has gone up from 0 since the client connected
+    pub episodes: u32,
+    clear_seconds: u32,
+    /// Elapsed seconds when the client was last let through the throttle
+    last_sent: f32,
+}
+
+/// Server side backoff state per connected client
+#[derive(Resource, Default)]
+pub struct ClientBackoff(HashMap<ClientId, Backoff>);
+
+impl ClientBackoff
+{
+    pub fn get(&self, client_id: ClientId) -> Option<&Backoff> {
+        self.0.get(&client_id)
+    }
+
+    /// The client's backoff level, 0 for one that has never been congested
+    pub fn level(&self, client_id: ClientId) -> u8 {
+        self.get(client_id).map_or(0, |backoff| backoff.level)
+    }
+}
+
+/// A player's skin and animation state, sent to each client that isn't backed off whenever either changes
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Cosmetics
+{
+    pub player: u64,
+    pub appearance: Appearance,
+    pub anim_state: AnimState,
+}
+
+/// Once a second, steps every client's backoff up while it's congested and down after it has been clear for a while
+fn update_backoff_system(
+    mut backoff: ResMut<ClientBackoff>,
+    mut announcements: EventWriter<ToClients<ServerAnnouncement>>,
+    mut server_events: EventReader<ServerEvent>,
+    mut since_update: Local<f32>,
+    server: Res<RenetServer>,
+    background: Res<BackgroundClients>,
+    time: Res<Time>,
+) {
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            backoff.0.remove(client_id);
+        }
+    }
+
+    *since_update += time.delta_seconds();
+    if *since_update < 1.0
+    {
+        return;
+    }
+    *since_update = 0.0;
+
+    for client_id in server.clients_id()
+    {
+        // Its packets are being dropped on purpose, which renet counts as loss
+        if background.contains(client_id)
+        {
+            continue;
+        }
+
+        let Ok(info) = server.network_info(client_id) else { continue; };
+        let state = backoff.0.entry(client_id).or_default();
+        // Likewise the packets the throttle drops, so while backed off only the round trip tells whether it's clear
+        let lossy = state.level == 0 && info.packet_loss > CONGESTED_PACKET_LOSS;
+        let congested = info.rtt > CONGESTED_RTT_MS || lossy;
+
+        if congested
+        {
+            state.clear_seconds = 0;
+            if state.level == MAX_BACKOFF
+            {
+                continue;
+            }
+
+            if state.level == 0
+            {
+                state.episodes += 1;
+                announcements.send(ToClients {
+                    mode: SendMode::Direct(client_id),
+                    event: ServerAnnouncement { severity: Severity::Info, text: "Reducing detail due to connection quality".to_string() },
+                });
+            }
+            state.level += 1;
+            warn!("Server: Client '{client_id}' is congested (rtt {:.0}ms, {:.0}% loss), backoff {}", info.rtt, info.packet_loss * 100.0, state.level);
+            continue;
+        }
+
+        if state.level == 0
+        {
+            continue;
+        }
+
+        state.clear_seconds += 1;
+        if state.clear_seconds >= RECOVERY_SECONDS
+        {
+            state.clear_seconds = 0;
+            state.level -= 1;
+            info!("Server: Client '{client_id}' connection is recovering, backoff {}", state.level);
+        }
+    }
+}
+
+/// Drops what replicon and the events just queued for backed off clients, except once every
+/// `SEND_INTERVAL_PER_LEVEL` times their level
+fn throttle_congested_system(
+    mut server: ResMut<RenetServer>,
+    mut backoff: ResMut<ClientBackoff>,
+    background: Res<BackgroundClients>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (client_id, state) in &mut backoff.0
+    {
+        // Already throttled harder while in the background
+        if state.level == 0 || background.contains(*client_id)
+        {
+            continue;
+        }
+
+        if now - state.last_sent >= SEND_INTERVAL_PER_LEVEL * state.level as f32
+        {
+            state.last_sent = now;
+            continue;
+        }
+
+        // Fails only for a client that has just disconnected, which is dropped from the map next frame
+        let _ = server.get_packets_to_send(*client_id);
+    }
+}
+
+/// Sends each client that isn't backed off the players whose cosmetics changed, or every player's when it has just
+/// connected or recovered
+fn send_cosmetics_system(
+    mut cosmetics: EventWriter<ToClients<Cosmetics>>,
+    mut server_events: EventReader<ServerEvent>,
+    mut up_to_date: Local<HashSet<ClientId>>,
+    server: Res<RenetServer>,
+    backoff: Res<ClientBackoff>,
+    players: Query<(&Player, Ref<Appearance>, Ref<AnimState>)>,
+) {
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            up_to_date.remove(client_id);
+        }
+    }
+
+    for client_id in server.clients_id()
+    {
+        if backoff.level(client_id) > 0
+        {
+            up_to_date.remove(&client_id);
+            continue;
+        }
+
+        let everything = up_to_date.insert(client_id);
+        for (player, appearance, anim_state) in &players
+        {
+            if everything || appearance.is_changed() || anim_state.is_changed()
+            {
+                cosmetics.send(ToClients {
+                    mode: SendMode::Direct(client_id),
+                    event: Cosmetics { player: player.0, appearance: *appearance, anim_state: *anim_state },
+                });
+            }
+        }
+    }
+}
+
+/// Puts received cosmetics on their player, holding on to any for a player that hasn't been replicated yet
+fn receive_cosmetics_system(
+    mut commands: Commands,
+    mut received: EventReader<Cosmetics>,
+    mut pending: Local<HashMap<u64, Cosmetics>>,
+    players: Query<(Entity, &Player)>,
+) {
+    for update in received.read()
+    {
+        pending.insert(update.player, *update);
+    }
+
+    pending.retain(|id, update| {
+        let Some((entity, _)) = players.iter().find(|(_, player)| player.0 == *id) else { return true; };
+        commands.entity(entity).insert((update.appearance, update.anim_state));
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{Position, bench::{step_session, tests::connected_session}};
+
+    /// Frames in which the client's copy of the player's position moved
+    fn count_moves(client_app: &mut App, player: u64, last: &mut Option<Vec2>) -> u32 {
+        let position = client_app.world.query::<(&Player, &Position)>().iter(&client_app.world)
+            .find_map(|(other, position)| (other.0 == player).then_some(position.0));
+        let moved = position.is_some() && *last != position;
+        *last = position;
+        moved as u32
+    }
+
+    fn appearance_of(client_app: &mut App, player: u64) -> Option<u8> {
+        client_app.world.query::<(&Player, &Appearance)>().iter(&client_app.world)
+            .find_map(|(other, appearance)| (other.0 == player).then_some(appearance.0))
+    }
+
+    #[test]
+    fn congested_client_is_sent_less_and_recovers_in_steps() {
+        // Client 3's player is the one watched, neither watching client predicts it
+        let (mut server_app, mut client_apps) = connected_session(3);
+        let congested_id = ClientId::from_raw(1);
+        let moving = server_app.world.query::<(Entity, &Player)>().iter(&server_app.world)
+            .find_map(|(entity, player)| (player.0 == 3).then_some(entity))
+            .unwrap();
+
+        server_app.world.resource_mut::<ClientBackoff>().0.insert(congested_id, Backoff { level: MAX_BACKOFF, episodes: 1, ..default() });
+        server_app.world.get_mut::<Appearance>(moving).unwrap().0 = 3;
+
+        // 3 seconds at the deepest backoff
+        let (mut congested_moves, mut clear_moves) = (0, 0);
+        let (mut congested_last, mut clear_last) = (None, None);
+        // Back and forth towards the middle, so it never reaches the edge of the world
+        let start = server_app.world.get::<Position>(moving).unwrap().0.x;
+        for frame in 0..180
+        {
+            server_app.world.get_mut::<Position>(moving).unwrap().0.x = start - start.signum() * (frame % 20) as f32;
+            step_session(&mut server_app, &mut client_apps);
+            congested_moves += count_moves(&mut client_apps[0], 3, &mut congested_last);
+            clear_moves += count_moves(&mut client_apps[1], 3, &mut clear_last);
+        }
+        assert!(congested_moves > 0, "the congested client got no updates at all");
+        assert!(congested_moves * 4 < clear_moves, "congested client saw {congested_moves} moves, clear one {clear_moves}");
+        assert_eq!(appearance_of(&mut client_apps[1], 3), Some(3));
+        assert_ne!(appearance_of(&mut client_apps[0], 3), Some(3), "cosmetics reached a backed off client");
+
+        // A link this clear steps down one level per `RECOVERY_SECONDS`, never skipping one
+        let mut levels = vec![MAX_BACKOFF];
+        for _ in 0..(MAX_BACKOFF as u32 * RECOVERY_SECONDS + 2) * 60
+        {
+            step_session(&mut server_app, &mut client_apps);
+            let level = server_app.world.resource::<ClientBackoff>().level(congested_id);
+            if levels.last() != Some(&level)
+            {
+                levels.push(level);
+            }
+        }
+        assert_eq!(levels, [3, 2, 1, 0]);
+
+        for _ in 0..20
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        assert_eq!(appearance_of(&mut client_apps[0], 3), Some(3), "a recovered client wasn't sent the cosmetics it missed");
+    }
+}
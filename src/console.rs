@@ -0,0 +1,192 @@
+use std::{io::BufRead, sync::{Mutex, mpsc::{self, Receiver}}, thread, time::Duration};
+
+use bevy::prelude::*;
+use bevy_replicon::{prelude::*, network_event::client_event, renet::{ClientId, SendType}};
+use serde::{Serialize, Deserialize};
+
+use crate::event_limits::{self, MAX_REMOTE_COMMAND_BYTES};
+
+/// Every command some system handles, anything else is refused with an error instead of silently ignored
+pub const KNOWN_COMMANDS: [&str; 13] = ["repl", "history", "name", "forcestart", "endround", "set", "kick", "bind", "pause", "resume", "flood", "room", "spawn"];
+
+/// Commands typed into the terminal the app was started from, one per line, or sent by a client holding the admin key
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ConsoleCommand>()
+            // Registered on every build, so clients and servers agree on the channel layout
+            .add_client_event_with::<RemoteConsoleCommand, _, _>(
+                SendType::ReliableOrdered { resend_time: Duration::from_millis(300) },
+                client_event::sending_system::<RemoteConsoleCommand>,
+                event_limits::limited_receiving_system::<RemoteConsoleCommand, MAX_REMOTE_COMMAND_BYTES>,
+            )
+            .add_systems(Startup, spawn_stdin_reader)
+            .add_systems(PreUpdate, read_console_system.run_if(resource_exists::<ConsoleInput>()))
+            .add_systems(Update, (receive_remote_commands_system, kick_console_system).chain().run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// A console line split into words, e.g. `repl off position` is `["repl", "off", "position"]`
+#[derive(Event, Debug)]
+pub struct ConsoleCommand
+{
+    pub args: Vec<String>,
+}
+
+impl ConsoleCommand
+{
+    /// Splits and lowercases a typed line, refusing blank lines and commands nothing handles
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let args: Vec<String> = line.split_whitespace().map(str::to_lowercase).collect();
+        let command = Self { args };
+        match command.name()
+        {
+            "" => Err("Empty command".to_string()),
+            name if !KNOWN_COMMANDS.contains(&name) => Err(format!("Unknown command '{name}', try one of: {}", KNOWN_COMMANDS.join(", "))),
+            _ => Ok(command),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.args.first().map_or("", String::as_str)
+    }
+
+    /// Commands about this machine rather than the game, a client runs them itself instead of sending them to the server
+    pub fn is_local(&self) -> bool {
+        matches!(self.name(), "bind" | "flood" | "room")
+    }
+}
+
+/// A console command typed by a client, run by the server only if `key` matches its `--admin-key`
+#[derive(Event, Serialize, Deserialize, Debug)]
+pub struct RemoteConsoleCommand
+{
+    pub key: String,
+    pub args: Vec<String>,
+}
+
+/// Server side, the `--admin-key` clients must send with their console commands, none are accepted without one
+#[derive(Resource)]
+pub struct AdminKey(pub String);
+
+/// Lines read by the stdin thread, waiting to be turned into events
+#[derive(Resource)]
+struct ConsoleInput(Mutex<Receiver<String>>);
+
+fn spawn_stdin_reader(
+    mut commands: Commands,
+) {
+    let (sender, receiver) = mpsc::channel();
+    // Reading stdin blocks, so it gets its own thread that ends along with stdin
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok)
+        {
+            if sender.send(line).is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    commands.insert_resource(ConsoleInput(Mutex::new(receiver)));
+}
+
+fn read_console_system(
+    mut input: ResMut<ConsoleInput>,
+    mut commands: EventWriter<ConsoleCommand>,
+) {
+    let Ok(receiver) = input.0.get_mut() else { return; };
+    for line in receiver.try_iter()
+    {
+        if line.trim().is_empty()
+        {
+            continue;
+        }
+
+        match ConsoleCommand::parse(&line)
+        {
+            Ok(command) => commands.send(command),
+            Err(e) => warn!("{e}"),
+        }
+    }
+}
+
+/// Runs the commands of clients that sent the right admin key, as if typed into the server's terminal
+fn receive_remote_commands_system(
+    mut remote_commands: EventReader<FromClient<RemoteConsoleCommand>>,
+    mut commands: EventWriter<ConsoleCommand>,
+    admin_key: Option<Res<AdminKey>>,
+) {
+    for FromClient { client_id, event } in remote_commands.read()
+    {
+        if admin_key.as_ref().map_or(true, |admin_key| admin_key.0 != event.key)
+        {
+            warn!("Server: Refused console command {:?} from client '{client_id}', wrong or no admin key", event.args);
+            continue;
+        }
+
+        match ConsoleCommand::parse(&event.args.join(" "))
+        {
+            Ok(command) =>
+            {
+                info!("Server: Client '{client_id}' ran console command {:?}", command.args);
+                commands.send(command);
+            }
+            Err(e) => warn!("Server: Client '{client_id}' sent a bad console command: {e}"),
+        }
+    }
+}
+
+/// `kick <client id>` disconnects that client
+fn kick_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut server: ResMut<RenetServer>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "kick"
+        {
+            continue;
+        }
+
+        let Some(client_id) = command.args.get(1).and_then(|id| id.parse().ok()).map(ClientId::from_raw) else
+        {
+            warn!("Server: Usage: kick <client id>");
+            continue;
+        };
+        if !server.is_connected(client_id)
+        {
+            warn!("Server: No client '{client_id}' is connected");
+            continue;
+        }
+
+        info!("Server: Kicking client '{client_id}'");
+        server.disconnect(client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn typed_lines_parse_into_commands() {
+        assert_eq!(ConsoleCommand::parse("spawn bot").unwrap().args, ["spawn", "bot"]);
+        assert_eq!(ConsoleCommand::parse("  SET move_speed   80 ").unwrap().args, ["set", "move_speed", "80"]);
+
+        let kick = ConsoleCommand::parse("kick 5").unwrap();
+        assert_eq!((kick.name(), kick.is_local()), ("kick", false));
+        assert!(ConsoleCommand::parse("bind shoot Q").unwrap().is_local());
+    }
+
+    #[test]
+    fn blank_and_unknown_lines_are_refused() {
+        assert_eq!(ConsoleCommand::parse("").unwrap_err(), "Empty command");
+        assert_eq!(ConsoleCommand::parse("   \t").unwrap_err(), "Empty command");
+        assert!(ConsoleCommand::parse("fly 100").unwrap_err().starts_with("Unknown command 'fly'"));
+    }
+}
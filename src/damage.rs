@@ -0,0 +1,482 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, ecs::entity::{EntityMapper, MapEntities}};
+use bevy_replicon::{prelude::*, renet::{ClientId, SendType}};
+use serde::{Serialize, Deserialize};
+
+use crate::{LocalPlayerId, Player, Position, SIMULATION_RATE, is_headless, pause::SimulationSet, player_state::{KillPlayer, PlayerState}, scoreboard::Score, settings::GameTuning, sfx::{PlaySfx, Sfx}, spawn_protection::Invulnerable, stats::PlayerStats, tick_timer::SimulationTick};
+
+/// How long a floating damage number lives
+const FLOAT_DURATION: f32 = 0.8;
+const FLOAT_SPEED: f32 = 30.0;
+/// How long the hit marker stays on screen after a confirmed hit
+const HIT_MARKER_DURATION: f32 = 0.2;
+/// Seconds damage counts towards an assist, older `DamageLog` entries are dropped
+pub const ASSIST_WINDOW: f32 = 5.0;
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DamageNumberPool>()
+            .add_event::<ApplyDamage>()
+            .replicate::<Health>()
+            .replicate::<LastDamagedBy>()
+            .add_mapped_server_event::<DamageDealt>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_server_event::<HitConfirmed>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_server_event::<PlayerKilled>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_systems(Startup, spawn_hit_marker_system.run_if(not(is_headless)))
+            .add_systems(Update, apply_damage_system.run_if(has_authority()))
+            .add_systems(FixedUpdate, regenerate_health_system.in_set(SimulationSet).run_if(has_authority()))
+            .add_systems(Update, show_hit_marker_system.run_if(not(is_headless)))
+            .add_systems(Update, log_kills_system)
+            .add_systems(Update,
+                (
+                    damage_hit_sfx_system,
+                    spawn_damage_numbers_system.run_if(not(is_headless)),
+                    float_damage_numbers_system.run_if(not(is_headless)),
+                ).chain()
+            );
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Health
+{
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Default for Health
+{
+    fn default() -> Self {
+        Self { current: 100, max: 100 }
+    }
+}
+
+/// Server-side request to damage a player, applied by `apply_damage_system`
+#[derive(Event)]
+pub struct ApplyDamage
+{
+    pub target: Entity,
+    pub amount: u32,
+    /// Player id of whoever dealt the damage
+    pub source: Option<u64>,
+}
+
+/// Player id of whoever damaged this player most recently, replicated so clients can show it
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct LastDamagedBy(pub u64);
+
+/// Server side, when this player last took damage, inserted by their first hit
+#[derive(Component)]
+pub struct Regeneration
+{
+    last_damaged_tick: u32,
+    /// Health regenerated but not yet whole, carried between fixed steps
+    progress: f32,
+}
+
+/// Server side, who damaged this player and when (in elapsed seconds), oldest first and at most `ASSIST_WINDOW` old
+///
+/// Sourceless damage is kept too, so a death to the environment isn't credited to whoever hit them before it.
+#[derive(Component, Default)]
+pub struct DamageLog(Vec<(Option<u64>, f32)>);
+
+impl DamageLog
+{
+    fn record(&mut self, source: Option<u64>, now: f32) {
+        self.0.retain(|(_, at)| now - at <= ASSIST_WINDOW);
+        self.0.push((source, now));
+    }
+
+    /// The killing blow gets the kill and every other recent damager an assist, unless the victim or nobody dealt it
+    pub fn attribute(&self, victim: u64, now: f32) -> (Option<u64>, Vec<u64>) {
+        let mut recent = self.0.iter().rev()
+            .filter(|(_, at)| now - at <= ASSIST_WINDOW)
+            .map(|(source, _)| *source);
+
+        let killer = recent.next().flatten().filter(|killer| *killer != victim);
+        let mut assists: Vec<u64> = recent.flatten()
+            .filter(|source| *source != victim && Some(*source) != killer)
+            .collect();
+        assists.sort_unstable();
+        assists.dedup();
+        (killer, assists)
+    }
+}
+
+/// Broadcast by the server when damage kills a player, with everyone who gets credit for it
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerKilled
+{
+    pub victim: u64,
+    /// `None` when the victim did it to themselves, or nobody else hurt them recently
+    pub killer: Option<u64>,
+    pub assists: Vec<u64>,
+}
+
+/// Broadcast by the server on every hit
+///
+/// Carries the hit position explicitly so it can be shown even when the target isn't replicated to this client.
+/// The server only ever sends this through `ToClients`, on a listen-server replicon re-emits it locally,
+/// so the host renders it once through the same path as every client.
+#[derive(Event, Serialize, Deserialize, Clone, Copy)]
+pub struct DamageDealt
+{
+    pub target: Entity,
+    pub amount: u32,
+    pub position: Vec2,
+}
+
+impl MapEntities for DamageDealt
+{
+    fn map_entities(&mut self, entity_mapper: &mut EntityMapper) {
+        self.target = entity_mapper.get_or_reserve(self.target);
+    }
+}
+
+/// Sent only to the shooter when one of their shots lands
+///
+/// A listen-server shooting is `SERVER_ID`, which replicon delivers through the local event path instead of the network.
+#[derive(Event, Serialize, Deserialize, Clone, Copy)]
+pub struct HitConfirmed
+{
+    /// Player id of whoever was hit
+    pub target: u64,
+    pub damage: u32,
+}
+
+/// The cross shown at screen center for a confirmed hit, with the time it has left
+#[derive(Component)]
+pub struct HitMarker(pub f32);
+
+#[derive(Component)]
+pub struct FloatingNumber
+{
+    pub age: f32,
+}
+
+/// Hidden floating number entities ready for reuse, so rapid fire doesn't allocate a text entity per hit
+#[derive(Resource, Default)]
+pub struct DamageNumberPool
+{
+    free: Vec<Entity>,
+}
+
+/// Server side, the only place `Health` goes down, broadcasts every hit and kills players that reach zero, scoring for the killer
+///
+/// Hits on players with spawn protection are ignored entirely, no damage number or hit confirmation.
+fn apply_damage_system(
+    mut commands: Commands,
+    mut damage_requests: EventReader<ApplyDamage>,
+    mut damage_events: EventWriter<ToClients<DamageDealt>>,
+    mut confirm_events: EventWriter<ToClients<HitConfirmed>>,
+    mut killed_events: EventWriter<ToClients<PlayerKilled>>,
+    mut kill_events: EventWriter<KillPlayer>,
+    mut players: Query<(&Player, &mut Health, &PlayerState, &Position, &mut DamageLog, Has<Invulnerable>)>,
+    mut scores: Query<(&Player, &mut Score)>,
+    mut stats: Query<(&Player, &mut PlayerStats)>,
+    tick: Res<SimulationTick>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+
+    for ApplyDamage { target, amount, source } in damage_requests.read()
+    {
+        let Ok((target_player, mut health, state, pos, mut damage_log, invulnerable)) = players.get_mut(*target) else { continue; };
+        if !state.is_alive() || invulnerable
+        {
+            continue;
+        }
+
+        health.current = health.current.saturating_sub(*amount);
+        info!("Server: {target:?} took {amount} damage from {source:?}, {} health left", health.current);
+
+        damage_log.record(*source, now);
+        commands.entity(*target).insert(Regeneration { last_damaged_tick: tick.0, progress: 0.0 });
+
+        damage_events.send(ToClients {
+            mode: SendMode::Broadcast,
+            event: DamageDealt { target: *target, amount: *amount, position: pos.0 },
+        });
+
+        if let Some(source) = source
+        {
+            if let Some((_, mut source_stats)) = stats.iter_mut().find(|(player, _)| player.0 == *source)
+            {
+                source_stats.hits += 1;
+            }
+            commands.entity(*target).insert(LastDamagedBy(*source));
+            confirm_events.send(ToClients {
+                mode: SendMode::Direct(ClientId::from_raw(*source)),
+                event: HitConfirmed { target: target_player.0, damage: *amount },
+            });
+        }
+
+        if health.current == 0
+        {
+            let (killer, assists) = damage_log.attribute(target_player.0, now);
+            if let Some((_, mut score)) = killer.and_then(|killer| scores.iter_mut().find(|(player, _)| player.0 == killer))
+            {
+                score.0 += 1;
+            }
+            killed_events.send(ToClients {
+                mode: SendMode::Broadcast,
+                event: PlayerKilled { victim: target_player.0, killer, assists },
+            });
+            kill_events.send(KillPlayer(*target));
+            // The next life starts with a clean slate
+            *damage_log = DamageLog::default();
+        }
+    }
+}
+
+/// Server side, heals living players who haven't been damaged for `GameTuning::regen_delay` at `regen_rate`, up to their max
+///
+/// Runs in the fixed step so the rate doesn't depend on frame rate. `Health` is only written when it goes up by a whole
+/// point, so it isn't re-replicated every step. A respawn already starts at full health, so there's nothing to regenerate.
+fn regenerate_health_system(
+    mut players: Query<(&mut Health, &PlayerState, &mut Regeneration)>,
+    tuning: Res<GameTuning>,
+    tick: Res<SimulationTick>,
+    time: Res<Time>,
+) {
+    if tuning.regen_rate <= 0.0
+    {
+        return;
+    }
+    let delay_ticks = (tuning.regen_delay * SIMULATION_RATE as f32) as u32;
+
+    for (mut health, state, mut regeneration) in &mut players
+    {
+        if !state.is_alive() || health.current >= health.max || tick.since(regeneration.last_damaged_tick) < delay_ticks
+        {
+            regeneration.progress = 0.0;
+            continue;
+        }
+
+        regeneration.progress += tuning.regen_rate * time.delta_seconds();
+        let whole = regeneration.progress.floor();
+        if whole >= 1.0
+        {
+            regeneration.progress -= whole;
+            health.current = (health.current + whole as u32).min(health.max);
+        }
+    }
+}
+
+fn log_kills_system(
+    mut killed_events: EventReader<PlayerKilled>,
+) {
+    for PlayerKilled { victim, killer, assists } in killed_events.read()
+    {
+        match killer
+        {
+            Some(killer) => info!("Client: Player {victim} was killed by {killer}, assisted by {assists:?}"),
+            None => info!("Client: Player {victim} died"),
+        }
+    }
+}
+
+fn damage_hit_sfx_system(
+    mut damage_events: EventReader<DamageDealt>,
+    mut confirm_events: EventReader<HitConfirmed>,
+    mut sfx_events: EventWriter<PlaySfx>,
+    local_player: Option<Res<LocalPlayerId>>,
+    players: Query<&Player>,
+) {
+    for _ in confirm_events.read()
+    {
+        sfx_events.send(PlaySfx(Sfx::HitMarker));
+    }
+
+    let Some(local_player) = local_player else { return; };
+
+    for damage in damage_events.read()
+    {
+        if players.get(damage.target).is_ok_and(|player| player.0 == local_player.0)
+        {
+            sfx_events.send(PlaySfx(Sfx::Hit));
+        }
+    }
+}
+
+fn spawn_damage_numbers_system(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageDealt>,
+    mut pool: ResMut<DamageNumberPool>,
+    mut numbers: Query<(&mut Text, &mut Transform, &mut Visibility, &mut FloatingNumber)>,
+) {
+    for damage in damage_events.read()
+    {
+        let text = Text::from_section(damage.amount.to_string(), TextStyle { font_size: 18.0, color: Color::RED, ..default() });
+        let transform = Transform::from_translation(damage.position.extend(2.0));
+
+        let reused = pool.free.pop().and_then(|entity| numbers.get_mut(entity).ok());
+        match reused
+        {
+            Some((mut pooled_text, mut pooled_transform, mut visibility, mut floating)) =>
+            {
+                *pooled_text = text;
+                *pooled_transform = transform;
+                *visibility = Visibility::Visible;
+                floating.age = 0.0;
+            }
+            None =>
+            {
+                commands.spawn((Text2dBundle { text, transform, ..default() }, FloatingNumber { age: 0.0 }));
+            }
+        }
+    }
+}
+
+/// Floats numbers upward and fades them out, returning them to the pool when done
+fn float_damage_numbers_system(
+    mut pool: ResMut<DamageNumberPool>,
+    mut numbers: Query<(Entity, &mut Text, &mut Transform, &mut Visibility, &mut FloatingNumber)>,
+    time: Res<Time>,
+) {
+    for (entity, mut text, mut transform, mut visibility, mut floating) in &mut numbers
+    {
+        if *visibility == Visibility::Hidden
+        {
+            continue;
+        }
+
+        floating.age += time.delta_seconds();
+        if floating.age >= FLOAT_DURATION
+        {
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+            continue;
+        }
+
+        transform.translation.y += FLOAT_SPEED * time.delta_seconds();
+        text.sections[0].style.color.set_a(1.0 - floating.age / FLOAT_DURATION);
+    }
+}
+
+fn spawn_hit_marker_system(
+    mut commands: Commands,
+) {
+    let mut marker = TextBundle::from_section(
+        "X",
+        TextStyle { font_size: 24.0, color: Color::RED, ..default() }
+    ).with_style(Style {
+        position_type: PositionType::Absolute, left: Val::Percent(50.0), top: Val::Percent(50.0), ..default()
+    });
+    marker.visibility = Visibility::Hidden;
+
+    commands.spawn((marker, HitMarker(0.0)));
+}
+
+fn show_hit_marker_system(
+    mut confirm_events: EventReader<HitConfirmed>,
+    mut markers: Query<(&mut Visibility, &mut HitMarker)>,
+    time: Res<Time>,
+) {
+    let confirmed = confirm_events.read().count() > 0;
+
+    for (mut visibility, mut marker) in &mut markers
+    {
+        if confirmed
+        {
+            marker.0 = HIT_MARKER_DURATION;
+        }
+
+        marker.0 -= time.delta_seconds();
+        visibility.set_if_neq(if marker.0 > 0.0 { Visibility::Visible } else { Visibility::Hidden });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::ecs::event::ManualEventReader;
+
+    use super::*;
+    use crate::bench::{step_session, tests::connected_session};
+
+    #[test]
+    fn last_damager_gets_the_kill_and_earlier_ones_assists() {
+        let mut log = DamageLog::default();
+        log.record(Some(2), 0.0);
+        log.record(Some(3), 1.0);
+        log.record(Some(2), 2.0);
+        log.record(Some(3), 3.0);
+        assert_eq!(log.attribute(1, 3.0), (Some(3), vec![2]));
+
+        // Only damage within the window earns credit
+        assert_eq!(log.attribute(1, 1.0 + ASSIST_WINDOW + 0.1), (Some(3), vec![2]));
+        assert_eq!(log.attribute(1, 3.0 + ASSIST_WINDOW + 0.1), (None, vec![]));
+    }
+
+    #[test]
+    fn self_inflicted_and_sourceless_deaths_have_no_killer() {
+        let mut log = DamageLog::default();
+        log.record(Some(2), 0.0);
+        log.record(Some(1), 1.0);
+        assert_eq!(log.attribute(1, 1.0), (None, vec![2]));
+
+        log.record(None, 2.0);
+        assert_eq!(log.attribute(1, 2.0), (None, vec![2]));
+    }
+
+    #[test]
+    fn health_climbs_at_the_regen_rate_once_the_delay_has_passed() {
+        let step = std::time::Duration::from_secs_f64(1.0 / SIMULATION_RATE);
+        let tuning = GameTuning::default();
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<SimulationTick>()
+            .insert_resource(tuning)
+            .add_systems(Update, regenerate_health_system);
+        let player = app.world.spawn((Health { current: 50, max: 100 }, PlayerState::Alive, Regeneration { last_damaged_tick: 0, progress: 0.0 })).id();
+        let run_seconds = |app: &mut App, seconds: f32| {
+            for _ in 0..(seconds * SIMULATION_RATE as f32).round() as u32
+            {
+                app.world.resource_mut::<SimulationTick>().0 += 1;
+                app.world.resource_mut::<Time>().advance_by(step);
+                app.update();
+            }
+            app.world.get::<Health>(player).unwrap().current
+        };
+
+        assert_eq!(run_seconds(&mut app, tuning.regen_delay - 0.1), 50);
+        let after_delay = run_seconds(&mut app, 0.1);
+        let after_two_seconds = run_seconds(&mut app, 2.0);
+        let expected = 2.0 * tuning.regen_rate;
+        assert!((after_two_seconds as f32 - after_delay as f32 - expected).abs() <= 1.0, "{after_delay} to {after_two_seconds} in 2 seconds");
+
+        run_seconds(&mut app, 100.0);
+        assert_eq!(app.world.get::<Health>(player).unwrap().current, 100);
+    }
+
+    #[test]
+    fn only_the_shooter_is_told_their_shot_landed() {
+        let (mut server_app, mut client_apps) = connected_session(3);
+        let target = server_app.world.query::<(Entity, &Player)>().iter(&server_app.world)
+            .find_map(|(entity, player)| (player.0 == 2).then_some(entity))
+            .unwrap();
+        server_app.world.entity_mut(target).remove::<Invulnerable>();
+        server_app.world.send_event(ApplyDamage { target, amount: 10, source: Some(1) });
+
+        let mut readers: Vec<ManualEventReader<HitConfirmed>> = client_apps.iter().map(|_| default()).collect();
+        let mut confirmed = vec![Vec::new(); client_apps.len()];
+        for _ in 0..30
+        {
+            step_session(&mut server_app, &mut client_apps);
+            for ((client_app, reader), confirmed) in client_apps.iter().zip(&mut readers).zip(&mut confirmed)
+            {
+                let events = client_app.world.resource::<Events<HitConfirmed>>();
+                confirmed.extend(reader.read(events).map(|hit| (hit.target, hit.damage)));
+            }
+        }
+
+        assert_eq!(confirmed, [vec![(2, 10)], vec![], vec![]]);
+    }
+}
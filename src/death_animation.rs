@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{Player, is_headless, pause::SimulationSet, player_state::PlayerState, settings::GameTuning};
+
+/// How many times its own size a burst grows to by the time it has faded out
+const BURST_GROWTH: f32 = 2.5;
+
+/// Keeps a dying entity around for `GameTuning::despawn_delay` seconds so clients can show it going, instead of it
+/// vanishing between one frame and the next
+///
+/// Projectiles that hit something, stop at a wall or expire burst where they are and are despawned at the end of it.
+/// Players aren't despawned on death, they burst as they die and stay behind greyed out until they respawn.
+pub struct DeathAnimationPlugin;
+
+impl Plugin for DeathAnimationPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate::<DeathAnimation>()
+            .add_systems(Update, (animate_player_deaths_system, count_down_system.in_set(SimulationSet)).chain().run_if(has_authority()))
+            .add_systems(Update, (spawn_bursts_system, fade_bursts_system).chain().run_if(not(is_headless)));
+    }
+}
+
+/// Seconds left of an entity's death, it takes no part in the game meanwhile
+///
+/// Projectiles with one are left out of hit checks and stop moving, players already are while dead.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DeathAnimation
+{
+    pub remaining: f32,
+}
+
+/// Server side, starts `entity`'s death animation, or despawns it straight away when `GameTuning::despawn_delay` is 0
+pub fn despawn_after_animation(commands: &mut Commands, entity: Entity, tuning: &GameTuning) {
+    if tuning.despawn_delay > 0.0
+    {
+        commands.entity(entity).insert(DeathAnimation { remaining: tuning.despawn_delay });
+    }
+    else
+    {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// A local-only sprite growing and fading over a dying entity, with how long it lasts
+#[derive(Component)]
+struct Burst
+{
+    age: f32,
+    lifetime: f32,
+    scale: Vec3,
+    alpha: f32,
+}
+
+/// Server side, a player's death animation starts as they die rather than before they're despawned
+fn animate_player_deaths_system(
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerState), (With<Player>, Changed<PlayerState>)>,
+    tuning: Res<GameTuning>,
+) {
+    if tuning.despawn_delay <= 0.0
+    {
+        return;
+    }
+
+    for (player, state) in &players
+    {
+        if matches!(state, PlayerState::Dead { .. })
+        {
+            commands.entity(player).insert(DeathAnimation { remaining: tuning.despawn_delay });
+        }
+    }
+}
+
+/// Server side, despawns everything but players once their animation is over, players just lose the component
+pub fn count_down_system(
+    mut commands: Commands,
+    mut dying: Query<(Entity, &mut DeathAnimation, Has<Player>)>,
+    time: Res<Time>,
+) {
+    for (entity, mut animation, is_player) in &mut dying
+    {
+        animation.remaining -= time.delta_seconds();
+        if animation.remaining > 0.0
+        {
+            continue;
+        }
+
+        if is_player
+        {
+            commands.entity(entity).remove::<DeathAnimation>();
+        }
+        else
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// The burst lasts as long as the animation had left when it got here, and takes over from a dying projectile's own sprite
+///
+/// An entity that dies before its sprite is attached gets no burst.
+fn spawn_bursts_system(
+    mut commands: Commands,
+    mut dying: Query<(&DeathAnimation, &Transform, &Sprite, &Handle<Image>, &mut Visibility, Has<Player>), Added<DeathAnimation>>,
+) {
+    for (animation, transform, sprite, texture, mut visibility, is_player) in &mut dying
+    {
+        if !is_player
+        {
+            *visibility = Visibility::Hidden;
+        }
+
+        let burst = Burst { age: 0.0, lifetime: animation.remaining, scale: transform.scale, alpha: sprite.color.a() };
+        let transform = transform.with_translation(transform.translation + Vec3::Z * 0.1);
+        commands.spawn((SpriteBundle { sprite: sprite.clone(), texture: texture.clone(), transform, ..default() }, burst));
+    }
+}
+
+fn fade_bursts_system(
+    mut commands: Commands,
+    mut bursts: Query<(Entity, &mut Burst, &mut Transform, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    for (entity, mut burst, mut transform, mut sprite) in &mut bursts
+    {
+        burst.age += time.delta_seconds();
+        let progress = burst.age / burst.lifetime;
+        if progress >= 1.0
+        {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.scale = burst.scale * (1.0 + (BURST_GROWTH - 1.0) * progress);
+        sprite.color.set_a(burst.alpha * (1.0 - progress));
+    }
+}
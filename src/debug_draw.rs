@@ -0,0 +1,244 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+
+use crate::{Cli, LocalPlayerId, Player, Position, appearance::BaseColor, ball::Ball, hitscan::PLAYER_RADIUS, input_map::{Action, InputBindings}};
+
+const BALL_RADIUS: f32 = 5.0;
+/// How far the drawn position may drift from the server's before a divergence line is shown
+const DIVERGENCE_THRESHOLD: f32 = 1.0;
+/// Positions kept per player for the path overlay
+const PATH_LENGTH: usize = 8;
+const PATH_DOT_RADIUS: f32 = 1.0;
+/// Half the width of the cross on the latest snapshot
+const PATH_CROSS_SIZE: f32 = 3.0;
+
+/// Gizmo overlay of colliders and who owns each entity, enabled with `--debug-draw`, and of where players
+/// are heading, toggled on clients with F4
+pub struct DebugDrawPlugin;
+
+impl Plugin for DebugDrawPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PathOverlay>()
+            .add_systems(Update, debug_draw_system.run_if(debug_draw_enabled))
+            .add_systems(Update,
+                (
+                    toggle_path_overlay_system,
+                    (record_paths_system, draw_paths_system).chain().run_if(path_overlay_enabled),
+                ).chain().run_if(resource_exists::<RenetClient>())
+            );
+    }
+}
+
+/// Whether the F4 path overlay is showing
+#[derive(Resource, Default)]
+pub struct PathOverlay(bool);
+
+/// The player's last `PATH_LENGTH` positions, a fixed ring so recording never allocates
+///
+/// For remote players each is a snapshot received from the server, for the local player each is a predicted step.
+#[derive(Component)]
+struct RecentPositions
+{
+    positions: [Vec2; PATH_LENGTH],
+    len: usize,
+    next: usize,
+}
+
+impl RecentPositions
+{
+    fn new(position: Vec2) -> Self {
+        let mut recent = Self { positions: [Vec2::ZERO; PATH_LENGTH], len: 0, next: 0 };
+        recent.push(position);
+        recent
+    }
+
+    fn push(&mut self, position: Vec2) {
+        self.positions[self.next] = position;
+        self.next = (self.next + 1) % PATH_LENGTH;
+        self.len = (self.len + 1).min(PATH_LENGTH);
+    }
+
+    /// Oldest first
+    fn iter(&self) -> impl Iterator<Item = Vec2> + '_ {
+        let start = (self.next + PATH_LENGTH - self.len) % PATH_LENGTH;
+        (0..self.len).map(move |index| self.positions[(start + index) % PATH_LENGTH])
+    }
+}
+
+/// How an entity's position is being driven on this machine
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetState
+{
+    /// Moved locally ahead of the server (this client's own player)
+    LocalPredicted,
+    /// Eased toward positions received from the server
+    RemoteInterpolated,
+    /// Simulated here, this machine is the server
+    Authoritative,
+}
+
+impl NetState
+{
+    pub fn classify(has_authority: bool, is_local_player: bool) -> Self {
+        if has_authority
+        {
+            NetState::Authoritative
+        }
+        else if is_local_player
+        {
+            NetState::LocalPredicted
+        }
+        else
+        {
+            NetState::RemoteInterpolated
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self
+        {
+            NetState::LocalPredicted => Color::GREEN,
+            NetState::RemoteInterpolated => Color::BLUE,
+            NetState::Authoritative => Color::RED,
+        }
+    }
+}
+
+/// `NetState` of an entity, `player` if it is one, on a machine that is the server when `has_authority`
+fn net_state_of(player: Option<&Player>, local_player: Option<&LocalPlayerId>, has_authority: bool) -> NetState {
+    let is_local_player = player.zip(local_player).is_some_and(|(player, local)| player.0 == local.0);
+    NetState::classify(has_authority, is_local_player)
+}
+
+fn debug_draw_enabled(cli: Res<Cli>) -> bool {
+    matches!(*cli, Cli::Client { debug_draw: true, .. })
+}
+
+fn path_overlay_enabled(overlay: Res<PathOverlay>) -> bool {
+    overlay.0
+}
+
+/// Starts every path afresh when turned on, positions from before it was off would be joined up across the gap
+fn toggle_path_overlay_system(
+    mut commands: Commands,
+    mut overlay: ResMut<PathOverlay>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    recorded: Query<Entity, With<RecentPositions>>,
+) {
+    if !bindings.just_pressed(Action::PathOverlay, &keys)
+    {
+        return;
+    }
+
+    overlay.0 = !overlay.0;
+    if overlay.0
+    {
+        for entity in &recorded
+        {
+            commands.entity(entity).remove::<RecentPositions>();
+        }
+    }
+    info!("Client: Path overlay {}", if overlay.0 { "on" } else { "off" });
+}
+
+/// Records a position whenever it changes, which for remote players only happens when a snapshot arrives
+fn record_paths_system(
+    mut commands: Commands,
+    mut players: Query<(Entity, Ref<Position>, Option<&mut RecentPositions>), With<Player>>,
+) {
+    for (player_entity, position, recent) in &mut players
+    {
+        match recent
+        {
+            Some(mut recent) if position.is_changed() => recent.push(position.0),
+            Some(_) => {}
+            None => { commands.entity(player_entity).insert(RecentPositions::new(position.0)); }
+        }
+    }
+}
+
+/// Remote players get a dot per received snapshot, a cross on the latest and a circle where they're drawn,
+/// the local player a line through its predicted steps, all in the player's own color
+fn draw_paths_system(
+    mut gizmos: Gizmos,
+    players: Query<(&Player, &Position, &Transform, &RecentPositions, Option<&BaseColor>)>,
+    local_player: Res<LocalPlayerId>,
+) {
+    for (player, position, transform, recent, base_color) in &players
+    {
+        let color = base_color.map_or(Color::WHITE, |base_color| base_color.0);
+        if player.0 == local_player.0
+        {
+            gizmos.linestrip_2d(recent.iter(), color);
+            continue;
+        }
+
+        for snapshot in recent.iter()
+        {
+            gizmos.circle_2d(snapshot, PATH_DOT_RADIUS, color);
+        }
+
+        let latest = position.0;
+        gizmos.line_2d(latest - Vec2::splat(PATH_CROSS_SIZE), latest + Vec2::splat(PATH_CROSS_SIZE), color);
+        gizmos.line_2d(latest + Vec2::new(-PATH_CROSS_SIZE, PATH_CROSS_SIZE), latest + Vec2::new(PATH_CROSS_SIZE, -PATH_CROSS_SIZE), color);
+        gizmos.circle_2d(transform.translation.truncate(), PLAYER_RADIUS, color);
+    }
+}
+
+/// Draws each collider colored by `NetState`, plus a line to the server position wherever the drawn sprite has diverged from it
+fn debug_draw_system(
+    mut gizmos: Gizmos,
+    entities: Query<(&Position, &Transform, Option<&Player>, Has<Ball>)>,
+    local_player: Option<Res<LocalPlayerId>>,
+    client: Option<Res<RenetClient>>,
+) {
+    let has_authority = client.is_none();
+
+    for (pos, transform, player, is_ball) in &entities
+    {
+        let color = net_state_of(player, local_player.as_deref(), has_authority).color();
+        let radius = if is_ball { BALL_RADIUS } else { PLAYER_RADIUS };
+
+        let drawn = transform.translation.truncate();
+        gizmos.circle_2d(drawn, radius, color);
+
+        if drawn.distance(pos.0) > DIVERGENCE_THRESHOLD
+        {
+            gizmos.line_2d(drawn, pos.0, Color::YELLOW);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::bench::tests::connected_session;
+
+    /// Every player's id and state as `debug_draw_system` would color it on this machine
+    fn player_states(app: &mut App) -> Vec<(u64, NetState)> {
+        let has_authority = !app.world.contains_resource::<RenetClient>();
+        let local_player = app.world.get_resource::<LocalPlayerId>().map(|local| LocalPlayerId(local.0));
+        let mut states: Vec<(u64, NetState)> = app.world.query::<&Player>().iter(&app.world)
+            .map(|player| (player.0, net_state_of(Some(player), local_player.as_ref(), has_authority)))
+            .collect();
+        states.sort_by_key(|(id, _)| *id);
+        states
+    }
+
+    #[test]
+    fn own_player_is_predicted_and_others_interpolated() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+
+        assert_eq!(player_states(&mut client_apps[0]), [(1, NetState::LocalPredicted), (2, NetState::RemoteInterpolated)]);
+        assert_eq!(player_states(&mut client_apps[1]), [(1, NetState::RemoteInterpolated), (2, NetState::LocalPredicted)]);
+        assert_eq!(player_states(&mut server_app), [(1, NetState::Authoritative), (2, NetState::Authoritative)]);
+
+        // Anything that isn't a player is only ever interpolated on a client, the host's own player is authoritative
+        assert_eq!(net_state_of(None, Some(&LocalPlayerId(1)), false), NetState::RemoteInterpolated);
+        assert_eq!(net_state_of(Some(&Player(0)), Some(&LocalPlayerId(0)), true), NetState::Authoritative);
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{is_headless, settings::{GameSettings, WorldBounds}};
+
+const STAR_COUNT: usize = 60;
+const STAR_SIZE: f32 = 2.0;
+
+/// Background stars laid out from the replicated `GameSettings::world_seed`, purely visual
+pub struct DecorationPlugin;
+
+impl Plugin for DecorationPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, decorate_world_system.run_if(not(is_headless)));
+    }
+}
+
+#[derive(Component)]
+pub struct Decoration;
+
+/// Star positions for a seed, the same on every machine given the same seed and bounds
+pub fn decoration_positions(seed: u64, bounds: WorldBounds, count: usize) -> Vec<Vec2> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let half = bounds.half_extents;
+    (0..count)
+        .map(|_| Vec2::new(rng.gen_range(-half.x..half.x), rng.gen_range(-half.y..half.y)))
+        .collect()
+}
+
+/// Lays the decoration out once the settings arrive, which for a late joiner is with its initial world state
+fn decorate_world_system(
+    mut commands: Commands,
+    settings: Query<&GameSettings, Changed<GameSettings>>,
+    decorations: Query<Entity, With<Decoration>>,
+    bounds: Res<WorldBounds>,
+) {
+    let Ok(settings) = settings.get_single() else { return; };
+
+    for decoration in &decorations
+    {
+        commands.entity(decoration).despawn();
+    }
+
+    for position in decoration_positions(settings.world_seed, *bounds, STAR_COUNT)
+    {
+        commands.spawn((SpriteBundle {
+            sprite: Sprite { color: Color::rgba(1.0, 1.0, 1.0, 0.4), custom_size: Some(Vec2::splat(STAR_SIZE)), ..default() },
+            transform: Transform::from_translation(position.extend(-1.0)),
+            ..default()
+        }, Decoration));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::bench::{step_session, tests::connected_session};
+
+    fn stars(app: &mut App) -> Vec<Vec2> {
+        app.world.query_filtered::<&Transform, With<Decoration>>().iter(&app.world).map(|transform| transform.translation.truncate()).collect()
+    }
+
+    #[test]
+    fn clients_given_the_same_seed_lay_out_the_same_stars() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        // Bench clients are headless, so they're given the system the windowed client runs
+        for client_app in &mut client_apps
+        {
+            client_app.add_systems(Update, decorate_world_system);
+        }
+        server_app.world.query::<&mut GameSettings>().single_mut(&mut server_app.world).world_seed = 1234;
+        for _ in 0..10
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let bounds = *client_apps[0].world.resource::<WorldBounds>();
+        let expected = decoration_positions(1234, bounds, STAR_COUNT);
+        assert_ne!(expected, decoration_positions(1235, bounds, STAR_COUNT));
+        for client_app in &mut client_apps
+        {
+            // Spawned in order, but queries aren't guaranteed to return them that way
+            let mut stars = stars(client_app);
+            let mut expected = expected.clone();
+            stars.sort_by(|a, b| a.x.total_cmp(&b.x));
+            expected.sort_by(|a, b| a.x.total_cmp(&b.x));
+            assert_eq!(stars, expected);
+        }
+    }
+}
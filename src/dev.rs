@@ -0,0 +1,114 @@
+use std::{io::{BufRead, BufReader, Read}, process::{Child, Command, Stdio}, thread, time::Duration};
+
+use crate::Cli;
+
+/// Every window opens at this size, tiled left to right then top to bottom
+const WINDOW_SIZE: (i32, i32) = (640, 360);
+/// Windows per row before starting the next one
+const WINDOWS_PER_ROW: i32 = 2;
+/// How often the children are checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs the `dev` subcommand, returning the server's exit code
+///
+/// Ctrl-C in the terminal reaches every process started from it, the children included, so there's nothing
+/// to forward. When the server exits any way at all, the clients still running are killed.
+pub fn run(cli: Cli) -> i32 {
+    let Cli::Dev { clients, bots, port, ref connection_args } = cli else {
+        unreachable!("dev::run is only called for `Cli::Dev`");
+    };
+    if bots > clients
+    {
+        eprintln!("--bots {bots} is more than the {clients} clients being started, raise --clients too");
+        return 2;
+    }
+    let connection_flags = connection_args.to_flags();
+
+    let mut server = match start("server", 0, &["server".to_string(), "--port".to_string(), port.to_string()], &connection_flags)
+    {
+        Ok(server) => server,
+        Err(e) =>
+        {
+            eprintln!("Couldn't start the server: {e}");
+            return 1;
+        }
+    };
+
+    let mut client_processes = Vec::new();
+    for index in 1..=clients
+    {
+        // Clients sharing an identity file would all claim the same player
+        let mut args = vec!["client".to_string(), "--port".to_string(), port.to_string(), "--identity-file".to_string(), format!("client_identity_{index}.txt")];
+        let bot = index > clients - bots;
+        if bot
+        {
+            args.push("--bot".to_string());
+        }
+        let name = if bot { format!("bot {index}") } else { format!("client {index}") };
+        match start(&name, index as i32, &args, &connection_flags)
+        {
+            Ok(client) => client_processes.push(client),
+            Err(e) => eprintln!("Couldn't start client {index}: {e}"),
+        }
+    }
+
+    let code = loop
+    {
+        match server.try_wait()
+        {
+            Ok(Some(status)) => break status.code().unwrap_or(1),
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) =>
+            {
+                eprintln!("Lost track of the server: {e}");
+                let _ = server.kill();
+                break 1;
+            }
+        }
+    };
+
+    println!("[dev] Server exited with {code}, stopping the clients");
+    for mut client in client_processes
+    {
+        // Already gone if it was closed by hand, which is fine
+        let _ = client.kill();
+        let _ = client.wait();
+    }
+    code
+}
+
+/// Starts this executable with `args`, its window in `slot` of the grid, copying its output here under `name`
+fn start(name: &str, slot: i32, args: &[String], connection_flags: &[String]) -> std::io::Result<Child> {
+    let (width, height) = WINDOW_SIZE;
+    let position = format!("{},{}", (slot % WINDOWS_PER_ROW) * width, (slot / WINDOWS_PER_ROW) * height);
+    let size = format!("{width},{height}");
+
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(args)
+        .args(["--window-pos", position.as_str(), "--window-size", size.as_str()])
+        .args(connection_flags)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(stdout) = child.stdout.take()
+    {
+        forward_lines(name, stdout);
+    }
+    if let Some(stderr) = child.stderr.take()
+    {
+        forward_lines(name, stderr);
+    }
+    Ok(child)
+}
+
+/// Prints each line from `output` prefixed with `name`, on its own thread until the process closes it
+fn forward_lines(name: &str, output: impl Read + Send + 'static) {
+    let prefix = format!("[{name}]");
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines().map_while(Result::ok)
+        {
+            println!("{prefix} {line}");
+        }
+    });
+}
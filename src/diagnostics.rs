@@ -0,0 +1,131 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+
+use crate::{LocalPlayerId, Player, Position};
+
+/// Weight of each new sample in the rolling averages
+const SMOOTHING: f32 = 0.1;
+
+/// Client side numbers for judging how well the netcode is doing
+pub struct NetworkDiagnosticsPlugin;
+
+impl Plugin for NetworkDiagnosticsPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<NetworkDiagnostics>()
+            .add_systems(PreUpdate,
+                measure_prediction_error_system.after(ClientSet::Receive).run_if(resource_exists::<RenetClient>())
+            )
+            .add_systems(PostUpdate, record_predicted_position_system.run_if(resource_exists::<RenetClient>()))
+            .add_systems(Update, send_rate_window_system);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct NetworkDiagnostics
+{
+    /// Rolling average of how far the server's `Position` was from the locally predicted one when it arrived
+    ///
+    /// Includes the distance covered during one round trip while moving, a large error while standing still
+    /// (or one that keeps growing) points to the client and server stepping movement differently.
+    pub prediction_error: f32,
+    /// Where this client had moved its player before the latest replication overwrote it
+    predicted: Option<Vec2>,
+    /// `PlayerMovement` events sent over the last full second
+    pub movement_sent_per_second: f32,
+    /// Sent so far in the current one second window, and how far into the window we are
+    movement_sent: u32,
+    window_elapsed: f32,
+}
+
+impl NetworkDiagnostics
+{
+    pub fn record_correction(&mut self, predicted: Vec2, authoritative: Vec2) {
+        let error = predicted.distance(authoritative);
+        self.prediction_error += (error - self.prediction_error) * SMOOTHING;
+    }
+
+    pub fn record_movement_sent(&mut self) {
+        self.movement_sent += 1;
+    }
+}
+
+fn local_position<'a>(players: &'a Query<(&Player, &Position)>, local_player: &LocalPlayerId) -> Option<&'a Position> {
+    players.iter().find(|(player, _)| player.0 == local_player.0).map(|(_, pos)| pos)
+}
+
+/// Runs once the frame's replication has been applied, comparing it against where prediction had the player
+pub fn measure_prediction_error_system(
+    mut diagnostics: ResMut<NetworkDiagnostics>,
+    players: Query<(&Player, &Position)>,
+    local_player: Res<LocalPlayerId>,
+    tick: Res<RepliconTick>,
+) {
+    if !tick.is_changed()
+    {
+        return;
+    }
+
+    let Some(predicted) = diagnostics.predicted else { return; };
+    let Some(authoritative) = local_position(&players, &local_player) else { return; };
+
+    diagnostics.record_correction(predicted, authoritative.0);
+}
+
+fn record_predicted_position_system(
+    mut diagnostics: ResMut<NetworkDiagnostics>,
+    players: Query<(&Player, &Position)>,
+    local_player: Res<LocalPlayerId>,
+) {
+    diagnostics.predicted = local_position(&players, &local_player).map(|pos| pos.0);
+}
+
+fn send_rate_window_system(
+    mut diagnostics: ResMut<NetworkDiagnostics>,
+    time: Res<Time>,
+) {
+    diagnostics.window_elapsed += time.delta_seconds();
+    if diagnostics.window_elapsed < 1.0
+    {
+        return;
+    }
+
+    diagnostics.movement_sent_per_second = diagnostics.movement_sent as f32 / diagnostics.window_elapsed;
+    diagnostics.movement_sent = 0;
+    diagnostics.window_elapsed = 0.0;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn measured_error_matches_an_injected_divergence() {
+        let mut app = App::new();
+        app
+            .init_resource::<NetworkDiagnostics>()
+            .init_resource::<RepliconTick>()
+            .insert_resource(LocalPlayerId(1))
+            .add_systems(PreUpdate, measure_prediction_error_system)
+            .add_systems(PostUpdate, record_predicted_position_system);
+        let player = app.world.spawn((Player(1), Position(Vec2::ZERO))).id();
+        app.update();
+
+        // Every update lands 5 units from where the player was predicted to be
+        for frame in 0..200
+        {
+            let authoritative = if frame % 2 == 0 { Vec2::new(3.0, 4.0) } else { Vec2::ZERO };
+            app.world.get_mut::<Position>(player).unwrap().0 = authoritative;
+            app.world.resource_mut::<RepliconTick>().set_changed();
+            app.update();
+        }
+        assert!((app.world.resource::<NetworkDiagnostics>().prediction_error - 5.0).abs() < 0.01);
+
+        // Frames without replication aren't corrections, however far the player has moved locally
+        app.world.get_mut::<Position>(player).unwrap().0 = Vec2::new(100.0, 0.0);
+        app.update();
+        assert!((app.world.resource::<NetworkDiagnostics>().prediction_error - 5.0).abs() < 0.01);
+    }
+}
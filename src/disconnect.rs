@@ -0,0 +1,182 @@
+use bevy::{prelude::*, app::AppExit};
+use bevy_replicon::{prelude::*, renet::{DisconnectReason, transport::{NetcodeClientTransport, NetcodeDisconnectReason}}};
+
+use crate::{Cli, ConnectionStatusText, LocalPlayerId, input_map::{Action, InputBindings}, is_headless, reconnect};
+
+/// When the client's own connection drops, says why in words a player understands and offers to reconnect or quit
+pub struct DisconnectPlugin;
+
+impl Plugin for DisconnectPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, spawn_disconnect_screen.run_if(not(is_headless)))
+            .add_systems(Update,
+                (
+                    client_disconnect_reason_system,
+                    disconnect_screen_input_system.run_if(not(is_headless)),
+                ).chain().run_if(resource_exists::<RenetClient>())
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct DisconnectScreen;
+
+#[derive(Component)]
+pub struct DisconnectScreenText;
+
+/// What to tell the player about a dropped connection
+///
+/// Renet's reason says which layer ended it, the transport's says why when it was netcode.
+pub fn describe_disconnect(reason: &DisconnectReason, transport_reason: Option<&NetcodeDisconnectReason>) -> &'static str {
+    match (reason, transport_reason)
+    {
+        (_, Some(NetcodeDisconnectReason::ConnectionDenied)) => "The server refused the connection, it may be full",
+        (_, Some(NetcodeDisconnectReason::ConnectTokenExpired)) => "The connection attempt expired, check the server address",
+        (_, Some(NetcodeDisconnectReason::ConnectionRequestTimedOut)) => "The server didn't answer, it may be down or running a different version or channel layout (protocol mismatch)",
+        (_, Some(NetcodeDisconnectReason::ConnectionResponseTimedOut)) => "The server stopped answering while connecting",
+        (_, Some(NetcodeDisconnectReason::ConnectionTimedOut)) => "Connection timed out, the server stopped responding",
+        (_, Some(NetcodeDisconnectReason::DisconnectedByServer)) | (DisconnectReason::DisconnectedByServer, _) => "Disconnected by the server, kicked by an admin or it shut down",
+        (_, Some(NetcodeDisconnectReason::DisconnectedByClient)) | (DisconnectReason::DisconnectedByClient, _) => "Disconnected",
+        (DisconnectReason::SendChannelError { .. } | DisconnectReason::ReceiveChannelError { .. }, _) => "Too much data was queued on a channel, the connection couldn't keep up",
+        (DisconnectReason::EmptyMessage { .. } | DisconnectReason::PacketSerialization { .. } | DisconnectReason::PacketDeserialization { .. } | DisconnectReason::ReceivedInvalidChannelId { .. }, _) => "The server sent something this client couldn't read, it may be running a different version",
+        _ => "Lost the connection to the server",
+    }
+}
+
+fn spawn_disconnect_screen(
+    mut commands: Commands,
+) {
+    let mut screen = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+        z_index: ZIndex::Global(5),
+        ..default()
+    };
+    screen.visibility = Visibility::Hidden;
+
+    commands.spawn((screen, DisconnectScreen)).with_children(|parent| {
+        parent.spawn((TextBundle::from_section("", TextStyle { font_size: 28.0, color: Color::WHITE, ..default() }), DisconnectScreenText));
+    });
+}
+
+/// Shows why the connection was dropped, e.g. a channel going over `--channel-memory`, instead of silently freezing
+fn client_disconnect_reason_system(
+    client: Res<RenetClient>,
+    transport: Option<Res<NetcodeClientTransport>>,
+    mut status_texts: Query<&mut Text, (With<ConnectionStatusText>, Without<DisconnectScreenText>)>,
+    mut screen_texts: Query<&mut Text, With<DisconnectScreenText>>,
+    mut screens: Query<&mut Visibility, With<DisconnectScreen>>,
+    local_player: Res<LocalPlayerId>,
+    mut shown: Local<bool>,
+) {
+    // Connected again after `--auto-reconnect`, so the reason no longer applies
+    if *shown && client.is_connected()
+    {
+        for mut text in &mut status_texts
+        {
+            text.sections[0].value = format!("Client: {}", local_player.0);
+        }
+        for mut visibility in &mut screens
+        {
+            *visibility = Visibility::Hidden;
+        }
+        *shown = false;
+    }
+
+    if *shown
+    {
+        return;
+    }
+    let Some(reason) = client.disconnect_reason() else { return; };
+    let transport_reason = transport.and_then(|transport| transport.disconnect_reason());
+
+    let message = describe_disconnect(&reason, transport_reason.as_ref());
+    error!("Client: Disconnected: {reason} ({transport_reason:?}), showing: {message}");
+    for mut text in &mut status_texts
+    {
+        text.sections[0].value = format!("Client: {}\nDisconnected: {message}", local_player.0);
+    }
+    for mut text in &mut screen_texts
+    {
+        text.sections[0].value = format!("{message}\n\nPress R to reconnect or Q to quit");
+    }
+    for mut visibility in &mut screens
+    {
+        *visibility = Visibility::Inherited;
+    }
+    *shown = true;
+}
+
+fn disconnect_screen_input_system(
+    mut commands: Commands,
+    mut exit: EventWriter<AppExit>,
+    screens: Query<&Visibility, With<DisconnectScreen>>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    client: Res<RenetClient>,
+    cli: Res<Cli>,
+    network_channels: Res<NetworkChannels>,
+) {
+    let showing = screens.iter().any(|visibility| *visibility != Visibility::Hidden);
+    if !showing || !client.is_disconnected()
+    {
+        return;
+    }
+
+    if bindings.just_pressed(Action::Reconnect, &keys)
+    {
+        reconnect::connect_again(&mut commands, &cli, &network_channels);
+    }
+    else if bindings.just_pressed(Action::LeaveDisconnected, &keys)
+    {
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const TRANSPORT_REASONS: [NetcodeDisconnectReason; 7] = [
+        NetcodeDisconnectReason::ConnectTokenExpired,
+        NetcodeDisconnectReason::ConnectionRequestTimedOut,
+        NetcodeDisconnectReason::ConnectionResponseTimedOut,
+        NetcodeDisconnectReason::ConnectionTimedOut,
+        NetcodeDisconnectReason::ConnectionDenied,
+        NetcodeDisconnectReason::DisconnectedByClient,
+        NetcodeDisconnectReason::DisconnectedByServer,
+    ];
+
+    #[test]
+    fn every_reason_has_its_own_message() {
+        let generic = describe_disconnect(&DisconnectReason::Transport, None);
+        assert!(!generic.is_empty());
+
+        let mut messages: Vec<&str> = TRANSPORT_REASONS.iter()
+            .map(|transport_reason| describe_disconnect(&DisconnectReason::Transport, Some(transport_reason)))
+            .collect();
+        assert!(messages.iter().all(|message| !message.is_empty() && *message != generic), "{messages:?}");
+        messages.sort_unstable();
+        messages.dedup();
+        assert_eq!(messages.len(), TRANSPORT_REASONS.len());
+
+        // Renet's own reasons, when the transport has none to give
+        for reason in [DisconnectReason::DisconnectedByClient, DisconnectReason::DisconnectedByServer]
+        {
+            assert!(!describe_disconnect(&reason, None).is_empty());
+            assert_ne!(describe_disconnect(&reason, None), generic);
+        }
+        assert!(describe_disconnect(&DisconnectReason::DisconnectedByServer, None).contains("kicked"));
+        assert!(describe_disconnect(&DisconnectReason::Transport, Some(&NetcodeDisconnectReason::ConnectionRequestTimedOut)).contains("protocol mismatch"));
+    }
+}
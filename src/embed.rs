@@ -0,0 +1,292 @@
+use std::{error::Error, ffi::OsString, sync::{Arc, Mutex, PoisonError, atomic::{AtomicUsize, Ordering}, mpsc::{self, Receiver, Sender}}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use bevy::prelude::*;
+use bevy_replicon::{prelude::*, renet::ClientId};
+
+use crate::{Cli, ListenPort, ServerArgs, WindowArgs, build_app, config, memory_transport::{LinkConditions, MemoryClientTransport, MemoryServerTransport}, serialization::{self, PositionScaleClaim}, shutdown::{ServerAnnouncement, Severity, ShutdownRequested}};
+
+/// How long `run_server` waits for the server to come up before deciding it never will
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long `ServerHandle::shutdown` waits for the server thread, well past the shutdown's own drain
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a `ServerHandle` call that needs an answer waits for the server's next frame
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Server side, carries out what an embedding app asks of a server started with `run_server`
+pub struct EmbedPlugin;
+
+impl Plugin for EmbedPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, embed_command_system.run_if(resource_exists::<EmbeddedServer>()));
+    }
+}
+
+/// What a `run_server` server is started with
+#[derive(Clone)]
+pub struct ServerSettings
+{
+    /// Localhost UDP port to listen on, 0 lets the OS pick a free one. `None` takes clients over the in-process
+    /// memory transport only, see `ServerHandle::connect_memory_client`
+    pub port: Option<u16>,
+    pub server_args: ServerArgs,
+}
+
+impl ServerSettings
+{
+    /// The settings `server --headless <flags>` would start with, `--port` and `--config` files included
+    pub fn from_flags(flags: impl IntoIterator<Item = impl Into<OsString>>) -> Result<Self, Box<dyn Error>> {
+        let mut args: Vec<OsString> = ["replicon_test_1", "server", "--headless"].into_iter().map(OsString::from).collect();
+        args.extend(flags.into_iter().map(Into::into));
+
+        let Cli::Server { port, server_args, .. } = config::parse_cli_from(args)? else { unreachable!("parsed as a server") };
+        Ok(Self { port: Some(port), server_args })
+    }
+}
+
+/// What a `ServerHandle` asks of its server, carried out by `embed_command_system` on its next frame
+enum EmbedCommand
+{
+    BroadcastNotice(String),
+    ConnectMemoryClient(ClientId, Sender<MemoryClientTransport>),
+    Shutdown,
+}
+
+/// Server side, present in a server started with `run_server`
+///
+/// An embedded server leaves Ctrl-C to the app embedding it, so it installs no signal handler of its own.
+#[derive(Resource)]
+pub struct EmbeddedServer
+{
+    commands: Mutex<Receiver<EmbedCommand>>,
+    connected_clients: Arc<AtomicUsize>,
+    /// Told the bound port once the server is up, then dropped
+    started: Option<Sender<Option<u16>>>,
+}
+
+/// A headless server running on a background thread, from `run_server`
+///
+/// Every method can be called from any thread. Dropping the handle shuts the server down too.
+pub struct ServerHandle
+{
+    commands: Sender<EmbedCommand>,
+    port: Option<u16>,
+    connected_clients: Arc<AtomicUsize>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    /// Dropped after the thread has been shut down, freeing the scale for servers started afterwards
+    _position_scale: PositionScaleClaim,
+}
+
+/// Builds the headless server app and runs it on a background thread, returning once it's listening
+///
+/// ```
+/// use replicon_test::{ServerSettings, run_server};
+///
+/// // Port 0 lets the OS pick a free one
+/// let server = run_server(ServerSettings::from_flags(["--port", "0", "--max-players", "4"]).unwrap());
+/// println!("Connect clients to port {}", server.port().unwrap());
+/// assert_eq!(server.connected_clients(), 0);
+///
+/// server.broadcast_notice("Integration tests starting");
+/// server.shutdown().unwrap();
+/// ```
+///
+/// # Panics
+///
+/// If the server fails to start, e.g. because its port is taken. Its log says why.
+///
+/// If another server from `run_server` is still running with a different `--position-scale`. Replicon's serialize
+/// functions can't see an app's resources, so the scale is one for the whole process.
+pub fn run_server(settings: ServerSettings) -> ServerHandle {
+    let ServerSettings { port, server_args } = settings;
+    let position_scale = serialization::claim_position_scale(server_args.connection_args.position_scale)
+        .unwrap_or_else(|in_use| panic!("Another embedded server is running with --position-scale {in_use}, they must all use the same one"));
+    let cli = Cli::Server {
+        port: port.unwrap_or(0),
+        headless: true,
+        turbo: false,
+        window_args: WindowArgs { window_pos: None, window_size: None },
+        server_args,
+        memory_transport: port.is_none(),
+    };
+
+    let (commands, receiver) = mpsc::channel();
+    let (started, listening) = mpsc::channel();
+    let connected_clients = Arc::new(AtomicUsize::new(0));
+    let embedded = EmbeddedServer { commands: Mutex::new(receiver), connected_clients: connected_clients.clone(), started: Some(started) };
+
+    let thread = thread::Builder::new()
+        .name("replicon_test server".to_string())
+        .spawn(move || {
+            let mut app = build_app(cli);
+            app.insert_resource(embedded);
+            app.run();
+        })
+        .expect("couldn't start the server thread");
+
+    // A server that fails to start panics in `cli_system`, dropping the sender without a word
+    let port = match listening.recv_timeout(STARTUP_TIMEOUT)
+    {
+        Ok(port) => port,
+        Err(e) => panic!("The embedded server didn't start ({e}), its log says why"),
+    };
+
+    ServerHandle { commands, port, connected_clients, thread: Mutex::new(Some(thread)), _position_scale: position_scale }
+}
+
+impl ServerHandle
+{
+    /// The UDP port clients connect to, `None` when the server only takes memory transport clients
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Clients connected as of the server's last frame, including any still waiting in the join queue
+    pub fn connected_clients(&self) -> usize {
+        self.connected_clients.load(Ordering::Relaxed)
+    }
+
+    /// Shows `text` to every connected player, like the server's own announcements
+    pub fn broadcast_notice(&self, text: &str) {
+        // Fails only once the server has stopped, when there's no one left to tell
+        let _ = self.commands.send(EmbedCommand::BroadcastNotice(text.to_string()));
+    }
+
+    /// Opens a memory transport link for a client with `client_id`, `None` if the server listens on a UDP port instead
+    ///
+    /// Insert the transport into the client app along with a `RenetClient` built from the same `ConnectionArgs`, and
+    /// add `MemoryTransportPlugin` to it.
+    pub fn connect_memory_client(&self, client_id: ClientId) -> Option<MemoryClientTransport> {
+        if self.port.is_some()
+        {
+            return None;
+        }
+
+        let (reply, transport) = mpsc::channel();
+        self.commands.send(EmbedCommand::ConnectMemoryClient(client_id, reply)).ok()?;
+        transport.recv_timeout(REPLY_TIMEOUT).ok()
+    }
+
+    /// Shuts the server down the same way Ctrl-C would, and waits up to `SHUTDOWN_TIMEOUT` for its thread to finish
+    ///
+    /// Calling it again once it has returned does nothing.
+    pub fn shutdown(&self) -> Result<(), Box<dyn Error>> {
+        let Some(thread) = self.thread.lock().unwrap_or_else(PoisonError::into_inner).take() else { return Ok(()); };
+        // Fails if the server has already stopped by itself, which is just as good
+        let _ = self.commands.send(EmbedCommand::Shutdown);
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !thread.is_finished()
+        {
+            if Instant::now() >= deadline
+            {
+                return Err(format!("the server thread didn't stop within {}s, left it running", SHUTDOWN_TIMEOUT.as_secs()).into());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        thread.join().map_err(|_| "the server thread panicked".into())
+    }
+}
+
+impl Drop for ServerHandle
+{
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown()
+        {
+            warn!("Embedded server: {e}");
+        }
+    }
+}
+
+/// Reports the port and client count to the `ServerHandle`, and carries out whatever it has asked for since last frame
+fn embed_command_system(
+    mut commands: Commands,
+    mut embedded: ResMut<EmbeddedServer>,
+    mut announcements: EventWriter<ToClients<ServerAnnouncement>>,
+    mut memory_transport: Option<ResMut<MemoryServerTransport>>,
+    server: Res<RenetServer>,
+    port: Option<Res<ListenPort>>,
+) {
+    let embedded = &mut *embedded;
+    if let Some(started) = embedded.started.take()
+    {
+        let _ = started.send(port.map(|port| port.0));
+    }
+    embedded.connected_clients.store(server.clients_id().len(), Ordering::Relaxed);
+
+    let Ok(received) = embedded.commands.get_mut() else { return; };
+    for command in received.try_iter()
+    {
+        match command
+        {
+            EmbedCommand::BroadcastNotice(text) =>
+            {
+                info!("Server: Notice from the embedding app: {text}");
+                announcements.send(ToClients { mode: SendMode::Broadcast, event: ServerAnnouncement { severity: Severity::Info, text } });
+            }
+            EmbedCommand::ConnectMemoryClient(client_id, reply) =>
+            {
+                let Some(transport) = memory_transport.as_mut() else { continue; };
+                let _ = reply.send(transport.connect(client_id, LinkConditions::default()));
+            }
+            EmbedCommand::Shutdown => commands.insert_resource(ShutdownRequested),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::ecs::event::ManualEventReader;
+    use clap::Parser;
+
+    use super::*;
+    use crate::LocalPlayerId;
+
+    const CLIENT_ID: ClientId = ClientId::from_raw(1);
+
+    /// Wall-clock time the client gets to connect, or to hear a notice, before the test gives up
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Updates the client in real time, the server's clock, until `done`
+    fn step_until(client_app: &mut App, mut done: impl FnMut(&mut App) -> bool) {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        while !done(client_app)
+        {
+            assert!(Instant::now() < deadline, "gave up after {}s", WAIT_TIMEOUT.as_secs());
+            client_app.update();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn embedded_server_takes_a_memory_client_and_shuts_down_in_time() {
+        let server = run_server(ServerSettings { port: None, ..ServerSettings::from_flags(["--seed", "1"]).unwrap() });
+        assert_eq!(server.port(), None);
+
+        let cli = Cli::parse_from(["replicon_test_1", "bench"]);
+        let mut client_app = build_app(cli.clone());
+        client_app.finish();
+        client_app.cleanup();
+        let connection_config = cli.connection_args().connection_config(client_app.world.resource::<NetworkChannels>());
+        client_app.world.insert_resource(RenetClient::new(connection_config));
+        client_app.world.insert_resource(server.connect_memory_client(CLIENT_ID).unwrap());
+        client_app.world.insert_resource(LocalPlayerId(CLIENT_ID.raw()));
+
+        step_until(&mut client_app, |_| server.connected_clients() == 1);
+
+        server.broadcast_notice("Hello from the test");
+        let mut notices = ManualEventReader::<ServerAnnouncement>::default();
+        step_until(&mut client_app, |client_app| {
+            let events = client_app.world.resource::<Events<ServerAnnouncement>>();
+            notices.read(events).any(|notice| notice.text == "Hello from the test")
+        });
+
+        let started = Instant::now();
+        server.shutdown().unwrap();
+        assert!(started.elapsed() < SHUTDOWN_TIMEOUT);
+        server.shutdown().unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, network_event::client_event::ClientEventChannel, renet::ClientId};
+use bincode::{DefaultOptions, ErrorKind, Options};
+use serde::de::DeserializeOwned;
+
+use crate::{input_history::DumpInputHistory, room::MAX_ROOM_NAME_CHARS};
+
+/// Largest `PlayerInput` the server will decode, the biggest variant (`Shoot` with a shotgun's five entities) just fits
+pub const MAX_PLAYER_INPUT_BYTES: u64 = 64;
+/// `PlayerMovement` is a `Vec2` and a `u32` sequence
+pub const MAX_PLAYER_MOVEMENT_BYTES: u64 = 16;
+/// `OtherPlayerInput` is a single `bool`
+pub const MAX_OTHER_PLAYER_INPUT_BYTES: u64 = 1;
+/// `AckSpawn` is a single `Entity`
+pub const MAX_ACK_SPAWN_BYTES: u64 = 8;
+/// `ClientFocus` is a single `bool`
+pub const MAX_CLIENT_FOCUS_BYTES: u64 = 1;
+/// `RemoteConsoleCommand` is an admin key and a console line, generous for either but no essay
+pub const MAX_REMOTE_COMMAND_BYTES: u64 = 512;
+/// `RoomRequest` is a room name, its length and up to `MAX_ROOM_NAME_CHARS` characters of up to 4 bytes
+pub const MAX_ROOM_REQUEST_BYTES: u64 = 8 + 4 * MAX_ROOM_NAME_CHARS as u64;
+/// Oversized events a client may send before it's disconnected, a few could be a bug but a stream is an attack
+const MAX_OVERSIZED_EVENTS: u32 = 10;
+
+/// Every event received through `limited_receiving_system`, by the name `--event-limit` knows it by, with its default limit
+pub const LIMITED_EVENTS: [(&str, u64); 7] = [
+    ("PlayerInput", MAX_PLAYER_INPUT_BYTES),
+    ("PlayerMovement", MAX_PLAYER_MOVEMENT_BYTES),
+    ("OtherPlayerInput", MAX_OTHER_PLAYER_INPUT_BYTES),
+    ("AckSpawn", MAX_ACK_SPAWN_BYTES),
+    ("ClientFocus", MAX_CLIENT_FOCUS_BYTES),
+    ("RemoteConsoleCommand", MAX_REMOTE_COMMAND_BYTES),
+    ("RoomRequest", MAX_ROOM_REQUEST_BYTES),
+];
+
+/// Server side, how many oversized events each client has sent
+#[derive(Resource, Default)]
+pub struct OversizedEvents(HashMap<ClientId, u32>);
+
+/// Server side, byte limits from `--event-limit` replacing the ones events were registered with, by event name
+#[derive(Resource, Default)]
+pub struct EventLimits(HashMap<String, u64>);
+
+impl EventLimits
+{
+    pub fn new(overrides: &[(String, u64)]) -> Self {
+        Self(overrides.iter().cloned().collect())
+    }
+
+    fn max_bytes<T>(&self, default: u64) -> u64 {
+        self.0.get(event_name::<T>()).copied().unwrap_or(default)
+    }
+}
+
+/// Parses an `--event-limit` of the form `<event>=<bytes>`, for one of `LIMITED_EVENTS`
+pub fn parse_event_limit(arg: &str) -> Result<(String, u64), String> {
+    let (name, bytes) = arg.split_once('=').ok_or("expected <event>=<bytes>")?;
+    if !LIMITED_EVENTS.iter().any(|(known, _)| *known == name)
+    {
+        let known: Vec<&str> = LIMITED_EVENTS.iter().map(|(known, _)| *known).collect();
+        return Err(format!("unknown event '{name}', expected one of {}", known.join(", ")));
+    }
+
+    let bytes: u64 = bytes.parse().map_err(|e| format!("invalid byte count '{bytes}': {e}"))?;
+    if bytes == 0
+    {
+        return Err("the limit must be at least 1 byte".to_string());
+    }
+    Ok((name.to_string(), bytes))
+}
+
+/// The type's own name without its module path, as `LIMITED_EVENTS` lists it
+fn event_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Why a client event was refused
+#[derive(PartialEq, Debug)]
+enum Rejection
+{
+    /// Longer than the limit, or claiming a string or collection that would be
+    Oversized,
+    /// Within the limit but not a valid event, more likely a bug or a version mismatch than an attack
+    Malformed,
+}
+
+/// Decodes one event message, refusing it unread if it's over `max_bytes`
+fn decode_limited<T: DeserializeOwned>(message: &[u8], max_bytes: u64) -> Result<T, Rejection> {
+    if message.len() as u64 > max_bytes
+    {
+        return Err(Rejection::Oversized);
+    }
+
+    // Matches the encoding of replicon's sending side (`bincode::serialize`)
+    let options = DefaultOptions::new().with_fixint_encoding().allow_trailing_bytes().with_limit(max_bytes);
+    options.deserialize(message).map_err(|e| match *e
+    {
+        ErrorKind::SizeLimit => Rejection::Oversized,
+        _ => Rejection::Malformed,
+    })
+}
+
+/// Drop-in for replicon's client event receiving system that refuses messages over `MAX_BYTES`, or over the
+/// `--event-limit` given for `T`
+///
+/// The length is checked before decoding, and decoding is capped too, so a small message claiming a huge
+/// collection can't make the server allocate for it. Only oversized events count toward disconnecting the
+/// client, malformed ones are logged and dropped. Register with `add_client_event_with`.
+pub fn limited_receiving_system<T: Event + DeserializeOwned, const MAX_BYTES: u64>(
+    mut client_events: EventWriter<FromClient<T>>,
+    mut server: ResMut<RenetServer>,
+    mut oversized: ResMut<OversizedEvents>,
+    mut dumps: EventWriter<DumpInputHistory>,
+    channel: Res<ClientEventChannel<T>>,
+    limits: Res<EventLimits>,
+) {
+    let max_bytes = limits.max_bytes::<T>(MAX_BYTES);
+
+    for client_id in server.clients_id()
+    {
+        while let Some(message) = server.receive_message(client_id, *channel)
+        {
+            match decode_limited(&message, max_bytes)
+            {
+                Ok(event) => client_events.send(FromClient { client_id, event }),
+                Err(Rejection::Malformed) =>
+                {
+                    warn!("Server: Dropped a malformed {} byte {} from client '{client_id}'", message.len(), event_name::<T>());
+                }
+                Err(Rejection::Oversized) =>
+                {
+                    let count = oversized.0.entry(client_id).or_default();
+                    *count += 1;
+                    warn!("Server: Dropped an oversized {} byte {} from client '{client_id}' (limit {max_bytes})", message.len(), event_name::<T>());
+
+                    if *count >= MAX_OVERSIZED_EVENTS
+                    {
+                        warn!("Server: Disconnecting client '{client_id}' after {count} oversized events");
+                        server.disconnect(client_id);
+                        dumps.send(DumpInputHistory(Some(client_id)));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use serde::{Serialize, Deserialize};
+
+    use super::*;
+    use crate::{PlayerInput, PlayerMovement, console::RemoteConsoleCommand, focus::ClientFocus, room::RoomRequest, spawn_ack::AckSpawn, OtherPlayerInput};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Note
+    {
+        text: String,
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+        bincode::serialize(value).unwrap()
+    }
+
+    fn rejection<T: DeserializeOwned>(message: &[u8], max_bytes: u64) -> Option<Rejection> {
+        decode_limited::<T>(message, max_bytes).err()
+    }
+
+    #[test]
+    fn events_within_the_limit_decode() {
+        let message = encode(&PlayerMovement { direction: Vec2::X, sequence: 7 });
+        let movement = decode_limited::<PlayerMovement>(&message, MAX_PLAYER_MOVEMENT_BYTES).unwrap();
+        assert_eq!((movement.direction, movement.sequence), (Vec2::X, 7));
+
+        let note = Note { text: "hello".to_string() };
+        assert_eq!(decode_limited::<Note>(&encode(&note), 64), Ok(note));
+    }
+
+    #[test]
+    fn oversized_event_is_rejected() {
+        let message = encode(&Note { text: "x".repeat(100) });
+        assert_eq!(rejection::<Note>(&message, 64), Some(Rejection::Oversized));
+    }
+
+    #[test]
+    fn short_event_claiming_a_long_string_is_oversized() {
+        // Just a length prefix, claiming far more than the limit allows
+        let message = 1_000_000u64.to_le_bytes();
+        assert_eq!(rejection::<Note>(&message, 64), Some(Rejection::Oversized));
+    }
+
+    #[test]
+    fn garbage_within_the_limit_is_malformed() {
+        // No `PlayerInput` variant has this index
+        let message = 200u32.to_le_bytes();
+        assert_eq!(rejection::<PlayerInput>(&message, MAX_PLAYER_INPUT_BYTES), Some(Rejection::Malformed));
+        assert_eq!(rejection::<PlayerMovement>(&[1, 2, 3], MAX_PLAYER_MOVEMENT_BYTES), Some(Rejection::Malformed));
+    }
+
+    #[test]
+    fn limits_can_be_overridden_by_event_name() {
+        let limits = EventLimits::new(&[parse_event_limit("PlayerInput=128").unwrap()]);
+        assert_eq!(limits.max_bytes::<PlayerInput>(MAX_PLAYER_INPUT_BYTES), 128);
+        assert_eq!(limits.max_bytes::<PlayerMovement>(MAX_PLAYER_MOVEMENT_BYTES), MAX_PLAYER_MOVEMENT_BYTES);
+
+        assert!(parse_event_limit("Nonsense=10").is_err());
+        assert!(parse_event_limit("PlayerInput=0").is_err());
+        assert!(parse_event_limit("PlayerInput").is_err());
+    }
+
+    #[test]
+    fn limited_events_are_listed_by_their_type_names() {
+        let names = [
+            event_name::<PlayerInput>(),
+            event_name::<PlayerMovement>(),
+            event_name::<OtherPlayerInput>(),
+            event_name::<AckSpawn>(),
+            event_name::<ClientFocus>(),
+            event_name::<RemoteConsoleCommand>(),
+            event_name::<RoomRequest>(),
+        ];
+        assert_eq!(names, LIMITED_EVENTS.map(|(name, _)| name));
+    }
+}
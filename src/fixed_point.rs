@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+
+/// Fixed-point steps per world unit, a power of two so every step converts to and from `f32` exactly
+///
+/// `f32` holds every multiple of 1/4096 exactly out to 4096 world units, far past any `--world-half-extent` in use.
+pub const FIXED_ONE: i32 = 1 << 12;
+
+/// A world position or direction as whole `FIXED_ONE`ths of a unit
+pub fn to_fixed(value: Vec2) -> IVec2 {
+    let scale = |value: f32| (value * FIXED_ONE as f32).round() as i32;
+    IVec2::new(scale(value.x), scale(value.y))
+}
+
+pub fn from_fixed(value: IVec2) -> Vec2 {
+    value.as_vec2() / FIXED_ONE as f32
+}
+
+/// One fixed tick of movement, all in integers: `direction` (a unit vector or shorter) at `speed` world units
+/// per second, where a second is `ticks_per_second` ticks
+///
+/// Rounds toward zero, so movement is the same in every direction, just a hair slower than the float version.
+pub fn step(position: IVec2, direction: IVec2, speed: i32, ticks_per_second: i32) -> IVec2 {
+    let moved = |direction: i32| (direction as i64 * speed as i64 / (FIXED_ONE as i64 * ticks_per_second as i64)) as i32;
+    position + IVec2::new(moved(direction.x), moved(direction.y))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{settings::GameSettings, step_position};
+
+    /// Moves a player through `steps` fixed ticks of seeded wandering, each tick's frame `delta` long
+    fn wander(steps: usize, delta: impl Fn(usize) -> f32) -> Vec<(u32, u32)> {
+        let settings = GameSettings { deterministic: true, ..default() };
+        let mut rng = ChaCha8Rng::seed_from_u64(632);
+        let mut position = Vec2::new(12.5, -40.0);
+        (0..steps)
+            .map(|index| {
+                let direction = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)) * rng.gen_range(0.0..=1.0);
+                position = step_position(position, direction, &settings, delta(index));
+                (position.x.to_bits(), position.y.to_bits())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_inputs_give_bit_identical_positions_whatever_the_frame_times() {
+        let steady = wander(600, |_| 1.0 / 60.0);
+        assert_eq!(steady, wander(600, |_| 1.0 / 60.0));
+        // Fixed-point movement ignores how long the frame took, only the tick counts
+        assert_eq!(steady, wander(600, |index| if index % 3 == 0 { 1.0 / 45.0 } else { 1.0 / 75.0 }));
+        assert_ne!(steady.first(), steady.last(), "the player never moved");
+    }
+}
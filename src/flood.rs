@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+
+use crate::{PlayerInput, console::ConsoleCommand, reconcile::PendingMovement};
+
+/// Seconds `flood` keeps the reliable channel full, also how far back before it the latency is compared against
+const FLOOD_SECONDS: f32 = 3.0;
+
+/// Reliable events sent every frame when `flood` is given no count
+const DEFAULT_EVENTS_PER_FRAME: usize = 100;
+
+/// How many times its latency before a flood movement may take during it and still count as flat
+const LATENCY_TOLERANCE: f32 = 1.25;
+
+/// Milliseconds of slack on top of `LATENCY_TOLERANCE`, replication updates arriving a frame apart aren't a change
+const LATENCY_SLACK_MS: f32 = 20.0;
+
+/// `flood [events per frame]` fills the reliable input channel for a few seconds, then reports whether movement
+/// latency stayed flat meanwhile
+///
+/// Movement has its own unreliable channel, so a backed up reliable channel shouldn't hold it up. Latency is the time
+/// from sending a movement to the server acknowledging it, so keep moving (or at least connected) while it runs.
+pub struct FloodPlugin;
+
+impl Plugin for FloodPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (flood_console_system, flood_system).chain().run_if(resource_exists::<RenetClient>()));
+    }
+}
+
+/// Client side, a flood in progress
+#[derive(Resource)]
+struct Flood
+{
+    events_per_frame: usize,
+    started: f32,
+}
+
+/// `flood [events per frame]`, a client command that always runs on the machine it was typed on
+fn flood_console_system(
+    mut commands: Commands,
+    mut console: EventReader<ConsoleCommand>,
+    flood: Option<Res<Flood>>,
+    time: Res<Time>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "flood"
+        {
+            continue;
+        }
+        if flood.is_some()
+        {
+            warn!("Client: Already flooding");
+            continue;
+        }
+
+        let events_per_frame = match command.args.get(1).map(|count| count.parse::<usize>())
+        {
+            None => DEFAULT_EVENTS_PER_FRAME,
+            Some(Ok(count)) if count > 0 => count,
+            _ =>
+            {
+                warn!("Usage: flood [events per frame]");
+                continue;
+            }
+        };
+        info!("Client: Flooding the reliable channel with {events_per_frame} events a frame for {FLOOD_SECONDS}s");
+        commands.insert_resource(Flood { events_per_frame, started: time.elapsed_seconds() });
+    }
+}
+
+/// Sends the flood's keepalives, which the server reads and ignores, then compares movement latency with before it
+fn flood_system(
+    mut commands: Commands,
+    mut inputs: EventWriter<PlayerInput>,
+    flood: Option<Res<Flood>>,
+    pending: Res<PendingMovement>,
+    time: Res<Time>,
+) {
+    let Some(flood) = flood else { return; };
+    let now = time.elapsed_seconds();
+    if now < flood.started + FLOOD_SECONDS
+    {
+        inputs.send_batch(std::iter::repeat(PlayerInput::None).take(flood.events_per_frame));
+        return;
+    }
+    commands.remove_resource::<Flood>();
+
+    let before = pending.mean_latency_millis(flood.started - FLOOD_SECONDS, flood.started);
+    let during = pending.mean_latency_millis(flood.started, now);
+    match (before, during)
+    {
+        (Some(before), Some(during)) if latency_stayed_flat(before, during) =>
+            info!("Client: Flood over, movement latency stayed flat, {before:.0}ms before and {during:.0}ms during"),
+        (Some(before), Some(during)) =>
+            warn!("Client: Flood over, movement latency went from {before:.0}ms to {during:.0}ms, the reliable channel is holding movement up"),
+        _ => warn!("Client: Flood over, but no movement was acknowledged before or during it to compare"),
+    }
+}
+
+fn latency_stayed_flat(before: f32, during: f32) -> bool {
+    during <= before * LATENCY_TOLERANCE + LATENCY_SLACK_MS
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn latency_within_tolerance_is_flat() {
+        assert!(latency_stayed_flat(50.0, 50.0));
+        assert!(latency_stayed_flat(50.0, 40.0));
+        assert!(latency_stayed_flat(50.0, 50.0 * LATENCY_TOLERANCE + LATENCY_SLACK_MS));
+        assert!(!latency_stayed_flat(50.0, 50.0 * LATENCY_TOLERANCE + LATENCY_SLACK_MS + 1.0));
+        assert!(!latency_stayed_flat(50.0, 300.0));
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap, window::WindowFocused};
+use bevy_replicon::{prelude::*, network_event::client_event, renet::{ClientId, SendType, ServerEvent}, server::ServerSet, RenetSend};
+use serde::{Serialize, Deserialize};
+
+use crate::{Player, bot::Bot, event_limits::{self, MAX_CLIENT_FOCUS_BYTES}, reconnect::ClientIdentity, resync::{self, ResyncGranted, ResyncRequests}, web_transport::web_server_send_system};
+
+/// Seconds between the frames a background client is sent anything at all
+const BACKGROUND_SEND_INTERVAL: f32 = 0.25;
+
+/// Seconds in the background after which regaining focus gets the client a resync, shorter alt-tabs only go back
+/// to full rate
+const RESYNC_AFTER: f32 = 2.0;
+
+/// Clients tell the server when their window loses or regains focus, and the server sends background clients less
+///
+/// Replicon builds the same updates for every client, so the throttling happens after it: a background client's
+/// packets are dropped on all but one frame every `BACKGROUND_SEND_INTERVAL`. Nothing is lost for good, replicon
+/// resends whatever changed since the client's last acknowledged update, and renet resends unacknowledged reliable
+/// messages. When focus comes back after `RESYNC_AFTER`, the server grants a resync (within its usual cooldown) so
+/// the client starts again from the full world rather than whatever the throttled updates left it with.
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<BackgroundClients>()
+            .add_client_event_with::<ClientFocus, _, _>(
+                SendType::ReliableOrdered { resend_time: Duration::from_millis(300) },
+                client_event::sending_system::<ClientFocus>,
+                event_limits::limited_receiving_system::<ClientFocus, MAX_CLIENT_FOCUS_BYTES>,
+            )
+            // A bot plays the same with its window in the background, so it never asks to be sent less
+            .add_systems(Update, report_focus_system.run_if(resource_exists::<RenetClient>()).run_if(not(resource_exists::<Bot>())))
+            .add_systems(Update, receive_focus_system.run_if(resource_exists::<RenetServer>()))
+            // After replicon has sent this frame's messages, before any transport takes the packets
+            .add_systems(PostUpdate, throttle_background_system.after(ServerSet::Send).before(web_server_send_system).before(RenetSend).run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// Sent whenever the client's window gains or loses focus, minimizing it loses focus too
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ClientFocus
+{
+    pub focused: bool,
+}
+
+/// Server side, a client whose window is in the background, with the elapsed seconds it went there and was last sent packets
+struct Background
+{
+    since: f32,
+    last_sent: f32,
+}
+
+/// Server side, clients whose window is in the background
+#[derive(Resource, Default)]
+pub struct BackgroundClients(HashMap<ClientId, Background>);
+
+impl BackgroundClients
+{
+    pub fn contains(&self, client_id: ClientId) -> bool {
+        self.0.contains_key(&client_id)
+    }
+}
+
+fn report_focus_system(
+    mut focus_events: EventReader<WindowFocused>,
+    mut focus_writer: EventWriter<ClientFocus>,
+    client: Res<RenetClient>,
+) {
+    // Only the latest matters, alt-tabbing quickly sends a burst
+    let Some(focused) = focus_events.read().last().map(|event| event.focused) else { return; };
+    if client.is_connected()
+    {
+        focus_writer.send(ClientFocus { focused });
+    }
+}
+
+fn receive_focus_system(
+    mut focus_reader: EventReader<FromClient<ClientFocus>>,
+    mut server_events: EventReader<ServerEvent>,
+    mut background: ResMut<BackgroundClients>,
+    mut grants: EventWriter<ToClients<ResyncGranted>>,
+    mut requests: ResMut<ResyncRequests>,
+    players: Query<(&Player, Option<&ClientIdentity>)>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for FromClient { client_id, event } in focus_reader.read()
+    {
+        if !event.focused
+        {
+            if !background.contains(*client_id)
+            {
+                info!("Server: Client '{client_id}' is now in the background, sending it less");
+                background.0.insert(*client_id, Background { since: now, last_sent: now });
+            }
+            continue;
+        }
+
+        let Some(left) = background.0.remove(client_id) else { continue; };
+        let away = now - left.since;
+        info!("Server: Client '{client_id}' is now in the foreground after {away:.1}s, back to full rate");
+        if away < RESYNC_AFTER
+        {
+            continue;
+        }
+
+        match requests.try_grant(resync::resync_key(&players, *client_id), now)
+        {
+            Ok(_) =>
+            {
+                info!("Server: Resyncing client '{client_id}' now it's back in the foreground");
+                grants.send(ToClients { mode: SendMode::Direct(*client_id), event: ResyncGranted });
+            }
+            Err(since) => info!("Server: Not resyncing client '{client_id}', it had one {since:.1} seconds ago"),
+        }
+    }
+
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            background.0.remove(client_id);
+        }
+    }
+}
+
+/// Drops what replicon and the events just queued for background clients, except once every `BACKGROUND_SEND_INTERVAL`
+fn throttle_background_system(
+    mut server: ResMut<RenetServer>,
+    mut background: ResMut<BackgroundClients>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (client_id, client) in &mut background.0
+    {
+        if now - client.last_sent >= BACKGROUND_SEND_INTERVAL
+        {
+            client.last_sent = now;
+            continue;
+        }
+
+        // Fails only for a client that has just disconnected, which is dropped from the map next frame
+        let _ = server.get_packets_to_send(*client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::ecs::event::ManualEventReader;
+
+    use super::*;
+    use crate::{Position, bench::{step_session, tests::connected_session}};
+
+    /// Frames in which the client's copy of the player's position moved
+    fn count_moves(client_app: &mut App, player: u64, last: &mut Option<Vec2>) -> u32 {
+        let position = client_app.world.query::<(&Player, &Position)>().iter(&client_app.world)
+            .find_map(|(other, position)| (other.0 == player).then_some(position.0));
+        let moved = position.is_some() && *last != position;
+        *last = position;
+        moved as u32
+    }
+
+    #[test]
+    fn background_client_is_sent_less_and_resynced_on_return() {
+        // Client 3's player is the one watched, neither watching client predicts it
+        let (mut server_app, mut client_apps) = connected_session(3);
+        let background_id = ClientId::from_raw(1);
+        let moving = server_app.world.query::<(Entity, &Player)>().iter(&server_app.world)
+            .find_map(|(entity, player)| (player.0 == 3).then_some(entity))
+            .unwrap();
+
+        client_apps[0].world.send_event(ClientFocus { focused: false });
+        for _ in 0..10
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        assert!(server_app.world.resource::<BackgroundClients>().contains(background_id));
+
+        // 3 seconds, past `RESYNC_AFTER`
+        let (mut background_moves, mut foreground_moves) = (0, 0);
+        let (mut background_last, mut foreground_last) = (None, None);
+        // Back and forth towards the middle, so it never reaches the edge of the world
+        let start = server_app.world.get::<Position>(moving).unwrap().0.x;
+        for frame in 0..180
+        {
+            server_app.world.get_mut::<Position>(moving).unwrap().0.x = start - start.signum() * (frame % 20) as f32;
+            step_session(&mut server_app, &mut client_apps);
+            background_moves += count_moves(&mut client_apps[0], 3, &mut background_last);
+            foreground_moves += count_moves(&mut client_apps[1], 3, &mut foreground_last);
+        }
+        assert!(background_moves > 0, "the background client got no updates at all");
+        assert!(background_moves * 4 < foreground_moves, "background client saw {background_moves} moves, foreground {foreground_moves}");
+
+        let mut grants = ManualEventReader::<ToClients<ResyncGranted>>::default();
+        grants.clear(server_app.world.resource::<Events<ToClients<ResyncGranted>>>());
+        client_apps[0].world.send_event(ClientFocus { focused: true });
+        let mut resynced = Vec::new();
+        for _ in 0..20
+        {
+            step_session(&mut server_app, &mut client_apps);
+            let events = server_app.world.resource::<Events<ToClients<ResyncGranted>>>();
+            resynced.extend(grants.read(events).map(|grant| matches!(grant.mode, SendMode::Direct(client_id) if client_id == background_id)));
+        }
+        assert!(!server_app.world.resource::<BackgroundClients>().contains(background_id));
+        assert_eq!(resynced, [true]);
+    }
+}
@@ -0,0 +1,415 @@
+use bevy::prelude::*;
+use bevy_replicon::{prelude::*, renet::ClientId};
+use serde::{Serialize, Deserialize};
+
+use crate::{Cli, Player, PlayerInput, SERVER_ID, SIMULATION_RATE, console::ConsoleCommand, has_local_player, input_map::{Action, InputBindings}, is_headless, pause::{SimulationSet, simulation_running}, shutdown::{ServerAnnouncement, Severity}, tick_timer::SimulationTick};
+
+/// Seconds counted down before a round starts
+pub const COUNTDOWN_SECS: u32 = 3;
+/// Players that must be connected and ready before the countdown starts by itself
+const MIN_READY_PLAYERS: usize = 2;
+
+/// Lobby -> countdown -> playing, driven by the server and derived from `SimulationTick` everywhere
+pub struct GamePhasePlugin;
+
+impl Plugin for GamePhasePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate::<GamePhase>()
+            .replicate::<Ready>()
+            .add_event::<RoundEnded>()
+            .add_systems(Startup, (spawn_countdown_text_system, spawn_ready_list_system).run_if(not(is_headless)))
+            .add_systems(Update, ready_input_system.run_if(has_local_player))
+            .add_systems(Update,
+                (
+                    toggle_ready_system,
+                    start_countdown_hotkey_system,
+                    force_start_console_system,
+                    end_round_console_system,
+                    ready_check_system,
+                    finish_countdown_system.in_set(SimulationSet),
+                ).chain().run_if(resource_exists::<RenetServer>())
+            )
+            .add_systems(Update, (update_countdown_text_system.run_if(simulation_running), update_ready_list_system).run_if(not(is_headless)));
+    }
+}
+
+/// Lives on the game-state singleton entity
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum GamePhase
+{
+    #[default]
+    Lobby,
+    /// Counting down to `Playing`, every peer computes the displayed number from `start_tick` and its `SimulationTick`
+    Countdown { start_tick: u32 },
+    Playing,
+}
+
+impl GamePhase
+{
+    /// Whole seconds left in the countdown at `tick`, `None` outside a countdown
+    ///
+    /// Based only on simulation steps so every client shows the same number for the same tick, regardless of frame rate
+    /// or how often replication was sent.
+    pub fn countdown_remaining(&self, tick: SimulationTick) -> Option<u32> {
+        let GamePhase::Countdown { start_tick } = self else { return None; };
+
+        let elapsed_secs = (tick.since(*start_tick) as f64 / SIMULATION_RATE) as u32;
+        Some(COUNTDOWN_SECS.saturating_sub(elapsed_secs))
+    }
+
+    /// Gameplay inputs are refused until the countdown has finished
+    pub fn inputs_locked(&self) -> bool {
+        matches!(self, GamePhase::Countdown { .. })
+    }
+}
+
+/// Server side, sent when a round ends and everyone goes back to the lobby
+#[derive(Event)]
+pub struct RoundEnded;
+
+/// Whether a player has readied up in the lobby, toggled with R
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct Ready(pub bool);
+
+/// The replicated phase, or `Lobby` before the singleton has arrived
+pub fn current_phase(phases: &Query<&GamePhase>) -> GamePhase {
+    phases.get_single().copied().unwrap_or_default()
+}
+
+#[derive(Component)]
+pub struct CountdownText;
+
+/// Every player and whether they're ready, shown until the round starts
+#[derive(Component)]
+pub struct ReadyListText;
+
+/// The ready key (R) toggles whether this player is ready, only before the round has started
+fn ready_input_system(
+    mut input_writer: EventWriter<PlayerInput>,
+    phases: Query<&GamePhase>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    if bindings.just_pressed(Action::Ready, &input) && current_phase(&phases) != GamePhase::Playing
+    {
+        input_writer.send(PlayerInput::ToggleReady);
+    }
+}
+
+/// Server side, applies `PlayerInput::ToggleReady` to the sender's player
+fn toggle_ready_system(
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    mut players: Query<(&Player, &mut Ready)>,
+    phases: Query<&GamePhase>,
+) {
+    let phase = current_phase(&phases);
+    for FromClient { client_id, event } in input_reader.read()
+    {
+        if !matches!(event, PlayerInput::ToggleReady) || phase == GamePhase::Playing
+        {
+            continue;
+        }
+
+        let Some((_, mut ready)) = players.iter_mut().find(|(player, _)| ClientId::from_raw(player.0) == *client_id) else { continue; };
+        ready.0 = !ready.0;
+        info!("Server: Client '{client_id}' is {}", if ready.0 { "ready" } else { "not ready" });
+    }
+}
+
+/// Server side, `forcestart` starts the countdown whoever is ready
+fn force_start_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut phases: Query<&mut GamePhase>,
+    tick: Res<SimulationTick>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "forcestart"
+        {
+            continue;
+        }
+
+        let Ok(mut phase) = phases.get_single_mut() else { continue; };
+        if *phase != GamePhase::Lobby
+        {
+            warn!("Server: forcestart only works in the lobby");
+            continue;
+        }
+
+        info!("Server: Force starting round countdown at tick {}", tick.0);
+        *phase = GamePhase::Countdown { start_tick: tick.0 };
+    }
+}
+
+/// Server side, `endround` ends the round in play and sends everyone back to the lobby, un-readied
+fn end_round_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut phases: Query<&mut GamePhase>,
+    mut readies: Query<&mut Ready>,
+    mut round_ended: EventWriter<RoundEnded>,
+    mut announcements: EventWriter<ToClients<ServerAnnouncement>>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "endround"
+        {
+            continue;
+        }
+
+        let Ok(mut phase) = phases.get_single_mut() else { continue; };
+        if *phase != GamePhase::Playing
+        {
+            warn!("Server: endround only works while a round is being played");
+            continue;
+        }
+
+        info!("Server: Round ended");
+        *phase = GamePhase::Lobby;
+        // Otherwise a lobby full of players still ready from last time would start the next countdown straight away
+        for mut ready in &mut readies
+        {
+            ready.0 = false;
+        }
+        round_ended.send(RoundEnded);
+        announcements.send(ToClients { mode: SendMode::Broadcast, event: ServerAnnouncement { severity: Severity::Info, text: "Round over, back to the lobby".to_string() } });
+    }
+}
+
+/// Server side, starts the countdown once every connected player is ready, and cancels it if one un-readies
+///
+/// Checked every frame, so a player disconnecting counts straight away. Players who were never ready
+/// don't cancel a countdown started with G or `forcestart`, only un-readying during it does.
+fn ready_check_system(
+    mut phases: Query<&mut GamePhase>,
+    mut announcements: EventWriter<ToClients<ServerAnnouncement>>,
+    players: Query<(&Player, Ref<Ready>)>,
+    server: Res<RenetServer>,
+    cli: Res<Cli>,
+    tick: Res<SimulationTick>,
+) {
+    let Ok(mut phase) = phases.get_single_mut() else { return; };
+
+    // A dedicated server's own player can't ready up, a host's can
+    let hosting = matches!(*cli, Cli::Host { .. });
+    let connected = players.iter().filter(|(player, _)| {
+        let client_id = ClientId::from_raw(player.0);
+        if client_id == SERVER_ID { hosting } else { server.is_connected(client_id) }
+    });
+
+    match *phase
+    {
+        GamePhase::Lobby =>
+        {
+            let (count, all_ready) = connected.fold((0, true), |(count, all_ready), (_, ready)| (count + 1, all_ready && ready.0));
+            if all_ready && count >= MIN_READY_PLAYERS
+            {
+                info!("Server: All {count} players ready, starting round countdown at tick {}", tick.0);
+                *phase = GamePhase::Countdown { start_tick: tick.0 };
+            }
+        }
+        GamePhase::Countdown { .. } =>
+        {
+            let Some((player, _)) = connected.into_iter().find(|(_, ready)| ready.is_changed() && !ready.0) else { return; };
+
+            let text = format!("Countdown cancelled, player {} isn't ready", player.0);
+            info!("Server: {text}");
+            announcements.send(ToClients { mode: SendMode::Broadcast, event: ServerAnnouncement { severity: Severity::Info, text } });
+            *phase = GamePhase::Lobby;
+        }
+        GamePhase::Playing => {}
+    }
+}
+
+/// Server side, G (`start_countdown`) starts the round countdown from the lobby
+fn start_countdown_hotkey_system(
+    mut phases: Query<&mut GamePhase>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    tick: Res<SimulationTick>,
+) {
+    if !bindings.just_pressed(Action::StartCountdown, &input)
+    {
+        return;
+    }
+
+    let Ok(mut phase) = phases.get_single_mut() else { return; };
+    if *phase == GamePhase::Lobby
+    {
+        info!("Server: Starting round countdown at tick {}", tick.0);
+        *phase = GamePhase::Countdown { start_tick: tick.0 };
+    }
+}
+
+fn finish_countdown_system(
+    mut phases: Query<&mut GamePhase>,
+    tick: Res<SimulationTick>,
+) {
+    let Ok(mut phase) = phases.get_single_mut() else { return; };
+    if phase.countdown_remaining(*tick) == Some(0)
+    {
+        info!("Server: Round started");
+        *phase = GamePhase::Playing;
+    }
+}
+
+fn spawn_countdown_text_system(
+    mut commands: Commands,
+) {
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle { font_size: 60.0, color: Color::WHITE, ..default() }
+    ).with_style(Style {
+        position_type: PositionType::Absolute, left: Val::Percent(48.0), top: Val::Percent(20.0), ..default()
+    }), CountdownText));
+}
+
+fn spawn_ready_list_system(
+    mut commands: Commands,
+) {
+    commands.spawn((TextBundle::default().with_style(Style {
+        position_type: PositionType::Absolute, left: Val::Px(5.0), top: Val::Percent(30.0), ..default()
+    }), ReadyListText));
+}
+
+/// Lists every player with a green "ready" or red "not ready" until the round starts
+fn update_ready_list_system(
+    mut text_query: Query<&mut Text, With<ReadyListText>>,
+    players: Query<(&Player, &Ready)>,
+    changed: Query<(), Or<(Changed<Ready>, Changed<Player>)>>,
+    mut removed: RemovedComponents<Player>,
+    phases: Query<Ref<GamePhase>>,
+) {
+    let removed_any = removed.read().count() > 0;
+    let phase_changed = phases.get_single().is_ok_and(|phase| phase.is_changed());
+    if changed.is_empty() && !removed_any && !phase_changed
+    {
+        return;
+    }
+    let Ok(mut text) = text_query.get_single_mut() else { return; };
+
+    if phases.get_single().is_ok_and(|phase| *phase == GamePhase::Playing)
+    {
+        text.sections.clear();
+        return;
+    }
+
+    let mut listed: Vec<(u64, bool)> = players.iter().map(|(player, ready)| (player.0, ready.0)).collect();
+    listed.sort_by_key(|(id, _)| *id);
+
+    text.sections = listed.into_iter()
+        .map(|(id, ready)| {
+            let (label, color) = if ready { ("ready", Color::GREEN) } else { ("not ready", Color::RED) };
+            TextSection::new(format!("{id}: {label}\n"), TextStyle { font_size: 20.0, color, ..default() })
+        })
+        .collect();
+}
+
+fn update_countdown_text_system(
+    mut text_query: Query<&mut Text, With<CountdownText>>,
+    phases: Query<&GamePhase>,
+    tick: Res<SimulationTick>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else { return; };
+
+    let remaining = phases.get_single().ok().and_then(|phase| phase.countdown_remaining(*tick));
+    let value = match remaining
+    {
+        Some(0) => "GO!".to_string(),
+        Some(remaining) => remaining.to_string(),
+        None => String::new(),
+    };
+
+    if text.sections[0].value != value
+    {
+        text.sections[0].value = value;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+
+    use bevy::{time::TimeUpdateStrategy, utils::HashMap};
+
+    use super::*;
+    use crate::bench::{step_session, tests::connected_session};
+
+    /// What the client shows at its current tick: the countdown's number, or the phase outside one
+    fn shown_at_tick(client_app: &mut App) -> (u32, String) {
+        let tick = *client_app.world.resource::<SimulationTick>();
+        let phase = client_app.world.query::<&GamePhase>().get_single(&client_app.world).copied().unwrap_or_default();
+        let shown = match phase.countdown_remaining(tick)
+        {
+            Some(remaining) => remaining.to_string(),
+            None => format!("{phase:?}"),
+        };
+        (tick.0, shown)
+    }
+
+    #[test]
+    fn countdown_counts_simulation_seconds() {
+        let phase = GamePhase::Countdown { start_tick: 100 };
+        let steps_per_second = SIMULATION_RATE as u32;
+
+        assert_eq!(phase.countdown_remaining(SimulationTick(100)), Some(COUNTDOWN_SECS));
+        assert_eq!(phase.countdown_remaining(SimulationTick(100 + steps_per_second - 1)), Some(COUNTDOWN_SECS));
+        assert_eq!(phase.countdown_remaining(SimulationTick(100 + steps_per_second)), Some(COUNTDOWN_SECS - 1));
+        assert_eq!(phase.countdown_remaining(SimulationTick(100 + COUNTDOWN_SECS * steps_per_second - 1)), Some(1));
+        assert_eq!(phase.countdown_remaining(SimulationTick(100 + COUNTDOWN_SECS * steps_per_second)), Some(0));
+        assert_eq!(GamePhase::Playing.countdown_remaining(SimulationTick(100)), None);
+    }
+
+    #[test]
+    fn countdown_survives_tick_wrapping() {
+        let phase = GamePhase::Countdown { start_tick: u32::MAX };
+        assert_eq!(phase.countdown_remaining(SimulationTick(SIMULATION_RATE as u32 - 1)), Some(COUNTDOWN_SECS - 1));
+    }
+
+    #[test]
+    fn clients_at_different_frame_rates_see_the_same_countdown() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        // The second client runs at 30 frames a second, two simulation steps a frame
+        client_apps[1].insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(2.0 / SIMULATION_RATE)));
+
+        let start_tick = server_app.world.resource::<SimulationTick>().0;
+        *server_app.world.query::<&mut GamePhase>().single_mut(&mut server_app.world) = GamePhase::Countdown { start_tick };
+
+        let mut seen: [HashMap<u32, String>; 2] = default();
+        let mut transitions: [Vec<String>; 2] = default();
+        for _ in 0..(COUNTDOWN_SECS + 1) * SIMULATION_RATE as u32
+        {
+            step_session(&mut server_app, &mut client_apps);
+            for ((client_app, seen), transitions) in client_apps.iter_mut().zip(&mut seen).zip(&mut transitions)
+            {
+                let (tick, shown) = shown_at_tick(client_app);
+                if transitions.last() != Some(&shown)
+                {
+                    transitions.push(shown.clone());
+                }
+                seen.insert(tick, shown);
+            }
+        }
+
+        // "GO!" lasts until the server's next update, so a client may not be shown it
+        for transitions in &mut transitions
+        {
+            transitions.retain(|shown| shown != "0");
+        }
+        assert_eq!(transitions[0], ["Lobby", "3", "2", "1", "Playing"]);
+        assert_eq!(transitions[0], transitions[1]);
+
+        let shared: Vec<u32> = seen[0].keys().filter(|tick| seen[1].contains_key(*tick)).copied().collect();
+        assert!(shared.len() > SIMULATION_RATE as usize, "the clients' ticks hardly overlapped");
+        for tick in shared
+        {
+            // Both hear of the round starting in their own time, but a number is only ever shown at its own ticks
+            if seen[0][&tick].parse::<u32>().is_ok() && seen[1][&tick].parse::<u32>().is_ok()
+            {
+                assert_eq!(seen[0][&tick], seen[1][&tick], "the clients showed different numbers at tick {tick}");
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::prelude::*;
+
+use crate::{PredictedSpawn, death_animation::DeathAnimation, is_headless, reconnect::ConnectionEpoch};
+
+/// Seconds a ghost takes to shrink and fade away
+const GHOST_LIFETIME: f32 = 0.25;
+
+/// Client side, replicated sprites the server despawns leave a ghost behind that shrinks and fades out,
+/// instead of vanishing between one frame and the next
+///
+/// The entity's data is gone by the time its despawn is seen, so every replicated sprite's look is cached each frame.
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SpriteCache>()
+            .add_systems(Update, (spawn_ghosts_system, fade_ghosts_system).chain().run_if(resource_exists::<RenetClient>()).run_if(not(is_headless)))
+            .add_systems(PostUpdate, cache_sprites_system.run_if(resource_exists::<RenetClient>()).run_if(not(is_headless)));
+    }
+}
+
+/// A local-only copy of a despawned sprite, with its age in seconds
+#[derive(Component)]
+struct Ghost
+{
+    age: f32,
+    scale: Vec3,
+    alpha: f32,
+}
+
+/// How every confirmed replicated sprite looked at the end of the last frame
+#[derive(Resource, Default)]
+struct SpriteCache(HashMap<Entity, (Transform, Sprite, Handle<Image>)>);
+
+/// Unconfirmed pre-spawns aren't cached, an orphaned prediction was never really there so it just disappears
+///
+/// Neither is anything playing its `DeathAnimation`, that already showed it going.
+fn cache_sprites_system(
+    mut cache: ResMut<SpriteCache>,
+    sprites: Query<(Entity, &Transform, &Sprite, &Handle<Image>), (With<Replication>, Without<PredictedSpawn>, Without<DeathAnimation>, Or<(Changed<Transform>, Changed<Sprite>)>)>,
+    dying: Query<Entity, Added<DeathAnimation>>,
+) {
+    for entity in &dying
+    {
+        cache.0.remove(&entity);
+    }
+    for (entity, transform, sprite, texture) in &sprites
+    {
+        cache.0.insert(entity, (*transform, sprite.clone(), texture.clone()));
+    }
+}
+
+/// A new session's reset clears the whole world at once, those despawns aren't the server's so they get no ghosts
+fn spawn_ghosts_system(
+    mut commands: Commands,
+    mut cache: ResMut<SpriteCache>,
+    mut removed: RemovedComponents<Replication>,
+    epoch: Res<ConnectionEpoch>,
+) {
+    if epoch.is_changed()
+    {
+        removed.clear();
+        cache.0.clear();
+        return;
+    }
+
+    for entity in removed.read()
+    {
+        let Some((transform, sprite, texture)) = cache.0.remove(&entity) else { continue; };
+        let ghost = Ghost { age: 0.0, scale: transform.scale, alpha: sprite.color.a() };
+        commands.spawn((SpriteBundle { sprite, texture, transform, ..default() }, ghost));
+    }
+}
+
+fn fade_ghosts_system(
+    mut commands: Commands,
+    mut ghosts: Query<(Entity, &mut Ghost, &mut Transform, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    for (entity, mut ghost, mut transform, mut sprite) in &mut ghosts
+    {
+        ghost.age += time.delta_seconds();
+        let remaining = 1.0 - ghost.age / GHOST_LIFETIME;
+        if remaining <= 0.0
+        {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.scale = ghost.scale * remaining;
+        sprite.color.set_a(ghost.alpha * remaining);
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{LocalPlayerId, Player, damage::Health, is_headless, player_state::PlayerState, room::OtherRoom};
+
+/// How many bars are spawned up front, the pool grows past this if needed
+const INITIAL_POOL_SIZE: usize = 8;
+const BAR_SIZE: Vec2 = Vec2::new(16.0, 2.0);
+/// Between the player's sprite and its name label
+const BAR_OFFSET: Vec3 = Vec3::new(0.0, 11.0, 1.0);
+/// The local player's bar is drawn this much bigger so it's easy to find at a glance
+const LOCAL_BAR_SCALE: f32 = 1.5;
+
+/// Health bars above players, a dark background with a fill scaled to `Health`, pooled like the name labels
+pub struct HealthBarPlugin;
+
+impl Plugin for HealthBarPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<HealthBarPool>()
+            .add_systems(Startup, fill_health_bar_pool_system.run_if(not(is_headless)))
+            .add_systems(Update,
+                (
+                    assign_health_bars_system,
+                    health_bar_follow_system,
+                ).chain().run_if(not(is_headless))
+            );
+    }
+}
+
+/// Marker for a pooled bar's background, the fill is its only child
+#[derive(Component)]
+pub struct HealthBar;
+
+#[derive(Component)]
+pub struct HealthBarFill;
+
+#[derive(Resource, Default)]
+pub struct HealthBarPool
+{
+    /// Hidden bars ready to be handed out
+    free: Vec<Entity>,
+    /// Player entity -> the bar following it
+    assigned: HashMap<Entity, Entity>,
+}
+
+/// Width of the fill for `health`, out of `BAR_SIZE.x`
+fn fill_width(health: &Health) -> f32 {
+    if health.max == 0
+    {
+        return 0.0;
+    }
+    BAR_SIZE.x * (health.current as f32 / health.max as f32).clamp(0.0, 1.0)
+}
+
+/// Green at full health through yellow to red when nearly dead
+fn fill_color(health: &Health) -> Color {
+    let ratio = fill_width(health) / BAR_SIZE.x;
+    Color::rgb((2.0 * (1.0 - ratio)).min(1.0), (2.0 * ratio).min(1.0), 0.0)
+}
+
+fn spawn_health_bar(commands: &mut Commands) -> Entity {
+    commands.spawn((SpriteBundle {
+        sprite: Sprite { color: Color::rgba(0.0, 0.0, 0.0, 0.6), custom_size: Some(BAR_SIZE), ..default() },
+        visibility: Visibility::Hidden,
+        ..default()
+    }, HealthBar)).with_children(|parent| {
+        parent.spawn((SpriteBundle {
+            sprite: Sprite { color: Color::GREEN, custom_size: Some(BAR_SIZE), ..default() },
+            // Just in front of the background
+            transform: Transform::from_xyz(0.0, 0.0, 0.1),
+            ..default()
+        }, HealthBarFill));
+    }).id()
+}
+
+fn fill_health_bar_pool_system(
+    mut commands: Commands,
+    mut pool: ResMut<HealthBarPool>,
+) {
+    for _ in 0..INITIAL_POOL_SIZE
+    {
+        let bar = spawn_health_bar(&mut commands);
+        pool.free.push(bar);
+    }
+}
+
+/// Hands bars to new players and returns bars of removed players to the pool
+fn assign_health_bars_system(
+    mut commands: Commands,
+    mut pool: ResMut<HealthBarPool>,
+    mut removed_players: RemovedComponents<Player>,
+    new_players: Query<Entity, Added<Player>>,
+    mut bars: Query<&mut Visibility, With<HealthBar>>,
+) {
+    for player_entity in removed_players.read()
+    {
+        let Some(bar) = pool.assigned.remove(&player_entity) else { continue; };
+        if let Ok(mut visibility) = bars.get_mut(bar)
+        {
+            *visibility = Visibility::Hidden;
+        }
+        pool.free.push(bar);
+    }
+
+    for player_entity in &new_players
+    {
+        if pool.assigned.contains_key(&player_entity)
+        {
+            continue;
+        }
+
+        // Pool exhausted, grow it, the bar becomes visible once it follows its player
+        let bar = pool.free.pop().unwrap_or_else(|| spawn_health_bar(&mut commands));
+        pool.assigned.insert(player_entity, bar);
+    }
+}
+
+/// Keeps each bar above its player and its fill matching their health, hidden while they're dead, respawning or in
+/// another room
+fn health_bar_follow_system(
+    pool: Res<HealthBarPool>,
+    players: Query<(&Player, &Transform, Option<&Health>, Option<&PlayerState>, Has<OtherRoom>), Without<HealthBar>>,
+    mut bars: Query<(&mut Transform, &mut Visibility, &Children), (With<HealthBar>, Without<Player>)>,
+    mut fills: Query<(&mut Sprite, &mut Transform), (With<HealthBarFill>, Without<HealthBar>, Without<Player>)>,
+    local_player: Option<Res<LocalPlayerId>>,
+) {
+    for (player_entity, bar) in &pool.assigned
+    {
+        let Ok((mut bar_transform, mut visibility, children)) = bars.get_mut(*bar) else { continue; };
+
+        // The player's sprite may not have been attached yet
+        let Ok((player, player_transform, health, state, other_room)) = players.get(*player_entity) else { continue; };
+        let Some(health) = health.filter(|_| state.map_or(true, PlayerState::is_alive) && !other_room) else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+
+        let scale = if local_player.as_ref().is_some_and(|local_player| local_player.0 == player.0) { LOCAL_BAR_SCALE } else { 1.0 };
+        bar_transform.translation = player_transform.translation + BAR_OFFSET;
+        bar_transform.scale = Vec3::splat(scale);
+        visibility.set_if_neq(Visibility::Visible);
+
+        for child in children
+        {
+            let Ok((mut sprite, mut fill_transform)) = fills.get_mut(*child) else { continue; };
+
+            // Anchored on the left, so it empties towards the left edge
+            let width = fill_width(health);
+            sprite.custom_size = Some(Vec2::new(width, BAR_SIZE.y));
+            sprite.color = fill_color(health);
+            fill_transform.translation.x = (width - BAR_SIZE.x) / 2.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn fill_width_matches_the_health_ratio() {
+        assert_eq!(fill_width(&Health { current: 100, max: 100 }), BAR_SIZE.x);
+        assert_eq!(fill_width(&Health { current: 25, max: 100 }), BAR_SIZE.x * 0.25);
+        assert_eq!(fill_width(&Health { current: 0, max: 100 }), 0.0);
+        // Overhealed or maxless players don't draw outside the background
+        assert_eq!(fill_width(&Health { current: 150, max: 100 }), BAR_SIZE.x);
+        assert_eq!(fill_width(&Health { current: 10, max: 0 }), 0.0);
+
+        assert_eq!(fill_color(&Health { current: 100, max: 100 }), Color::rgb(0.0, 1.0, 0.0));
+        assert_eq!(fill_color(&Health { current: 0, max: 100 }), Color::rgb(1.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_replicon::{prelude::*, server::ServerSet};
+use serde::{Serialize, Deserialize};
+
+use crate::{EntityKind, LocalPlayerId, Player, has_local_player, is_headless, PlayerInput, Position, damage::ApplyDamage, game_phase::{self, GamePhase}, input_map::PlayerControls, player_state::PlayerState, room::{self, Room}, send_rate::send_rate_system, settings::GameSettings, stats::PlayerStats, tick_timer::{TickTimed, TickTimer, tick_down_system}};
+
+/// How many ticks of positions are kept for rewinding, also the furthest a shot can be rewound
+pub const HISTORY_TICKS: usize = 20;
+pub const PLAYER_RADIUS: f32 = 7.5;
+const HITSCAN_RANGE: f32 = 500.0;
+const HITSCAN_DAMAGE: u32 = 25;
+/// How far a shot's claimed origin may be from the shooter before it is snapped back to them
+const MAX_ORIGIN_OFFSET: f32 = 20.0;
+/// Seconds a shot's tracer stays visible
+const TRACER_DURATION: f32 = 0.15;
+
+/// Instant-hit weapon (F key or left click) resolved server-side against positions rewound to what the shooter saw
+pub struct HitscanPlugin;
+
+impl Plugin for HitscanPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate::<TracerEffect>()
+            .add_systems(Update, hitscan_input_system.run_if(has_local_player))
+            .add_systems(Update, (receive_hitscan_system, tick_down_system::<TracerLifetime>, expire_tracers_system).chain().run_if(has_authority()))
+            .add_systems(Update, draw_tracers_system.run_if(not(is_headless)))
+            .add_systems(PostUpdate,
+                record_position_history_system
+                    .after(send_rate_system)
+                    .before(ServerSet::Send)
+                    .run_if(resource_exists::<RenetServer>())
+            );
+    }
+}
+
+/// Server side, the positions this player was replicated with over the last `HISTORY_TICKS` ticks
+#[derive(Component, Default)]
+pub struct PositionHistory(VecDeque<(RepliconTick, Vec2)>);
+
+impl PositionHistory
+{
+    /// The position at the latest recorded tick not after `tick`, falling back to the oldest record
+    pub fn at(&self, tick: RepliconTick) -> Option<Vec2> {
+        self.0.iter().rev()
+            .find(|(recorded, _)| *recorded <= tick)
+            .or(self.0.front())
+            .map(|(_, pos)| *pos)
+    }
+}
+
+/// A shot's path from where it was fired to where it stopped, replicated on a short-lived entity
+#[derive(Component, Serialize, Deserialize, Clone, Copy)]
+pub struct TracerEffect
+{
+    pub from: Vec2,
+    pub to: Vec2,
+}
+
+/// Server side, ticks left before the tracer entity is despawned
+#[derive(Component)]
+pub struct TracerLifetime(pub TickTimer);
+
+impl TickTimed for TracerLifetime
+{
+    fn timer_mut(&mut self) -> &mut TickTimer {
+        &mut self.0
+    }
+}
+
+/// Records every player's position whenever a replication tick goes out, bounded to `HISTORY_TICKS` entries
+fn record_position_history_system(
+    mut commands: Commands,
+    mut players: Query<(Entity, &Position, Option<&mut PositionHistory>), With<Player>>,
+    tick: Res<RepliconTick>,
+) {
+    if !tick.is_changed()
+    {
+        return;
+    }
+
+    for (player_entity, pos, history) in &mut players
+    {
+        let Some(mut history) = history else {
+            commands.entity(player_entity).insert(PositionHistory(VecDeque::from([(*tick, pos.0)])));
+            continue;
+        };
+
+        if history.0.len() == HISTORY_TICKS
+        {
+            history.0.pop_front();
+        }
+        history.0.push_back((*tick, pos.0));
+    }
+}
+
+/// Client side, fires toward the cursor on left click, along the last direction the player moved in with F,
+/// or with the gamepad's right bumper toward the right stick (or the way it moved when the stick is centered)
+///
+/// The cursor aim is tracked every frame while it's usable, so when the cursor is outside the window or the window
+/// isn't focused a click fires where it last pointed, which is toward the edge it left through.
+fn hitscan_input_system(
+    mut input_writer: EventWriter<PlayerInput>,
+    mut facing: Local<Option<Vec2>>,
+    mut aim: Local<Option<Vec2>>,
+    controls: PlayerControls,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    local_player: Res<LocalPlayerId>,
+    players: Query<(&Player, &Position)>,
+    tick: Res<RepliconTick>,
+) {
+    let direction = controls.movement();
+    if direction != Vec2::ZERO
+    {
+        *facing = Some(direction);
+    }
+
+    let Some((_, origin)) = players.iter().find(|(player, _)| player.0 == local_player.0) else { return; };
+
+    if let Some(cursor_aim) = cursor_aim(windows.get_single().ok(), cameras.get_single().ok(), origin.0)
+    {
+        *aim = Some(cursor_aim);
+    }
+
+    let clicked = mouse.just_pressed(MouseButton::Left);
+    let gamepad_fired = controls.gamepad_hitscan_pressed();
+    if !controls.hitscan_key_pressed() && !clicked && !gamepad_fired
+    {
+        return;
+    }
+
+    // A gamepad aims with the right stick, or fires the way it's moving when the stick is centered
+    let direction = match (*aim, controls.aim())
+    {
+        (_, Some(stick_aim)) if gamepad_fired => stick_aim,
+        (Some(aim), _) if clicked => aim,
+        _ => facing.unwrap_or(Vec2::X),
+    };
+
+    // The client's tick is the latest world state it has received, which is what it was aiming at
+    input_writer.send(PlayerInput::HitscanShot {
+        origin: origin.0,
+        direction,
+        client_tick: *tick,
+    });
+}
+
+/// Direction from `origin` to the cursor, `None` while the window is unfocused, the cursor is outside it,
+/// or the cursor is right on top of `origin`
+fn cursor_aim(window: Option<&Window>, camera: Option<(&Camera, &GlobalTransform)>, origin: Vec2) -> Option<Vec2> {
+    let window = window.filter(|window| window.focused)?;
+    let (camera, camera_transform) = camera?;
+    let cursor = camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)?;
+
+    let direction = (cursor - origin).normalize_or_zero();
+    (direction != Vec2::ZERO && direction.is_finite()).then_some(direction)
+}
+
+/// Distance along the ray to where it first enters the circle, if it does
+pub fn ray_circle_distance(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let to_center = center - origin;
+    let along = to_center.dot(direction);
+    let closest_sq = to_center.length_squared() - along * along;
+    let radius_sq = radius * radius;
+    if closest_sq > radius_sq
+    {
+        return None;
+    }
+
+    let distance = along - (radius_sq - closest_sq).sqrt();
+    (distance >= 0.0).then_some(distance)
+}
+
+/// Server side, rewinds every other player in the shooter's room to the shooter's tick and damages the first one the ray hits
+fn receive_hitscan_system(
+    mut commands: Commands,
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    mut damage_requests: EventWriter<ApplyDamage>,
+    players: Query<(Entity, &Player, &Position, &PlayerState, Option<&PositionHistory>, Option<&Room>)>,
+    mut stats: Query<&mut PlayerStats>,
+    server: Option<Res<RenetServer>>,
+    settings: Query<&GameSettings>,
+    phases: Query<&GamePhase>,
+    tick: Res<RepliconTick>,
+) {
+    let replication_rate = settings.get_single().copied().unwrap_or_default().replication_rate;
+    let inputs_locked = game_phase::current_phase(&phases).inputs_locked();
+
+    for FromClient { client_id, event } in input_reader.read()
+    {
+        let PlayerInput::HitscanShot { origin, direction, client_tick } = event else { continue; };
+        if inputs_locked
+        {
+            continue;
+        }
+
+        let Some((shooter_entity, _, shooter_pos, shooter_state, _, shooter_room)) = players.iter()
+            .find(|(_, player, ..)| player.0 == client_id.raw()) else { continue; };
+        if !shooter_state.is_alive() || !direction.is_finite() || *direction == Vec2::ZERO
+        {
+            continue;
+        }
+
+        if let Ok(mut stats) = stats.get_mut(shooter_entity)
+        {
+            stats.shots_fired += 1;
+        }
+
+        // Don't trust the claimed origin beyond a small allowance for latency
+        let origin = if origin.distance(shooter_pos.0) > MAX_ORIGIN_OFFSET { shooter_pos.0 } else { *origin };
+        let direction = direction.normalize();
+
+        // The client can't have seen further back than its round trip, so it can't rewind further than that either
+        let rtt_secs = server.as_ref()
+            .and_then(|server| server.network_info(*client_id).ok())
+            .map_or(0.0, |info| info.rtt as f32 / 1000.0);
+        let max_rewind = ((rtt_secs * replication_rate).ceil() as u32 + 2).min(HISTORY_TICKS as u32);
+        let rewind = tick.get().saturating_sub(client_tick.get()).min(max_rewind);
+        let rewound_tick = RepliconTick::new(tick.get() - rewind);
+
+        let hit = players.iter()
+            .filter(|(entity, _, _, state, _, room)| *entity != shooter_entity && state.is_alive() && room::room_of(*room) == room::room_of(shooter_room))
+            .filter_map(|(entity, _, pos, _, history, _)| {
+                let rewound_pos = history.and_then(|history| history.at(rewound_tick)).unwrap_or(pos.0);
+                ray_circle_distance(origin, direction, rewound_pos, PLAYER_RADIUS).map(|distance| (entity, distance))
+            })
+            .filter(|(_, distance)| *distance <= HITSCAN_RANGE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        let end_distance = hit.map_or(HITSCAN_RANGE, |(_, distance)| distance);
+        commands.spawn((TracerEffect { from: origin, to: origin + direction * end_distance }, EntityKind::Effect, TracerLifetime(TickTimer::from_seconds(TRACER_DURATION)), room::room_of(shooter_room), Replication));
+
+        if let Some((target, _)) = hit
+        {
+            info!("Server: Client '{client_id}' hitscan hit {target:?} rewound {rewind} ticks");
+            damage_requests.send(ApplyDamage { target, amount: HITSCAN_DAMAGE, source: Some(client_id.raw()) });
+        }
+    }
+}
+
+fn expire_tracers_system(
+    mut commands: Commands,
+    tracers: Query<(Entity, &TracerLifetime)>,
+) {
+    for (entity, lifetime) in &tracers
+    {
+        if lifetime.0.finished()
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn draw_tracers_system(
+    mut gizmos: Gizmos,
+    tracers: Query<&TracerEffect>,
+) {
+    for tracer in &tracers
+    {
+        gizmos.line_2d(tracer.from, tracer.to, Color::ORANGE);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::{ecs::event::ManualEventReader, render::camera::{ManualTextureViews, camera_system}, window::{WindowCreated, WindowResized, WindowResolution}};
+    use bevy_replicon::renet::ClientId;
+
+    use super::*;
+    use crate::input_map::{ActiveGamepad, InputBindings};
+
+    /// Fires one shot from player 1 at the origin along +x, returning who it damaged
+    fn shoot_at(targets: &[Vec2]) -> Vec<Entity> {
+        let mut app = App::new();
+        app
+            .init_resource::<RepliconTick>()
+            .add_event::<FromClient<PlayerInput>>()
+            .add_event::<ApplyDamage>()
+            .add_systems(Update, receive_hitscan_system);
+        app.world.spawn((Player(1), Position(Vec2::ZERO), PlayerState::Alive));
+        for (index, target) in targets.iter().enumerate()
+        {
+            app.world.spawn((Player(index as u64 + 2), Position(*target), PlayerState::Alive));
+        }
+
+        let event = PlayerInput::HitscanShot { origin: Vec2::ZERO, direction: Vec2::X, client_tick: RepliconTick::default() };
+        app.world.send_event(FromClient { client_id: ClientId::from_raw(1), event });
+        app.update();
+
+        let mut reader = ManualEventReader::<ApplyDamage>::default();
+        reader.read(app.world.resource::<Events<ApplyDamage>>()).map(|damage| damage.target).collect()
+    }
+
+    #[test]
+    fn aligned_target_is_hit_and_offset_one_missed() {
+        assert_eq!(shoot_at(&[Vec2::new(100.0, 0.0)]).len(), 1);
+        assert!(shoot_at(&[Vec2::new(100.0, PLAYER_RADIUS * 2.0)]).is_empty());
+        // The ray starts inside the shooter, who mustn't count as the first thing it hits
+        assert!(shoot_at(&[]).is_empty());
+    }
+
+    /// Player 1 at the origin with an 800x600 window, and a camera bevy's own system sizes to it so the cursor converts
+    fn aiming_app() -> App {
+        let mut app = App::new();
+        app
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<ActiveGamepad>()
+            .init_resource::<InputBindings>()
+            .init_resource::<RepliconTick>()
+            .init_resource::<Assets<Image>>()
+            .init_resource::<ManualTextureViews>()
+            .insert_resource(LocalPlayerId(1))
+            .add_event::<WindowResized>()
+            .add_event::<WindowCreated>()
+            .add_event::<AssetEvent<Image>>()
+            .add_event::<PlayerInput>()
+            .add_systems(Update, (camera_system::<OrthographicProjection>, hitscan_input_system).chain());
+        app.world.spawn((Window { resolution: WindowResolution::new(800.0, 600.0), focused: true, ..default() }, PrimaryWindow));
+        app.world.spawn(Camera2dBundle::default());
+        app.world.spawn((Player(1), Position(Vec2::ZERO)));
+        app
+    }
+
+    fn set_window(app: &mut App, cursor: Option<Vec2>, focused: bool) {
+        let mut window = app.world.query_filtered::<&mut Window, With<PrimaryWindow>>().single_mut(&mut app.world);
+        window.set_cursor_position(cursor);
+        window.focused = focused;
+    }
+
+    /// Left clicks over one update, returning the direction of the shot it fired
+    fn click(app: &mut App, reader: &mut ManualEventReader<PlayerInput>) -> Vec2 {
+        app.world.resource_mut::<Input<MouseButton>>().press(MouseButton::Left);
+        app.update();
+        let mut mouse = app.world.resource_mut::<Input<MouseButton>>();
+        mouse.release(MouseButton::Left);
+        mouse.clear();
+
+        let shots: Vec<Vec2> = reader.read(app.world.resource::<Events<PlayerInput>>())
+            .filter_map(|input| match input
+            {
+                PlayerInput::HitscanShot { direction, .. } => Some(*direction),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(shots.len(), 1);
+        shots[0]
+    }
+
+    #[test]
+    fn click_with_the_cursor_off_the_window_fires_where_it_last_aimed() {
+        let mut app = aiming_app();
+        let mut reader = ManualEventReader::<PlayerInput>::default();
+        // Right of the middle of the window, straight along +x from the player
+        set_window(&mut app, Some(Vec2::new(600.0, 300.0)), true);
+        app.update();
+        let aimed = click(&mut app, &mut reader);
+        assert!(aimed.abs_diff_eq(Vec2::X, 1e-4), "aimed {aimed} at a cursor right of the player");
+
+        set_window(&mut app, None, true);
+        for _ in 0..3
+        {
+            app.update();
+        }
+        let off_window = click(&mut app, &mut reader);
+        assert!(off_window.is_finite());
+        assert_eq!(off_window, aimed);
+
+        // Over the window again but unfocused, where the cursor isn't trusted either
+        set_window(&mut app, Some(Vec2::new(200.0, 300.0)), false);
+        assert_eq!(click(&mut app, &mut reader), aimed);
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_replicon::{prelude::*, renet::ServerEvent};
+
+use crate::Cli;
+
+/// Frame length while idle, connection attempts wait at most this long to be polled
+const IDLE_FRAME: Duration = Duration::from_millis(100);
+
+/// Slows a headless server down to a frame every `IDLE_FRAME` while nobody is connected
+///
+/// The simulation keeps its rate, the fixed step just runs several times per frame. Turbo and the benchmark
+/// never idle, they're meant to run flat out.
+pub struct IdlePlugin;
+
+impl Plugin for IdlePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ServerLoopMode>()
+            .add_systems(Last, idle_system.run_if(can_idle).run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// How the server's loop is currently running, always `Active` where it can't idle
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ServerLoopMode
+{
+    #[default]
+    Active,
+    /// No clients, sleeping out most of every frame
+    Idle,
+}
+
+fn can_idle(cli: Res<Cli>) -> bool {
+    cli.is_headless() && !cli.is_turbo() && !matches!(*cli, Cli::Bench { .. })
+}
+
+/// Wakes on a connection the same frame renet reports it, and idles again once the last client has left
+///
+/// Sleeps in `Last`, so a frame that idles has already polled the transport and sent everything.
+fn idle_system(
+    mut mode: ResMut<ServerLoopMode>,
+    mut server_events: EventReader<ServerEvent>,
+    mut frame_ended: Local<Option<Instant>>,
+    server: Res<RenetServer>,
+) {
+    let connected = server_events.read().any(|event| matches!(event, ServerEvent::ClientConnected { .. }));
+    let next = if connected || server.connected_clients() > 0 { ServerLoopMode::Active } else { ServerLoopMode::Idle };
+    if *mode != next
+    {
+        match next
+        {
+            ServerLoopMode::Active => info!("Server: Client connected, back to the full frame rate"),
+            ServerLoopMode::Idle => info!("Server: No clients connected, idling at one frame every {}ms", IDLE_FRAME.as_millis()),
+        }
+        *mode = next;
+    }
+
+    if *mode == ServerLoopMode::Idle
+    {
+        // Measured from the end of the last frame so the idle frame length doesn't drift with how long frames take
+        if let Some(remaining) = frame_ended.and_then(|ended| IDLE_FRAME.checked_sub(ended.elapsed()))
+        {
+            std::thread::sleep(remaining);
+        }
+    }
+    *frame_ended = Some(Instant::now());
+}
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, input::InputSystem};
+use bevy_replicon::prelude::*;
+
+use crate::{Cli, console::{ConsoleCommand, RemoteConsoleCommand}, input_map::{Action, InputBindings}, is_headless};
+
+/// Lines of scrollback kept, and shown above the input line
+const SCROLLBACK_LINES: usize = 10;
+
+/// The console in a window, toggled with the backquote key
+///
+/// Typed lines become the same `ConsoleCommand`s as lines typed into the server's terminal. A client sends them to
+/// the server instead, along with its `--admin-key`. While it's open, keyboard and mouse input don't reach the game.
+pub struct InGameConsolePlugin;
+
+impl Plugin for InGameConsolePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InGameConsole>()
+            .add_systems(Startup, spawn_console_system.run_if(not(is_headless)))
+            .add_systems(PreUpdate, console_input_system.after(InputSystem).run_if(not(is_headless)))
+            .add_systems(Update, draw_console_system.run_if(not(is_headless)));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct InGameConsole
+{
+    pub open: bool,
+    input: String,
+    scrollback: VecDeque<String>,
+}
+
+impl InGameConsole
+{
+    fn print(&mut self, line: String) {
+        if self.scrollback.len() == SCROLLBACK_LINES
+        {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(line);
+    }
+}
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+fn spawn_console_system(
+    mut commands: Commands,
+) {
+    let mut console = NodeBundle {
+        style: Style { position_type: PositionType::Absolute, top: Val::Px(0.0), left: Val::Px(0.0), width: Val::Percent(100.0), padding: UiRect::all(Val::Px(5.0)), ..default() },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+        z_index: ZIndex::Global(10),
+        ..default()
+    };
+    console.visibility = Visibility::Hidden;
+
+    commands.spawn(console).with_children(|parent| {
+        parent.spawn((TextBundle::from_section("", TextStyle { font_size: 18.0, color: Color::WHITE, ..default() }), ConsoleText));
+    });
+}
+
+/// Toggles the console and types into it, then hides the keyboard and mouse from the game while it's open
+fn console_input_system(
+    mut console: ResMut<InGameConsole>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut commands: EventWriter<ConsoleCommand>,
+    mut remote_commands: EventWriter<RemoteConsoleCommand>,
+    client: Option<Res<RenetClient>>,
+    bindings: Res<InputBindings>,
+    cli: Res<Cli>,
+) {
+    if bindings.just_pressed(Action::Console, &keys)
+    {
+        console.open = !console.open;
+        keys.reset_all();
+        characters.clear();
+        return;
+    }
+    if !console.open
+    {
+        characters.clear();
+        return;
+    }
+
+    for character in characters.read()
+    {
+        // The backquote that opened the console arrives here too
+        if !character.char.is_control() && character.char != '`'
+        {
+            console.input.push(character.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back)
+    {
+        console.input.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Return) && !console.input.trim().is_empty()
+    {
+        let line = std::mem::take(&mut console.input);
+        console.print(format!("> {line}"));
+        match ConsoleCommand::parse(&line)
+        {
+            Err(e) => console.print(e),
+            Ok(command) if client.is_none() || command.is_local() => commands.send(command),
+            Ok(command) =>
+            {
+                match &*cli
+                {
+                    Cli::Client { admin_key: Some(key), .. } =>
+                    {
+                        remote_commands.send(RemoteConsoleCommand { key: key.clone(), args: command.args });
+                        console.print("Sent to the server, its log shows the result".to_string());
+                    }
+                    _ => console.print("Commands run on the server, connect with --admin-key to send them".to_string()),
+                }
+            }
+        }
+    }
+
+    keys.reset_all();
+    mouse.reset_all();
+}
+
+fn draw_console_system(
+    console: Res<InGameConsole>,
+    mut texts: Query<(&mut Text, &Parent), With<ConsoleText>>,
+    mut visibilities: Query<&mut Visibility>,
+) {
+    if !console.is_changed()
+    {
+        return;
+    }
+
+    for (mut text, parent) in &mut texts
+    {
+        let lines: String = console.scrollback.iter().map(|line| format!("{line}\n")).collect();
+        text.sections[0].value = format!("{lines}> {}_", console.input);
+
+        if let Ok(mut visibility) = visibilities.get_mut(parent.get())
+        {
+            *visibility = if console.open { Visibility::Inherited } else { Visibility::Hidden };
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::VecDeque, fmt::Write as _, fs, time::SystemTime};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::{ClientId, ServerEvent}};
+
+use crate::{PlayerInput, audit::AuditFailures, console::ConsoleCommand};
+
+/// Inputs kept per client, older ones are dropped
+const HISTORY_LEN: usize = 512;
+
+/// Server side record of every `PlayerInput` received, for reconstructing what led up to a bug
+pub struct InputHistoryPlugin;
+
+impl Plugin for InputHistoryPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InputHistory>()
+            .add_event::<DumpInputHistory>()
+            .add_systems(Update,
+                (
+                    record_inputs_system,
+                    history_console_system,
+                    dump_on_audit_failure_system,
+                    dump_input_history_system,
+                    drop_disconnected_history_system,
+                ).chain().run_if(resource_exists::<RenetServer>())
+            );
+    }
+}
+
+/// One received input, `index` counts up across all clients so interleaving can be reconstructed
+pub struct InputRecord
+{
+    pub index: u64,
+    pub tick: RepliconTick,
+    pub input: String,
+}
+
+#[derive(Resource, Default)]
+pub struct InputHistory
+{
+    clients: HashMap<ClientId, VecDeque<InputRecord>>,
+    next_index: u64,
+    /// Write a client's history to disk when it disconnects, from `--dump-history-on-disconnect`
+    pub dump_on_disconnect: bool,
+}
+
+impl InputHistory
+{
+    fn record(&mut self, client_id: ClientId, tick: RepliconTick, input: &PlayerInput) {
+        let records = self.clients.entry(client_id).or_default();
+        if records.len() == HISTORY_LEN
+        {
+            records.pop_front();
+        }
+        records.push_back(InputRecord { index: self.next_index, tick, input: format!("{input:?}") });
+        self.next_index += 1;
+    }
+
+    fn format(&self, client_id: ClientId) -> Option<String> {
+        let records = self.clients.get(&client_id)?;
+        let mut text = String::new();
+        for record in records
+        {
+            let _ = writeln!(text, "#{} tick {}: {}", record.index, record.tick.get(), record.input);
+        }
+        Some(text)
+    }
+
+    /// Writes the client's history to `input_history_<client>_<unix seconds>.txt`
+    fn write_to_file(&self, client_id: ClientId) {
+        let Some(text) = self.format(client_id) else { return; };
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        let path = format!("input_history_{client_id}_{timestamp}.txt");
+        match fs::write(&path, text)
+        {
+            Ok(()) => info!("Server: Wrote input history of client '{client_id}' to {path}"),
+            Err(e) => warn!("Server: Couldn't write input history of client '{client_id}' to {path}: {e}"),
+        }
+    }
+}
+
+/// Asks for a client's input history to be written to disk, or every client's when `None`
+#[derive(Event)]
+pub struct DumpInputHistory(pub Option<ClientId>);
+
+fn record_inputs_system(
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    mut history: ResMut<InputHistory>,
+    tick: Res<RepliconTick>,
+) {
+    for FromClient { client_id, event } in input_reader.read()
+    {
+        history.record(*client_id, *tick, event);
+    }
+}
+
+/// `history <client_id>` logs every input still recorded for that client
+fn history_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    history: Res<InputHistory>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "history"
+        {
+            continue;
+        }
+
+        let Some(client_id) = command.args.get(1).and_then(|arg| arg.parse().ok()).map(ClientId::from_raw) else {
+            warn!("Server: Usage: history <client_id>");
+            continue;
+        };
+
+        match history.format(client_id)
+        {
+            Some(text) => info!("Server: Input history of client '{client_id}':\n{text}"),
+            None => info!("Server: No input history for client '{client_id}'"),
+        }
+    }
+}
+
+/// An audit failure can't be pinned on one client, so everyone's history is kept
+fn dump_on_audit_failure_system(
+    mut dumps: EventWriter<DumpInputHistory>,
+    audit_failures: Res<AuditFailures>,
+) {
+    if audit_failures.is_changed() && !audit_failures.is_added()
+    {
+        dumps.send(DumpInputHistory(None));
+    }
+}
+
+fn dump_input_history_system(
+    mut dumps: EventReader<DumpInputHistory>,
+    history: Res<InputHistory>,
+) {
+    for DumpInputHistory(client_id) in dumps.read()
+    {
+        match client_id
+        {
+            Some(client_id) => history.write_to_file(*client_id),
+            None =>
+            {
+                for client_id in history.clients.keys()
+                {
+                    history.write_to_file(*client_id);
+                }
+            }
+        }
+    }
+}
+
+fn drop_disconnected_history_system(
+    mut server_events: EventReader<ServerEvent>,
+    mut history: ResMut<InputHistory>,
+) {
+    for event in server_events.read()
+    {
+        let ServerEvent::ClientDisconnected { client_id, .. } = event else { continue; };
+        if history.dump_on_disconnect
+        {
+            history.write_to_file(*client_id);
+        }
+        history.clients.remove(client_id);
+    }
+}
@@ -0,0 +1,590 @@
+use std::fs;
+
+use bevy::{prelude::*, ecs::system::SystemParam, input::{InputSystem, gamepad::{GamepadConnection, GamepadConnectionEvent}}, reflect::{DynamicEnum, DynamicVariant, FromReflect, TypeInfo, Typed, VariantInfo}, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{Cli, console::ConsoleCommand, is_headless};
+
+/// Stick deflection below this reads as centered, the rest of the range is rescaled to start from zero
+pub const STICK_DEAD_ZONE: f32 = 0.2;
+
+/// Keyboard and gamepad read through the same functions, so every input system sends the same `PlayerInput`s
+/// whichever one the player uses
+///
+/// Every key is looked up in `InputBindings`, loaded from the `[bindings]` table of `--config` and changed at runtime
+/// with `bind`. By default left stick moves, right stick aims, right trigger fires a projectile (Space) and right
+/// bumper a hitscan shot (F).
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ActiveGamepad>()
+            .init_resource::<InputBindings>()
+            .add_systems(Startup, load_bindings_system)
+            .add_systems(Startup, spawn_bindings_overlay_system.run_if(not(is_headless)))
+            .add_systems(PreUpdate, track_gamepad_system.after(InputSystem))
+            .add_systems(Update, bind_console_system)
+            .add_systems(Update, bindings_overlay_system.run_if(not(is_headless)));
+    }
+}
+
+/// Everything a player can bind a key to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action
+{
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Shoot,
+    Hitscan,
+    Grab,
+    Ready,
+    Scoreboard,
+    Pistol,
+    Shotgun,
+    Lob,
+    Spectate,
+    Resync,
+    Minimap,
+    Console,
+    BindingsOverlay,
+    PathOverlay,
+    Mute,
+    Quit,
+    ConfirmQuit,
+    CancelQuit,
+    Reconnect,
+    LeaveDisconnected,
+    SpectatePrevious,
+    SpectateNext,
+    CameraUp,
+    CameraDown,
+    CameraLeft,
+    CameraRight,
+    StartCountdown,
+    Pause,
+    KillRandomPlayer,
+    RemoveMoveDirection,
+    Nudge,
+}
+
+/// When an action is read, two actions sharing a key only conflict if both can be read at once
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Context
+{
+    /// Playing, or a server's window, where the hotkeys are read every frame
+    Play,
+    /// Spectating a player, alongside `Play`
+    Following,
+    /// Flying the spectator camera, alongside `Play`
+    FreeCamera,
+    /// Only while the disconnect screen shows
+    Disconnected,
+    /// Only while asked whether to quit
+    QuitPrompt,
+}
+
+impl Context
+{
+    fn overlaps(self, other: Context) -> bool {
+        use Context::*;
+        self == other || matches!((self, other), (Play, Following | FreeCamera) | (Following | FreeCamera, Play))
+    }
+}
+
+impl Action
+{
+    pub const ALL: [Action; 35] = [
+        Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight, Action::Shoot, Action::Hitscan, Action::Grab,
+        Action::Ready, Action::Scoreboard, Action::Pistol, Action::Shotgun, Action::Lob, Action::Spectate, Action::Resync,
+        Action::Minimap, Action::Console, Action::BindingsOverlay, Action::PathOverlay, Action::Mute, Action::Quit,
+        Action::ConfirmQuit, Action::CancelQuit, Action::Reconnect, Action::LeaveDisconnected, Action::SpectatePrevious,
+        Action::SpectateNext, Action::CameraUp, Action::CameraDown, Action::CameraLeft, Action::CameraRight,
+        Action::StartCountdown, Action::Pause, Action::KillRandomPlayer, Action::RemoveMoveDirection, Action::Nudge,
+    ];
+
+    /// As written in `[bindings]` and `bind`
+    pub fn name(self) -> &'static str {
+        match self
+        {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Shoot => "shoot",
+            Action::Hitscan => "hitscan",
+            Action::Grab => "grab",
+            Action::Ready => "ready",
+            Action::Scoreboard => "scoreboard",
+            Action::Pistol => "pistol",
+            Action::Shotgun => "shotgun",
+            Action::Lob => "lob",
+            Action::Spectate => "spectate",
+            Action::Resync => "resync",
+            Action::Minimap => "minimap",
+            Action::Console => "console",
+            Action::BindingsOverlay => "bindings_overlay",
+            Action::PathOverlay => "path_overlay",
+            Action::Mute => "mute",
+            Action::Quit => "quit",
+            Action::ConfirmQuit => "confirm_quit",
+            Action::CancelQuit => "cancel_quit",
+            Action::Reconnect => "reconnect",
+            Action::LeaveDisconnected => "leave_disconnected",
+            Action::SpectatePrevious => "spectate_previous",
+            Action::SpectateNext => "spectate_next",
+            Action::CameraUp => "camera_up",
+            Action::CameraDown => "camera_down",
+            Action::CameraLeft => "camera_left",
+            Action::CameraRight => "camera_right",
+            Action::StartCountdown => "start_countdown",
+            Action::Pause => "pause",
+            Action::KillRandomPlayer => "kill_random_player",
+            Action::RemoveMoveDirection => "remove_move_direction",
+            Action::Nudge => "nudge",
+        }
+    }
+
+    fn context(self) -> Context {
+        match self
+        {
+            Action::SpectatePrevious | Action::SpectateNext => Context::Following,
+            Action::CameraUp | Action::CameraDown | Action::CameraLeft | Action::CameraRight => Context::FreeCamera,
+            Action::Reconnect | Action::LeaveDisconnected => Context::Disconnected,
+            Action::ConfirmQuit | Action::CancelQuit => Context::QuitPrompt,
+            _ => Context::Play,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A key, and a gamepad button that does the same
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Binding
+{
+    pub key: KeyCode,
+    pub button: Option<GamepadButtonType>,
+}
+
+/// A `[bindings]` entry, either just a key (`shoot = "Space"`) or a key and gamepad button
+/// (`shoot = { key = "Space", button = "RightTrigger2" }`), names as in bevy's `KeyCode` and `GamepadButtonType`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum BindingEntry
+{
+    Key(String),
+    Full { key: String, button: Option<String> },
+}
+
+/// The `[bindings]` table of a `--config` file, by action name
+pub type BindingsTable = HashMap<String, BindingEntry>;
+
+/// Which key (and gamepad button) triggers each action, every action always has one
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct InputBindings(HashMap<Action, Binding>);
+
+impl Default for InputBindings
+{
+    fn default() -> Self {
+        let key = |key| Binding { key, button: None };
+        Self([
+            (Action::MoveUp, key(KeyCode::W)),
+            (Action::MoveDown, key(KeyCode::S)),
+            (Action::MoveLeft, key(KeyCode::A)),
+            (Action::MoveRight, key(KeyCode::D)),
+            (Action::Shoot, Binding { key: KeyCode::Space, button: Some(GamepadButtonType::RightTrigger2) }),
+            (Action::Hitscan, Binding { key: KeyCode::F, button: Some(GamepadButtonType::RightTrigger) }),
+            (Action::Grab, key(KeyCode::E)),
+            (Action::Ready, key(KeyCode::R)),
+            (Action::Scoreboard, key(KeyCode::Tab)),
+            (Action::Pistol, key(KeyCode::Key1)),
+            (Action::Shotgun, key(KeyCode::Key2)),
+            (Action::Lob, key(KeyCode::Key3)),
+            (Action::Spectate, key(KeyCode::V)),
+            (Action::Resync, key(KeyCode::F12)),
+            (Action::Minimap, key(KeyCode::Z)),
+            (Action::Console, key(KeyCode::Grave)),
+            (Action::BindingsOverlay, key(KeyCode::F1)),
+            (Action::PathOverlay, key(KeyCode::F4)),
+            (Action::Mute, key(KeyCode::F8)),
+            (Action::Quit, key(KeyCode::Escape)),
+            (Action::ConfirmQuit, key(KeyCode::Y)),
+            (Action::CancelQuit, key(KeyCode::N)),
+            (Action::Reconnect, key(KeyCode::R)),
+            (Action::LeaveDisconnected, key(KeyCode::Q)),
+            (Action::SpectatePrevious, key(KeyCode::Left)),
+            (Action::SpectateNext, key(KeyCode::Right)),
+            (Action::CameraUp, key(KeyCode::Up)),
+            (Action::CameraDown, key(KeyCode::Down)),
+            (Action::CameraLeft, key(KeyCode::Left)),
+            (Action::CameraRight, key(KeyCode::Right)),
+            (Action::StartCountdown, key(KeyCode::G)),
+            (Action::Pause, key(KeyCode::P)),
+            (Action::KillRandomPlayer, key(KeyCode::K)),
+            (Action::RemoveMoveDirection, key(KeyCode::M)),
+            (Action::Nudge, key(KeyCode::Return)),
+        ].into_iter().collect())
+    }
+}
+
+impl InputBindings
+{
+    /// The defaults with every entry of `table` applied, refusing unknown actions, keys and buttons
+    pub fn from_table(table: &BindingsTable) -> Result<Self, String> {
+        let mut bindings = Self::default();
+        for (name, entry) in table
+        {
+            let (key, button) = match entry
+            {
+                BindingEntry::Key(key) => (key, None),
+                BindingEntry::Full { key, button } => (key, button.as_ref()),
+            };
+            let action = Action::from_name(name).ok_or_else(|| format!("`bindings.{name}` isn't an action"))?;
+            bindings.bind(action, key, button.map(String::as_str)).map_err(|e| format!("`bindings.{name}`: {e}"))?;
+        }
+        Ok(bindings)
+    }
+
+    /// Rebinds `action` to the named key, and button if one is given, leaving everything unchanged on an unknown name
+    pub fn bind(&mut self, action: Action, key: &str, button: Option<&str>) -> Result<(), String> {
+        let key = parse_variant::<KeyCode>(key).ok_or_else(|| format!("there's no key called `{key}`"))?;
+        let button = match button
+        {
+            Some(button) => Some(parse_variant::<GamepadButtonType>(button).ok_or_else(|| format!("there's no gamepad button called `{button}`"))?),
+            None => self.get(action).button,
+        };
+        self.0.insert(action, Binding { key, button });
+        Ok(())
+    }
+
+    pub fn get(&self, action: Action) -> Binding {
+        self.0[&action]
+    }
+
+    pub fn pressed(&self, action: Action, keys: &Input<KeyCode>) -> bool {
+        keys.pressed(self.get(action).key)
+    }
+
+    pub fn just_pressed(&self, action: Action, keys: &Input<KeyCode>) -> bool {
+        keys.just_pressed(self.get(action).key)
+    }
+
+    pub fn just_released(&self, action: Action, keys: &Input<KeyCode>) -> bool {
+        keys.just_released(self.get(action).key)
+    }
+
+    /// Keys bound to more than one action that can be read at the same time, each with the actions sharing it
+    ///
+    /// R readies up in the lobby and reconnects on the disconnect screen, which never both apply, so that's no conflict.
+    pub fn conflicts(&self) -> Vec<(KeyCode, Vec<Action>)> {
+        let mut by_key: HashMap<KeyCode, Vec<Action>> = HashMap::new();
+        for action in Action::ALL
+        {
+            by_key.entry(self.get(action).key).or_default().push(action);
+        }
+
+        let mut conflicts = Vec::new();
+        for (key, actions) in by_key
+        {
+            let clashing: Vec<Action> = actions.iter().copied()
+                .filter(|action| actions.iter().any(|other| other != action && other.context().overlaps(action.context())))
+                .collect();
+            if !clashing.is_empty()
+            {
+                conflicts.push((key, clashing));
+            }
+        }
+        conflicts
+    }
+
+    fn warn_conflicts(&self) {
+        for (key, actions) in self.conflicts()
+        {
+            let names: Vec<&str> = actions.iter().map(|action| action.name()).collect();
+            warn!("{key:?} is bound to more than one action: {}", names.join(", "));
+        }
+    }
+}
+
+/// A unit variant of `T` by name, ignoring case since console lines are lowercased
+fn parse_variant<T: FromReflect + Typed>(name: &str) -> Option<T> {
+    let TypeInfo::Enum(info) = T::type_info() else { return None; };
+    let variant = info.iter().find(|variant| matches!(variant, VariantInfo::Unit(_)) && variant.name().eq_ignore_ascii_case(name))?;
+    T::from_reflect(&DynamicEnum::new(variant.name(), DynamicVariant::Unit))
+}
+
+/// The gamepad input is read from, the first one connected
+#[derive(Resource, Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+/// Everything the input systems read, so none of them has to know where an input came from
+#[derive(SystemParam)]
+pub struct PlayerControls<'w>
+{
+    keys: Res<'w, Input<KeyCode>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    gamepad: Res<'w, ActiveGamepad>,
+    bindings: Res<'w, InputBindings>,
+}
+
+impl PlayerControls<'_>
+{
+    /// The movement keys, or the left stick when no key is held, normalized
+    pub fn movement(&self) -> Vec2 {
+        let keyboard = self.keyboard_direction();
+        let direction = if keyboard != Vec2::ZERO { keyboard } else { self.stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY) };
+        direction.normalize_or_zero()
+    }
+
+    /// The right stick's direction, while it's pushed past the dead zone
+    pub fn aim(&self) -> Option<Vec2> {
+        let aim = self.stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY).normalize_or_zero();
+        (aim != Vec2::ZERO).then_some(aim)
+    }
+
+    pub fn shoot_pressed(&self) -> bool {
+        self.bindings.just_pressed(Action::Shoot, &self.keys) || self.button_just_pressed(Action::Shoot)
+    }
+
+    /// Whether the fire key or trigger is down, for full-auto
+    pub fn shoot_held(&self) -> bool {
+        let button = self.bindings.get(Action::Shoot).button;
+        self.bindings.pressed(Action::Shoot, &self.keys)
+            || self.gamepad.0.zip(button).is_some_and(|(gamepad, button)| self.buttons.pressed(GamepadButton::new(gamepad, button)))
+    }
+
+    pub fn hitscan_key_pressed(&self) -> bool {
+        self.bindings.just_pressed(Action::Hitscan, &self.keys)
+    }
+
+    /// Just the gamepad's hitscan button, the keyboard and mouse ones are read with the cursor aim
+    pub fn gamepad_hitscan_pressed(&self) -> bool {
+        self.button_just_pressed(Action::Hitscan)
+    }
+
+    fn button_just_pressed(&self, action: Action) -> bool {
+        let button = self.bindings.get(action).button;
+        self.gamepad.0.zip(button).is_some_and(|(gamepad, button)| self.buttons.just_pressed(GamepadButton::new(gamepad, button)))
+    }
+
+    fn keyboard_direction(&self) -> Vec2 {
+        let mut direction = Vec2::ZERO;
+        if self.bindings.pressed(Action::MoveRight, &self.keys)
+        {
+            direction.x += 1.0;
+        }
+        if self.bindings.pressed(Action::MoveLeft, &self.keys)
+        {
+            direction.x -= 1.0;
+        }
+        if self.bindings.pressed(Action::MoveUp, &self.keys)
+        {
+            direction.y += 1.0;
+        }
+        if self.bindings.pressed(Action::MoveDown, &self.keys)
+        {
+            direction.y -= 1.0;
+        }
+        direction
+    }
+
+    fn stick(&self, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+        let Some(gamepad) = self.gamepad.0 else { return Vec2::ZERO; };
+        let raw = Vec2::new(
+            self.axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.0),
+            self.axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.0),
+        );
+        apply_dead_zone(raw)
+    }
+}
+
+/// Zero inside `STICK_DEAD_ZONE`, and rescaled outside it so the stick still reaches full deflection
+///
+/// Radial rather than per axis, so pushing diagonally doesn't snap to the nearest axis.
+fn apply_dead_zone(raw: Vec2) -> Vec2 {
+    let length = raw.length();
+    if !length.is_finite() || length <= STICK_DEAD_ZONE
+    {
+        return Vec2::ZERO;
+    }
+
+    let scaled = ((length - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE)).min(1.0);
+    raw / length * scaled
+}
+
+/// Just the `[bindings]` table, the rest of the file is the server's business
+#[derive(Deserialize)]
+struct BindingsFile
+{
+    #[serde(default)]
+    bindings: BindingsTable,
+}
+
+/// Replaces the defaults with the `[bindings]` of `--config`, keeping them if the file or a binding is invalid
+fn load_bindings_system(
+    mut bindings: ResMut<InputBindings>,
+    cli: Res<Cli>,
+) {
+    if let Some(path) = cli.config_path()
+    {
+        let loaded = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| toml::from_str::<BindingsFile>(&text).map_err(|e| e.to_string()))
+            .and_then(|file| InputBindings::from_table(&file.bindings));
+        match loaded
+        {
+            Ok(loaded) => *bindings = loaded,
+            Err(e) => warn!("Couldn't load key bindings from {}, using the defaults: {e}", path.display()),
+        }
+    }
+
+    bindings.warn_conflicts();
+}
+
+/// `bind <action> <key> [gamepad button]`, e.g. `bind shoot f`, always runs on the machine it was typed on
+fn bind_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "bind"
+        {
+            continue;
+        }
+
+        let (Some(action), Some(key)) = (command.args.get(1), command.args.get(2)) else {
+            warn!("Usage: bind <action> <key> [gamepad button], actions are: {}", Action::ALL.map(Action::name).join(", "));
+            continue;
+        };
+        let Some(action) = Action::from_name(action) else {
+            warn!("There's no action called `{action}`, try one of: {}", Action::ALL.map(Action::name).join(", "));
+            continue;
+        };
+
+        match bindings.bind(action, key, command.args.get(3).map(String::as_str))
+        {
+            Ok(()) =>
+            {
+                info!("Bound {} to {:?}", action.name(), bindings.get(action).key);
+                bindings.warn_conflicts();
+            }
+            Err(e) => warn!("Couldn't bind {}: {e}", action.name()),
+        }
+    }
+}
+
+/// Every action and what it's bound to, held with F1 (`bindings_overlay`)
+#[derive(Component)]
+pub struct BindingsOverlayText;
+
+fn spawn_bindings_overlay_system(
+    mut commands: Commands,
+) {
+    let mut overlay = TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() })
+        .with_style(Style { position_type: PositionType::Absolute, left: Val::Px(5.0), top: Val::Px(5.0), ..default() })
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.7));
+    overlay.visibility = Visibility::Hidden;
+    commands.spawn((overlay, BindingsOverlayText));
+}
+
+fn bindings_overlay_system(
+    mut overlays: Query<(&mut Text, &mut Visibility), With<BindingsOverlayText>>,
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let Ok((mut text, mut visibility)) = overlays.get_single_mut() else { return; };
+    visibility.set_if_neq(if bindings.pressed(Action::BindingsOverlay, &keys) { Visibility::Inherited } else { Visibility::Hidden });
+
+    if bindings.is_changed() || text.sections[0].value.is_empty()
+    {
+        text.sections[0].value = Action::ALL.into_iter()
+            .map(|action| {
+                let binding = bindings.get(action);
+                let button = binding.button.map_or(String::new(), |button| format!(" / {button:?}"));
+                format!("{}: {:?}{button}", action.name(), binding.key)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
+
+/// Picks up the first gamepad connected, and falls back to another (or the keyboard alone) when it's unplugged
+fn track_gamepad_system(
+    mut connections: EventReader<GamepadConnectionEvent>,
+    mut active: ResMut<ActiveGamepad>,
+    gamepads: Res<Gamepads>,
+) {
+    for event in connections.read()
+    {
+        match &event.connection
+        {
+            GamepadConnection::Connected(info) =>
+            {
+                info!("Gamepad {} connected: {}", event.gamepad.id, info.name);
+                if active.0.is_none()
+                {
+                    active.0 = Some(event.gamepad);
+                }
+            }
+            GamepadConnection::Disconnected =>
+            {
+                info!("Gamepad {} disconnected", event.gamepad.id);
+                if active.0 == Some(event.gamepad)
+                {
+                    active.0 = gamepads.iter().find(|gamepad| *gamepad != event.gamepad);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn only_keys_read_at_the_same_time_conflict() {
+        // R is both ready and reconnect, and the arrows both spectator modes, none of which clash
+        assert!(InputBindings::default().conflicts().is_empty());
+
+        let mut bindings = InputBindings::default();
+        bindings.bind(Action::Shoot, "R", None).unwrap();
+        let conflicts = bindings.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        let (key, mut actions) = conflicts[0].clone();
+        actions.sort_by_key(|action| action.name());
+        assert_eq!((key, actions), (KeyCode::R, vec![Action::Ready, Action::Shoot]));
+
+        // Free-fly and following are never read together, but either is read alongside play
+        bindings.bind(Action::Shoot, "Space", None).unwrap();
+        bindings.bind(Action::CameraUp, "Left", None).unwrap();
+        assert!(bindings.conflicts().is_empty());
+        bindings.bind(Action::Minimap, "Left", None).unwrap();
+        assert_eq!(bindings.conflicts().len(), 1);
+    }
+
+    #[test]
+    fn stick_inside_the_dead_zone_is_still_and_outside_reaches_full() {
+        assert_eq!(apply_dead_zone(Vec2::new(0.1, -0.1)), Vec2::ZERO);
+        assert_eq!(apply_dead_zone(Vec2::new(STICK_DEAD_ZONE, 0.0)), Vec2::ZERO);
+        assert_eq!(apply_dead_zone(Vec2::NAN), Vec2::ZERO);
+
+        // Just past the edge starts near zero rather than jumping to the raw deflection
+        assert!(apply_dead_zone(Vec2::new(STICK_DEAD_ZONE + 0.01, 0.0)).x < 0.05);
+        assert!((apply_dead_zone(Vec2::new(0.0, 1.0)) - Vec2::Y).length() < 1e-5);
+
+        // Diagonal stays diagonal, and never goes past full deflection
+        let diagonal = apply_dead_zone(Vec2::new(1.0, 1.0));
+        assert!((diagonal.x - diagonal.y).abs() < 1e-5);
+        assert!((diagonal.length() - 1.0).abs() < 1e-5);
+    }
+}
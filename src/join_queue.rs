@@ -0,0 +1,178 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_replicon::{prelude::*, renet::{ClientId, SendType}};
+use serde::{Serialize, Deserialize};
+
+use crate::is_headless;
+
+/// Clients `--queue` lets wait beyond `--max-players`, netcode refuses any more than that
+pub const QUEUE_CAPACITY: usize = 16;
+
+/// With `--queue`, clients connecting to a full server wait for a slot instead of being refused
+///
+/// Queued clients stay connected and see the game, but have no player until one leaves.
+pub struct JoinQueuePlugin;
+
+impl Plugin for JoinQueuePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_server_event::<QueueStatus>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_systems(Update, send_queue_positions_system.run_if(resource_exists::<JoinQueue>()))
+            .add_systems(Startup, spawn_queue_text.run_if(not(is_headless)))
+            .add_systems(Update, show_queue_status_system.run_if(resource_exists::<RenetClient>()).run_if(not(is_headless)));
+    }
+}
+
+/// Sent to a queued client whenever its place changes, a position of 0 means it has been given a player
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct QueueStatus
+{
+    pub position: u32,
+}
+
+/// Server side, who has a player slot and who's waiting for one, in order
+#[derive(Resource)]
+pub struct JoinQueue
+{
+    max_players: usize,
+    admitted: HashSet<ClientId>,
+    waiting: VecDeque<ClientId>,
+}
+
+impl JoinQueue
+{
+    pub fn new(max_players: usize) -> Self {
+        Self { max_players, admitted: default(), waiting: default() }
+    }
+
+    /// Gives the client a slot if one is free and returns true, otherwise queues it
+    pub fn join(&mut self, client_id: ClientId) -> bool {
+        if self.admitted.len() < self.max_players
+        {
+            self.admitted.insert(client_id);
+            return true;
+        }
+
+        self.waiting.push_back(client_id);
+        false
+    }
+
+    /// Frees the client's slot or its place in the queue, returning whoever was promoted into a freed slot
+    pub fn leave(&mut self, client_id: ClientId) -> Option<ClientId> {
+        if !self.admitted.remove(&client_id)
+        {
+            // Everyone behind it moves up, `send_queue_positions_system` tells them
+            self.waiting.retain(|waiting| *waiting != client_id);
+            return None;
+        }
+
+        let promoted = self.waiting.pop_front()?;
+        self.admitted.insert(promoted);
+        Some(promoted)
+    }
+
+    /// Every waiting client with its position, the next to be promoted is 1
+    pub fn positions(&self) -> impl Iterator<Item = (ClientId, u32)> + '_ {
+        self.waiting.iter().enumerate().map(|(index, client_id)| (*client_id, index as u32 + 1))
+    }
+}
+
+/// Tells every queued client its position whenever the queue changes
+fn send_queue_positions_system(
+    mut statuses: EventWriter<ToClients<QueueStatus>>,
+    queue: Res<JoinQueue>,
+) {
+    if !queue.is_changed()
+    {
+        return;
+    }
+
+    for (client_id, position) in queue.positions()
+    {
+        statuses.send(ToClients { mode: SendMode::Direct(client_id), event: QueueStatus { position } });
+    }
+}
+
+#[derive(Component)]
+pub struct QueueText;
+
+fn spawn_queue_text(
+    mut commands: Commands,
+) {
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle { font_size: 32.0, color: Color::ORANGE, ..default() }
+    ).with_style(Style {
+        position_type: PositionType::Absolute, top: Val::Percent(20.0), left: Val::Percent(40.0), ..default()
+    }), QueueText));
+}
+
+fn show_queue_status_system(
+    mut statuses: EventReader<QueueStatus>,
+    mut texts: Query<&mut Text, With<QueueText>>,
+) {
+    let Some(status) = statuses.read().last() else { return; };
+
+    let value = match status.position
+    {
+        0 =>
+        {
+            info!("Client: Left the queue, joining the game");
+            String::new()
+        }
+        position =>
+        {
+            info!("Client: Server is full, waiting in the queue at position {position}");
+            format!("Server full, position {position} in queue")
+        }
+    };
+    for mut text in &mut texts
+    {
+        text.sections[0].value = value.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::ecs::event::ManualEventReader;
+    use clap::Parser;
+
+    use super::*;
+    use crate::{Cli, Player, bench::{step_session, tests::{connected_session_with, join_session}}};
+
+    fn has_player(app: &mut App, id: u64) -> bool {
+        app.world.query::<&Player>().iter(&app.world).any(|player| player.0 == id)
+    }
+
+    /// Steps the session for a second, collecting every queue position client 2 was sent
+    fn step_second(server_app: &mut App, client_apps: &mut [App], reader: &mut ManualEventReader<QueueStatus>) -> Vec<u32> {
+        let mut positions = Vec::new();
+        for _ in 0..60
+        {
+            step_session(server_app, client_apps);
+            positions.extend(reader.read(client_apps[1].world.resource::<Events<QueueStatus>>()).map(|status| status.position));
+        }
+        positions
+    }
+
+    #[test]
+    fn queued_client_is_promoted_into_the_slot_a_leaving_player_frees() {
+        let cli = Cli::parse_from(["replicon_test_1", "bench", "--queue", "--max-players", "1"]);
+        let (mut server_app, mut client_apps) = connected_session_with(&cli, 1);
+        client_apps.push(join_session(&mut server_app, 2));
+        let mut reader = ManualEventReader::<QueueStatus>::default();
+
+        assert_eq!(step_second(&mut server_app, &mut client_apps, &mut reader), [1]);
+        assert!(!has_player(&mut server_app, 2));
+        assert!(client_apps[1].world.resource::<RenetClient>().is_connected(), "a queued client should stay connected");
+
+        client_apps[0].world.resource_mut::<RenetClient>().disconnect();
+        assert_eq!(step_second(&mut server_app, &mut client_apps, &mut reader), [0]);
+        assert!(has_player(&mut server_app, 2));
+        assert!(has_player(&mut client_apps[1], 2), "the promoted client never saw its player");
+        assert_eq!(server_app.world.resource::<JoinQueue>().positions().count(), 0);
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{Player, is_headless, room::OtherRoom};
+
+/// How many label entities are spawned up front, the pool grows past this if needed
+const INITIAL_POOL_SIZE: usize = 8;
+const LABEL_OFFSET: Vec3 = Vec3::new(0.0, 16.0, 1.0);
+
+/// Name labels above players, taken from a pool of reusable `Text2dBundle`s instead of spawned/despawned per player
+pub struct LabelPlugin;
+
+impl Plugin for LabelPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LabelPool>()
+            .add_systems(Startup, fill_label_pool_system.run_if(not(is_headless)))
+            .add_systems(Update,
+                (
+                    assign_labels_system,
+                    label_follow_system,
+                ).chain().run_if(not(is_headless))
+            );
+    }
+}
+
+/// Marker for pooled label entities
+#[derive(Component)]
+pub struct PlayerLabel;
+
+#[derive(Resource, Default)]
+pub struct LabelPool
+{
+    /// Hidden labels ready to be handed out
+    free: Vec<Entity>,
+    /// Player entity -> the label following it
+    assigned: HashMap<Entity, Entity>,
+}
+
+fn spawn_label(commands: &mut Commands) -> Entity {
+    commands.spawn((Text2dBundle {
+        text: Text::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }),
+        visibility: Visibility::Hidden,
+        ..default()
+    }, PlayerLabel)).id()
+}
+
+fn fill_label_pool_system(
+    mut commands: Commands,
+    mut pool: ResMut<LabelPool>,
+) {
+    for _ in 0..INITIAL_POOL_SIZE
+    {
+        let label = spawn_label(&mut commands);
+        pool.free.push(label);
+    }
+}
+
+/// Hands labels to new players and returns labels of removed players to the pool
+fn assign_labels_system(
+    mut commands: Commands,
+    mut pool: ResMut<LabelPool>,
+    mut removed_players: RemovedComponents<Player>,
+    new_players: Query<(Entity, &Player), Added<Player>>,
+    mut labels: Query<(&mut Text, &mut Visibility), With<PlayerLabel>>,
+) {
+    for player_entity in removed_players.read()
+    {
+        let Some(label) = pool.assigned.remove(&player_entity) else { continue; };
+        if let Ok((_, mut visibility)) = labels.get_mut(label)
+        {
+            *visibility = Visibility::Hidden;
+        }
+        pool.free.push(label);
+    }
+
+    for (player_entity, player) in &new_players
+    {
+        if pool.assigned.contains_key(&player_entity)
+        {
+            continue;
+        }
+
+        let name = format!("P{}", player.0 % 10_000);
+        let label = match pool.free.pop()
+        {
+            Some(label) =>
+            {
+                if let Ok((mut text, _)) = labels.get_mut(label)
+                {
+                    text.sections[0].value = name;
+                }
+                label
+            }
+            // Pool exhausted, grow it, the label becomes visible once it follows its player
+            None =>
+            {
+                let label = spawn_label(&mut commands);
+                commands.entity(label).insert(Text::from_section(name, TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }));
+                label
+            }
+        };
+        pool.assigned.insert(player_entity, label);
+    }
+}
+
+/// Keeps each active label above its player's sprite, hidden while they're in another room
+fn label_follow_system(
+    pool: Res<LabelPool>,
+    players: Query<(&Transform, Has<OtherRoom>), (With<Player>, Without<PlayerLabel>)>,
+    mut labels: Query<(&mut Transform, &mut Visibility), With<PlayerLabel>>,
+) {
+    for (player_entity, label) in &pool.assigned
+    {
+        let Ok((mut label_transform, mut visibility)) = labels.get_mut(*label) else { continue; };
+
+        // The player's sprite may not have been attached yet
+        let Ok((player_transform, other_room)) = players.get(*player_entity) else { continue; };
+        if other_room
+        {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+
+        label_transform.translation = player_transform.translation + LABEL_OFFSET;
+        visibility.set_if_neq(Visibility::Visible);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn label_app() -> App {
+        let mut app = App::new();
+        app
+            .init_resource::<LabelPool>()
+            .add_systems(Startup, fill_label_pool_system)
+            .add_systems(Update, (assign_labels_system, label_follow_system).chain());
+        app.update();
+        app
+    }
+
+    fn labels(app: &mut App) -> Vec<Entity> {
+        let mut labels: Vec<Entity> = app.world.query_filtered::<Entity, With<PlayerLabel>>().iter(&app.world).collect();
+        labels.sort();
+        labels
+    }
+
+    fn spawn_player(app: &mut App, id: u64) -> Entity {
+        app.world.spawn((Player(id), Transform::default())).id()
+    }
+
+    #[test]
+    fn labels_are_reused_across_a_despawn_and_respawn() {
+        let mut app = label_app();
+        let pooled = labels(&mut app);
+        assert_eq!(pooled.len(), INITIAL_POOL_SIZE);
+
+        let first = spawn_player(&mut app, 1);
+        app.update();
+        let label = app.world.resource::<LabelPool>().assigned[&first];
+        assert_eq!(app.world.get::<Visibility>(label), Some(&Visibility::Visible));
+
+        app.world.despawn(first);
+        app.update();
+        assert_eq!(app.world.get::<Visibility>(label), Some(&Visibility::Hidden));
+
+        let second = spawn_player(&mut app, 2);
+        app.update();
+        assert_eq!(app.world.resource::<LabelPool>().assigned[&second], label, "the freed label wasn't handed out again");
+        assert_eq!(app.world.get::<Text>(label).unwrap().sections[0].value, "P2");
+        assert_eq!(labels(&mut app), pooled, "labels were spawned or despawned");
+    }
+
+    #[test]
+    fn pool_grows_once_and_keeps_its_extra_labels() {
+        let mut app = label_app();
+        let players: Vec<Entity> = (0..INITIAL_POOL_SIZE as u64 + 2).map(|id| spawn_player(&mut app, id)).collect();
+        app.update();
+        let grown = labels(&mut app);
+        assert_eq!(grown.len(), INITIAL_POOL_SIZE + 2);
+
+        for player in players
+        {
+            app.world.despawn(player);
+        }
+        app.update();
+        for id in 0..INITIAL_POOL_SIZE as u64 + 2
+        {
+            spawn_player(&mut app, id);
+        }
+        app.update();
+        assert_eq!(labels(&mut app), grown);
+    }
+}
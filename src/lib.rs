@@ -0,0 +1,2758 @@
+use std::{error::Error, net::{IpAddr, Ipv4Addr}, path::PathBuf, time::Duration};
+
+use bevy::{prelude::*, app::ScheduleRunnerPlugin, log::LogPlugin, render::{RenderPlugin, settings::WgpuSettings}, time::TimeUpdateStrategy, utils::{HashMap, Instant}, window::{ExitCondition, WindowResolution}, winit::WinitPlugin};
+use bevy_replicon::{prelude::*, server::{TickPolicy, VisibilityPolicy}, renet::{ChannelConfig, ConnectionConfig, SendType, ServerEvent, ClientId, transport::NetcodeServerTransport}, client, network_event::client_event, replicon_core::replication_rules::remove_component};
+use clap::{Args, Parser};
+use rand::seq::IteratorRandom;
+use serde::{Serialize, Deserialize};
+
+use afk::{AfkPlugin, AfkTimeout, DEFAULT_AFK_TIMEOUT};
+use anim_state::{AnimHold, AnimState, AnimStatePlugin};
+use appearance::{Appearance, AppearanceCycle, AppearancePlugin, BaseColor, SkinAssets};
+use audit::{AuditFailures, AuditPlugin};
+use bench::BenchPlugin;
+use bot::BotPlugin;
+use camera_shake::CameraShakePlugin;
+use checksum::ChecksumPlugin;
+use ball::{Ball, BallPlugin, ControlledBy, GrabIntent, Velocity, BALL_SPEED};
+use client_stats::{ClientInputStats, ClientStatsPlugin};
+use congestion::CongestionPlugin;
+use config::{ConfigPlugin, ConfigWatch};
+use console::{AdminKey, ConsolePlugin};
+use damage::{DamageLog, DamagePlugin, Health};
+use death_animation::{DeathAnimation, DeathAnimationPlugin};
+use debug_draw::DebugDrawPlugin;
+use decoration::DecorationPlugin;
+use disconnect::DisconnectPlugin;
+use embed::EmbedPlugin;
+use diagnostics::{NetworkDiagnostics, NetworkDiagnosticsPlugin};
+use event_limits::{EventLimits, MAX_OTHER_PLAYER_INPUT_BYTES, MAX_PLAYER_INPUT_BYTES, MAX_PLAYER_MOVEMENT_BYTES, OversizedEvents};
+use flood::FloodPlugin;
+use focus::FocusPlugin;
+use game_phase::{GamePhase, GamePhasePlugin, Ready};
+use ghost::GhostPlugin;
+use health_bar::HealthBarPlugin;
+use hitscan::HitscanPlugin;
+use idle::IdlePlugin;
+use in_game_console::InGameConsolePlugin;
+use input_history::{InputHistory, InputHistoryPlugin};
+use input_map::{Action, InputBindings, InputMapPlugin, PlayerControls};
+use join_queue::{JoinQueue, JoinQueuePlugin, QueueStatus};
+use labels::LabelPlugin;
+use memory_transport::{MemoryServerTransport, MemoryTransportPlugin};
+use minimap::MinimapPlugin;
+use pause::{Pause, PausePlugin, SimulationSet};
+use player_state::{PlayerState, PlayerStatePlugin};
+use projectile::{Bounces, PredictedProjectile, ProjectileAge, ProjectileMotion, ProjectilePlugin, SpawnTick};
+use reconcile::{InputAck, PendingMovement, ReconcilePlugin};
+use reconnect::{ClientIdentity, DisconnectedPlayers, ReconnectPlugin};
+use reliable_replication::ReplicateReliableExt;
+use replay::{ReplayIdentities, ReplayPlayback, ReplayPlugin, Recording, SessionRecorder};
+use resync::ResyncPlugin;
+use room::{Room, RoomPlugin};
+use replication_interval::ReplicationIntervalPlugin;
+use replication_mask::{ReplicationMaskPlugin, deserialize_masked, serialize_masked};
+use rng::{RngPlugin, RngSeed};
+use scoreboard::{Score, ScoreboardPlugin};
+use send_rate::SendRatePlugin;
+use serialization::{DEFAULT_POSITION_SCALE, SerializationPlugin};
+use server_info::{ServerInfo, ServerInfoPlugin};
+use settings::{GameSettings, GameTuning, SettingsPlugin};
+use sfx::{DEFAULT_MUSIC_VOLUME, PlaySfx, Sfx, SfxPlugin, WorldStateReceived};
+use shutdown::ShutdownPlugin;
+use spawn_ack::SpawnAckPlugin;
+use spawn_protection::SpawnProtectionPlugin;
+use spawn_validation::{MappedSpawns, MappedSpawnsDespawned, SpawnRejected, SpawnValidationPlugin};
+use spatial_hash::SpatialHashPlugin;
+use spectator::SpectatorPlugin;
+use stats::{PlayerStats, StatsFile, StatsPlugin};
+use stats_csv::StatsCsvPlugin;
+use system_timings::{DEFAULT_SYSTEM_BUDGET_MS, SystemTimings, SystemTimingsPlugin, TimedSystem};
+use tick_timer::{SimulationClock, TickTimed, TickTimer, TickTimerPlugin, tick_down_system};
+use trace_replication::TraceReplicationPlugin;
+use turbo::TurboPlugin;
+use weapon::{FireMode, ProjectileKind, SelectedWeapon, ShotBudgets, WeaponKind, WeaponPlugin};
+use web_transport::{WebServerTransport, WebTransportPlugin};
+
+mod afk;
+mod anim_state;
+mod appearance;
+mod audit;
+mod ball;
+mod bench;
+mod bot;
+mod camera_shake;
+mod checksum;
+mod client_stats;
+mod config;
+mod congestion;
+mod console;
+mod damage;
+mod death_animation;
+mod debug_draw;
+mod decoration;
+mod dev;
+mod diagnostics;
+mod disconnect;
+mod embed;
+mod event_limits;
+mod fixed_point;
+mod flood;
+mod focus;
+mod game_phase;
+mod ghost;
+mod health_bar;
+mod hitscan;
+mod idle;
+mod in_game_console;
+mod input_map;
+mod input_history;
+mod join_queue;
+mod labels;
+pub mod memory_transport;
+mod minimap;
+mod pause;
+mod player_state;
+mod projectile;
+mod reconcile;
+mod reconnect;
+mod reliable_replication;
+mod replay;
+mod resync;
+mod room;
+mod replication_interval;
+mod replication_mask;
+mod rng;
+mod scoreboard;
+mod send_rate;
+mod serialization;
+mod server_info;
+mod settings;
+mod sfx;
+mod shutdown;
+mod spawn_ack;
+mod spawn_protection;
+mod spawn_validation;
+mod spatial_hash;
+mod spectator;
+mod stats;
+mod stats_csv;
+mod system_timings;
+mod tick_timer;
+mod trace_replication;
+mod transport;
+mod turbo;
+mod weapon;
+mod web_transport;
+
+pub use embed::{ServerHandle, ServerSettings, run_server};
+
+/// The `replicon_test_1` binary, parses the command line and runs whichever mode it asks for
+pub fn run() {
+    let cli = match config::parse_cli()
+    {
+        Ok(cli) => cli,
+        Err(e) =>
+        {
+            eprintln!("Invalid configuration: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    if matches!(cli, Cli::Bench { .. })
+    {
+        std::process::exit(bench::run(cli));
+    }
+    if matches!(cli, Cli::Dev { .. })
+    {
+        std::process::exit(dev::run(cli));
+    }
+
+    build_app(cli).run();
+}
+
+/// Everything but the run loop, so the benchmark can step several apps by hand
+fn build_app(cli: Cli) -> App {
+    let fixed_step = Duration::from_secs_f64(1.0 / SIMULATION_RATE);
+
+    // Turbo doesn't wait between frames, and advances time by exactly one fixed step each frame instead of by the wall clock
+    let (frame_wait, time_update_strategy) = if cli.is_turbo() || matches!(cli, Cli::Bench { .. })
+    {
+        (Duration::ZERO, TimeUpdateStrategy::ManualDuration(fixed_step))
+    }
+    // A replay sets every frame's time step itself, to the one recorded
+    else if matches!(cli, Cli::ReplayView { .. })
+    {
+        (fixed_step, TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+    }
+    else
+    {
+        (fixed_step, TimeUpdateStrategy::Automatic)
+    };
+
+    // A headless server has no window or GPU, so it runs the schedule on a timer instead of winit's event loop
+    let default_plugins = if cli.is_headless()
+    {
+        DefaultPlugins
+            .set(WindowPlugin { primary_window: None, exit_condition: ExitCondition::DontExit, ..default() })
+            .set(RenderPlugin { render_creation: WgpuSettings { backends: None, ..default() }.into() })
+            .disable::<WinitPlugin>()
+            .add(ScheduleRunnerPlugin::run_loop(frame_wait))
+    }
+    else if matches!(cli, Cli::ReplayView { .. })
+    {
+        // Recorded clients have no renet connection, so renet would log an error for everything replicon sends them
+        DefaultPlugins.build()
+            .set(WindowPlugin { close_when_requested: false, ..default() })
+            .set(LogPlugin { filter: "wgpu=error,naga=warn,renet=off".to_string(), ..default() })
+    }
+    else
+    {
+        // Closing the window goes through the shutdown module, so the connection is torn down before exiting
+        let window = cli.window_args().map_or_else(Window::default, WindowArgs::window);
+        DefaultPlugins.build()
+            .set(WindowPlugin { primary_window: Some(window), close_when_requested: false, ..default() })
+    };
+
+    // Process-global, not per app: every app built in one process (the bench's server and clients, tests) encodes
+    // with whichever scale was set last, so they must all be built with the same `--position-scale`. `run_server`
+    // refuses to start a server with another scale while one is running
+    serialization::set_position_scale(cli.connection_args().position_scale);
+
+    let mut app = App::new();
+    app
+        // Networking
+        .add_plugins((
+            default_plugins,
+            // Blacklisting lets rooms hide other rooms' entities from each client
+            ReplicationPlugins.build().set(ServerPlugin { tick_policy: TickPolicy::Manual, visibility_policy: VisibilityPolicy::Blacklist, ..default() }),
+            MemoryTransportPlugin,
+            SettingsPlugin,
+            SendRatePlugin,
+            SerializationPlugin,
+            ReconnectPlugin,
+            ShutdownPlugin,
+            ConsolePlugin,
+            ReplicationMaskPlugin,
+            ReplicationIntervalPlugin,
+            InputHistoryPlugin,
+            SpawnAckPlugin,
+            ServerInfoPlugin,
+            ConfigPlugin,
+        ))
+        // Gameplay
+        .add_plugins((
+            BallPlugin,
+            PlayerStatePlugin,
+            DamagePlugin,
+            AfkPlugin,
+            HitscanPlugin,
+            ProjectilePlugin,
+            RngPlugin,
+            GamePhasePlugin,
+            ScoreboardPlugin,
+            JoinQueuePlugin,
+            InputMapPlugin,
+            AnimStatePlugin,
+            SpawnProtectionPlugin,
+            SpatialHashPlugin,
+            WeaponPlugin,
+        ))
+        // Presentation and debugging
+        .add_plugins((
+            SfxPlugin,
+            AppearancePlugin,
+            LabelPlugin,
+            DecorationPlugin,
+            DebugDrawPlugin,
+            ClientStatsPlugin,
+            NetworkDiagnosticsPlugin,
+            TraceReplicationPlugin,
+            TurboPlugin,
+            AuditPlugin,
+            BenchPlugin,
+            SystemTimingsPlugin,
+            SpectatorPlugin,
+            ReplayPlugin,
+            CameraShakePlugin,
+        ))
+        .add_plugins((InGameConsolePlugin, ResyncPlugin, FocusPlugin, CongestionPlugin, DisconnectPlugin, ChecksumPlugin, StatsPlugin, HealthBarPlugin, SpawnValidationPlugin, StatsCsvPlugin, IdlePlugin, GhostPlugin, ReconcilePlugin, DeathAnimationPlugin, PausePlugin))
+        .add_plugins((MinimapPlugin, TickTimerPlugin, FloodPlugin, EmbedPlugin, WebTransportPlugin, RoomPlugin, BotPlugin))
+        .insert_resource(Time::<Fixed>::from_hz(SIMULATION_RATE))
+        .insert_resource(Time::<Virtual>::from_max_delta(MAX_CATCH_UP))
+        .insert_resource(time_update_strategy)
+        .insert_resource(cli)
+        .init_resource::<InputsCount>()
+        .init_resource::<AwaitingConfirmation>()
+        .init_resource::<Timmy>()
+        .init_resource::<ProbeStats>()
+        .init_resource::<RemovalsSeen>()
+        .init_resource::<InputSmoothing>()
+        .init_resource::<MaxEntities>()
+        // Spawns and insertions go over replicon's reliable init channel, later changes (`Position` every tick) over its
+        // unreliable update channel. A client holds any update until the init message it depends on has arrived, so a
+        // `Position` can't beat its spawn. `Player` must never be lost, so any change to it goes out reliably too.
+        .replicate_reliable::<Player>()
+        .replicate::<EntityKind>()
+        .replicate_with::<MoveDirection>(serialize_masked::<MoveDirection>, deserialize_masked::<MoveDirection>, remove_component::<MoveDirection>)
+        .init_resource::<OversizedEvents>()
+        .init_resource::<EventLimits>()
+        .add_client_event_with::<PlayerInput, _, _>(
+            SendType::ReliableOrdered { resend_time: Duration::from_millis(300) },
+            client_event::sending_system::<PlayerInput>,
+            event_limits::limited_receiving_system::<PlayerInput, MAX_PLAYER_INPUT_BYTES>,
+        )
+        // Movement is refreshed periodically anyway, so it takes its own unreliable channel where a lost packet can't hold up shots
+        .add_client_event_with::<PlayerMovement, _, _>(
+            SendType::Unreliable,
+            client_event::sending_system::<PlayerMovement>,
+            event_limits::limited_receiving_system::<PlayerMovement, MAX_PLAYER_MOVEMENT_BYTES>,
+        )
+        // Registered even when the nudge test is off, so every build agrees on the channel layout
+        .add_client_event_with::<OtherPlayerInput, _, _>(
+            SendType::ReliableOrdered { resend_time: Duration::from_millis(300) },
+            client_event::sending_system::<OtherPlayerInput>,
+            event_limits::limited_receiving_system::<OtherPlayerInput, MAX_OTHER_PLAYER_INPUT_BYTES>,
+        )
+        .add_systems(
+            Startup,
+        (
+            cli_system.map(Result::unwrap),
+            init_system,
+            log_network_channels_system,
+        ))
+        .add_systems(Update,
+            (
+                player_input_system,
+                player_movement_system,
+                grab_input_system,
+                nudge_input_system,
+            ).run_if(has_local_player)
+        )
+        .add_systems(FixedUpdate, move_player_system.in_set(SimulationSet))
+        .add_systems(Update, sync_position_transform_system.run_if(has_authority()))
+        .add_systems(Update, interpolate_position_transform_system.run_if(resource_exists::<RenetClient>()))
+        .add_systems(Update, (update_input_count_text, update_debug_overlay_text).run_if(resource_exists::<UiReady>()))
+        .add_systems(Update, 
+            (
+            entity_tracker_system,
+            attach_extras_to_players,
+            attach_extras_to_projectiles,
+        ))
+        .add_systems(Update,
+            (
+                receive_player_input_system,
+                receive_player_movement_system,
+            ).run_if(has_authority())
+        )
+        .add_systems(Update, receive_nudge_system.run_if(has_authority()).run_if(nudge_test_enabled))
+        .add_systems(Update,
+            (
+                server_connection_events_system,
+                server_remove_move_direction_system,
+                tick_down_system::<RestoreMoveDirection>.before(server_restore_move_direction_system),
+                server_restore_move_direction_system,
+            ).run_if(resource_exists::<RenetServer>())
+        )
+        .add_systems(Update,
+            (
+                client_move_direction_removal_system,
+                confirm_predicted_spawns_system.after(entity_tracker_system),
+            ).run_if(resource_exists::<RenetClient>())
+        )
+        .add_systems(Update, 
+            (client_tracker_system, client_random_spawn_system).run_if(has_local_player)
+        );
+
+    app
+}
+
+const SERVER_ID: ClientId = ClientId::from_raw(0);
+const PORT: u16 = 5003;
+/// Fixed simulation steps per second, independent of how often replication is sent
+const SIMULATION_RATE: f64 = 60.0;
+/// Seconds between probe spawns when `--probe-local` is used without `--probe-spawns`
+const DEFAULT_PROBE_INTERVAL: f32 = 5.0;
+/// Seconds a pre-spawned entity waits for the server before it's counted as orphaned
+const ORPHAN_TIMEOUT: f32 = 2.0;
+/// Seconds between resends of an unchanged movement direction
+const MOVEMENT_REFRESH_INTERVAL: f32 = 0.5;
+/// Squared length past which a received movement direction is clamped, a little over 1 for float error
+const MAX_MOVEMENT_LENGTH_SQUARED: f32 = 1.0001;
+/// Most real time one frame's fixed steps catch up on after a hitch, the rest is dropped rather than simulated in a burst
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+/// Default for `--max-entities`, far above normal play but low enough to keep replication responsive
+const DEFAULT_MAX_ENTITIES: usize = 2000;
+
+#[derive(Component, Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct Player(pub u64);
+
+#[derive(Parser, PartialEq, Clone, Resource)]
+pub enum Cli
+{
+    Server {
+        #[arg(short, long, default_value_t = PORT)]
+        port: u16,
+
+        /// Run without a window, renderer or any asset loading
+        #[arg(long)]
+        headless: bool,
+
+        /// Step the simulation as fast as possible instead of in real time, for synthetic load tests only
+        #[arg(long, requires = "headless")]
+        turbo: bool,
+
+        #[command(flatten)]
+        window_args: WindowArgs,
+
+        #[command(flatten)]
+        server_args: ServerArgs,
+
+        /// Set by `run_server` for a server only reachable over the in-process memory transport, there's no flag for it
+        #[arg(skip)]
+        memory_transport: bool,
+    },
+    Client {
+        #[arg(short, long, default_value_t = Ipv4Addr::LOCALHOST.into())]
+        ip: IpAddr,
+
+        #[arg(short, long, default_value_t = PORT)]
+        port: u16,
+
+        /// Draw colliders and each entity's prediction/replication state
+        #[arg(long)]
+        debug_draw: bool,
+
+        /// Seconds for movement to ramp up to full speed, key release still stops immediately
+        #[arg(long, default_value_t = 0.0)]
+        input_smoothing: f32,
+
+        /// Milliseconds any system reacting to replicated spawns may take in a frame before a warning is logged
+        #[arg(long, default_value_t = DEFAULT_SYSTEM_BUDGET_MS)]
+        system_budget_ms: f32,
+
+        /// File holding this machine's identity, reconnecting with the same identity gets back the same player
+        #[arg(long, default_value = "client_identity.txt")]
+        identity_file: PathBuf,
+
+        /// Log every replicated component change with its old and new value, very noisy
+        #[arg(long)]
+        trace_replication: bool,
+
+        /// Every this many seconds, pre-spawn a replicated entity and send the matching shoot input, like pressing Space
+        #[arg(long)]
+        probe_spawns: Option<f32>,
+
+        /// Also spawn a bare, unreplicated entity on each probe, for comparison with the replicated ones
+        #[arg(long)]
+        probe_local: bool,
+
+        /// Check entity invariants every second and log any violations
+        #[arg(long)]
+        audit: bool,
+
+        /// Acknowledge every replicated spawn back to the server, which logs who received what
+        #[arg(long)]
+        ack_spawns: bool,
+
+        /// Keep trying to connect again after losing the connection, e.g. to a restarting server
+        #[arg(long)]
+        auto_reconnect: bool,
+
+        /// Don't shake the camera when hit, the white flash still shows
+        #[arg(long)]
+        no_camera_shake: bool,
+
+        /// Sound effect volume, 1 is as recorded
+        #[arg(long, default_value_t = 1.0)]
+        sfx_volume: f32,
+
+        /// Background music volume, 0 doesn't play it at all
+        #[arg(long, default_value_t = DEFAULT_MUSIC_VOLUME)]
+        music_volume: f32,
+
+        /// Sent with commands typed into the in-game console, the server only runs them if it matches its `--admin-key`
+        #[arg(long)]
+        admin_key: Option<String>,
+
+        /// With `--audit`, ticks the client may be behind or ahead of a state checksum and still compare against it
+        #[arg(long, default_value_t = 0)]
+        checksum_tolerance: u32,
+
+        /// Append a row of network stats to this CSV file every second
+        #[arg(long)]
+        stats_csv: Option<PathBuf>,
+
+        /// TOML file in the server's `--config` format, a client only reads its `[bindings]` table
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Room to play in, only players in the same room see and hit each other. `room <name>` in the console switches
+        #[arg(long)]
+        room: Option<String>,
+
+        /// Play by itself: wander, shoot now and then and ready up in the lobby, through the same keys a player would press
+        #[arg(long)]
+        bot: bool,
+
+        #[command(flatten)]
+        window_args: WindowArgs,
+
+        #[command(flatten)]
+        connection_args: ConnectionArgs,
+    },
+    /// Listen-server, runs the server and plays as the `SERVER_ID` player in the same window
+    Host {
+        #[arg(short, long, default_value_t = PORT)]
+        port: u16,
+
+        /// Don't shake the camera when hit, the white flash still shows
+        #[arg(long)]
+        no_camera_shake: bool,
+
+        /// Sound effect volume, 1 is as recorded
+        #[arg(long, default_value_t = 1.0)]
+        sfx_volume: f32,
+
+        /// Background music volume, 0 doesn't play it at all
+        #[arg(long, default_value_t = DEFAULT_MUSIC_VOLUME)]
+        music_volume: f32,
+
+        #[command(flatten)]
+        window_args: WindowArgs,
+
+        #[command(flatten)]
+        server_args: ServerArgs,
+    },
+    /// Runs a headless server and scripted clients in one process over the in-memory transport, writing per-second samples to a CSV
+    Bench {
+        /// Scripted clients to connect
+        #[arg(long, default_value_t = 4)]
+        clients: u64,
+
+        /// Simulated seconds to run for
+        #[arg(long, default_value_t = 30.0)]
+        duration: f32,
+
+        /// Shots per second from each client, on average
+        #[arg(long, default_value_t = 2.0)]
+        shoot_rate: f32,
+
+        /// Movement direction changes per second from each client, on average
+        #[arg(long, default_value_t = 1.0)]
+        move_rate: f32,
+
+        /// Seeds the clients' scripts, and the server's randomness unless `--seed` is also given
+        #[arg(long, default_value_t = 0)]
+        bench_seed: u64,
+
+        #[arg(long, default_value = "bench.csv")]
+        output: PathBuf,
+
+        #[command(flatten)]
+        server_args: ServerArgs,
+    },
+    /// Plays back a session recorded with `--record`, without any networking. Pass the same server flags it was recorded with
+    ReplayView {
+        path: PathBuf,
+
+        #[command(flatten)]
+        server_args: ServerArgs,
+    },
+    /// Starts a server and some clients as separate processes with their windows tiled, their output prefixed in this terminal
+    Dev {
+        /// Client processes to start alongside the server
+        #[arg(long, default_value_t = 3)]
+        clients: u32,
+
+        /// How many of the clients are started with `--bot`, the last ones so the first windows are still yours
+        #[arg(long, default_value_t = 0)]
+        bots: u32,
+
+        #[arg(short, long, default_value_t = PORT)]
+        port: u16,
+
+        /// Passed to the server and every client, so they always agree
+        #[command(flatten)]
+        connection_args: ConnectionArgs,
+    },
+}
+
+/// Where the window opens and how big it is, for tiling several instances on one screen
+#[derive(Args, PartialEq, Clone)]
+pub struct WindowArgs
+{
+    /// Top-left corner of the window on screen as `x,y`, where the OS lets the app choose (Wayland doesn't)
+    #[arg(long, value_parser = parse_pair)]
+    pub window_pos: Option<IVec2>,
+
+    /// Window size in logical pixels as `width,height`
+    #[arg(long, value_parser = parse_pair)]
+    pub window_size: Option<IVec2>,
+}
+
+impl WindowArgs
+{
+    pub fn window(&self) -> Window {
+        let mut window = Window::default();
+        if let Some(pos) = self.window_pos
+        {
+            window.position = WindowPosition::At(pos);
+        }
+        if let Some(size) = self.window_size
+        {
+            window.resolution = WindowResolution::new(size.x as f32, size.y as f32);
+        }
+        window
+    }
+}
+
+/// `x,y` for `WindowArgs`
+fn parse_pair(value: &str) -> Result<IVec2, String> {
+    let (x, y) = value.split_once(',').ok_or_else(|| format!("`{value}` isn't two numbers separated by a comma"))?;
+    let parse = |part: &str| part.trim().parse::<i32>().map_err(|_| format!("`{part}` isn't a whole number"));
+    Ok(IVec2::new(parse(x)?, parse(y)?))
+}
+
+/// Options shared by the dedicated server and the listen-server
+#[derive(Args, PartialEq, Clone)]
+pub struct ServerArgs
+{
+    /// Replication updates sent per second, the simulation keeps running at its own fixed rate
+    #[arg(long, default_value_t = GameSettings::default().replication_rate)]
+    pub replication_rate: f32,
+
+    /// Seconds without any input from a client before its player is stopped and marked AFK
+    #[arg(long, default_value_t = DEFAULT_AFK_TIMEOUT)]
+    pub afk_timeout: f32,
+
+    /// Requested `SO_RCVBUF` size in bytes for the server socket (the OS may clamp it)
+    #[arg(long)]
+    pub recv_buffer: Option<usize>,
+
+    /// Requested `SO_SNDBUF` size in bytes for the server socket (the OS may clamp it)
+    #[arg(long)]
+    pub send_buffer: Option<usize>,
+
+    /// Also take browser clients (the `wasm` build) over WebSocket on this localhost port, alongside UDP clients
+    #[arg(long)]
+    pub web_port: Option<u16>,
+
+    /// Seed for background decoration and gameplay randomness, random when unset
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Check entity invariants every second and log any violations
+    #[arg(long)]
+    pub audit: bool,
+
+    /// Shift players sideways whenever any client presses Return, floods a second client event alongside shooting
+    #[arg(long)]
+    pub enable_nudge_test: bool,
+
+    /// Half the width and height of the playable area
+    #[arg(long, default_value_t = GameSettings::default().world_half_extent)]
+    pub world_half_extent: f32,
+
+    /// Live projectiles allowed at once, the oldest are despawned to make room for new ones
+    #[arg(long, default_value_t = GameSettings::default().max_projectiles)]
+    pub max_projectiles: usize,
+
+    /// TOML file of server options (see `config::Config`), flags given on the command line take precedence over it
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Shown to clients once they've joined, control characters are removed and it's cut to 32 characters
+    #[arg(long, default_value = "replicon_test")]
+    pub name: String,
+
+    /// A line about the server shown alongside its name, cut to 96 characters
+    #[arg(long, default_value = "")]
+    pub description: String,
+
+    /// Name of the map being played, informational only for now
+    #[arg(long, default_value = "default")]
+    pub map: String,
+
+    /// Write a client's recent inputs to a file when it disconnects, not just on audit failures
+    #[arg(long)]
+    pub dump_history_on_disconnect: bool,
+
+    /// Record every client input and connection to this file on exit, for playing back with `replay-view`
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+
+    /// Replicated entities the server allows at once, past it the oldest projectiles are recycled or shots refused
+    #[arg(long, default_value_t = DEFAULT_MAX_ENTITIES)]
+    pub max_entities: usize,
+
+    /// Clients that can have a player at once, the host's own player doesn't count
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_players: u16,
+
+    /// Queue clients connecting past `--max-players` until a player leaves, instead of refusing them
+    #[arg(long)]
+    pub queue: bool,
+
+    /// Move players with fixed-point integer math, so the same inputs give bit-identical positions on any machine
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Append a row of network stats to this CSV file every second
+    #[arg(long)]
+    pub stats_csv: Option<PathBuf>,
+
+    /// Round summaries are appended to this CSV file, one line per player at the end of every round
+    #[arg(long, default_value = "stats.csv")]
+    pub stats_file: PathBuf,
+
+    /// Clients sending this key can run console commands from their in-game console, none can without it
+    #[arg(long)]
+    pub admin_key: Option<String>,
+
+    /// Largest message in bytes the server decodes for one client event, as `<event>=<bytes>`, repeatable
+    #[arg(long = "event-limit", value_parser = event_limits::parse_event_limit)]
+    pub event_limits: Vec<(String, u64)>,
+
+    #[command(flatten)]
+    pub connection_args: ConnectionArgs,
+
+    /// Filled in from the `--config` file's `[tuning]` table, there are no flags for it
+    #[arg(skip)]
+    pub tuning: GameTuning,
+}
+
+/// Renet connection limits, clients must pass the same values as the server they connect to
+#[derive(Args, PartialEq, Clone)]
+pub struct ConnectionArgs
+{
+    /// Bytes each connection may send per tick, renet's default when unset
+    #[arg(long)]
+    pub available_bytes_per_tick: Option<u64>,
+
+    /// Memory cap in bytes for every channel, renet disconnects a connection whose reliable channel exceeds it
+    #[arg(long)]
+    pub channel_memory: Option<usize>,
+
+    /// Steps per world unit positions are rounded to on the wire, finer is more precise but reaches less far from the origin
+    #[arg(long, default_value_t = DEFAULT_POSITION_SCALE, value_parser = clap::value_parser!(u16).range(1..))]
+    pub position_scale: u16,
+}
+
+impl ConnectionArgs
+{
+    /// The `ConnectionConfig` for both ends of a connection, built the same way on each side so they always match
+    pub fn connection_config(&self, network_channels: &NetworkChannels) -> ConnectionConfig {
+        let mut server_channels_config = network_channels.get_server_configs();
+        let mut client_channels_config = network_channels.get_client_configs();
+        if let Some(bytes) = self.channel_memory
+        {
+            for channel in server_channels_config.iter_mut().chain(client_channels_config.iter_mut())
+            {
+                channel.max_memory_usage_bytes = bytes;
+            }
+        }
+
+        let defaults = ConnectionConfig::default();
+        ConnectionConfig {
+            available_bytes_per_tick: self.available_bytes_per_tick.unwrap_or(defaults.available_bytes_per_tick),
+            server_channels_config,
+            client_channels_config,
+        }
+    }
+
+    /// The same options as command line flags, for starting other processes that have to match this one
+    pub fn to_flags(&self) -> Vec<String> {
+        let mut flags = vec!["--position-scale".to_string(), self.position_scale.to_string()];
+        if let Some(bytes) = self.available_bytes_per_tick
+        {
+            flags.extend(["--available-bytes-per-tick".to_string(), bytes.to_string()]);
+        }
+        if let Some(bytes) = self.channel_memory
+        {
+            flags.extend(["--channel-memory".to_string(), bytes.to_string()]);
+        }
+        flags
+    }
+
+    /// The component format version, the position scale and the channel layout, so peers that would misread each
+    /// other can't connect
+    ///
+    /// Netcode drops connection requests with another protocol id, so a client built with a different set of
+    /// replicon events fails its handshake instead of reading one event's messages as another's.
+    pub fn protocol_id(&self, network_channels: &NetworkChannels) -> u64 {
+        let layout = channel_layout_hash(&network_channels.get_server_configs(), &network_channels.get_client_configs());
+        serialization::FORMAT_VERSION as u64 | (self.position_scale as u64) << 8 | (layout as u64) << 32
+    }
+}
+
+impl Cli
+{
+    pub fn is_headless(&self) -> bool {
+        matches!(self, Cli::Server { headless: true, .. } | Cli::Bench { .. })
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        matches!(self, Cli::Server { turbo: true, .. })
+    }
+
+    pub fn is_bot(&self) -> bool {
+        matches!(self, Cli::Client { bot: true, .. })
+    }
+
+    pub fn server_args_mut(&mut self) -> Option<&mut ServerArgs> {
+        match self
+        {
+            Cli::Server { server_args, .. } | Cli::Host { server_args, .. } | Cli::Bench { server_args, .. } | Cli::ReplayView { server_args, .. } => Some(server_args),
+            Cli::Client { .. } | Cli::Dev { .. } => None,
+        }
+    }
+
+    /// The room a client asked for with `--room`
+    pub fn room(&self) -> Option<&str> {
+        match self
+        {
+            Cli::Client { room, .. } => room.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn connection_args(&self) -> &ConnectionArgs {
+        match self
+        {
+            Cli::Server { server_args, .. } | Cli::Host { server_args, .. } | Cli::Bench { server_args, .. } | Cli::ReplayView { server_args, .. } => &server_args.connection_args,
+            Cli::Client { connection_args, .. } | Cli::Dev { connection_args, .. } => connection_args,
+        }
+    }
+
+    pub fn window_args(&self) -> Option<&WindowArgs> {
+        match self
+        {
+            Cli::Server { window_args, .. } | Cli::Client { window_args, .. } | Cli::Host { window_args, .. } => Some(window_args),
+            Cli::Bench { .. } | Cli::ReplayView { .. } | Cli::Dev { .. } => None,
+        }
+    }
+
+    pub fn config_path(&self) -> Option<&PathBuf> {
+        match self
+        {
+            Cli::Server { server_args, .. } | Cli::Host { server_args, .. } | Cli::Bench { server_args, .. } | Cli::ReplayView { server_args, .. } => server_args.config.as_ref(),
+            Cli::Client { config, .. } => config.as_ref(),
+            Cli::Dev { .. } => None,
+        }
+    }
+
+    pub fn stats_csv(&self) -> Option<&PathBuf> {
+        match self
+        {
+            Cli::Server { server_args, .. } | Cli::Host { server_args, .. } | Cli::Bench { server_args, .. } | Cli::ReplayView { server_args, .. } => server_args.stats_csv.as_ref(),
+            Cli::Client { stats_csv, .. } => stats_csv.as_ref(),
+            Cli::Dev { .. } => None,
+        }
+    }
+
+    pub fn audit_enabled(&self) -> bool {
+        match self
+        {
+            Cli::Server { server_args, .. } | Cli::Host { server_args, .. } | Cli::ReplayView { server_args, .. } => server_args.audit,
+            Cli::Client { audit, .. } => *audit,
+            Cli::Dev { .. } => false,
+            // Audit failures are how the benchmark spots a client that has desynced
+            Cli::Bench { .. } => true,
+        }
+    }
+}
+
+/// Run condition for systems that only make sense with a window (rendering, asset loading)
+pub fn is_headless(cli: Res<Cli>) -> bool
+{
+    cli.is_headless()
+}
+
+/// Run condition for the server side of the Return key nudge test
+pub fn nudge_test_enabled(cli: Res<Cli>) -> bool
+{
+    match &*cli
+    {
+        Cli::Server { server_args, .. } | Cli::Host { server_args, .. } | Cli::ReplayView { server_args, .. } => server_args.enable_nudge_test,
+        Cli::Client { .. } | Cli::Bench { .. } | Cli::Dev { .. } => false,
+    }
+}
+
+/// Run condition for systems driven by a local player's input (clients and listen-server hosts)
+pub fn has_local_player(cli: Res<Cli>) -> bool
+{
+    !matches!(*cli, Cli::Server { .. } | Cli::Bench { .. } | Cli::ReplayView { .. })
+}
+
+// A resource to track the number of entities spawned locally
+#[derive(Resource, Default)]
+pub struct InputsCount
+{
+    /// Pre-spawned by this client's own input, waiting for the server to map them
+    pub predicted: u64,
+    /// Authoritative spawns: this client's predictions the server confirmed, and entities that arrived through replication
+    pub confirmed: u64,
+    /// Predictions the server never confirmed within `ORPHAN_TIMEOUT`
+    pub orphaned: u64,
+}
+
+/// Client side, marks a pre-spawned entity until the server's replication confirms it, with its age in seconds
+#[derive(Component)]
+pub struct PredictedSpawn(pub f32);
+
+/// Client side, every entity counted in `InputsCount::predicted` that hasn't been confirmed or orphaned yet, with
+/// seconds since it was predicted
+///
+/// Kept apart from the `PredictedSpawn` query so a pre-spawn that's despawned before it could be seen confirmed
+/// still leaves the count. Only a `MappedSpawnsDespawned` from the server counts it as confirmed, anything else
+/// despawning it is a local drop and counts as orphaned.
+#[derive(Resource, Default)]
+pub struct AwaitingConfirmation(pub HashMap<Entity, f32>);
+
+#[derive(Resource)]
+pub struct LocalPlayerId(pub u64);
+
+/// Server side, the UDP port the server is bound to, the one the OS picked when asked for port 0
+#[derive(Resource)]
+pub struct ListenPort(pub u16);
+
+/// Server side, global cap on replicated entities, a backstop against spawn floods regardless of who sends them
+#[derive(Resource)]
+pub struct MaxEntities(pub usize);
+
+impl Default for MaxEntities
+{
+    fn default() -> Self {
+        Self(DEFAULT_MAX_ENTITIES)
+    }
+}
+
+/// Seconds the sent movement direction takes to ramp up to the raw input, 0 disables smoothing
+#[derive(Resource, Default)]
+pub struct InputSmoothing(pub f32);
+
+// The event that clients will send to the server when it receives input
+// This event will spawn the entities on the server
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub enum PlayerInput
+{
+    None,
+    /// One pre-spawned entity per projectile the weapon fires
+    Shoot { weapon: WeaponKind, projectiles: Vec<Entity> },
+    /// The grab key (E) was pressed, take the ball if in range
+    Grab,
+    /// The grab key was released, hand back control of the ball
+    Release,
+    /// Instant-hit shot, `client_tick` is the latest server tick the client had when firing, used for lag compensation
+    HitscanShot { origin: Vec2, direction: Vec2, client_tick: RepliconTick },
+    /// R was pressed in the lobby, flips the sender's `Ready`
+    ToggleReady,
+    /// The client's view has drifted, it wants to be sent the whole world again
+    RequestResync,
+}
+
+/// Sent on Return, the server nudges every player's position when `--enable-nudge-test` is on
+#[derive(Event, Serialize, Deserialize, Debug)]
+pub struct OtherPlayerInput(pub bool);
+
+/// The movement direction clients send whenever it changes, on its own unreliable channel
+///
+/// Every one sent gets the next `sequence`, the server acknowledges the newest it has applied in `InputAck`
+/// and drops any that arrive after a newer one.
+#[derive(Event, Serialize, Deserialize, Clone, Copy)]
+pub struct PlayerMovement
+{
+    pub direction: Vec2,
+    pub sequence: u32,
+}
+
+// A dud component that will be attached to the pre-spawned entities
+#[derive(Component, Serialize, Deserialize, Default)]
+pub struct PlayerSpawnedComponent
+{
+    random_stuff: [u64; 20],
+    other_random_stuff: [u64; 13],
+}
+
+/// What a replicated entity is, sent with every spawn so clients never have to guess from which components arrived
+///
+/// Pickups will get their own kind once they exist.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntityKind
+{
+    Player,
+    /// Anything shot by a player, projectiles and the bench and probe shots that have no motion
+    Projectile,
+    Ball,
+    /// Short lived visuals such as hitscan tracers
+    Effect,
+}
+
+#[derive(Component)]
+pub struct RandomOtherComponent;
+
+#[derive(Component)]
+pub struct RandomComponent;
+
+#[derive(Component, Serialize, Deserialize, Debug)]
+pub struct Position(pub Vec2);
+
+#[derive(Component, Serialize, Deserialize, Default, PartialEq, Debug)]
+pub struct MoveDirection(pub Vec2);
+
+/// Server-side timer for re-inserting a `MoveDirection` removed by the removal hotkey
+#[derive(Component)]
+pub struct RestoreMoveDirection(pub TickTimer);
+
+impl TickTimed for RestoreMoveDirection
+{
+    fn timer_mut(&mut self) -> &mut TickTimer {
+        &mut self.0
+    }
+}
+
+// A resource to track the number of replicated `MoveDirection` removals this client has observed
+#[derive(Resource, Default)]
+pub struct RemovalsSeen(pub u64);
+
+/// Present while the HUD text exists, the systems that update it wait for this instead of assuming it was spawned
+///
+/// Inserted by `init_system`, so a future menu that tears the HUD down removes it again.
+#[derive(Resource)]
+pub struct UiReady;
+
+// Marker component for the text object that tracks spawn counts
+#[derive(Component)]
+pub struct PlayerSpawnCountText;
+
+// Marker component for the debug overlay text in the top right
+#[derive(Component)]
+pub struct DebugOverlayText;
+
+// Marker component for the client's id/connection status text in the top left
+#[derive(Component)]
+pub struct ConnectionStatusText;
+
+/// Client side, paces the spawn probes enabled by `--probe-spawns`/`--probe-local`
+#[derive(Resource)]
+pub struct Timmy
+{
+    pub timer: Timer,
+}
+
+impl Default for Timmy
+{
+    fn default() -> Self {
+        Self { timer: Timer::from_seconds(DEFAULT_PROBE_INTERVAL, TimerMode::Repeating) }
+    }
+}
+
+/// Marks an entity pre-spawned by the replication probe rather than the player
+#[derive(Component)]
+pub struct ProbeSpawn;
+
+/// Client side, how the replication probe's spawns turned out
+#[derive(Resource, Default)]
+pub struct ProbeStats
+{
+    pub confirmed: u64,
+    pub orphaned: u64,
+}
+
+/// Per player system that sends grab/release inputs for the ball while the grab key (E) is held
+fn grab_input_system(
+    mut input_writer: EventWriter<PlayerInput>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    if bindings.just_pressed(Action::Grab, &input)
+    {
+        input_writer.send(PlayerInput::Grab);
+    }
+    else if bindings.just_released(Action::Grab, &input)
+    {
+        input_writer.send(PlayerInput::Release);
+    }
+}
+
+fn nudge_input_system(
+    mut input_writer: EventWriter<OtherPlayerInput>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    if bindings.just_pressed(Action::Nudge, &input)
+    {
+        input_writer.send(OtherPlayerInput(true));
+    }
+}
+
+/// Server side, spreads players left and right on every nudge
+fn receive_nudge_system(
+    mut input_reader: EventReader<FromClient<OtherPlayerInput>>,
+    mut players: Query<&mut Position, With<Player>>,
+) {
+    for FromClient { client_id, event } in input_reader.read()
+    {
+        info!("Received event '{event:?}' from '{client_id}");
+        for (i, mut position) in players.iter_mut().enumerate()
+        {
+            position.0 += Vec2::new(25.0 * ((i as i32 % 3) - 1) as f32, 0.0);
+        }
+    }
+}
+
+/// Per player system that gathers movement inputs
+fn player_movement_system(
+    mut movement_events: EventWriter<PlayerMovement>,
+    mut diagnostics: ResMut<NetworkDiagnostics>,
+    mut pending: ResMut<PendingMovement>,
+    mut smoothed: Local<Vec2>,
+    mut last_sent: Local<Option<Vec2>>,
+    mut since_sent: Local<f32>,
+    mut players: Query<(&Player, &mut MoveDirection)>,
+    controls: PlayerControls,
+    smoothing: Res<InputSmoothing>,
+    local_player: Res<LocalPlayerId>,
+    time: Res<Time>,
+) {
+    let direction = controls.movement();
+    if direction == Vec2::ZERO
+    {
+        // Releasing the keys (or the stick) stops straight away instead of decaying, so the player stays responsive
+        *smoothed = Vec2::ZERO;
+    }
+    else
+    {
+        *smoothed = smooth_direction(*smoothed, direction, smoothing.0, time.delta_seconds());
+    }
+
+    // Predict with exactly what the server is sent, so smoothing can't make the two disagree
+    if let Some((_, mut move_direction)) = players.iter_mut().find(|(player, _)| player.0 == local_player.0)
+    {
+        move_direction.set_if_neq(MoveDirection(*smoothed));
+    }
+
+    // Only changes are sent, plus a periodic refresh so a lost unreliable packet is corrected soon after
+    *since_sent += time.delta_seconds();
+    if *last_sent == Some(*smoothed) && *since_sent < MOVEMENT_REFRESH_INTERVAL
+    {
+        return;
+    }
+
+    *last_sent = Some(*smoothed);
+    *since_sent = 0.0;
+    let sequence = pending.push(*smoothed, time.elapsed_seconds());
+    movement_events.send(PlayerMovement { direction: *smoothed, sequence });
+    diagnostics.record_movement_sent();
+}
+
+/// Ramps `current` toward `target` so it gets there in `smoothing_secs`, or jumps straight there when smoothing is off
+pub fn smooth_direction(current: Vec2, target: Vec2, smoothing_secs: f32, delta_secs: f32) -> Vec2 {
+    if smoothing_secs <= 0.0
+    {
+        return target;
+    }
+
+    let step = (delta_secs / smoothing_secs).min(1.0);
+    current.lerp(target, step).clamp_length_max(1.0)
+}
+
+pub fn move_player_system(
+    mut players: Query<(&mut Position, &MoveDirection, Option<&PlayerState>), With<Player>>,
+    settings: Query<&GameSettings>,
+    time: Res<Time>,
+) {
+    // Replicated from the server's tuning, so prediction moves at the same speed even after a reload
+    let settings = settings.get_single().copied().unwrap_or_default();
+    for (mut pos, dir, state) in &mut players
+    {
+        if state.is_some_and(|state| !state.is_alive())
+        {
+            continue;
+        }
+
+        pos.0 = step_position(pos.0, dir.0, &settings, time.delta_seconds());
+    }
+}
+
+/// One fixed step of a player's movement, also used to replay unacknowledged movement when reconciling
+pub fn step_position(position: Vec2, direction: Vec2, settings: &GameSettings, delta_secs: f32) -> Vec2 {
+    if settings.deterministic
+    {
+        // Positions stay on the fixed-point grid, so converting back to integers each tick is exact
+        let speed = (settings.move_speed * fixed_point::FIXED_ONE as f32).round() as i32;
+        let moved = fixed_point::step(fixed_point::to_fixed(position), fixed_point::to_fixed(direction), speed, SIMULATION_RATE as i32);
+        return fixed_point::from_fixed(moved);
+    }
+
+    position + direction * delta_secs * settings.move_speed
+}
+
+/// Keeps sprites in sync with their `Position` on the server
+fn sync_position_transform_system(
+    mut positioned: Query<(&Position, &mut Transform), Changed<Position>>,
+) {
+    for (pos, mut transform) in &mut positioned
+    {
+        transform.translation = pos.0.extend(transform.translation.z);
+    }
+}
+
+/// Client side, eases sprites toward their replicated `Position` over one replication interval instead of snapping
+fn interpolate_position_transform_system(
+    mut positioned: Query<(&Position, &mut Transform)>,
+    mut timings: ResMut<SystemTimings>,
+    settings: Query<&GameSettings>,
+    time: Res<Time>,
+) {
+    let started = Instant::now();
+    let replication_rate = settings.get_single().copied().unwrap_or_default().replication_rate;
+    let step = (time.delta_seconds() * replication_rate).min(1.0);
+
+    for (pos, mut transform) in &mut positioned
+    {
+        let z = transform.translation.z;
+        transform.translation = transform.translation.truncate().lerp(pos.0, step).extend(z);
+    }
+    timings.record(TimedSystem::Interpolation, started.elapsed(), positioned.iter().count());
+}
+
+fn receive_player_movement_system(
+    mut movement_reader: EventReader<FromClient<PlayerMovement>>,
+    mut players: Query<(&Player, &mut MoveDirection, &PlayerState, &mut InputAck)>,
+    mut balls: Query<(&ControlledBy, &mut Velocity), With<Ball>>,
+    phases: Query<&GamePhase>,
+    mut input_stats: ResMut<ClientInputStats>,
+) {
+    let inputs_locked = game_phase::current_phase(&phases).inputs_locked();
+
+    for FromClient { client_id, event: PlayerMovement { direction: move_dir, sequence } } in movement_reader.read()
+    {
+        let Some(move_dir) = validate_movement(*client_id, *move_dir) else
+        {
+            input_stats.record_rejected(*client_id);
+            continue;
+        };
+
+        let Some((_, mut direction, state, mut ack)) = players.iter_mut().find(|(player, ..)| ClientId::from_raw(player.0) == *client_id) else { continue; };
+
+        // Unreliable, so an older direction can arrive after a newer one
+        if *sequence <= ack.sequence
+        {
+            continue;
+        }
+        // Acknowledged even if it's refused below, the client has nothing left to replay for it either way
+        *ack = InputAck { sequence: *sequence, steps: 0 };
+
+        if !state.is_alive() || inputs_locked
+        {
+            // Standing still is sent every frame too, only count attempts to actually move
+            if move_dir != Vec2::ZERO
+            {
+                input_stats.record_rejected(*client_id);
+            }
+            continue;
+        }
+
+        // The ball's controller steers the ball instead of their own player
+        if let Some((_, mut velocity)) = balls.iter_mut().find(|(controlled_by, _)| controlled_by.0 == client_id.raw())
+        {
+            velocity.0 = move_dir * BALL_SPEED;
+            continue;
+        }
+
+        direction.0 = move_dir;
+    }
+}
+
+/// Clamps a received movement direction to unit length, so no client can move faster than `move_speed` by sending
+/// a longer one, and drops ones that aren't numbers at all
+///
+/// Positions are only ever moved by the server's own fixed steps, so this bounds every step to `move_speed` over one
+/// step. After a hitch those steps catch up several at a time, but never for more than `MAX_CATCH_UP` of real time.
+fn validate_movement(client_id: ClientId, move_dir: Vec2) -> Option<Vec2> {
+    if !move_dir.is_finite()
+    {
+        warn!("Server: Client '{client_id}' sent a non-finite movement {move_dir}, ignoring it");
+        return None;
+    }
+
+    // Normalizing on the client can land a hair over 1
+    if move_dir.length_squared() > MAX_MOVEMENT_LENGTH_SQUARED
+    {
+        warn!("Server: Client '{client_id}' sent movement of length {}, clamping it to 1", move_dir.length());
+        return Some(move_dir.clamp_length_max(1.0));
+    }
+
+    Some(move_dir)
+}
+
+fn player_input_system(
+    mut commands: Commands,
+    mut input_writer: EventWriter<PlayerInput>,
+    client: Option<Res<RenetClient>>,
+    controls: PlayerControls,
+    local_player: Res<LocalPlayerId>,
+    players: Query<(&Player, &PlayerState, &Position, Option<&MoveDirection>)>,
+    phases: Query<&GamePhase>,
+    settings: Query<&GameSettings>,
+    selected_weapon: Res<SelectedWeapon>,
+    mut next_shot_at: Local<f32>,
+    mut weapon_ready_at: Local<HashMap<WeaponKind, f32>>,
+    time: Res<Time>,
+) {
+    let settings = settings.get_single().copied().unwrap_or_default();
+    let weapon = selected_weapon.0;
+    let now = time.elapsed_seconds();
+    let firing = match settings.fire_mode
+    {
+        FireMode::SemiAuto => controls.shoot_pressed(),
+        // Held down, shots go out at the fire rate the server enforces
+        FireMode::FullAuto { .. } => controls.shoot_held() && now >= *next_shot_at,
+    };
+    let cooled_down = weapon_ready_at.get(&weapon).map_or(true, |ready_at| now >= *ready_at);
+    if !firing || !cooled_down || game_phase::current_phase(&phases).inputs_locked()
+    {
+        return;
+    }
+
+    // Dead players can't shoot, so don't pre-spawn entities the server will never map
+    let local = players.iter().find(|(player, ..)| player.0 == local_player.0);
+    if local.is_some_and(|(_, state, ..)| !state.is_alive())
+    {
+        return;
+    }
+    *next_shot_at = now + settings.fire_mode.cooldown();
+    weapon_ready_at.insert(weapon, now + weapon.cooldown());
+
+    // Launched right away from where this client sees itself, the server's launches replace them when they arrive
+    let launches = local.map(|(_, _, pos, direction)| {
+        weapon.launches(local_player.0, pos.0, direction.map_or(Vec2::ZERO, |direction| direction.0), settings.projectile_speed)
+    });
+
+    let mut projectiles = Vec::with_capacity(weapon.projectile_count());
+    for index in 0..weapon.projectile_count()
+    {
+        let spawned_entity = commands.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, ProjectileKind(weapon), Replication)).id();
+        info!("Client: Spawned {spawned_entity:?} From Input");
+
+        // The host's pre-spawn is already authoritative, only a client's needs confirming
+        if client.is_some()
+        {
+            commands.entity(spawned_entity).insert(PredictedSpawn(0.0));
+
+            if let Some(motion) = launches.as_ref().and_then(|launches| launches.get(index))
+            {
+                commands.entity(spawned_entity).insert((*motion, Bounces(settings.projectile_bounces), PredictedProjectile));
+            }
+        }
+        projectiles.push(spawned_entity);
+    }
+
+    input_writer.send(PlayerInput::Shoot { weapon, projectiles });
+}
+
+// Server-side system that receives the events and spawns its own version of the entity
+fn receive_player_input_system(
+    mut commands: Commands,
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    mut mapping: ResMut<ClientEntityMap>,
+    tick: Res<RepliconTick>,
+    players: Query<(Entity, &Player, &PlayerState, &Position, Option<&MoveDirection>, Option<&Room>)>,
+    phases: Query<&GamePhase>,
+    mut input_stats: ResMut<ClientInputStats>,
+    tuning: Res<GameTuning>,
+    replicated: Query<(), (With<Replication>, Without<DeathAnimation>)>,
+    projectiles: Query<(Entity, &ProjectileAge), Without<DeathAnimation>>,
+    max_entities: Res<MaxEntities>,
+    mut shot_budgets: ResMut<ShotBudgets>,
+    mut mapped_spawns: ResMut<MappedSpawns>,
+    mut rejections: EventWriter<ToClients<SpawnRejected>>,
+    mut stats: Query<&mut PlayerStats>,
+    time: Res<Time>,
+) {
+    let inputs_locked = game_phase::current_phase(&phases).inputs_locked();
+
+    // Youngest first, so popping recycles the oldest projectile
+    let mut replicated_count = replicated.iter().count();
+    let mut recyclable: Vec<(Entity, f32)> = projectiles.iter().map(|(entity, age)| (entity, age.0)).collect();
+    recyclable.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    for FromClient { client_id, event } in input_reader.read()
+    {
+        // Inputs from dead or respawning players, or during the round countdown, are ignored
+        let sender_alive = players.iter().any(|(_, player, state, ..)| ClientId::from_raw(player.0) == *client_id && state.is_alive());
+        let sender_alive = sender_alive && !inputs_locked;
+        if !sender_alive && matches!(event, PlayerInput::Shoot { .. })
+        {
+            input_stats.record_rejected(*client_id);
+        }
+
+        match event 
+        {
+            PlayerInput::None => continue,
+            // Resolved with lag compensation by the hitscan module
+            PlayerInput::HitscanShot { .. } => continue,
+            // Handled by the game phase module
+            PlayerInput::ToggleReady => continue,
+            // Handled by the resync module
+            PlayerInput::RequestResync => continue,
+            PlayerInput::Shoot { weapon, projectiles: client_entities } =>
+            {
+                if !sender_alive
+                {
+                    continue;
+                }
+
+                // A shot carrying the wrong number of entities for its weapon would leave some unmapped or unused
+                if client_entities.len() != weapon.projectile_count()
+                {
+                    warn!("Server: Client '{client_id}' fired {weapon:?} with {} entities instead of {}", client_entities.len(), weapon.projectile_count());
+                    input_stats.record_rejected(*client_id);
+                    continue;
+                }
+
+                // An entity the server already mapped would end up mapped to two server entities, the host's are never mapped
+                let reused = mapped_spawns.reused(*client_id, client_entities);
+                if !reused.is_empty()
+                {
+                    warn!("Server: Client '{client_id}' fired with already used entities {reused:?}, refusing the shot");
+                    input_stats.record_rejected(*client_id);
+                    input_stats.record_reused(*client_id, reused.len());
+                    rejections.send(ToClients { mode: SendMode::Direct(*client_id), event: SpawnRejected { client_entities: client_entities.clone() } });
+                    continue;
+                }
+
+                // Faster than the fire rate or the weapon's cooldown, the client's predictions are left to be cleaned up as orphans
+                if !shot_budgets.try_fire(*client_id, *weapon, tuning.fire_mode(), time.elapsed_seconds())
+                {
+                    input_stats.record_rejected(*client_id);
+                    if *client_id == SERVER_ID
+                    {
+                        for client_entity in client_entities
+                        {
+                            commands.entity(*client_entity).despawn();
+                        }
+                    }
+                    continue;
+                }
+
+                // Fired from the shooter along the way they're moving
+                let Some((shooter, _, _, shooter_pos, direction, shooter_room)) = players.iter().find(|(_, player, ..)| ClientId::from_raw(player.0) == *client_id) else { continue; };
+                if let Ok(mut stats) = stats.get_mut(shooter)
+                {
+                    stats.shots_fired += 1;
+                }
+                let launches = weapon.launches(client_id.raw(), shooter_pos.0, direction.map_or(Vec2::ZERO, |direction| direction.0), tuning.projectile_speed);
+
+                for (client_entity, motion) in client_entities.iter().zip(launches)
+                {
+                    // The host's pre-spawned entity is already counted, a remote client's is about to be
+                    let new_entities = if *client_id == SERVER_ID { 0 } else { 1 };
+                    if replicated_count + new_entities > max_entities.0
+                    {
+                        if let Some((oldest, _)) = recyclable.pop()
+                        {
+                            warn!("Server: {replicated_count} replicated entities reached the cap of {}, recycling projectile {oldest:?}", max_entities.0);
+                            commands.entity(oldest).despawn();
+                            replicated_count -= 1;
+                        }
+                        else
+                        {
+                            // The client's prediction is left unconfirmed and cleaned up as an orphan
+                            warn!("Server: {replicated_count} replicated entities reached the cap of {}, refusing projectile from client '{client_id}'", max_entities.0);
+                            if *client_id == SERVER_ID
+                            {
+                                commands.entity(*client_entity).despawn();
+                            }
+                            continue;
+                        }
+                    }
+
+                    // The host's pre-spawned entity is already the authoritative one, it only needs no mapping
+                    let projectile = projectile_bundle(*weapon, motion, tuning.projectile_bounces, *tick, room::room_of(shooter_room));
+                    if *client_id == SERVER_ID
+                    {
+                        commands.entity(*client_entity).insert(projectile);
+                        continue;
+                    }
+
+                    let server_entity = commands.spawn(projectile).id();
+                    replicated_count += 1;
+
+                    info!("Server: Spawned {server_entity:?} From Client Event (which spawned {client_entity:?})");
+
+                    // Several mappings for one client in one tick are fine, replicon keeps them all in a list and each
+                    // names its own pair, so a shotgun blast or two shots read together map one to one.
+                    // Only a client entity sent twice could map to two server entities, `mapped_spawns` refuses that.
+                    mapping.insert(*client_id, ClientMapping { tick: *tick, server_entity: server_entity, client_entity: *client_entity });
+                    mapped_spawns.record(*client_id, *client_entity, server_entity);
+                }
+            },
+            PlayerInput::Grab | PlayerInput::Release =>
+            {
+                for (player_entity, player, ..) in &players
+                {
+                    if ClientId::from_raw(player.0) != *client_id
+                    {
+                        continue;
+                    }
+
+                    if matches!(event, PlayerInput::Grab)
+                    {
+                        commands.entity(player_entity).insert(GrabIntent);
+                    }
+                    else
+                    {
+                        commands.entity(player_entity).remove::<GrabIntent>();
+                    }
+
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Server side, removes `MoveDirection` from a random player when M (`remove_move_direction`) is pressed, to test
+/// replicated removals
+fn server_remove_move_direction_system(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    players: Query<Entity, (With<Player>, With<MoveDirection>)>,
+) {
+    if !bindings.just_pressed(Action::RemoveMoveDirection, &input)
+    {
+        return;
+    }
+
+    let Some(player_entity) = players.iter().choose(&mut rand::thread_rng()) else { return; };
+
+    info!("Server: Removing MoveDirection from {player_entity:?}");
+    commands.entity(player_entity)
+        .remove::<MoveDirection>()
+        .insert(RestoreMoveDirection(TickTimer::from_seconds(2.0)));
+}
+
+/// Server side, re-adds `MoveDirection` once a removed player's restore timer finishes
+fn server_restore_move_direction_system(
+    mut commands: Commands,
+    restoring: Query<(Entity, &RestoreMoveDirection)>,
+) {
+    for (player_entity, restore) in &restoring
+    {
+        if !restore.0.finished()
+        {
+            continue;
+        }
+
+        info!("Server: Restoring MoveDirection on {player_entity:?}");
+        commands.entity(player_entity)
+            .remove::<RestoreMoveDirection>()
+            .insert(MoveDirection::default());
+    }
+}
+
+/// Client side, logs replicated removals and re-additions of `MoveDirection`
+fn client_move_direction_removal_system(
+    mut removed: RemovedComponents<MoveDirection>,
+    mut removals_seen: ResMut<RemovalsSeen>,
+    added: Query<Entity, (With<Player>, Added<MoveDirection>)>,
+) {
+    for entity in removed.read()
+    {
+        info!("Client: Seen MoveDirection removed from {entity:?}");
+        removals_seen.0 += 1;
+    }
+
+    for entity in &added
+    {
+        info!("Client: Seen MoveDirection added to {entity:?}");
+    }
+}
+
+/// Runs on both server and client, adds extra components when a PlayerSpawnedComponent entity is first created/replicated
+fn entity_tracker_system(
+    mut commands: Commands,
+    mut input_count: ResMut<InputsCount>,
+    mut sfx_events: EventWriter<PlaySfx>,
+    mut timings: ResMut<SystemTimings>,
+    world_state_received: Res<WorldStateReceived>,
+    new_entites: Query<(Entity, &EntityKind, Has<PredictedSpawn>), Added<Replication>>
+) {
+    let started = Instant::now();
+    for (entity, kind, predicted) in &new_entites
+    {
+        if *kind != EntityKind::Projectile
+        {
+            continue;
+        }
+
+        info!("Client: Seen Entity {entity:?} Spawned");
+        if predicted
+        {
+            input_count.predicted += 1;
+        }
+        else
+        {
+            input_count.confirmed += 1;
+        }
+
+        commands.entity(entity).insert(RandomComponent);
+
+        // Entities that came with a late join's initial world state were already there, so stay quiet for those
+        if world_state_received.0
+        {
+            sfx_events.send(PlaySfx(Sfx::Pew));
+        }
+    }
+    timings.record(TimedSystem::EntityTracker, started.elapsed(), new_entites.iter().count());
+}
+
+/// Client side only function to try and trigger this bug I am experiencing
+fn client_tracker_system(
+    mut commands: Commands,
+    mut timings: ResMut<SystemTimings>,
+    new_entites: Query<(Entity, &EntityKind), Added<Replication>>
+) {
+    let started = Instant::now();
+    for (entity, kind) in &new_entites
+    {
+        if *kind != EntityKind::Projectile
+        {
+            continue;
+        }
+        commands.entity(entity).insert(RandomOtherComponent);
+    }
+    timings.record(TimedSystem::ClientTracker, started.elapsed(), new_entites.iter().count());
+}
+
+/// Client side replication probe, keeps exercising the client-mapping path during long unattended runs
+fn client_random_spawn_system(
+    mut commands: Commands,
+    mut input_writer: EventWriter<PlayerInput>,
+    mut tim: ResMut<Timmy>,
+    cli: Res<Cli>,
+    time: Res<Time>,
+) {
+    let Cli::Client { probe_spawns, probe_local, .. } = *cli else { return; };
+    if probe_spawns.is_none() && !probe_local
+    {
+        return;
+    }
+
+    if let Some(interval) = probe_spawns
+    {
+        tim.timer.set_duration(Duration::from_secs_f32(interval));
+    }
+    if !tim.timer.tick(time.delta()).just_finished()
+    {
+        return;
+    }
+
+    if probe_local
+    {
+        commands.spawn(TransformBundle::from_transform(Transform::from_translation(Vec3::new(1.0, 3.0, -69.0))));
+    }
+
+    if probe_spawns.is_some()
+    {
+        let probe = commands.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, Replication, PredictedSpawn(0.0), ProbeSpawn)).id();
+        info!("Client: Spawned probe {probe:?}");
+        input_writer.send(PlayerInput::Shoot { weapon: WeaponKind::Pistol, projectiles: vec![probe] });
+    }
+}
+
+/// Client side, moves pre-spawned entities from predicted to confirmed once replication writes to them, or to orphaned if it never does
+fn confirm_predicted_spawns_system(
+    mut commands: Commands,
+    mut input_count: ResMut<InputsCount>,
+    mut probe_stats: ResMut<ProbeStats>,
+    mut awaiting: ResMut<AwaitingConfirmation>,
+    mut mapped_despawns: EventReader<MappedSpawnsDespawned>,
+    mut predicted: Query<(Entity, &mut PredictedSpawn, Ref<PlayerSpawnedComponent>, Has<ProbeSpawn>, Has<PredictedProjectile>)>,
+    new_predictions: Query<Entity, Added<PredictedSpawn>>,
+    time: Res<Time>,
+) {
+    awaiting.0.extend(new_predictions.iter().map(|entity| (entity, 0.0)));
+
+    // The server mapped these and despawned its entities, possibly before replication ever showed them confirmed.
+    // Replication only despawns the local entity if it applied the mapping first, so it's despawned here either way.
+    let mut resolved = Vec::new();
+    for MappedSpawnsDespawned { client_entities } in mapped_despawns.read()
+    {
+        for &entity in client_entities
+        {
+            if let Some(mut local) = commands.get_entity(entity)
+            {
+                local.despawn();
+            }
+            if awaiting.0.remove(&entity).is_none()
+            {
+                continue;
+            }
+
+            info!("Client: Server confirmed {entity:?} and despawned it in the same tick");
+            input_count.predicted -= 1;
+            input_count.confirmed += 1;
+            if predicted.get(entity).is_ok_and(|(_, _, _, is_probe, _)| is_probe)
+            {
+                probe_stats.confirmed += 1;
+            }
+            resolved.push(entity);
+        }
+    }
+
+    for (entity, mut spawn, component, is_probe, is_projectile) in &mut predicted
+    {
+        if resolved.contains(&entity)
+        {
+            continue;
+        }
+
+        // Replication inserts the server's copy over the pre-spawned one, which changes it without re-adding it
+        if component.is_changed() && !component.is_added()
+        {
+            info!("Client: Server confirmed {entity:?}");
+            input_count.predicted -= 1;
+            input_count.confirmed += 1;
+            awaiting.0.remove(&entity);
+            commands.entity(entity).remove::<PredictedSpawn>();
+
+            // Confirmed probes have done their job, removing them keeps long runs from growing the world
+            if is_probe
+            {
+                probe_stats.confirmed += 1;
+                commands.entity(entity).despawn();
+            }
+            continue;
+        }
+
+        spawn.0 += time.delta_seconds();
+        if spawn.0 > ORPHAN_TIMEOUT
+        {
+            warn!("Client: Server never confirmed {entity:?}");
+            input_count.predicted -= 1;
+            input_count.orphaned += 1;
+            awaiting.0.remove(&entity);
+            commands.entity(entity).remove::<PredictedSpawn>();
+
+            if is_probe
+            {
+                probe_stats.orphaned += 1;
+            }
+
+            // The server refused the shot, so the predicted projectile shouldn't keep flying
+            if is_projectile
+            {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+
+    // Despawned here without the server saying it took them, given the same wait as any pre-spawn so a
+    // `MappedSpawnsDespawned` still on its way can count them first
+    let delta = time.delta_seconds();
+    awaiting.0.retain(|entity, age| {
+        *age += delta;
+        if predicted.contains(*entity) || *age <= ORPHAN_TIMEOUT
+        {
+            return true;
+        }
+
+        warn!("Client: {entity:?} was despawned before the server confirmed it");
+        input_count.predicted -= 1;
+        input_count.orphaned += 1;
+        false
+    });
+}
+
+fn update_input_count_text(
+    input_count: Res<InputsCount>,
+    mut text_query: Query<&mut Text, With<PlayerSpawnCountText>>,
+    mut drawn: Local<bool>,
+) {
+    // Counts that changed before there was any text are picked up by the first draw
+    if *drawn && !input_count.is_changed()
+    {
+        return;
+    }
+    let Ok(mut text) = text_query.get_single_mut() else { return; };
+
+    let InputsCount { predicted, confirmed, orphaned } = *input_count;
+    text.sections[0].value = format!("{confirmed} confirmed, {predicted} predicted, {orphaned} orphaned");
+    *drawn = true;
+}
+
+fn update_debug_overlay_text(
+    removals_seen: Res<RemovalsSeen>,
+    diagnostics: Res<NetworkDiagnostics>,
+    audit_failures: Res<AuditFailures>,
+    projectiles: Query<(), With<PlayerSpawnedComponent>>,
+    settings: Query<&GameSettings>,
+    timings: Res<SystemTimings>,
+    mut shown_projectiles: Local<usize>,
+    mut shown_timings: Local<Vec<Duration>>,
+    mut text_query: Query<&mut Text, With<DebugOverlayText>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else { return; };
+    let projectile_count = projectiles.iter().count();
+    // `SystemTimings` changes every frame, so only a different rolling max is worth redrawing for
+    let rolling_maxima: Vec<Duration> = TimedSystem::ALL.into_iter().map(|system| timings.rolling_max(system)).collect();
+    if !removals_seen.is_changed() && !diagnostics.is_changed() && !audit_failures.is_changed() && projectile_count == *shown_projectiles && rolling_maxima == *shown_timings
+    {
+        return;
+    }
+    *shown_projectiles = projectile_count;
+    let max_projectiles = settings.get_single().copied().unwrap_or_default().max_projectiles;
+
+    let removals = removals_seen.0;
+    let prediction_error = diagnostics.prediction_error;
+    let movement_rate = diagnostics.movement_sent_per_second;
+    let failures = audit_failures.0;
+    let timing_lines: String = TimedSystem::ALL.into_iter().zip(&rolling_maxima)
+        .map(|(system, max)| format!("\n{} max {:.2}ms", system.name(), max.as_secs_f32() * 1000.0))
+        .collect();
+    *shown_timings = rolling_maxima;
+    text.sections[0].value = format!("{removals} removals seen\nprediction error {prediction_error:.1}\n{movement_rate:.0} movement sends/s\n{failures} audit failures\n{projectile_count}/{max_projectiles} projectiles{timing_lines}");
+}
+
+fn init_system(
+    mut commands: Commands,
+) {
+    commands.spawn(Camera2dBundle::default());
+    commands.insert_resource(UiReady);
+
+    commands.spawn((TextBundle::from_section(
+        "0 confirmed, 0 predicted, 0 orphaned", 
+        TextStyle { font_size: 30.0, color: Color::WHITE, ..default() }
+    ).with_style(Style { 
+        align_self: AlignSelf::FlexEnd, justify_self: JustifySelf::Start, flex_direction: FlexDirection::Column, ..default() 
+    }), PlayerSpawnCountText));
+
+    commands.spawn((TextBundle::from_section(
+        "0 removals seen",
+        TextStyle { font_size: 20.0, color: Color::WHITE, ..default() }
+    ).with_style(Style {
+        position_type: PositionType::Absolute, top: Val::Px(5.0), right: Val::Px(5.0), ..default()
+    }), DebugOverlayText));
+}
+
+fn cli_system(
+    mut commands: Commands,
+    cli: Res<Cli>,
+    network_channels: Res<NetworkChannels>,
+    mut appearance_cycle: ResMut<AppearanceCycle>,
+) -> Result<(), Box<dyn Error>> {
+    match *cli {
+        Cli::Server { port, memory_transport, ref server_args, .. } => {
+            if memory_transport
+            {
+                info!("Starting a server on the in-process memory transport");
+                start_server(&mut commands, &network_channels, None, server_args)?;
+            }
+            else
+            {
+                info!("Starting a server on port {port}");
+                start_server(&mut commands, &network_channels, Some(port), server_args)?;
+            }
+
+            commands.spawn(TextBundle::from_section(
+                "Server",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+
+            commands.insert_resource(LocalPlayerId(SERVER_ID.raw()));
+            spawn_server_player(&mut commands, &mut appearance_cycle);
+            ball::spawn_ball(&mut commands);
+        }
+        Cli::Host { port, ref server_args, .. } => {
+            info!("Starting a listen-server on port {port}");
+            start_server(&mut commands, &network_channels, Some(port), server_args)?;
+
+            commands.spawn(TextBundle::from_section(
+                "Host",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+
+            // The host is authoritative over its own player, so its inputs are applied directly without prediction
+            commands.insert_resource(LocalPlayerId(SERVER_ID.raw()));
+            spawn_server_player(&mut commands, &mut appearance_cycle);
+            ball::spawn_ball(&mut commands);
+        }
+        Cli::Client { port, ip, input_smoothing, ref identity_file, ref connection_args, .. } => {
+            info!("Starting a client connecting to: {ip:?}:{port}");
+            let client = RenetClient::new(connection_args.connection_config(&network_channels));
+
+            let identity = reconnect::load_or_create_identity(identity_file);
+            let (transport, client_id) = transport::client_transport(ip, port, identity, connection_args.protocol_id(&network_channels))?;
+
+            commands.insert_resource(client);
+            commands.insert_resource(transport);
+
+            commands.spawn((TextBundle::from_section(
+                format!("Client: {client_id:?}"),
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ), ConnectionStatusText));
+
+            commands.insert_resource(LocalPlayerId(client_id));
+            commands.insert_resource(InputSmoothing(input_smoothing));
+        }
+        // Each app's server or client is set up by `bench::run` before its first update
+        Cli::Bench { .. } => {}
+        // Never builds an app, `dev::run` only starts other processes
+        Cli::Dev { .. } => {}
+        Cli::ReplayView { ref path, ref server_args } => {
+            let recording = Recording::load(path)?;
+            info!("Replaying {}, space pauses, right steps while paused, +/- change the speed", path.display());
+
+            // No transport, the recording stands in for every client
+            let server_args = ServerArgs { seed: Some(recording.seed), record: None, ..server_args.clone() };
+            insert_server_state(&mut commands, &server_args)?;
+            commands.insert_resource(RenetServer::new(server_args.connection_args.connection_config(&network_channels)));
+
+            commands.spawn(TextBundle::from_section(
+                "Replay",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+
+            commands.insert_resource(LocalPlayerId(SERVER_ID.raw()));
+            spawn_server_player(&mut commands, &mut appearance_cycle);
+            ball::spawn_ball(&mut commands);
+            commands.insert_resource(ReplayPlayback::new(recording));
+        }
+    }
+
+    Ok(())
+}
+
+/// Everything a player is spawned with, shared by the server's own player and clients' so the host plays as exactly
+/// what a client would
+fn player_bundle(client_id: ClientId, appearance: Appearance) -> impl Bundle {
+    (Player(client_id.raw()), Position(Vec2::ZERO), PlayerState::Alive, Health::default(), DamageLog::default(), Ready::default(), Score::default(), AnimState::default(), AnimHold::default(), appearance, InputAck::default(), MoveDirection::default(), EntityKind::Player, Room::default(), Replication)
+}
+
+/// Everything an authoritative projectile has, whether the server spawned it for a client or the host pre-spawned it
+///
+/// It's in its shooter's room, and stays there if they switch.
+fn projectile_bundle(weapon: WeaponKind, motion: ProjectileMotion, bounces: u8, tick: RepliconTick, room: Room) -> impl Bundle {
+    (PlayerSpawnedComponent::default(), EntityKind::Projectile, ProjectileKind(weapon), motion, Bounces(bounces), SpawnTick(tick), room, Replication)
+}
+
+/// The `SERVER_ID` player every server has, which only moves on a listen-server
+fn spawn_server_player(
+    commands: &mut Commands,
+    appearance_cycle: &mut AppearanceCycle,
+) {
+    commands.spawn(player_bundle(SERVER_ID, appearance_cycle.next_appearance()));
+}
+
+/// Inserts the `RenetServer` and its transport, shared by the dedicated and listen-server modes
+///
+/// Without a `port` the server only takes clients over the in-process memory transport, as embedded by `run_server`.
+fn start_server(
+    commands: &mut Commands,
+    network_channels: &NetworkChannels,
+    port: Option<u16>,
+    server_args: &ServerArgs,
+) -> Result<(), Box<dyn Error>> {
+    insert_server_state(commands, server_args)?;
+
+    let server = RenetServer::new(server_args.connection_args.connection_config(network_channels));
+
+    match port
+    {
+        Some(port) =>
+        {
+            let protocol_id = server_args.connection_args.protocol_id(network_channels);
+            let (transport, port) = transport::server_transport(port, protocol_id, server_args)?;
+            commands.insert_resource(transport);
+            commands.insert_resource(ListenPort(port));
+            if let Some(web_port) = server_args.web_port
+            {
+                commands.insert_resource(WebServerTransport::listen(web_port, protocol_id)?);
+            }
+        }
+        None => commands.insert_resource(MemoryServerTransport::default()),
+    }
+
+    commands.insert_resource(server);
+    if let Some(path) = &server_args.record
+    {
+        commands.insert_resource(SessionRecorder::new(path.clone()));
+    }
+
+    Ok(())
+}
+
+/// Spawns the game-state singleton and inserts the server-only resources, whatever transport the server uses
+fn insert_server_state(
+    commands: &mut Commands,
+    server_args: &ServerArgs,
+) -> Result<(), Box<dyn Error>> {
+    if server_args.replication_rate <= 0.0
+    {
+        return Err("--replication-rate must be greater than zero".into());
+    }
+    if !server_args.afk_timeout.is_finite() || server_args.afk_timeout < 0.0
+    {
+        return Err("--afk-timeout must be a number of seconds, zero or more".into());
+    }
+    if server_args.world_half_extent * server_args.connection_args.position_scale as f32 > i16::MAX as f32
+    {
+        return Err(format!("--position-scale {} can't reach the world edge at {}, lower one of them",
+            server_args.connection_args.position_scale, server_args.world_half_extent).into());
+    }
+
+    let world_seed = server_args.seed.unwrap_or_else(rand::random);
+    let mut settings = GameSettings { replication_rate: server_args.replication_rate, world_seed, world_half_extent: server_args.world_half_extent, max_projectiles: server_args.max_projectiles, deterministic: server_args.deterministic, ..default() };
+    server_args.tuning.mirror_into(&mut settings);
+    commands.spawn((settings, server_args.seed.map_or_else(RngSeed::random, RngSeed), GamePhase::Lobby, Pause::default(), SimulationClock::default(), ServerInfo::new(&server_args.name, &server_args.description, &server_args.map), Replication));
+    commands.insert_resource(server_args.tuning);
+    if let Some(path) = &server_args.config
+    {
+        commands.insert_resource(ConfigWatch::new(path.clone()));
+    }
+    commands.insert_resource(AfkTimeout(server_args.afk_timeout));
+    commands.insert_resource(MaxEntities(server_args.max_entities));
+    commands.insert_resource(EventLimits::new(&server_args.event_limits));
+    commands.insert_resource(StatsFile(server_args.stats_file.clone()));
+    commands.insert_resource(InputHistory { dump_on_disconnect: server_args.dump_history_on_disconnect, ..default() });
+    if server_args.queue
+    {
+        commands.insert_resource(JoinQueue::new(server_args.max_players as usize));
+    }
+    if let Some(admin_key) = &server_args.admin_key
+    {
+        commands.insert_resource(AdminKey(admin_key.clone()));
+    }
+
+    Ok(())
+}
+
+/// Logs every replicon channel, so the layout clients and servers must agree on is visible in both logs
+///
+/// The hash is the part of the protocol id a mismatched layout changes, comparing it between two logs finds the culprit.
+fn log_network_channels_system(
+    network_channels: Res<NetworkChannels>,
+) {
+    let server_channels = network_channels.get_server_configs();
+    let client_channels = network_channels.get_client_configs();
+    for channel in &server_channels
+    {
+        info!("Server channel {}: {:?}", channel.channel_id, channel.send_type);
+    }
+    for channel in &client_channels
+    {
+        info!("Client channel {}: {:?}", channel.channel_id, channel.send_type);
+    }
+    info!("Channel layout hash {:08x}", channel_layout_hash(&server_channels, &client_channels));
+}
+
+/// Every channel's id and send type, in order, folded into the protocol id so only matching layouts can connect
+///
+/// Memory limits are left out, they're local to each end and `--channel-memory` may differ between them.
+fn channel_layout_hash(
+    server_channels: &[ChannelConfig],
+    client_channels: &[ChannelConfig],
+) -> u32 {
+    let bytes = [server_channels, client_channels].into_iter().flat_map(|channels| {
+        let channel_bytes = channels.iter().flat_map(|channel| {
+            let (kind, resend_millis) = match channel.send_type
+            {
+                SendType::Unreliable => (0u8, 0u64),
+                SendType::ReliableOrdered { resend_time } => (1, resend_time.as_millis() as u64),
+                SendType::ReliableUnordered { resend_time } => (2, resend_time.as_millis() as u64),
+            };
+            [channel.channel_id, kind].into_iter().chain(resend_millis.to_le_bytes())
+        });
+        // The count keeps a channel moving from the end of one list to the start of the other from hashing the same
+        (channels.len() as u32).to_le_bytes().into_iter().chain(channel_bytes)
+    });
+    let hash = checksum::fnv1a(bytes);
+    (hash ^ hash >> 32) as u32
+}
+
+fn server_connection_events_system(
+    mut commands: Commands,
+    mut server_events: EventReader<ServerEvent>,
+    mut appearance_cycle: ResMut<AppearanceCycle>,
+    mut disconnected: ResMut<DisconnectedPlayers>,
+    mut players: Query<(Entity, &Player, &mut MoveDirection, Option<&ClientIdentity>)>,
+    mut queue: Option<ResMut<JoinQueue>>,
+    mut queue_statuses: EventWriter<ToClients<QueueStatus>>,
+    transport: Option<Res<NetcodeServerTransport>>,
+    web_transport: Option<Res<WebServerTransport>>,
+    memory_transport: Option<Res<MemoryServerTransport>>,
+    replay_identities: Res<ReplayIdentities>,
+    time: Res<Time>,
+) {
+    let identity_of = |client_id: ClientId| transport.as_ref()
+        .and_then(|transport| transport.user_data(client_id))
+        .and_then(|user_data| reconnect::identity_from_user_data(&user_data))
+        .or_else(|| web_transport.as_ref().and_then(|web_transport| web_transport.identity(client_id)))
+        .or_else(|| memory_transport.as_ref().and_then(|memory_transport| memory_transport.identity(client_id)))
+        .or_else(|| replay_identities.0.get(&client_id).copied());
+
+    for event in server_events.read()
+    {
+        match event
+        {
+            ServerEvent::ClientConnected { client_id} => 
+            {
+                info!("Client '{client_id}' connected");
+
+                if queue.as_mut().is_some_and(|queue| !queue.join(*client_id))
+                {
+                    info!("Server: Full, client '{client_id}' is waiting in the queue");
+                    continue;
+                }
+
+                spawn_client_player(&mut commands, *client_id, identity_of(*client_id), &mut appearance_cycle, &mut disconnected);
+            }
+            ServerEvent::ClientDisconnected { client_id, reason } =>
+            {
+                info!("Client '{client_id}' disconnected: {reason}");
+
+                // Players with an identity wait for their client to come back, stopped where they are
+                let dropped = players.iter_mut().find(|(_, player, _, _)| player.0 == client_id.raw());
+                if let Some((player_entity, _, mut direction, Some(identity))) = dropped
+                {
+                    direction.0 = Vec2::ZERO;
+                    disconnected.park(*identity, player_entity, time.elapsed_seconds());
+                }
+
+                let Some(promoted) = queue.as_mut().and_then(|queue| queue.leave(*client_id)) else { continue; };
+                info!("Server: Client '{promoted}' promoted from the queue");
+                queue_statuses.send(ToClients { mode: SendMode::Direct(promoted), event: QueueStatus { position: 0 } });
+                spawn_client_player(&mut commands, promoted, identity_of(promoted), &mut appearance_cycle, &mut disconnected);
+            }
+        }
+    }
+}
+
+/// Gives a newly connected (or promoted) client its player, the one it left behind if it's reconnecting
+fn spawn_client_player(
+    commands: &mut Commands,
+    client_id: ClientId,
+    identity: Option<ClientIdentity>,
+    appearance_cycle: &mut AppearanceCycle,
+    disconnected: &mut DisconnectedPlayers,
+) {
+    if let Some(player_entity) = identity.and_then(|identity| disconnected.reclaim(identity))
+    {
+        info!("Server: Client '{client_id}' reconnected, giving back its previous player");
+        // The new connection numbers its movement from the start again
+        commands.entity(player_entity).insert((Player(client_id.raw()), InputAck::default()));
+        return;
+    }
+
+    let player_entity = commands.spawn(player_bundle(client_id, appearance_cycle.next_appearance())).id();
+    if let Some(identity) = identity
+    {
+        commands.entity(player_entity).insert(identity);
+    }
+}
+
+fn attach_extras_to_players(
+    mut commands: Commands,
+    mut timings: ResMut<SystemTimings>,
+    players: Query<(Entity, &EntityKind, &Player, &Position, Option<&Appearance>), Added<Replication>>,
+    skins: Option<Res<SkinAssets>>,
+) {
+    let started = Instant::now();
+    for (player_entity, kind, player, pos, appearance) in &players
+    {
+        if *kind != EntityKind::Player
+        {
+            continue;
+        }
+
+        // Unknown or missing skins fall back to a square in the player's color
+        let texture = skins.as_ref().zip(appearance).and_then(|(skins, appearance)| skins.skin(*appearance));
+        let base_color = if texture.is_some() { Color::WHITE } else { ball::player_color(player.0) };
+
+        let mut coms = commands.entity(player_entity);
+        coms.insert((SpriteBundle 
+        {
+            sprite: Sprite 
+            {  
+                color: base_color,
+                custom_size: Some(Vec2::new(15.0, 15.0)),
+                ..default()
+            },
+            texture: texture.unwrap_or_default(),
+            transform: Transform::from_translation(pos.0.extend(0.0)),
+            ..default()
+        }, BaseColor(base_color)));
+    }
+    timings.record(TimedSystem::AttachExtrasToPlayers, started.elapsed(), players.iter().count());
+}
+
+fn attach_extras_to_projectiles(
+    mut commands: Commands,
+    projectiles: Query<(Entity, &EntityKind, Option<&ProjectileKind>), Added<Replication>>,
+    skins: Option<Res<SkinAssets>>,
+) {
+    for (projectile_entity, kind, projectile_kind) in &projectiles
+    {
+        if *kind != EntityKind::Projectile
+        {
+            continue;
+        }
+
+        // Each weapon's projectiles look different, the pistol's keep a skin's texture untinted
+        let texture = skins.as_ref().and_then(|skins| skins.projectile());
+        let (color, size) = match projectile_kind.map_or(WeaponKind::Pistol, |projectile_kind| projectile_kind.0)
+        {
+            WeaponKind::Pistol => (if texture.is_some() { Color::WHITE } else { Color::YELLOW }, 5.0),
+            WeaponKind::Shotgun => (Color::ORANGE, 3.0),
+            WeaponKind::Lob => (Color::LIME_GREEN, 9.0),
+        };
+
+        commands.entity(projectile_entity).insert(SpriteBundle
+        {
+            sprite: Sprite
+            {
+                color,
+                custom_size: Some(Vec2::splat(size)),
+                ..default()
+            },
+            texture: texture.unwrap_or_default(),
+            ..default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::{ecs::{event::ManualEventReader, system::CommandQueue}, input::{ButtonState, keyboard::KeyboardInput}};
+
+    use super::*;
+    use crate::{bench::{step_session, tests::{connected_session, join_session}}, projectile::ProjectileCorrection};
+
+    /// Server side, stands in for a projectile hitting something on the tick it's fired
+    fn despawn_new_projectiles_system(
+        mut commands: Commands,
+        projectiles: Query<Entity, Added<ProjectileMotion>>,
+    ) {
+        for projectile in &projectiles
+        {
+            commands.entity(projectile).despawn();
+        }
+    }
+
+    fn player_position(app: &mut App, id: u64) -> Option<Vec2> {
+        app.world.query::<(&Player, &Position)>().iter(&app.world).find(|(player, _)| player.0 == id).map(|(_, position)| position.0)
+    }
+
+    #[test]
+    fn host_accepts_a_client_and_moves_its_own_player() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let mut queue = CommandQueue::default();
+        spawn_server_player(&mut Commands::new(&mut queue, &server_app.world), &mut AppearanceCycle::default());
+        queue.apply(&mut server_app.world);
+
+        // What replicon resends locally for a host's own `PlayerMovement`, there's no connection to send it over
+        server_app.world.send_event(FromClient { client_id: SERVER_ID, event: PlayerMovement { direction: Vec2::X, sequence: 1 } });
+        for _ in 0..SIMULATION_RATE as usize
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let host_position = player_position(&mut server_app, SERVER_ID.raw()).unwrap();
+        assert!(host_position.x > 0.0, "the host's player didn't move");
+        assert_eq!(server_app.world.resource::<RenetServer>().clients_id().len(), 1);
+        let seen_by_client = player_position(&mut client_apps[0], SERVER_ID.raw()).expect("the client never saw the host's player");
+        assert!(seen_by_client.x > 0.0);
+    }
+
+    fn prediction_app() -> App {
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<InputsCount>()
+            .init_resource::<ProbeStats>()
+            .init_resource::<AwaitingConfirmation>()
+            .add_event::<MappedSpawnsDespawned>()
+            .add_systems(Update, confirm_predicted_spawns_system);
+        app
+    }
+
+    fn predict(app: &mut App) -> Entity {
+        app.world.resource_mut::<InputsCount>().predicted += 1;
+        let entity = app.world.spawn((PlayerSpawnedComponent::default(), PredictedSpawn(0.0))).id();
+        app.update();
+        entity
+    }
+
+    fn counts(app: &App) -> (u64, u64, u64) {
+        let count = app.world.resource::<InputsCount>();
+        (count.predicted, count.confirmed, count.orphaned)
+    }
+
+    #[test]
+    fn confirm_and_despawn_in_one_tick_leaves_nothing_outstanding() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        server_app.add_systems(Update, despawn_new_projectiles_system.after(receive_player_input_system));
+
+        let shot = client_apps[0].world.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, Replication, PredictedSpawn(0.0))).id();
+        client_apps[0].world.send_event(PlayerInput::Shoot { weapon: WeaponKind::Pistol, projectiles: vec![shot] });
+        for _ in 0..SIMULATION_RATE as usize
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        assert!(client_apps[0].world.get_entity(shot).is_none());
+        assert_eq!(counts(&client_apps[0]), (0, 1, 0));
+        assert!(client_apps[0].world.resource::<AwaitingConfirmation>().0.is_empty());
+    }
+
+    /// A pistol shot launched along +x from where the client sees its player, the way `player_input_system` predicts one
+    fn spawn_predicted_projectile(client_app: &mut App) -> Entity {
+        let origin = player_position(client_app, 1).unwrap();
+        let motion = ProjectileMotion::new(1, origin, Vec2::X, GameTuning::default().projectile_speed);
+        client_app.world.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, ProjectileKind(WeaponKind::Pistol), Replication, PredictedSpawn(0.0), motion, Bounces(0), PredictedProjectile)).id()
+    }
+
+    #[test]
+    fn predicted_projectile_switches_to_the_servers_launch_once_confirmed() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        // Far from the wall it flies towards, so it's still in the air when the server's launch comes back
+        for (_, mut position) in server_app.world.query::<(&Player, &mut Position)>().iter_mut(&mut server_app.world)
+        {
+            position.0 = Vec2::new(-150.0, 0.0);
+        }
+        for _ in 0..10
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let shot = spawn_predicted_projectile(&mut client_apps[0]);
+        client_apps[0].world.send_event(PlayerInput::Shoot { weapon: WeaponKind::Pistol, projectiles: vec![shot] });
+        for _ in 0..SIMULATION_RATE as usize
+        {
+            step_session(&mut server_app, &mut client_apps);
+            if client_apps[0].world.get::<PredictedProjectile>(shot).is_none()
+            {
+                break;
+            }
+        }
+
+        assert!(client_apps[0].world.get::<PredictedProjectile>(shot).is_none(), "the prediction was never reconciled");
+        let server_motion = *server_app.world.query::<&ProjectileMotion>().single(&server_app.world);
+        let client_motion = *client_apps[0].world.get::<ProjectileMotion>(shot).unwrap();
+        assert_eq!(client_motion.origin, server_motion.origin);
+        assert_eq!(client_motion.velocity, server_motion.velocity);
+        // Both launched from the same place, so only the round trip the prediction ran ahead by is left to blend out
+        let correction = client_apps[0].world.get::<ProjectileCorrection>(shot).expect("reconciled without a correction").0;
+        let speed = GameTuning::default().projectile_speed;
+        assert!(correction.length() <= speed * 6.0 / SIMULATION_RATE as f32, "correction of {correction} for one shot");
+
+        step_session(&mut server_app, &mut client_apps);
+        assert_eq!(counts(&client_apps[0]), (0, 1, 0));
+    }
+
+    #[test]
+    fn rejected_predicted_projectile_is_removed_without_waiting_to_be_orphaned() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let shot = spawn_predicted_projectile(&mut client_apps[0]);
+        // Naming the same entity for every pellet is refused whole, and the client told
+        client_apps[0].world.send_event(PlayerInput::Shoot { weapon: WeaponKind::Shotgun, projectiles: vec![shot; WeaponKind::Shotgun.projectile_count()] });
+
+        let mut frames = 0;
+        while client_apps[0].world.get_entity(shot).is_some()
+        {
+            assert!((frames as f32) < ORPHAN_TIMEOUT * SIMULATION_RATE as f32 / 2.0, "the rejected projectile was still flying after {frames} frames");
+            step_session(&mut server_app, &mut client_apps);
+            frames += 1;
+        }
+
+        assert_eq!(counts(&client_apps[0]), (0, 0, 1));
+        assert!(server_app.world.query::<&ProjectileMotion>().iter(&server_app.world).next().is_none());
+    }
+
+    #[test]
+    fn each_entity_kind_is_drawn_by_its_own_branch() {
+        let mut app = App::new();
+        app
+            .init_resource::<SystemTimings>()
+            .add_systems(Update, (attach_extras_to_players, attach_extras_to_projectiles, ball::attach_extras_to_ball));
+        let player = app.world.spawn((EntityKind::Player, Player(1), Position(Vec2::ZERO), Replication)).id();
+        let projectile = app.world.spawn((EntityKind::Projectile, ProjectileKind(WeaponKind::Shotgun), Replication)).id();
+        let ball = app.world.spawn((EntityKind::Ball, Position(Vec2::ZERO), Replication)).id();
+        // Has everything the player and ball branches read, so only the kind keeps them from drawing it
+        let effect = app.world.spawn((EntityKind::Effect, Player(2), Position(Vec2::ZERO), Replication)).id();
+        app.update();
+
+        let size = |entity| app.world.get::<Sprite>(entity).and_then(|sprite| sprite.custom_size);
+        assert_eq!(size(player), Some(Vec2::splat(15.0)));
+        assert!(app.world.get::<BaseColor>(player).is_some());
+        assert_eq!(size(projectile), Some(Vec2::splat(3.0)));
+        assert_eq!(size(ball), Some(Vec2::splat(10.0)));
+        assert_eq!(size(effect), None);
+    }
+
+    #[test]
+    fn move_direction_removal_and_restore_are_each_seen_once() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        // Player 1 is the only one with a `MoveDirection` to remove when M is pressed
+        server_app.world.send_event(KeyboardInput { scan_code: 0, key_code: Some(KeyCode::M), state: ButtonState::Pressed, window: Entity::PLACEHOLDER });
+
+        // Whether player 1 had it, each time that changed as each client saw it
+        let mut seen: Vec<Vec<bool>> = vec![Vec::new(); 2];
+        for frame in 0..3 * SIMULATION_RATE as usize
+        {
+            // Joins while the removal is in effect
+            if frame == SIMULATION_RATE as usize / 2
+            {
+                client_apps.push(join_session(&mut server_app, 2));
+            }
+            step_session(&mut server_app, &mut client_apps);
+
+            for (client_app, seen) in client_apps.iter_mut().zip(&mut seen)
+            {
+                let has_direction = client_app.world.query::<(&Player, Has<MoveDirection>)>().iter(&client_app.world)
+                    .find_map(|(player, has_direction)| (player.0 == 1).then_some(has_direction));
+                if let Some(has_direction) = has_direction.filter(|has_direction| seen.last() != Some(has_direction))
+                {
+                    seen.push(has_direction);
+                }
+            }
+        }
+
+        assert_eq!(seen[0], [true, false, true]);
+        assert_eq!(client_apps[0].world.resource::<RemovalsSeen>().0, 1);
+        assert_eq!(seen[1], [false, true], "the late client didn't see the restore exactly once");
+        assert_eq!(client_apps[1].world.resource::<RemovalsSeen>().0, 0);
+    }
+
+    #[test]
+    fn one_space_press_counts_one_spawn_on_each_side() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        // Bench clients have no local input, this client gets the real shooting system back
+        client_apps[0].add_systems(Update, player_input_system);
+        client_apps[0].world.send_event(KeyboardInput { scan_code: 0, key_code: Some(KeyCode::Space), state: ButtonState::Pressed, window: Entity::PLACEHOLDER });
+        for _ in 0..SIMULATION_RATE as usize
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        assert_eq!(counts(&client_apps[0]), (0, 1, 0));
+        assert_eq!(counts(&server_app), (0, 1, 0));
+    }
+
+    #[test]
+    fn server_despawn_notice_confirms_and_despawns() {
+        let mut app = prediction_app();
+        let entity = predict(&mut app);
+
+        app.world.send_event(MappedSpawnsDespawned { client_entities: vec![entity] });
+        app.update();
+
+        assert!(app.world.get_entity(entity).is_none());
+        assert_eq!(counts(&app), (0, 1, 0));
+    }
+
+    #[test]
+    fn local_despawn_is_orphaned_not_confirmed() {
+        let mut app = prediction_app();
+        let entity = predict(&mut app);
+
+        app.world.despawn(entity);
+        app.update();
+        assert_eq!(counts(&app), (1, 0, 0));
+
+        app.world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(ORPHAN_TIMEOUT + 0.1));
+        app.update();
+        assert_eq!(counts(&app), (0, 0, 1));
+    }
+
+    fn component_names(world: &World, entity: Entity) -> Vec<&str> {
+        let mut names: Vec<&str> = world.inspect_entity(entity).into_iter().map(|info| info.name()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn server_player_has_the_same_components_as_a_client_player() {
+        let mut world = World::new();
+        let mut appearance_cycle = AppearanceCycle::default();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        spawn_server_player(&mut commands, &mut appearance_cycle);
+        spawn_client_player(&mut commands, ClientId::from_raw(1), None, &mut appearance_cycle, &mut DisconnectedPlayers::default());
+        queue.apply(&mut world);
+
+        let players: Vec<(Entity, u64)> = world.query::<(Entity, &Player)>().iter(&world).map(|(entity, player)| (entity, player.0)).collect();
+        let server_player = players.iter().find(|(_, id)| *id == SERVER_ID.raw()).unwrap().0;
+        let client_player = players.iter().find(|(_, id)| *id == 1).unwrap().0;
+        assert_eq!(component_names(&world, server_player), component_names(&world, client_player));
+        assert!(world.get::<MoveDirection>(server_player).is_some());
+    }
+
+    #[test]
+    fn out_of_range_movement_moves_at_most_move_speed_per_step() {
+        let step = Duration::from_secs_f64(1.0 / SIMULATION_RATE);
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<ClientInputStats>()
+            .add_event::<FromClient<PlayerMovement>>()
+            .add_systems(Update, (receive_player_movement_system, move_player_system).chain());
+        let player = app.world.spawn((Player(1), Position(Vec2::ZERO), MoveDirection::default(), PlayerState::Alive, InputAck::default())).id();
+        let max_step = GameSettings::default().move_speed * step.as_secs_f32();
+
+        let mut sequence = 0;
+        for direction in [Vec2::new(1000.0, 0.0), Vec2::new(-3.0, 4.0), Vec2::NAN, Vec2::new(f32::INFINITY, 0.0)]
+        {
+            sequence += 1;
+            let before = app.world.get::<Position>(player).unwrap().0;
+            app.world.send_event(FromClient { client_id: ClientId::from_raw(1), event: PlayerMovement { direction, sequence } });
+            app.world.resource_mut::<Time>().advance_by(step);
+            app.update();
+
+            let position = app.world.get::<Position>(player).unwrap().0;
+            assert!(position.is_finite(), "{direction} moved the player to {position}");
+            assert!(position.distance(before) <= max_step * 1.001, "{direction} moved the player {} in one step", position.distance(before));
+        }
+    }
+
+    #[test]
+    fn holding_a_key_sends_only_the_change_and_refreshes() {
+        let frame = Duration::from_secs_f64(1.0 / 144.0);
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<input_map::ActiveGamepad>()
+            .init_resource::<InputBindings>()
+            .init_resource::<NetworkDiagnostics>()
+            .init_resource::<PendingMovement>()
+            .init_resource::<InputSmoothing>()
+            .insert_resource(LocalPlayerId(1))
+            .add_event::<PlayerMovement>()
+            .add_systems(Update, player_movement_system);
+        app.world.spawn((Player(1), MoveDirection::default()));
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::W);
+
+        let mut reader = ManualEventReader::<PlayerMovement>::default();
+        let mut sent = 0;
+        for _ in 0..3 * 144
+        {
+            app.world.resource_mut::<Time>().advance_by(frame);
+            app.update();
+            sent += reader.read(app.world.resource::<Events<PlayerMovement>>()).count();
+        }
+
+        // The first frame's change, then a refresh every `MOVEMENT_REFRESH_INTERVAL`, instead of one a frame
+        assert!((1..=7).contains(&sent), "sent {sent} movements in 3 seconds");
+    }
+
+    #[test]
+    fn sudden_input_ramps_up_when_smoothed() {
+        let delta_secs = 1.0 / 60.0;
+        let mut direction = Vec2::ZERO;
+        let mut ramp = Vec::new();
+        for _ in 0..60
+        {
+            direction = smooth_direction(direction, Vec2::X, 0.1, delta_secs);
+            ramp.push(direction.x);
+        }
+
+        assert!(ramp[0] > 0.0 && ramp[0] < 0.5, "the first frame jumped to {}", ramp[0]);
+        assert!(ramp.windows(2).all(|pair| pair[1] > pair[0] && pair[1] <= 1.0), "not a ramp: {ramp:?}");
+        assert!(ramp[59] > 0.99);
+        assert_eq!(smooth_direction(Vec2::ZERO, Vec2::X, 0.0, delta_secs), Vec2::X);
+    }
+
+    #[test]
+    fn dead_players_ignore_movement() {
+        let step = Duration::from_secs_f64(1.0 / SIMULATION_RATE);
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<ClientInputStats>()
+            .add_event::<FromClient<PlayerMovement>>()
+            .add_systems(Update, (receive_player_movement_system, move_player_system).chain());
+        let player = app.world.spawn((Player(1), Position(Vec2::ZERO), MoveDirection::default(), PlayerState::Dead { since_tick: 0 }, InputAck::default())).id();
+
+        app.world.send_event(FromClient { client_id: ClientId::from_raw(1), event: PlayerMovement { direction: Vec2::X, sequence: 1 } });
+        for _ in 0..10
+        {
+            app.world.resource_mut::<Time>().advance_by(step);
+            app.update();
+        }
+
+        assert_eq!(app.world.get::<Position>(player).unwrap().0, Vec2::ZERO);
+        assert_eq!(app.world.get::<MoveDirection>(player).unwrap().0, Vec2::ZERO);
+        // Still acknowledged, so the client stops replaying it
+        assert_eq!(app.world.get::<InputAck>(player).unwrap().sequence, 1);
+    }
+
+    #[test]
+    fn two_shots_in_one_tick_map_to_distinct_server_entities() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let mut shots = Vec::new();
+        for _ in 0..2
+        {
+            let shot = client_apps[0].world.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, Replication, PredictedSpawn(0.0))).id();
+            client_apps[0].world.send_event(PlayerInput::Shoot { weapon: WeaponKind::Pistol, projectiles: vec![shot] });
+            shots.push(shot);
+        }
+
+        let mut server_entities = Vec::new();
+        for _ in 0..SIMULATION_RATE as usize
+        {
+            step_session(&mut server_app, &mut client_apps);
+            let entity_map = client_apps[0].world.resource::<ServerEntityMap>();
+            server_entities = shots.iter().filter_map(|shot| entity_map.to_server().get(shot).copied()).collect();
+            if server_entities.len() == shots.len()
+            {
+                break;
+            }
+        }
+
+        assert_eq!(server_entities.len(), 2, "not every shot was mapped");
+        assert_ne!(server_entities[0], server_entities[1]);
+        for server_entity in server_entities
+        {
+            assert!(server_app.world.get::<PlayerSpawnedComponent>(server_entity).is_some());
+        }
+    }
+
+    #[test]
+    fn counts_from_before_the_ui_exists_are_drawn_once_it_does() {
+        let mut app = App::new();
+        app
+            .init_resource::<InputsCount>()
+            .add_systems(Update, update_input_count_text.run_if(resource_exists::<UiReady>()));
+        for frame in 0..5
+        {
+            app.world.resource_mut::<InputsCount>().confirmed = frame;
+            app.update();
+        }
+
+        app.world.spawn((TextBundle::from_section("", TextStyle::default()), PlayerSpawnCountText));
+        app.world.insert_resource(UiReady);
+        app.update();
+
+        let text = app.world.query_filtered::<&Text, With<PlayerSpawnCountText>>().single(&app.world);
+        assert_eq!(text.sections[0].value, "4 confirmed, 0 predicted, 0 orphaned");
+    }
+
+    #[test]
+    fn a_reused_shot_entity_gets_one_server_entity() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let shot = client_apps[0].world.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, Replication, PredictedSpawn(0.0))).id();
+        let shoot = PlayerInput::Shoot { weapon: WeaponKind::Pistol, projectiles: vec![shot] };
+
+        // Twice in one message batch, then again once the first has been mapped
+        client_apps[0].world.send_event(shoot.clone());
+        client_apps[0].world.send_event(shoot.clone());
+        for _ in 0..10
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        client_apps[0].world.send_event(shoot);
+        for _ in 0..10
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let server_shots = server_app.world.query_filtered::<(), With<PlayerSpawnedComponent>>().iter(&server_app.world).count();
+        assert_eq!(server_shots, 1);
+        assert!(client_apps[0].world.resource::<ServerEntityMap>().to_server().contains_key(&shot));
+    }
+
+    fn replicated_count(app: &mut App) -> usize {
+        app.world.query_filtered::<(), (With<Replication>, Without<DeathAnimation>)>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn replicated_entities_stop_growing_at_the_cap() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let cap = replicated_count(&mut server_app) + 3;
+        server_app.world.insert_resource(MaxEntities(cap));
+
+        // Two volleys past the cap, the second has the first's projectiles to recycle
+        let mut peak = 0;
+        for _ in 0..2
+        {
+            for _ in 0..5
+            {
+                let shot = client_apps[0].world.spawn((PlayerSpawnedComponent::default(), EntityKind::Projectile, Replication, PredictedSpawn(0.0))).id();
+                client_apps[0].world.send_event(PlayerInput::Shoot { weapon: WeaponKind::Pistol, projectiles: vec![shot] });
+            }
+            for _ in 0..10
+            {
+                step_session(&mut server_app, &mut client_apps);
+                let count = replicated_count(&mut server_app);
+                assert!(count <= cap, "{count} replicated entities with a cap of {cap}");
+                peak = peak.max(count);
+            }
+        }
+        assert_eq!(peak, cap);
+    }
+
+    fn channel(channel_id: u8, send_type: SendType) -> ChannelConfig {
+        ChannelConfig { channel_id, max_memory_usage_bytes: 5 * 1024 * 1024, send_type }
+    }
+
+    #[test]
+    fn channel_layout_mismatches_change_the_protocol_id() {
+        let reliable = SendType::ReliableOrdered { resend_time: Duration::from_millis(300) };
+        let server = [channel(0, reliable.clone()), channel(1, SendType::Unreliable)];
+        let client = [channel(0, reliable.clone()), channel(1, SendType::Unreliable)];
+        let layout = channel_layout_hash(&server, &client);
+
+        // Memory limits are each end's own business
+        let mut roomier = client.clone();
+        roomier[0].max_memory_usage_bytes *= 2;
+        assert_eq!(channel_layout_hash(&server, &roomier), layout);
+
+        let resent_sooner = [channel(0, SendType::ReliableOrdered { resend_time: Duration::from_millis(100) }), channel(1, SendType::Unreliable)];
+        assert_ne!(channel_layout_hash(&server, &resent_sooner), layout);
+        let swapped = [channel(0, SendType::Unreliable), channel(1, reliable.clone())];
+        assert_ne!(channel_layout_hash(&server, &swapped), layout);
+        let extra = [channel(0, reliable.clone()), channel(1, SendType::Unreliable), channel(2, SendType::Unreliable)];
+        assert_ne!(channel_layout_hash(&server, &extra), layout);
+        assert_ne!(channel_layout_hash(&extra, &client[..1]), layout);
+    }
+
+    #[test]
+    fn every_build_registers_the_same_channels() {
+        let cli = Cli::parse_from(["replicon_test_1", "bench"]);
+        let connection_args = cli.connection_args().clone();
+        let mut first = build_app(cli.clone());
+        first.finish();
+        let mut second = build_app(cli);
+        second.finish();
+
+        let first_id = connection_args.protocol_id(first.world.resource::<NetworkChannels>());
+        assert_eq!(connection_args.protocol_id(second.world.resource::<NetworkChannels>()), first_id);
+        let finer = ConnectionArgs { position_scale: connection_args.position_scale + 1, ..connection_args.clone() };
+        assert_ne!(finer.protocol_id(first.world.resource::<NetworkChannels>()), first_id);
+    }
+}
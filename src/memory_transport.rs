@@ -0,0 +1,197 @@
+use std::{collections::VecDeque, sync::{Mutex, mpsc::{self, Receiver, Sender}}, time::{Duration, Instant}};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::ClientId, RenetReceive, RenetSend};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::reconnect::ClientIdentity;
+
+/// Renet transport that moves packets through in-process channels instead of UDP sockets
+///
+/// Lets a server app and any number of client apps in the same process talk to each other,
+/// for tests and tools that shouldn't depend on the machine's networking.
+pub struct MemoryTransportPlugin;
+
+impl Plugin for MemoryTransportPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(PreUpdate, (
+                memory_server_receive_system.run_if(resource_exists::<MemoryServerTransport>()),
+                memory_client_receive_system.run_if(resource_exists::<MemoryClientTransport>()),
+            ).in_set(RenetReceive))
+            .add_systems(PostUpdate, (
+                memory_server_send_system.run_if(resource_exists::<MemoryServerTransport>()),
+                memory_client_send_system.run_if(resource_exists::<MemoryClientTransport>()),
+            ).in_set(RenetSend));
+    }
+}
+
+/// Simulated network conditions for one direction of a link
+#[derive(Clone, Copy, Default)]
+pub struct LinkConditions
+{
+    pub latency: Duration,
+    /// Chance from 0 to 1 of any packet being dropped
+    pub loss: f32,
+    /// Seed for the loss rolls, so lossy runs repeat exactly
+    pub seed: u64,
+}
+
+/// One end of a connection, sends to and receives from the matching end
+struct MemoryEnd
+{
+    sender: Sender<(Instant, Vec<u8>)>,
+    receiver: Mutex<Receiver<(Instant, Vec<u8>)>>,
+    /// Received packets still waiting out their latency
+    in_flight: VecDeque<(Instant, Vec<u8>)>,
+    conditions: LinkConditions,
+    rng: ChaCha8Rng,
+}
+
+impl MemoryEnd
+{
+    fn pair(conditions: LinkConditions) -> (Self, Self) {
+        let (to_client, from_server) = mpsc::channel();
+        let (to_server, from_client) = mpsc::channel();
+
+        let server_end = Self::new(to_client, from_client, conditions);
+        let client_end = Self::new(to_server, from_server, conditions);
+        (server_end, client_end)
+    }
+
+    fn new(sender: Sender<(Instant, Vec<u8>)>, receiver: Receiver<(Instant, Vec<u8>)>, conditions: LinkConditions) -> Self {
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+            in_flight: VecDeque::new(),
+            conditions,
+            rng: ChaCha8Rng::seed_from_u64(conditions.seed),
+        }
+    }
+
+    /// Returns false once the other end has been dropped
+    fn send(&mut self, packet: Vec<u8>) -> bool {
+        if self.rng.gen::<f32>() < self.conditions.loss
+        {
+            return true;
+        }
+
+        self.sender.send((Instant::now() + self.conditions.latency, packet)).is_ok()
+    }
+
+    /// Packets whose latency has passed, in the order they were sent
+    fn receive(&mut self) -> Vec<Vec<u8>> {
+        if let Ok(receiver) = self.receiver.get_mut()
+        {
+            self.in_flight.extend(receiver.try_iter());
+        }
+
+        let now = Instant::now();
+        let mut arrived = Vec::new();
+        while self.in_flight.front().is_some_and(|(deliver_at, _)| *deliver_at <= now)
+        {
+            if let Some((_, packet)) = self.in_flight.pop_front()
+            {
+                arrived.push(packet);
+            }
+        }
+        arrived
+    }
+}
+
+/// Server side, one channel pair per connected client
+#[derive(Resource, Default)]
+pub struct MemoryServerTransport
+{
+    links: HashMap<ClientId, MemoryEnd>,
+    /// Connected since the last receive, still to be added to the `RenetServer`
+    pending: Vec<ClientId>,
+    /// What netcode's user data would have carried for each client that has one
+    identities: HashMap<ClientId, ClientIdentity>,
+}
+
+impl MemoryServerTransport
+{
+    /// Opens a link for a new client, insert the returned transport into the client app along with a `RenetClient`
+    pub fn connect(&mut self, client_id: ClientId, conditions: LinkConditions) -> MemoryClientTransport {
+        let (server_end, client_end) = MemoryEnd::pair(conditions);
+        self.links.insert(client_id, server_end);
+        self.pending.push(client_id);
+        MemoryClientTransport { link: client_end }
+    }
+
+    /// `connect` for a client presenting a stable identity, so it can take back a player it left behind
+    pub fn connect_with_identity(&mut self, client_id: ClientId, identity: ClientIdentity, conditions: LinkConditions) -> MemoryClientTransport {
+        self.identities.insert(client_id, identity);
+        self.connect(client_id, conditions)
+    }
+
+    pub fn identity(&self, client_id: ClientId) -> Option<ClientIdentity> {
+        self.identities.get(&client_id).copied()
+    }
+}
+
+#[derive(Resource)]
+pub struct MemoryClientTransport
+{
+    link: MemoryEnd,
+}
+
+fn memory_server_receive_system(
+    mut transport: ResMut<MemoryServerTransport>,
+    mut server: ResMut<RenetServer>,
+) {
+    let transport = &mut *transport;
+    for client_id in transport.pending.drain(..)
+    {
+        server.add_connection(client_id);
+    }
+
+    for (client_id, link) in &mut transport.links
+    {
+        for packet in link.receive()
+        {
+            if server.process_packet_from(&packet, *client_id).is_err()
+            {
+                warn!("Server: Dropped a memory transport packet from unknown client '{client_id}'");
+            }
+        }
+    }
+}
+
+fn memory_server_send_system(
+    mut transport: ResMut<MemoryServerTransport>,
+    mut server: ResMut<RenetServer>,
+) {
+    transport.links.retain(|client_id, link| {
+        let Ok(packets) = server.get_packets_to_send(*client_id) else { return false; };
+        packets.into_iter().all(|packet| link.send(packet))
+    });
+}
+
+fn memory_client_receive_system(
+    mut transport: ResMut<MemoryClientTransport>,
+    mut client: ResMut<RenetClient>,
+) {
+    client.set_connected();
+    for packet in transport.link.receive()
+    {
+        client.process_packet(&packet);
+    }
+}
+
+fn memory_client_send_system(
+    mut transport: ResMut<MemoryClientTransport>,
+    mut client: ResMut<RenetClient>,
+) {
+    for packet in client.get_packets_to_send()
+    {
+        if !transport.link.send(packet)
+        {
+            client.disconnect();
+            return;
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+
+use crate::{LocalPlayerId, Player, Position, ball::Ball, input_map::{Action, InputBindings}, is_headless, room::OtherRoom};
+
+/// Side of the square panel in pixels
+const PANEL_SIZE: f32 = 150.0;
+/// Half the side of the square of world the panel covers, a little past where players spawn
+const WORLD_HALF_EXTENT: f32 = 250.0;
+/// How many blip entities are spawned up front, the pool grows past this if needed
+const INITIAL_POOL_SIZE: usize = 8;
+const PLAYER_BLIP_SIZE: f32 = 5.0;
+const LOCAL_BLIP_SIZE: f32 = 7.0;
+const BALL_BLIP_SIZE: f32 = 4.0;
+
+/// A small map in the bottom right corner with a blip per player and the ball, and the camera's view outlined, toggled with the minimap key (Z)
+///
+/// Blips are taken from a pool of reusable UI nodes, like the name labels, instead of spawned/despawned per entity.
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<BlipPool>()
+            .add_systems(Startup, spawn_minimap_system.run_if(not(is_headless)))
+            .add_systems(Update,
+                (
+                    toggle_minimap_system,
+                    update_blips_system,
+                    update_viewport_system,
+                ).run_if(not(is_headless))
+            );
+    }
+}
+
+#[derive(Component)]
+struct MinimapPanel;
+
+#[derive(Component)]
+struct MinimapViewport;
+
+/// Marker for pooled blip entities
+#[derive(Component)]
+struct MinimapBlip;
+
+#[derive(Resource, Default)]
+struct BlipPool
+{
+    panel: Option<Entity>,
+    /// Hidden blips ready to be handed out
+    free: Vec<Entity>,
+    /// Tracked entity -> the blip plotting it
+    assigned: HashMap<Entity, Entity>,
+}
+
+/// Maps a world position to pixels from the panel's top left, positions past the covered area are clamped to its border
+fn world_to_panel(position: Vec2) -> Vec2 {
+    let normalized = (position / WORLD_HALF_EXTENT).clamp(Vec2::NEG_ONE, Vec2::ONE);
+    Vec2::new(normalized.x + 1.0, 1.0 - normalized.y) * (PANEL_SIZE / 2.0)
+}
+
+fn spawn_blip(commands: &mut Commands, panel: Entity) -> Entity {
+    let mut blip = NodeBundle {
+        style: Style { position_type: PositionType::Absolute, ..default() },
+        ..default()
+    };
+    blip.visibility = Visibility::Hidden;
+
+    let blip = commands.spawn((blip, MinimapBlip)).id();
+    commands.entity(panel).add_child(blip);
+    blip
+}
+
+fn spawn_minimap_system(
+    mut commands: Commands,
+    mut pool: ResMut<BlipPool>,
+) {
+    let panel = commands.spawn((NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            right: Val::Px(5.0),
+            width: Val::Px(PANEL_SIZE),
+            height: Val::Px(PANEL_SIZE),
+            border: UiRect::all(Val::Px(1.0)),
+            overflow: Overflow::clip(),
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        border_color: Color::GRAY.into(),
+        ..default()
+    }, MinimapPanel)).id();
+
+    let viewport = commands.spawn((NodeBundle {
+        style: Style { position_type: PositionType::Absolute, border: UiRect::all(Val::Px(1.0)), ..default() },
+        border_color: Color::rgba(1.0, 1.0, 1.0, 0.5).into(),
+        ..default()
+    }, MinimapViewport)).id();
+    commands.entity(panel).add_child(viewport);
+
+    pool.panel = Some(panel);
+    for _ in 0..INITIAL_POOL_SIZE
+    {
+        let blip = spawn_blip(&mut commands, panel);
+        pool.free.push(blip);
+    }
+}
+
+fn toggle_minimap_system(
+    mut panels: Query<&mut Visibility, With<MinimapPanel>>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    if !bindings.just_pressed(Action::Minimap, &keys)
+    {
+        return;
+    }
+
+    for mut visibility in &mut panels
+    {
+        *visibility = if *visibility == Visibility::Hidden { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+/// Hands blips to newly plotted entities, moves every blip to its entity's position, and returns the blips of
+/// entities that were despawned or stopped being plotted to the pool
+fn update_blips_system(
+    mut commands: Commands,
+    mut pool: ResMut<BlipPool>,
+    plotted: Query<(Entity, &Position, Option<&Player>), (Or<(With<Player>, With<Ball>)>, Without<OtherRoom>)>,
+    mut blips: Query<(&mut Style, &mut BackgroundColor, &mut Visibility), With<MinimapBlip>>,
+    local_player: Option<Res<LocalPlayerId>>,
+) {
+    let Some(panel) = pool.panel else { return; };
+
+    let mut seen = HashSet::new();
+    for (entity, position, player) in &plotted
+    {
+        seen.insert(entity);
+        let blip = match pool.assigned.get(&entity)
+        {
+            Some(blip) => *blip,
+            None =>
+            {
+                // Pool exhausted, grow it, the blip is placed next frame once it exists
+                let blip = pool.free.pop().unwrap_or_else(|| spawn_blip(&mut commands, panel));
+                pool.assigned.insert(entity, blip);
+                blip
+            }
+        };
+        let Ok((mut style, mut color, mut visibility)) = blips.get_mut(blip) else { continue; };
+
+        let is_local = match (player, &local_player)
+        {
+            (Some(player), Some(local_player)) => player.0 == local_player.0,
+            _ => false,
+        };
+        let (size, blip_color) = match player
+        {
+            Some(_) if is_local => (LOCAL_BLIP_SIZE, Color::YELLOW),
+            Some(_) => (PLAYER_BLIP_SIZE, Color::CYAN),
+            None => (BALL_BLIP_SIZE, Color::WHITE),
+        };
+
+        let center = world_to_panel(position.0);
+        style.left = Val::Px(center.x - size / 2.0);
+        style.top = Val::Px(center.y - size / 2.0);
+        style.width = Val::Px(size);
+        style.height = Val::Px(size);
+        color.0 = blip_color;
+        visibility.set_if_neq(Visibility::Inherited);
+    }
+
+    let gone: Vec<Entity> = pool.assigned.keys().filter(|entity| !seen.contains(*entity)).copied().collect();
+    for entity in gone
+    {
+        let Some(blip) = pool.assigned.remove(&entity) else { continue; };
+        if let Ok((_, _, mut visibility)) = blips.get_mut(blip)
+        {
+            *visibility = Visibility::Hidden;
+        }
+        pool.free.push(blip);
+    }
+}
+
+/// Outlines the part of the world the camera shows, clamped to the panel like the blips
+fn update_viewport_system(
+    cameras: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+    mut viewports: Query<&mut Style, With<MinimapViewport>>,
+) {
+    let Ok((transform, projection)) = cameras.get_single() else { return; };
+    let Ok(mut style) = viewports.get_single_mut() else { return; };
+
+    let center = transform.translation.truncate();
+    let top_left = world_to_panel(center + Vec2::new(projection.area.min.x, projection.area.max.y));
+    let bottom_right = world_to_panel(center + Vec2::new(projection.area.max.x, projection.area.min.y));
+
+    style.left = Val::Px(top_left.x);
+    style.top = Val::Px(top_left.y);
+    style.width = Val::Px(bottom_right.x - top_left.x);
+    style.height = Val::Px(bottom_right.y - top_left.y);
+}
@@ -0,0 +1,210 @@
+use bevy::{prelude::*, ecs::event::ManualEventReader};
+use bevy_replicon::{prelude::*, renet::ClientId, server::ServerSet};
+use serde::{Serialize, Deserialize};
+
+use crate::{PlayerInput, PlayerMovement, console::ConsoleCommand, input_map::{Action, InputBindings}, is_headless, settings::GameTuning};
+
+/// Inputs kept for after a pause, past it the newest are dropped, so a long pause can't grow it forever
+const MAX_HELD_INPUTS: usize = 1024;
+
+/// Freezes the simulation for everyone while someone inspects the world, with `pause`/`resume` or P on the server
+///
+/// Only what's in `SimulationSet` stops, replication, input collection and UI carry on. The fixed clock keeps
+/// stepping through a pause with nothing to run, so resuming has no backlog of steps to burst through.
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<HeldInputs>()
+            .replicate::<Pause>()
+            .configure_sets(FixedUpdate, SimulationSet.run_if(simulation_running))
+            .configure_sets(Update, SimulationSet.run_if(simulation_running))
+            .add_systems(PreUpdate, hold_paused_inputs_system.after(ServerSet::Receive).run_if(has_authority()))
+            .add_systems(Update, pause_system.run_if(resource_exists::<RenetServer>()))
+            .add_systems(Startup, spawn_pause_screen.run_if(not(is_headless)))
+            .add_systems(Update, show_pause_screen_system.run_if(not(is_headless)));
+    }
+}
+
+/// Everything that moves the game on (movement, projectiles, timers), skipped while paused
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationSet;
+
+/// Lives on the game-state singleton entity, the tick the simulation was paused on while it is
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct Pause
+{
+    pub since_tick: Option<u32>,
+}
+
+/// Server side, gameplay inputs that arrived during a pause, sent on as if just received once it's over
+#[derive(Resource, Default)]
+struct HeldInputs
+{
+    inputs: Vec<(ClientId, PlayerInput)>,
+    movements: Vec<(ClientId, PlayerMovement)>,
+}
+
+impl HeldInputs
+{
+    fn len(&self) -> usize {
+        self.inputs.len() + self.movements.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Component)]
+struct PauseScreen;
+
+/// Run condition for the simulation, false while the server has it paused, on clients too once they've heard
+pub fn simulation_running(pauses: Query<&Pause>) -> bool {
+    pauses.get_single().map_or(true, |pause| pause.since_tick.is_none())
+}
+
+/// Server side, takes gameplay inputs out of this frame's events while paused, and puts the held ones back after
+///
+/// Runs before anything else reads them, so every reader sees an input once, when it takes effect. With
+/// `GameTuning::queue_paused_inputs` off they're dropped. Shots are always dropped: a `Shoot`'s pre-spawned
+/// entities are orphaned long before most pauses end, and a hitscan shot's tick is too old to rewind to.
+pub fn hold_paused_inputs_system(
+    mut held: ResMut<HeldInputs>,
+    mut inputs: ResMut<Events<FromClient<PlayerInput>>>,
+    mut movements: ResMut<Events<FromClient<PlayerMovement>>>,
+    mut seen_inputs: Local<ManualEventReader<FromClient<PlayerInput>>>,
+    mut seen_movements: Local<ManualEventReader<FromClient<PlayerMovement>>>,
+    pauses: Query<&Pause>,
+    tuning: Res<GameTuning>,
+) {
+    if !pauses.get_single().is_ok_and(|pause| pause.since_tick.is_some())
+    {
+        if !held.is_empty()
+        {
+            info!("Server: Applying {} inputs held during the pause", held.len());
+            inputs.extend(held.inputs.drain(..).map(|(client_id, event)| FromClient { client_id, event }));
+            movements.extend(held.movements.drain(..).map(|(client_id, event)| FromClient { client_id, event }));
+        }
+        seen_inputs.clear(&inputs);
+        seen_movements.clear(&movements);
+        return;
+    }
+
+    // Older events have already been read by everything, only the ones since the last frame are this system's to hold
+    let new_inputs = seen_inputs.len(&inputs);
+    let new_movements = seen_movements.len(&movements);
+    let received_inputs: Vec<_> = inputs.drain().collect();
+    let received_movements: Vec<_> = movements.drain().collect();
+
+    // Keepalives, ready and resync get through, they don't act on the world
+    let mut through = Vec::new();
+    let old_inputs = received_inputs.len() - new_inputs;
+    for FromClient { client_id, event } in received_inputs.into_iter().skip(old_inputs)
+    {
+        match event
+        {
+            PlayerInput::None | PlayerInput::ToggleReady | PlayerInput::RequestResync => through.push(FromClient { client_id, event }),
+            PlayerInput::Shoot { .. } | PlayerInput::HitscanShot { .. } => {}
+            PlayerInput::Grab | PlayerInput::Release =>
+            {
+                if tuning.queue_paused_inputs && held.len() < MAX_HELD_INPUTS
+                {
+                    held.inputs.push((client_id, event));
+                }
+            }
+        }
+    }
+    if tuning.queue_paused_inputs
+    {
+        let room = MAX_HELD_INPUTS.saturating_sub(held.len());
+        let old_movements = received_movements.len() - new_movements;
+        held.movements.extend(received_movements.into_iter().skip(old_movements).take(room).map(|FromClient { client_id, event }| (client_id, event)));
+    }
+
+    inputs.extend(through);
+    seen_inputs.clear(&inputs);
+    seen_movements.clear(&movements);
+}
+
+/// Server side, `pause`, `resume` and P freeze and unfreeze the simulation
+///
+/// Gameplay timers count `SimulationTick`s, which stop with the simulation, so nothing needs moving on when it resumes.
+fn pause_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut pauses: Query<&mut Pause>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    tick: Res<RepliconTick>,
+) {
+    let Ok(mut pause) = pauses.get_single_mut() else { return; };
+
+    let mut wanted = None;
+    for command in console.read()
+    {
+        match command.name()
+        {
+            "pause" => wanted = Some(true),
+            "resume" => wanted = Some(false),
+            _ => {}
+        }
+    }
+    if bindings.just_pressed(Action::Pause, &input)
+    {
+        wanted = Some(pause.since_tick.is_none());
+    }
+
+    match (wanted, pause.since_tick)
+    {
+        (Some(true), None) =>
+        {
+            info!("Server: Paused at tick {}", tick.get());
+            pause.since_tick = Some(tick.get());
+        }
+        (Some(false), Some(since_tick)) =>
+        {
+            let paused_ticks = tick.get().wrapping_sub(since_tick);
+            info!("Server: Resumed after {paused_ticks} ticks");
+            pause.since_tick = None;
+        }
+        (Some(true), Some(_)) => warn!("Server: Already paused"),
+        (Some(false), None) => warn!("Server: Not paused"),
+        (None, _) => {}
+    }
+}
+
+fn spawn_pause_screen(
+    mut commands: Commands,
+) {
+    let mut screen = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::rgba(0.5, 0.5, 0.5, 0.3).into(),
+        z_index: ZIndex::Global(4),
+        ..default()
+    };
+    screen.visibility = Visibility::Hidden;
+
+    commands.spawn((screen, PauseScreen)).with_children(|parent| {
+        parent.spawn(TextBundle::from_section("PAUSED", TextStyle { font_size: 60.0, color: Color::WHITE, ..default() }));
+    });
+}
+
+fn show_pause_screen_system(
+    mut screens: Query<&mut Visibility, With<PauseScreen>>,
+    pauses: Query<&Pause>,
+) {
+    let paused = pauses.get_single().is_ok_and(|pause| pause.since_tick.is_some());
+    for mut visibility in &mut screens
+    {
+        visibility.set_if_neq(if paused { Visibility::Inherited } else { Visibility::Hidden });
+    }
+}
@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use bevy_replicon::{prelude::*, renet::RenetServer};
+use rand::seq::IteratorRandom;
+use serde::{Serialize, Deserialize};
+
+use crate::{Player, Position, MoveDirection, afk::Afk, appearance::BaseColor, damage::Health, input_map::{Action, InputBindings}, pause::SimulationSet, rng::GameRng, tick_timer::SimulationTick};
+
+/// How many fixed simulation steps a player stays dead before respawning, 3 seconds
+pub const DEAD_TICKS: u32 = 180;
+
+/// Single source of truth for a player's lifecycle, instead of despawning and respawning their entity
+pub struct PlayerStatePlugin;
+
+impl Plugin for PlayerStatePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<KillPlayer>()
+            .replicate::<PlayerState>()
+            .add_systems(Update,
+                (
+                    server_kill_hotkey_system,
+                ).run_if(resource_exists::<RenetServer>())
+            )
+            .add_systems(Update,
+                (
+                    player_state_system.in_set(SimulationSet),
+                ).run_if(has_authority())
+            )
+            .add_systems(Update, grey_out_inactive_players);
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub enum PlayerState
+{
+    #[default]
+    Alive,
+    /// `since_tick` is the `SimulationTick` they died on
+    Dead { since_tick: u32 },
+    /// Moved back to the spawn point, becomes `Alive` on the next state update
+    Respawning,
+}
+
+impl PlayerState
+{
+    /// Whether the player can move, shoot and collide
+    pub fn is_alive(&self) -> bool {
+        matches!(self, PlayerState::Alive)
+    }
+}
+
+/// Server-side request to kill a player, applied by `player_state_system`
+#[derive(Event)]
+pub struct KillPlayer(pub Entity);
+
+/// Server side, all `PlayerState` transitions happen here: Alive -> Dead -> Respawning -> Alive
+fn player_state_system(
+    mut kill_events: EventReader<KillPlayer>,
+    mut players: Query<(&mut PlayerState, &mut Position, Option<&mut MoveDirection>, Option<&mut Health>), With<Player>>,
+    mut rng: Option<ResMut<GameRng>>,
+    tick: Res<SimulationTick>,
+) {
+    for KillPlayer(player_entity) in kill_events.read()
+    {
+        let Ok((mut state, _, move_direction, _)) = players.get_mut(*player_entity) else { continue; };
+        if !state.is_alive()
+        {
+            continue;
+        }
+
+        info!("Server: Player {player_entity:?} died");
+        *state = PlayerState::Dead { since_tick: tick.0 };
+        if let Some(mut move_direction) = move_direction
+        {
+            move_direction.0 = Vec2::ZERO;
+        }
+    }
+
+    for (mut state, mut pos, _, health) in &mut players
+    {
+        match *state
+        {
+            PlayerState::Alive => continue,
+            PlayerState::Dead { since_tick } =>
+            {
+                if tick.since(since_tick) < DEAD_TICKS
+                {
+                    continue;
+                }
+
+                pos.0 = rng.as_mut().map_or(Vec2::ZERO, |rng| rng.next_spawn_point());
+                if let Some(mut health) = health
+                {
+                    health.current = health.max;
+                }
+                *state = PlayerState::Respawning;
+            }
+            PlayerState::Respawning => *state = PlayerState::Alive,
+        }
+    }
+}
+
+/// Server side debug hotkey, kills a random living player when K (`kill_random_player`) is pressed
+fn server_kill_hotkey_system(
+    mut kill_events: EventWriter<KillPlayer>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    players: Query<(Entity, &PlayerState), With<Player>>,
+) {
+    if !bindings.just_pressed(Action::KillRandomPlayer, &input)
+    {
+        return;
+    }
+
+    let alive = players.iter().filter(|(_, state)| state.is_alive());
+    if let Some((player_entity, _)) = alive.choose(&mut rand::thread_rng())
+    {
+        kill_events.send(KillPlayer(player_entity));
+    }
+}
+
+/// Greys out players that are dead, respawning or AFK
+pub fn grey_out_inactive_players(
+    mut players: Query<(&PlayerState, Option<&Afk>, &mut Sprite, Option<&BaseColor>)>,
+) {
+    for (state, afk, mut sprite, base_color) in &mut players
+    {
+        let color = if state.is_alive() && afk.is_none() { base_color.map_or(Color::WHITE, |base| base.0) } else { Color::GRAY };
+        if sprite.color != color
+        {
+            sprite.color = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn state_of(app: &App, player: Entity) -> PlayerState {
+        *app.world.get::<PlayerState>(player).unwrap()
+    }
+
+    #[test]
+    fn respawns_after_dead_ticks_simulation_steps() {
+        let mut app = App::new();
+        app
+            .init_resource::<SimulationTick>()
+            .add_event::<KillPlayer>()
+            .add_systems(Update, player_state_system);
+        let player = app.world.spawn((Player(1), PlayerState::Alive, Position(Vec2::ONE))).id();
+
+        app.world.send_event(KillPlayer(player));
+        app.update();
+        assert_eq!(state_of(&app, player), PlayerState::Dead { since_tick: 0 });
+
+        // Frames without a simulation step, such as ones where a spawn sends replication early, don't count
+        for _ in 0..DEAD_TICKS * 2
+        {
+            app.update();
+        }
+        assert_eq!(state_of(&app, player), PlayerState::Dead { since_tick: 0 });
+
+        app.world.resource_mut::<SimulationTick>().0 = DEAD_TICKS - 1;
+        app.update();
+        assert_eq!(state_of(&app, player), PlayerState::Dead { since_tick: 0 });
+
+        app.world.resource_mut::<SimulationTick>().0 = DEAD_TICKS;
+        app.update();
+        assert_eq!(state_of(&app, player), PlayerState::Respawning);
+
+        app.update();
+        assert_eq!(state_of(&app, player), PlayerState::Alive);
+    }
+}
@@ -0,0 +1,419 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{Player, PlayerSpawnedComponent, Position, PredictedSpawn, damage::ApplyDamage, death_animation::{DeathAnimation, despawn_after_animation}, hitscan::PLAYER_RADIUS, pause::SimulationSet, player_state::PlayerState, room::{self, Room}, settings::{GameSettings, GameTuning, WorldBounds}, spatial_hash::{SpatialHash, SpatialHashSet}, weapon::{ProjectileKind, WeaponKind}};
+
+/// Seconds a lobbed projectile is in the air, it lands and hits whoever is near at the end of it
+const LOB_FLIGHT_TIME: f32 = 1.0;
+/// How near a lobbed projectile's landing a player must be to be hit
+const LOB_SPLASH_RADIUS: f32 = PLAYER_RADIUS * 2.0;
+/// Height of a lobbed projectile's arc at its peak, drawn as an offset up the screen
+const LOB_ARC_HEIGHT: f32 = 20.0;
+/// Seconds for most of a reconciliation correction to be blended away
+const CORRECTION_TIME: f32 = 0.1;
+/// Seconds between eviction warnings, so a stress test logs that the cap is limiting it without flooding the log
+const EVICTION_WARNING_INTERVAL: f32 = 5.0;
+/// Seconds apart two wall contacts can be and still count as one corner hit
+const CORNER_EPSILON: f32 = 1e-5;
+
+/// Projectiles fired with Space, moved locally from their replicated launch instead of by streamed positions
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate::<ProjectileMotion>()
+            .replicate::<Bounces>()
+            .add_systems(PreUpdate,
+                reconcile_predicted_projectiles_system
+                    .after(ClientSet::Receive)
+                    .run_if(resource_exists::<RenetClient>())
+            )
+            .add_systems(Update,
+                (
+                    start_projectile_clock_system,
+                    extrapolate_projectiles_system.in_set(SimulationSet),
+                    projectile_hit_system.after(SpatialHashSet).in_set(SimulationSet).run_if(has_authority()),
+                ).chain()
+            )
+            .add_systems(Update, evict_projectiles_system.run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// Where and how fast a projectile was launched, only ever sent once with its spawn
+///
+/// Clients and the server compute the position from this and the projectile's age, so nothing
+/// is replicated while it flies. Despawning (hit or expired) reaches clients through replicon's reliable channel.
+#[derive(Component, Serialize, Deserialize, Clone, Copy)]
+pub struct ProjectileMotion
+{
+    pub origin: Vec2,
+    pub velocity: Vec2,
+    /// Player id of the shooter, who can't be hit by their own projectile
+    pub owner: u64,
+}
+
+impl ProjectileMotion
+{
+    pub fn new(owner: u64, origin: Vec2, direction: Vec2, speed: f32) -> Self {
+        let direction = if direction == Vec2::ZERO { Vec2::X } else { direction.normalize() };
+        Self { origin, velocity: direction * speed, owner }
+    }
+
+    /// Where the projectile is after `age` seconds, reflecting off the edges of the world up to `bounces` times
+    ///
+    /// The path is worked out from the launch every time rather than stepped, so the server and every client
+    /// put the contacts in exactly the same place whatever their frame rate, and nothing is ever drawn past a wall.
+    pub fn trace(&self, age: f32, bounds: &WorldBounds, bounces: u8) -> ProjectileTrace {
+        let half = bounds.half_extents;
+        let mut position = self.origin;
+        let mut velocity = self.velocity;
+        let mut remaining = age;
+        let mut bounces_left = bounces;
+        loop
+        {
+            let to_x = time_to_wall(position.x, velocity.x, half.x);
+            let to_y = time_to_wall(position.y, velocity.y, half.y);
+            let contact = to_x.min(to_y);
+            if contact >= remaining
+            {
+                return ProjectileTrace { position: position + velocity * remaining, stopped: false };
+            }
+
+            position += velocity * contact;
+            remaining -= contact;
+            if bounces_left == 0
+            {
+                return ProjectileTrace { position, stopped: true };
+            }
+            bounces_left -= 1;
+
+            // Reflecting off an axis-aligned wall just negates the velocity along its normal. A corner reflects
+            // off both walls as one bounce, an axis at a time, so there's never a diagonal normal to normalize.
+            if to_x <= contact + CORNER_EPSILON
+            {
+                velocity.x = -velocity.x;
+                position.x = position.x.clamp(-half.x, half.x);
+            }
+            if to_y <= contact + CORNER_EPSILON
+            {
+                velocity.y = -velocity.y;
+                position.y = position.y.clamp(-half.y, half.y);
+            }
+        }
+    }
+}
+
+/// Seconds until a point moving at `velocity` along one axis reaches the wall at `half_extent` in that direction
+fn time_to_wall(position: f32, velocity: f32, half_extent: f32) -> f32 {
+    if velocity == 0.0
+    {
+        return f32::INFINITY;
+    }
+
+    let wall = if velocity > 0.0 { half_extent } else { -half_extent };
+    ((wall - position) / velocity).max(0.0)
+}
+
+/// Result of `ProjectileMotion::trace`
+pub struct ProjectileTrace
+{
+    pub position: Vec2,
+    /// Reached a wall with no bounces left, the server despawns it and clients hold it there until it does
+    pub stopped: bool,
+}
+
+/// Walls a projectile reflects off before it's despawned by the next, projectiles without one stop at the first wall
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Bounces(pub u8);
+
+fn bounces_of(bounces: Option<&Bounces>) -> u8 {
+    bounces.map_or(0, |bounces| bounces.0)
+}
+
+/// Seconds since the server launched this projectile, as best this machine can tell
+#[derive(Component)]
+pub struct ProjectileAge(pub f32);
+
+/// Server side, the tick a projectile was spawned on, for evicting the oldest first
+#[derive(Component)]
+pub struct SpawnTick(pub RepliconTick);
+
+/// Client side, a projectile launched locally when shooting, flying on its predicted motion until the server's arrives
+#[derive(Component)]
+pub struct PredictedProjectile;
+
+/// How far the drawn projectile is from where its motion puts it, shrinking away so a reconciliation doesn't snap
+#[derive(Component)]
+pub struct ProjectileCorrection(pub Vec2);
+
+fn one_way_latency(client: Option<&RenetClient>) -> f32 {
+    client.map_or(0.0, |client| client.network_info().rtt as f32 / 2000.0)
+}
+
+/// Clients learn about a projectile half a round trip after the server launched it, so start their clock that far in
+///
+/// A predicted projectile was launched here and now, so its clock starts at zero.
+fn start_projectile_clock_system(
+    mut commands: Commands,
+    new_projectiles: Query<(Entity, Has<PredictedProjectile>), Added<ProjectileMotion>>,
+    client: Option<Res<RenetClient>>,
+) {
+    let head_start = one_way_latency(client.as_deref());
+    for (projectile, predicted) in &new_projectiles
+    {
+        commands.entity(projectile).insert(ProjectileAge(if predicted { 0.0 } else { head_start }));
+    }
+}
+
+/// Client side, switches a predicted projectile over to the server's launch once replicon has mapped and overwritten it
+///
+/// The server launched half a round trip after this client did, and that launch took another half to get here.
+/// Whatever distance remains between the two is blended out by `ProjectileCorrection`.
+fn reconcile_predicted_projectiles_system(
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, Ref<ProjectileMotion>, &mut ProjectileAge, Option<&Bounces>, Option<&Transform>), (With<PredictedProjectile>, With<PredictedSpawn>)>,
+    client: Res<RenetClient>,
+    bounds: Res<WorldBounds>,
+) {
+    let one_way = one_way_latency(Some(&client));
+    for (projectile, motion, mut age, bounces, transform) in &mut projectiles
+    {
+        if !motion.is_changed() || motion.is_added()
+        {
+            continue;
+        }
+
+        let bounces = bounces_of(bounces);
+        let shown = transform.map_or(motion.trace(age.0, &bounds, bounces).position, |transform| transform.translation.truncate());
+        age.0 = one_way;
+        let correction = shown - motion.trace(age.0, &bounds, bounces).position;
+        commands.entity(projectile)
+            .remove::<PredictedProjectile>()
+            .insert(ProjectileCorrection(correction));
+    }
+}
+
+/// Height a lobbed projectile is drawn at `age` seconds into its flight, a parabola peaking halfway
+fn lob_height(age: f32) -> f32 {
+    let t = (age / LOB_FLIGHT_TIME).clamp(0.0, 1.0);
+    4.0 * LOB_ARC_HEIGHT * t * (1.0 - t)
+}
+
+fn extrapolate_projectiles_system(
+    mut projectiles: Query<(&ProjectileMotion, &mut ProjectileAge, Option<&Bounces>, Option<&ProjectileKind>, Option<&mut Transform>, Option<&mut ProjectileCorrection>), Without<DeathAnimation>>,
+    bounds: Res<WorldBounds>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    for (motion, mut age, bounces, kind, transform, correction) in &mut projectiles
+    {
+        age.0 += delta;
+
+        let mut offset = correction.map_or(Vec2::ZERO, |mut correction| {
+            correction.0 *= (-delta / CORRECTION_TIME).exp();
+            correction.0
+        });
+        if kind.is_some_and(|kind| kind.0 == WeaponKind::Lob)
+        {
+            offset.y += lob_height(age.0);
+        }
+
+        // The sprite may not have been attached yet
+        let Some(mut transform) = transform else { continue; };
+        let position = motion.trace(age.0, &bounds, bounces_of(bounces)).position;
+        transform.translation = (position + offset).extend(transform.translation.z);
+    }
+}
+
+/// Server side, damages the first other player a projectile reaches and despawns it, or despawns it
+/// at a wall it has no bounces left for, or after `GameTuning::projectile_lifetime`
+///
+/// Only players in the projectile's room can be hit. A lobbed projectile flies over everyone and walls alike, and only
+/// hits, with a wider reach, where it lands.
+/// Each of these plays out its `DeathAnimation` first, and a projectile in the middle of one can't hit anything again.
+fn projectile_hit_system(
+    mut commands: Commands,
+    mut damage_requests: EventWriter<ApplyDamage>,
+    projectiles: Query<(Entity, &ProjectileMotion, &ProjectileAge, Option<&Bounces>, Option<&ProjectileKind>, Option<&Room>), Without<DeathAnimation>>,
+    players: Query<(&Player, &Position, &PlayerState, Option<&Room>)>,
+    spatial_hash: Res<SpatialHash>,
+    bounds: Res<WorldBounds>,
+    tuning: Res<GameTuning>,
+) {
+    for (projectile, motion, age, bounces, kind, projectile_room) in &projectiles
+    {
+        let weapon = kind.map_or(WeaponKind::Pistol, |kind| kind.0);
+        let trace = motion.trace(age.0, &bounds, bounces_of(bounces));
+        let landed = weapon == WeaponKind::Lob && age.0 >= LOB_FLIGHT_TIME;
+        if age.0 > tuning.projectile_lifetime || (trace.stopped && weapon != WeaponKind::Lob)
+        {
+            despawn_after_animation(&mut commands, projectile, &tuning);
+            continue;
+        }
+        if weapon == WeaponKind::Lob && !landed
+        {
+            continue;
+        }
+
+        let reach = if landed { LOB_SPLASH_RADIUS } else { PLAYER_RADIUS };
+        let position = trace.position;
+        let hit = spatial_hash.near(position).find(|target| {
+            players.get(*target).is_ok_and(|(player, pos, state, player_room)| {
+                player.0 != motion.owner && state.is_alive() && room::room_of(player_room) == room::room_of(projectile_room) && pos.0.distance(position) <= reach
+            })
+        });
+
+        if let Some(target) = hit
+        {
+            damage_requests.send(ApplyDamage { target, amount: weapon.damage(), source: Some(motion.owner) });
+            despawn_after_animation(&mut commands, projectile, &tuning);
+        }
+        else if landed
+        {
+            despawn_after_animation(&mut commands, projectile, &tuning);
+        }
+    }
+}
+
+/// Server side, despawns the oldest projectiles once there are more than `GameSettings::max_projectiles`
+///
+/// Despawns replicate like any other, and only entities with a `SpawnTick` are considered, so players are never evicted.
+/// Projectiles playing their `DeathAnimation` are already on their way out, so they neither count nor get evicted.
+fn evict_projectiles_system(
+    mut commands: Commands,
+    mut last_warning: Local<Option<f32>>,
+    projectiles: Query<(Entity, &SpawnTick), (With<PlayerSpawnedComponent>, Without<Player>, Without<DeathAnimation>)>,
+    settings: Query<&GameSettings>,
+    time: Res<Time>,
+) {
+    let max_projectiles = settings.get_single().copied().unwrap_or_default().max_projectiles;
+    let count = projectiles.iter().count();
+    if count <= max_projectiles
+    {
+        return;
+    }
+
+    let mut by_age: Vec<(Entity, RepliconTick)> = projectiles.iter().map(|(entity, spawn_tick)| (entity, spawn_tick.0)).collect();
+    by_age.sort_by_key(|(_, tick)| tick.get());
+    let excess = count - max_projectiles;
+    for (projectile, _) in by_age.into_iter().take(excess)
+    {
+        commands.entity(projectile).despawn();
+    }
+
+    let now = time.elapsed_seconds();
+    if last_warning.map_or(true, |warned_at| now - warned_at >= EVICTION_WARNING_INTERVAL)
+    {
+        warn!("Server: {count} projectiles over the cap of {max_projectiles}, evicted the oldest {excess}");
+        *last_warning = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+
+    use bevy::ecs::event::ManualEventReader;
+
+    use super::*;
+    use crate::{bench::{step_session, tests::connected_session}, death_animation, projectile_bundle, spatial_hash::SpatialHashPlugin};
+
+    /// Frame length the tests step time by
+    const FRAME: f32 = 0.05;
+
+    #[test]
+    fn dying_projectile_lingers_without_hitting_then_despawns() {
+        let tuning = GameTuning::default();
+        let mut app = App::new();
+        app
+            .add_plugins(SpatialHashPlugin)
+            .init_resource::<Time>()
+            .init_resource::<WorldBounds>()
+            .insert_resource(tuning)
+            .add_event::<ApplyDamage>()
+            .add_systems(Update, (projectile_hit_system.after(SpatialHashSet), death_animation::count_down_system).chain());
+        app.world.spawn((Player(2), Position(Vec2::ZERO), PlayerState::Alive));
+        // Standing still on the target, so it would hit every frame it's allowed to
+        let projectile = app.world.spawn((ProjectileMotion::new(1, Vec2::ZERO, Vec2::X, 0.0), ProjectileAge(0.0))).id();
+
+        let mut damage_reader = ManualEventReader::<ApplyDamage>::default();
+        let mut damage_dealt = 0;
+        app.update();
+        damage_dealt += damage_reader.read(app.world.resource::<Events<ApplyDamage>>()).count();
+        assert_eq!(damage_dealt, 1);
+        assert!(app.world.get::<DeathAnimation>(projectile).is_some());
+
+        let frames_in_window = (tuning.despawn_delay / FRAME).floor() as usize - 1;
+        for _ in 0..frames_in_window
+        {
+            app.world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(FRAME));
+            app.update();
+            damage_dealt += damage_reader.read(app.world.resource::<Events<ApplyDamage>>()).count();
+            assert!(app.world.get_entity(projectile).is_some());
+        }
+        assert_eq!(damage_dealt, 1);
+
+        for _ in 0..3
+        {
+            app.world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(FRAME));
+            app.update();
+        }
+        assert!(app.world.get_entity(projectile).is_none());
+    }
+
+    #[test]
+    fn dying_projectiles_are_not_evicted_or_counted() {
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .add_systems(Update, evict_projectiles_system);
+        app.world.spawn(GameSettings { max_projectiles: 1, ..default() });
+        let dying = app.world.spawn((PlayerSpawnedComponent::default(), SpawnTick(RepliconTick::new(1)), DeathAnimation { remaining: 1.0 })).id();
+        let live = app.world.spawn((PlayerSpawnedComponent::default(), SpawnTick(RepliconTick::new(2)))).id();
+
+        app.update();
+        assert!(app.world.get_entity(dying).is_some());
+        assert!(app.world.get_entity(live).is_some());
+    }
+
+    /// Where `app`'s copy of the projectile shot by `owner` is, from its own launch and clock
+    fn traced_position(app: &mut App, owner: u64) -> Option<Vec2> {
+        let bounds = *app.world.resource::<WorldBounds>();
+        app.world.query::<(&ProjectileMotion, &ProjectileAge)>().iter(&app.world)
+            .find(|(motion, _)| motion.owner == owner)
+            .map(|(motion, age)| motion.trace(age.0, &bounds, 0).position)
+    }
+
+    #[test]
+    fn client_extrapolates_a_projectile_to_where_the_server_has_it() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        // Out of the way in one corner, the projectile flies along the bottom of the world and hits nobody
+        for mut position in server_app.world.query::<&mut Position>().iter_mut(&mut server_app.world)
+        {
+            position.0 = Vec2::splat(150.0);
+        }
+        let speed = GameTuning::default().projectile_speed;
+        let motion = ProjectileMotion::new(99, Vec2::splat(-150.0), Vec2::X, speed);
+        server_app.world.spawn(projectile_bundle(WeaponKind::Pistol, motion, 0, RepliconTick::new(0), Room::default()));
+
+        let mut compared = 0;
+        for _ in 0..60
+        {
+            step_session(&mut server_app, &mut client_apps);
+            let server_position = traced_position(&mut server_app, 99).expect("the projectile was despawned in flight");
+            let Some(client_position) = traced_position(&mut client_apps[0], 99) else { continue; };
+
+            // The client runs half a round trip ahead, to stand in for the time the launch took to reach it
+            let rtt = client_apps[0].world.resource::<RenetClient>().network_info().rtt as f32 / 1000.0;
+            let tolerance = speed * (rtt / 2.0 + 2.0 / 60.0);
+            let distance = client_position.distance(server_position);
+            assert!(distance <= tolerance, "client has the projectile at {client_position}, server at {server_position}");
+            compared += 1;
+        }
+        assert!(compared >= 55, "the client only had the projectile for {compared} frames");
+    }
+}
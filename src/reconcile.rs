@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{LocalPlayerId, MoveDirection, Player, Position, SIMULATION_RATE, diagnostics, move_player_system, pause::SimulationSet, player_state::PlayerState, settings::GameSettings, step_position};
+
+/// Movement inputs kept waiting for acknowledgement, a client that stops hearing back doesn't grow it forever
+const MAX_PENDING: usize = 128;
+
+/// Acknowledgement round trips kept for `PendingMovement::mean_latency_millis`, several seconds' worth while moving
+const MAX_LATENCY_SAMPLES: usize = 512;
+
+/// Puts the local player back where its unacknowledged movement would take it from each position the server sends
+///
+/// The server applies a direction every fixed step until the next one arrives, and counts those steps in `InputAck`.
+/// A client replays what it predicted past that count: the rest of the acknowledged input's steps, then every later
+/// input whole. Without it, the local player snaps back by a round trip of movement whenever the server's position lands.
+pub struct ReconcilePlugin;
+
+impl Plugin for ReconcilePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PendingMovement>()
+            .replicate::<InputAck>()
+            .add_systems(FixedUpdate, count_applied_steps_system.after(move_player_system).in_set(SimulationSet).run_if(has_authority()))
+            .add_systems(FixedUpdate, count_predicted_steps_system.after(move_player_system).in_set(SimulationSet).run_if(resource_exists::<RenetClient>()).run_if(resource_exists::<LocalPlayerId>()))
+            .add_systems(PreUpdate,
+                reconcile_system
+                    .after(ClientSet::Receive)
+                    .after(diagnostics::measure_prediction_error_system)
+                    .run_if(resource_exists::<RenetClient>())
+                    .run_if(resource_exists::<LocalPlayerId>())
+            )
+            .add_systems(PostUpdate, record_predicted_system.run_if(resource_exists::<RenetClient>()).run_if(resource_exists::<LocalPlayerId>()));
+    }
+}
+
+/// The newest `PlayerMovement` the server has applied to this player, and how many fixed steps it has moved it for
+///
+/// Replicated along with `Position`, so the two in one update always describe the same moment.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct InputAck
+{
+    pub sequence: u32,
+    pub steps: u32,
+}
+
+/// A direction this client sent, when it was sent, and the fixed steps it has predicted with it so far
+struct PendingInput
+{
+    sequence: u32,
+    direction: Vec2,
+    sent_at: f32,
+    steps: u32,
+}
+
+/// Client side, the local player's movement inputs the server hasn't moved past yet
+#[derive(Resource, Default)]
+pub struct PendingMovement
+{
+    inputs: VecDeque<PendingInput>,
+    last_sequence: u32,
+    /// The local player's position at the end of the last frame, anything else means replication wrote it
+    predicted: Option<Vec2>,
+    /// The newest sequence a round trip was measured for, each acknowledgement is counted once
+    last_measured: u32,
+    /// When each measured acknowledgement arrived and how long after its input was sent, in milliseconds
+    latencies: VecDeque<(f32, f32)>,
+}
+
+impl PendingMovement
+{
+    /// Starts a new input sent at `now` seconds with the next sequence, which the caller sends along with it
+    pub fn push(&mut self, direction: Vec2, now: f32) -> u32 {
+        self.last_sequence += 1;
+        if self.inputs.len() == MAX_PENDING
+        {
+            self.inputs.pop_front();
+        }
+        self.inputs.push_back(PendingInput { sequence: self.last_sequence, direction, sent_at: now, steps: 0 });
+        self.last_sequence
+    }
+
+    /// The mean time from sending a movement to the server acknowledging it, over acknowledgements that arrived
+    /// between `from` and `to` seconds, none if nothing was acknowledged then
+    ///
+    /// Includes the wait for the next replication update, so it's the delay a player feels rather than the bare ping.
+    pub fn mean_latency_millis(&self, from: f32, to: f32) -> Option<f32> {
+        let samples: Vec<f32> = self.latencies.iter().filter(|(at, _)| (from..to).contains(at)).map(|(_, millis)| *millis).collect();
+        (!samples.is_empty()).then(|| samples.iter().sum::<f32>() / samples.len() as f32)
+    }
+
+    /// Everything before the acknowledged input is already in the server's position
+    fn discard_acknowledged(&mut self, ack: &InputAck) {
+        self.inputs.retain(|input| input.sequence >= ack.sequence);
+    }
+
+    /// Steps `from`, the server's position, through what was predicted past `ack`
+    fn replay(&self, from: Vec2, ack: &InputAck, settings: &GameSettings) -> Vec2 {
+        let delta_secs = 1.0 / SIMULATION_RATE as f32;
+        let mut replayed = from;
+        for input in &self.inputs
+        {
+            let steps = if input.sequence == ack.sequence { input.steps.saturating_sub(ack.steps) } else { input.steps };
+            for _ in 0..steps
+            {
+                replayed = step_position(replayed, input.direction, settings, delta_secs);
+            }
+        }
+        replayed
+    }
+
+    /// Records the round trip of the input `sequence` if it's newly acknowledged and still pending
+    fn measure(&mut self, sequence: u32, now: f32) {
+        if sequence <= self.last_measured
+        {
+            return;
+        }
+        self.last_measured = sequence;
+
+        let Some(input) = self.inputs.iter().find(|input| input.sequence == sequence) else { return; };
+        let millis = (now - input.sent_at) * 1000.0;
+        if self.latencies.len() == MAX_LATENCY_SAMPLES
+        {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back((now, millis));
+    }
+}
+
+/// Server side, only steps with a direction are counted, standing still never needs replaying
+fn count_applied_steps_system(
+    mut players: Query<(&MoveDirection, Option<&PlayerState>, &mut InputAck)>,
+) {
+    for (direction, state, mut ack) in &mut players
+    {
+        if direction.0 != Vec2::ZERO && state.map_or(true, PlayerState::is_alive)
+        {
+            ack.steps += 1;
+        }
+    }
+}
+
+/// Client side, counts the local player's predicted steps against the input they were taken with
+fn count_predicted_steps_system(
+    mut pending: ResMut<PendingMovement>,
+    players: Query<(&Player, Option<&PlayerState>)>,
+    local_player: Res<LocalPlayerId>,
+) {
+    let alive = players.iter().any(|(player, state)| player.0 == local_player.0 && state.map_or(true, PlayerState::is_alive));
+    if let Some(input) = pending.inputs.back_mut().filter(|input| alive && input.direction != Vec2::ZERO)
+    {
+        input.steps += 1;
+    }
+}
+
+fn reconcile_system(
+    mut pending: ResMut<PendingMovement>,
+    mut players: Query<(&Player, &mut Position, &InputAck, Option<&PlayerState>)>,
+    settings: Query<&GameSettings>,
+    local_player: Res<LocalPlayerId>,
+    time: Res<Time>,
+) {
+    let Some((_, mut position, ack, state)) = players.iter_mut().find(|(player, ..)| player.0 == local_player.0) else { return; };
+
+    pending.measure(ack.sequence, time.elapsed_seconds());
+    pending.discard_acknowledged(ack);
+
+    // Replication only writes the position when the server's has changed, otherwise it's still the prediction
+    if pending.predicted.map_or(true, |predicted| predicted == position.0) || state.is_some_and(|state| !state.is_alive())
+    {
+        return;
+    }
+
+    let settings = settings.get_single().copied().unwrap_or_default();
+    position.0 = pending.replay(position.0, ack, &settings);
+}
+
+fn record_predicted_system(
+    mut pending: ResMut<PendingMovement>,
+    players: Query<(&Player, &Position)>,
+    local_player: Res<LocalPlayerId>,
+) {
+    pending.predicted = players.iter().find(|(player, _)| player.0 == local_player.0).map(|(_, position)| position.0);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn acknowledging_a_middle_input_replays_only_what_came_after() {
+        let settings = GameSettings::default();
+        let mut pending = PendingMovement::default();
+        for (direction, steps) in [(Vec2::Y, 4), (Vec2::X, 6), (Vec2::NEG_Y, 3)]
+        {
+            pending.push(direction, 0.0);
+            pending.inputs.back_mut().unwrap().steps = steps;
+        }
+
+        // The server has moved 2 of the second input's 6 steps
+        let ack = InputAck { sequence: 2, steps: 2 };
+        pending.discard_acknowledged(&ack);
+        assert_eq!(pending.inputs.iter().map(|input| input.sequence).collect::<Vec<_>>(), [2, 3]);
+
+        let step = settings.move_speed / SIMULATION_RATE as f32;
+        let replayed = pending.replay(Vec2::ZERO, &ack, &settings);
+        assert!((replayed - Vec2::new(4.0 * step, -3.0 * step)).length() < 1e-4, "replayed to {replayed}");
+    }
+
+    #[test]
+    fn each_acknowledgement_is_measured_once() {
+        let mut pending = PendingMovement::default();
+        pending.push(Vec2::X, 1.0);
+        pending.push(Vec2::Y, 1.5);
+
+        pending.measure(1, 1.1);
+        pending.measure(1, 1.4);
+        pending.measure(2, 1.6);
+        assert_eq!(pending.latencies.len(), 2);
+
+        let mean = pending.mean_latency_millis(0.0, 2.0).unwrap();
+        assert!((mean - 100.0).abs() < 0.1, "mean of {mean}ms");
+        assert_eq!(pending.mean_latency_millis(2.0, 3.0), None);
+    }
+}
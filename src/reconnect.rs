@@ -0,0 +1,301 @@
+use std::{fs, path::Path};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::transport::NETCODE_USER_DATA_BYTES, RenetReceive};
+
+use crate::{AwaitingConfirmation, Cli, InputsCount, LocalPlayerId, ProbeStats, RemovalsSeen, reconcile::PendingMovement, transport};
+
+/// Seconds a disconnected player's entity is kept for its client to come back to
+const RECONNECT_GRACE: f32 = 30.0;
+/// Seconds a client with `--auto-reconnect` waits after losing the connection before trying again
+const RETRY_DELAY: f32 = 2.0;
+
+/// Lets a client that drops and reconnects take back its old player instead of getting a new one
+///
+/// Clients send a stable identity in the netcode `user_data`, the server parks the player entity of a dropped
+/// client under that identity and hands it to whichever new connection presents the same identity in time.
+pub struct ReconnectPlugin;
+
+impl Plugin for ReconnectPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DisconnectedPlayers>()
+            .init_resource::<ConnectionEpoch>()
+            .add_systems(PreUpdate,
+                reset_on_new_session_system
+                    .after(RenetReceive)
+                    .before(ClientSet::Receive)
+                    .run_if(resource_exists::<RenetClient>())
+            )
+            .add_systems(Update, expire_disconnected_players_system)
+            .add_systems(Update, auto_reconnect_system.run_if(resource_exists::<RenetClient>()));
+    }
+}
+
+/// Server side, the identity the owning client connected with
+#[derive(Component, Clone, Copy)]
+pub struct ClientIdentity(pub u64);
+
+/// Client side, how many times this client has connected, each connection is a new session with its own world state
+#[derive(Resource, Default)]
+pub struct ConnectionEpoch(pub u32);
+
+/// Server side, player entities of dropped clients by identity, with the elapsed time they dropped at
+#[derive(Resource, Default)]
+pub struct DisconnectedPlayers(HashMap<u64, (Entity, f32)>);
+
+impl DisconnectedPlayers
+{
+    pub fn park(&mut self, identity: ClientIdentity, player_entity: Entity, now: f32) {
+        self.0.insert(identity.0, (player_entity, now));
+    }
+
+    /// Takes back the player entity parked under this identity, if it hasn't expired
+    pub fn reclaim(&mut self, identity: ClientIdentity) -> Option<Entity> {
+        self.0.remove(&identity.0).map(|(player_entity, _)| player_entity)
+    }
+}
+
+pub fn identity_to_user_data(identity: ClientIdentity) -> [u8; NETCODE_USER_DATA_BYTES] {
+    let mut user_data = [0; NETCODE_USER_DATA_BYTES];
+    user_data[..8].copy_from_slice(&identity.0.to_le_bytes());
+    user_data
+}
+
+/// `None` for clients that didn't send an identity
+pub fn identity_from_user_data(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Option<ClientIdentity> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&user_data[..8]);
+    let identity = u64::from_le_bytes(bytes);
+    (identity != 0).then_some(ClientIdentity(identity))
+}
+
+/// Reads this machine's identity from `path`, generating and saving a new one the first time
+pub fn load_or_create_identity(path: &Path) -> ClientIdentity {
+    let stored = fs::read_to_string(path).ok().and_then(|text| u64::from_str_radix(text.trim(), 16).ok());
+    if let Some(identity) = stored.filter(|identity| *identity != 0)
+    {
+        return ClientIdentity(identity);
+    }
+
+    let identity = rand::random::<u64>().max(1);
+    if let Err(e) = fs::write(path, format!("{identity:016x}"))
+    {
+        warn!("Client: Couldn't save identity to '{}', reconnecting will spawn a new player: {e}", path.display());
+    }
+    ClientIdentity(identity)
+}
+
+/// Despawns parked players whose client didn't come back within `RECONNECT_GRACE`
+fn expire_disconnected_players_system(
+    mut commands: Commands,
+    mut disconnected: ResMut<DisconnectedPlayers>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    disconnected.0.retain(|identity, (player_entity, since)| {
+        if now - *since < RECONNECT_GRACE
+        {
+            return true;
+        }
+
+        info!("Server: Identity {identity:016x} didn't reconnect in time, removing its player");
+        commands.entity(*player_entity).despawn();
+        false
+    });
+}
+
+/// Client side, clears out the previous session's world as a new one starts, before its initial state is applied
+///
+/// Entities from a server that went away were never despawned by it, so every replicated entity still here
+/// (including unconfirmed pre-spawns) is stale. Exclusive so the despawns land before `ClientSet::Receive`.
+fn reset_on_new_session_system(
+    world: &mut World,
+    mut was_connected: Local<bool>,
+) {
+    let connected = world.resource::<RenetClient>().is_connected();
+    let just_connected = connected && !*was_connected;
+    *was_connected = connected;
+    if !just_connected
+    {
+        return;
+    }
+
+    let mut epoch = world.resource_mut::<ConnectionEpoch>();
+    epoch.0 += 1;
+    let epoch = epoch.0;
+
+    let stale: Vec<Entity> = world.query_filtered::<Entity, With<Replication>>().iter(world).collect();
+    info!("Client: Connected, session {epoch}, clearing {} entities from the previous session", stale.len());
+    for entity in stale
+    {
+        world.entity_mut(entity).despawn_recursive();
+    }
+
+    world.insert_resource(InputsCount::default());
+    world.insert_resource(AwaitingConfirmation::default());
+    world.insert_resource(ProbeStats::default());
+    world.insert_resource(RemovalsSeen::default());
+    world.insert_resource(PendingMovement::default());
+}
+
+/// Client side, with `--auto-reconnect` starts a fresh connection to the same server after the current one drops
+fn auto_reconnect_system(
+    mut commands: Commands,
+    mut waited: Local<f32>,
+    client: Res<RenetClient>,
+    cli: Res<Cli>,
+    network_channels: Res<NetworkChannels>,
+    time: Res<Time>,
+) {
+    let Cli::Client { auto_reconnect: true, .. } = *cli else { return; };
+    if !client.is_disconnected()
+    {
+        *waited = 0.0;
+        return;
+    }
+
+    *waited += time.delta_seconds();
+    if *waited < RETRY_DELAY
+    {
+        return;
+    }
+    *waited = 0.0;
+
+    connect_again(&mut commands, &cli, &network_channels);
+}
+
+/// Client side, replaces the connection with a new one to the same server, starting a new session
+pub fn connect_again(
+    commands: &mut Commands,
+    cli: &Cli,
+    network_channels: &NetworkChannels,
+) {
+    let Cli::Client { ip, port, ref identity_file, ref connection_args, .. } = *cli else { return; };
+
+    // The same identity gets this client its old player back if the server is still the same one
+    let identity = load_or_create_identity(identity_file);
+    match transport::client_transport(ip, port, identity, connection_args.protocol_id(network_channels))
+    {
+        Ok((transport, client_id)) =>
+        {
+            info!("Client: Reconnecting to {ip}:{port} as {client_id}");
+            commands.insert_resource(RenetClient::new(connection_args.connection_config(network_channels)));
+            commands.insert_resource(transport);
+            commands.insert_resource(LocalPlayerId(client_id));
+        }
+        Err(e) => warn!("Client: Couldn't reconnect to {ip}:{port}: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use clap::Parser;
+    use bevy_replicon::renet::ClientId;
+
+    use super::*;
+    use crate::{Player, Position, bench::{memory_session, step_session, tests::connected_session}, build_app, memory_transport::{LinkConditions, MemoryClientTransport, MemoryServerTransport}, scoreboard::Score};
+
+    /// Put on everything the client had before the restart
+    #[derive(Component)]
+    struct FromOldServer;
+
+    fn player_with_score(app: &mut App) -> Vec<(Entity, u64, u32)> {
+        app.world.query::<(Entity, &Player, &Score)>().iter(&app.world).map(|(entity, player, score)| (entity, player.0, score.0)).collect()
+    }
+
+    #[test]
+    fn client_reconnecting_with_its_identity_gets_its_player_and_score_back() {
+        let identity = ClientIdentity(0x5eed);
+        let cli = Cli::parse_from(["replicon_test_1", "bench"]);
+        let (mut server_app, _) = memory_session(&cli, 0, 0).unwrap();
+        let mut client_apps = vec![build_app(cli.clone())];
+        let client_app = &mut client_apps[0];
+        client_app.finish();
+        client_app.cleanup();
+        let connection_config = cli.connection_args().connection_config(client_app.world.resource::<NetworkChannels>());
+
+        let transport = server_app.world.resource_mut::<MemoryServerTransport>().connect_with_identity(ClientId::from_raw(1), identity, LinkConditions::default());
+        client_app.world.insert_resource(RenetClient::new(connection_config.clone()));
+        client_app.world.insert_resource(transport);
+        client_app.world.insert_resource(LocalPlayerId(1));
+        for _ in 0..30
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        let [(player_entity, 1, 0)] = player_with_score(&mut server_app)[..] else { panic!("the client got no player") };
+        server_app.world.get_mut::<Score>(player_entity).unwrap().0 = 7;
+        server_app.world.get_mut::<Position>(player_entity).unwrap().0 = Vec2::new(40.0, -30.0);
+
+        // The connection drops, and the same machine comes back as a new client id
+        server_app.world.resource_mut::<RenetServer>().disconnect(ClientId::from_raw(1));
+        let client_app = &mut client_apps[0];
+        client_app.world.resource_mut::<RenetClient>().disconnect();
+        client_app.world.remove_resource::<MemoryClientTransport>();
+        step_session(&mut server_app, &mut client_apps);
+
+        let transport = server_app.world.resource_mut::<MemoryServerTransport>().connect_with_identity(ClientId::from_raw(2), identity, LinkConditions::default());
+        let client_app = &mut client_apps[0];
+        client_app.world.insert_resource(RenetClient::new(connection_config));
+        client_app.world.insert_resource(transport);
+        client_app.world.insert_resource(LocalPlayerId(2));
+        for _ in 0..30
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        assert_eq!(player_with_score(&mut server_app), [(player_entity, 2, 7)], "the old player wasn't reclaimed");
+        assert_eq!(server_app.world.get::<Position>(player_entity).unwrap().0, Vec2::new(40.0, -30.0));
+        let client_players: Vec<(u64, u32)> = player_with_score(&mut client_apps[0]).into_iter().map(|(_, id, score)| (id, score)).collect();
+        assert_eq!(client_players, [(2, 7)]);
+    }
+
+    fn census(app: &mut App) -> usize {
+        app.world.query_filtered::<(), With<Replication>>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn client_of_a_restarted_server_ends_up_with_only_the_new_servers_world() {
+        let (mut old_server_app, mut client_apps) = connected_session(1);
+        // Something only the old server has, so a leftover would show in the census
+        old_server_app.world.spawn((Replication, Position(Vec2::new(5.0, 5.0))));
+        for _ in 0..10
+        {
+            step_session(&mut old_server_app, &mut client_apps);
+        }
+        let client_app = &mut client_apps[0];
+        let old: Vec<Entity> = client_app.world.query_filtered::<Entity, With<Replication>>().iter(&client_app.world).collect();
+        assert!(old.len() > 1);
+        for entity in old
+        {
+            client_app.world.entity_mut(entity).insert(FromOldServer);
+        }
+
+        // The server goes away, and the client spends a frame disconnected as it would waiting to reconnect
+        drop(old_server_app);
+        client_app.world.resource_mut::<RenetClient>().disconnect();
+        client_app.world.remove_resource::<MemoryClientTransport>();
+        client_app.update();
+
+        let cli = Cli::parse_from(["replicon_test_1", "bench"]);
+        let (mut server_app, _) = memory_session(&cli, 0, 0).unwrap();
+        let transport = server_app.world.resource_mut::<MemoryServerTransport>().connect(ClientId::from_raw(1), LinkConditions::default());
+        let connection_config = cli.connection_args().connection_config(client_app.world.resource::<NetworkChannels>());
+        client_app.world.insert_resource(RenetClient::new(connection_config));
+        client_app.world.insert_resource(transport);
+
+        for _ in 0..60
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let client_app = &mut client_apps[0];
+        assert_eq!(client_app.world.resource::<ConnectionEpoch>().0, 2);
+        assert_eq!(client_app.world.resource::<InputsCount>().predicted, 0);
+        assert_eq!(client_app.world.query_filtered::<(), With<FromOldServer>>().iter(&client_app.world).count(), 0, "entities from the old server survived");
+        assert!(client_app.world.query::<&Player>().iter(&client_app.world).any(|player| player.0 == 1), "no player on the new server");
+        assert_eq!(census(client_app), census(&mut server_app));
+    }
+}
@@ -0,0 +1,205 @@
+use std::{io::Cursor, marker::PhantomData, time::Duration};
+
+use bevy::{prelude::*, ptr::Ptr};
+use bevy_replicon::{prelude::*, client::ClientSet, renet::SendType, replicon_core::replication_rules::remove_component, server::ServerSet};
+use bincode::{DefaultOptions, Options};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+
+use crate::send_rate::send_rate_system;
+
+/// Seconds a change for an entity this client hasn't been sent yet is kept for it before being dropped
+const UNAPPLIED_TIMEOUT: f32 = 2.0;
+
+/// Components that must arrive, and whose changes are rare enough to resend reliably
+pub trait Reliable: Component + Serialize + DeserializeOwned + Clone {}
+
+impl<T: Component + Serialize + DeserializeOwned + Clone> Reliable for T {}
+
+pub trait ReplicateReliableExt
+{
+    /// Like `replicate`, but changes after the component is inserted are also sent over a reliable ordered channel
+    ///
+    /// Replicon picks its channel per message rather than per component: insertions go over its reliable init
+    /// channel, later changes over its unreliable update channel. A lost update is resent until acknowledged, but
+    /// only in a later update, so a component registered here also sends each change as a `ReliableChange`. Both
+    /// copies carry the tick they were sent on, and the client keeps whichever is newer.
+    fn replicate_reliable<T: Reliable>(&mut self) -> &mut Self;
+}
+
+impl ReplicateReliableExt for App
+{
+    fn replicate_reliable<T: Reliable>(&mut self) -> &mut Self {
+        self
+            .init_resource::<UnappliedChanges<T>>()
+            .replicate_with::<T>(serialize_reliable::<T>, deserialize_newer::<T>, remove_component::<T>)
+            .add_server_event::<ReliableChange<T>>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_systems(PostUpdate, send_changes_system::<T>.after(send_rate_system).before(ServerSet::Send).run_if(resource_exists::<RenetServer>()))
+            .add_systems(PreUpdate, apply_changes_system::<T>.after(ClientSet::Receive).run_if(resource_exists::<RenetClient>()))
+    }
+}
+
+/// A change to a reliably replicated component, on the server's entity, made on the tick it was sent with
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct ReliableChange<T: Component>
+{
+    pub entity: Entity,
+    pub tick: u32,
+    pub value: T,
+}
+
+/// Client side, the tick the entity's `T` was sent on, so an older copy arriving late can't replace a newer one
+#[derive(Component)]
+pub struct ReliableTick<T: Component>
+{
+    tick: u32,
+    marker: PhantomData<T>,
+}
+
+impl<T: Component> ReliableTick<T>
+{
+    fn new(tick: u32) -> Self {
+        Self { tick, marker: PhantomData }
+    }
+}
+
+/// Client side, changes that arrived before the entity they're for, with the elapsed time they arrived at
+#[derive(Resource)]
+struct UnappliedChanges<T: Component>
+{
+    changes: Vec<(f32, ReliableChange<T>)>,
+}
+
+impl<T: Component> Default for UnappliedChanges<T>
+{
+    fn default() -> Self {
+        Self { changes: Vec::new() }
+    }
+}
+
+/// Whether `tick` is the same as `last` or after it, allowing for wrapping
+fn is_newer(tick: u32, last: u32) -> bool {
+    tick.wrapping_sub(last) < u32::MAX / 2
+}
+
+fn serialize_reliable<T: Reliable>(component: Ptr, cursor: &mut Cursor<Vec<u8>>) -> bincode::Result<()> {
+    // SAFETY: replicon only calls this with a pointer to the registered component type
+    let component = unsafe { component.deref::<T>() };
+    DefaultOptions::new().serialize_into(cursor, component)
+}
+
+/// Inserts the value unless the entity already has one from a later tick, which `apply_changes_system` may have put there
+fn deserialize_newer<T: Reliable>(
+    entity: &mut EntityWorldMut,
+    _entity_map: &mut ServerEntityMap,
+    cursor: &mut Cursor<&[u8]>,
+    tick: RepliconTick,
+) -> bincode::Result<()> {
+    let component: T = DefaultOptions::new().deserialize_from(cursor)?;
+    if entity.get::<ReliableTick<T>>().is_some_and(|last| !is_newer(tick.get(), last.tick))
+    {
+        return Ok(());
+    }
+
+    entity.insert((component, ReliableTick::<T>::new(tick.get())));
+    Ok(())
+}
+
+/// Server side, sends every change to a `T` that's already been replicated, insertions go out with the init message
+fn send_changes_system<T: Reliable>(
+    mut changes: EventWriter<ToClients<ReliableChange<T>>>,
+    components: Query<(Entity, Ref<T>), (Changed<T>, With<Replication>)>,
+    tick: Res<RepliconTick>,
+) {
+    for (entity, component) in &components
+    {
+        if component.is_added()
+        {
+            continue;
+        }
+
+        changes.send(ToClients {
+            mode: SendMode::Broadcast,
+            event: ReliableChange { entity, tick: tick.get(), value: component.clone() },
+        });
+    }
+}
+
+/// Client side, applies each change once its entity is here, unless replication already brought something newer
+fn apply_changes_system<T: Reliable>(
+    mut received: EventReader<ReliableChange<T>>,
+    mut unapplied: ResMut<UnappliedChanges<T>>,
+    mut components: Query<(&mut T, &mut ReliableTick<T>)>,
+    entity_map: Res<ServerEntityMap>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    unapplied.changes.extend(received.read().map(|change| (now, change.clone())));
+
+    unapplied.changes.retain(|(arrived, change)| {
+        let Some((mut component, mut last)) = entity_map.to_client().get(&change.entity).and_then(|&entity| components.get_mut(entity).ok()) else {
+            return now - arrived < UNAPPLIED_TIMEOUT;
+        };
+
+        if is_newer(change.tick, last.tick)
+        {
+            *component = change.value.clone();
+            last.tick = change.tick;
+        }
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::ecs::event::ManualEventReader;
+
+    use super::*;
+    use crate::{Player, Position, bench::{step_session, tests::connected_session}, scoreboard::Score};
+
+    #[test]
+    fn ticks_compare_across_wrapping() {
+        assert!(is_newer(5, 5));
+        assert!(is_newer(6, 5));
+        assert!(!is_newer(4, 5));
+        assert!(is_newer(2, u32::MAX - 2));
+        assert!(!is_newer(u32::MAX - 2, 2));
+    }
+
+    #[test]
+    fn score_changes_are_sent_reliably_and_positions_are_not() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let player = server_app.world.query::<(Entity, &Player)>().iter(&server_app.world)
+            .find_map(|(entity, player)| (player.0 == 1).then_some(entity))
+            .unwrap();
+
+        // Spawned with the init message, before any change could go over the reliable channel
+        let client_player = client_apps[0].world.query::<(&Player, &Score, &ReliableTick<Score>)>().iter(&client_apps[0].world)
+            .find(|(player, ..)| player.0 == 1)
+            .map(|(_, score, _)| *score);
+        assert_eq!(client_player, Some(Score(0)));
+
+        let mut sent = ManualEventReader::<ToClients<ReliableChange<Score>>>::default();
+        sent.clear(server_app.world.resource::<Events<ToClients<ReliableChange<Score>>>>());
+        server_app.world.get_mut::<Score>(player).unwrap().0 = 3;
+        server_app.world.get_mut::<Position>(player).unwrap().0 = Vec2::new(5.0, 0.0);
+        step_session(&mut server_app, &mut client_apps);
+
+        let events = server_app.world.resource::<Events<ToClients<ReliableChange<Score>>>>();
+        let changes: Vec<_> = sent.read(events).map(|change| (change.event.entity, change.event.value)).collect();
+        assert_eq!(changes, [(player, Score(3))]);
+        // Positions only ever go through replicon's unreliable updates
+        assert!(!server_app.world.contains_resource::<Events<ToClients<ReliableChange<Position>>>>());
+
+        for _ in 0..10
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        let (score, position) = client_apps[0].world.query::<(&Player, &Score, &Position)>().iter(&client_apps[0].world)
+            .find(|(player, ..)| player.0 == 1)
+            .map(|(_, score, position)| (*score, position.0))
+            .unwrap();
+        assert_eq!(score, Score(3));
+        assert!((position.x - 5.0).abs() < 0.1, "client has the player at {position}");
+    }
+}
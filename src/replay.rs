@@ -0,0 +1,302 @@
+use std::{error::Error, fs::File, io::{BufReader, BufWriter}, path::{Path, PathBuf}, time::Duration};
+
+use bevy::{prelude::*, app::AppExit, time::TimeUpdateStrategy, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::{ClientId, DisconnectReason, ServerEvent, transport::NetcodeServerTransport}};
+use serde::{Serialize, Deserialize};
+
+use crate::{Cli, Player, PlayerInput, PlayerMovement, Position, damage::Health, pause, reconnect::{self, ClientIdentity}, rng::RngSeed, web_transport::WebServerTransport};
+
+/// Bumped whenever `Recording` changes shape
+const RECORDING_VERSION: u8 = 4;
+/// Slowest playback speed, each `-` halves the speed down to this
+const MIN_SPEED: f32 = 0.125;
+
+/// Server side `--record`, and the `replay-view` mode that plays a recording back through the normal server systems
+///
+/// A recording holds every frame's time step along with the client inputs, movement and connections the server received
+/// during it. Replaying feeds those into the same systems with the same seed, so the session plays out the same way.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ReplayIdentities>()
+            .add_systems(PreUpdate, record_frame_system.after(ServerSet::Receive).after(pause::hold_paused_inputs_system).run_if(resource_exists::<SessionRecorder>()))
+            .add_systems(Last, save_recording_system.run_if(resource_exists::<SessionRecorder>()))
+            .add_systems(PreUpdate, feed_frame_system.after(ServerSet::Receive).before(pause::hold_paused_inputs_system).run_if(resource_exists::<ReplayPlayback>()))
+            .add_systems(Last, playback_control_system.run_if(resource_exists::<ReplayPlayback>()));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+enum RecordedConnection
+{
+    Connected { client_id: u64, identity: Option<u64> },
+    Disconnected { client_id: u64 },
+}
+
+/// Everything the server received during one frame
+#[derive(Serialize, Deserialize)]
+struct RecordedFrame
+{
+    delta: Duration,
+    connections: Vec<RecordedConnection>,
+    inputs: Vec<(u64, PlayerInput)>,
+    movements: Vec<(u64, PlayerMovement)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Recording
+{
+    version: u8,
+    /// The recorded server's `RngSeed`, a replay runs with it in place of `--seed`
+    pub seed: u64,
+    frames: Vec<RecordedFrame>,
+    /// `world_checksum` at the end of the last frame, a replay that ends anywhere else has diverged
+    checksum: u64,
+}
+
+impl Recording
+{
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {e}", path.display()))?;
+        let recording: Recording = bincode::deserialize_from(BufReader::new(file))?;
+        if recording.version != RECORDING_VERSION
+        {
+            return Err(format!("{} is recording version {}, this build plays version {RECORDING_VERSION}", path.display(), recording.version).into());
+        }
+
+        Ok(recording)
+    }
+}
+
+/// Server side, frames received so far, written to `path` when the app exits
+#[derive(Resource)]
+pub struct SessionRecorder
+{
+    path: PathBuf,
+    frames: Vec<RecordedFrame>,
+}
+
+impl SessionRecorder
+{
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, frames: Vec::new() }
+    }
+}
+
+/// Identities of recorded clients, standing in for the netcode user data a replay has no transport for
+#[derive(Resource, Default)]
+pub struct ReplayIdentities(pub HashMap<ClientId, ClientIdentity>);
+
+/// Where a `replay-view` is in its recording
+#[derive(Resource)]
+pub struct ReplayPlayback
+{
+    recording: Recording,
+    next_frame: usize,
+    paused: bool,
+    speed: f32,
+    /// Recorded frames owed at the current speed, one is played each time it reaches a whole frame
+    progress: f32,
+    /// Whether this update plays the next recorded frame, decided at the end of the previous one
+    playing_frame: bool,
+    finished: bool,
+}
+
+impl ReplayPlayback
+{
+    pub fn new(recording: Recording) -> Self {
+        Self { recording, next_frame: 0, paused: false, speed: 1.0, progress: 0.0, playing_frame: false, finished: false }
+    }
+}
+
+/// FNV-1a over every player's id, position and health, ordered by id
+///
+/// Hashed by hand because `DefaultHasher` isn't guaranteed to give the same answer in another build.
+fn world_checksum(players: &Query<(&Player, &Position, &Health)>) -> u64 {
+    let mut state: Vec<(u64, u32, u32, u32)> = players.iter()
+        .map(|(player, position, health)| (player.0, position.0.x.to_bits(), position.0.y.to_bits(), health.current))
+        .collect();
+    state.sort_by_key(|(id, ..)| *id);
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bincode::serialize(&state).unwrap_or_default()
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn record_frame_system(
+    mut recorder: ResMut<SessionRecorder>,
+    mut server_events: EventReader<ServerEvent>,
+    mut inputs: EventReader<FromClient<PlayerInput>>,
+    mut movements: EventReader<FromClient<PlayerMovement>>,
+    transport: Option<Res<NetcodeServerTransport>>,
+    web_transport: Option<Res<WebServerTransport>>,
+    time: Res<Time>,
+) {
+    let connections = server_events.read()
+        .map(|event| match event
+        {
+            ServerEvent::ClientConnected { client_id } =>
+            {
+                let identity = transport.as_ref()
+                    .and_then(|transport| transport.user_data(*client_id))
+                    .and_then(|user_data| reconnect::identity_from_user_data(&user_data))
+                    .or_else(|| web_transport.as_ref().and_then(|web_transport| web_transport.identity(*client_id)));
+                RecordedConnection::Connected { client_id: client_id.raw(), identity: identity.map(|identity| identity.0) }
+            }
+            ServerEvent::ClientDisconnected { client_id, .. } => RecordedConnection::Disconnected { client_id: client_id.raw() },
+        })
+        .collect();
+
+    recorder.frames.push(RecordedFrame {
+        delta: time.delta(),
+        connections,
+        inputs: inputs.read().map(|FromClient { client_id, event }| (client_id.raw(), event.clone())).collect(),
+        movements: movements.read().map(|FromClient { client_id, event }| (client_id.raw(), *event)).collect(),
+    });
+}
+
+fn save_recording_system(
+    mut exits: EventReader<AppExit>,
+    mut recorder: ResMut<SessionRecorder>,
+    players: Query<(&Player, &Position, &Health)>,
+    seeds: Query<&RngSeed>,
+) {
+    if exits.read().count() == 0
+    {
+        return;
+    }
+
+    let recording = Recording {
+        version: RECORDING_VERSION,
+        seed: seeds.get_single().map_or(0, |seed| seed.0),
+        frames: std::mem::take(&mut recorder.frames),
+        checksum: world_checksum(&players),
+    };
+
+    let result = File::create(&recorder.path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| bincode::serialize_into(BufWriter::new(file), &recording).map_err(|e| e.to_string()));
+    match result
+    {
+        Ok(()) => info!("Server: Wrote {} recorded frames to {}", recording.frames.len(), recorder.path.display()),
+        Err(e) => error!("Server: Couldn't write the recording to {}: {e}", recorder.path.display()),
+    }
+}
+
+/// Plays the next recorded frame's connections and inputs, as if they'd just been received
+fn feed_frame_system(
+    mut playback: ResMut<ReplayPlayback>,
+    mut identities: ResMut<ReplayIdentities>,
+    mut server_events: EventWriter<ServerEvent>,
+    mut inputs: EventWriter<FromClient<PlayerInput>>,
+    mut movements: EventWriter<FromClient<PlayerMovement>>,
+) {
+    if !playback.playing_frame
+    {
+        return;
+    }
+
+    let playback = &mut *playback;
+    let Some(frame) = playback.recording.frames.get_mut(playback.next_frame) else { return; };
+    playback.next_frame += 1;
+
+    for connection in &frame.connections
+    {
+        match *connection
+        {
+            RecordedConnection::Connected { client_id, identity } =>
+            {
+                let client_id = ClientId::from_raw(client_id);
+                if let Some(identity) = identity
+                {
+                    identities.0.insert(client_id, ClientIdentity(identity));
+                }
+                server_events.send(ServerEvent::ClientConnected { client_id });
+            }
+            RecordedConnection::Disconnected { client_id } =>
+            {
+                server_events.send(ServerEvent::ClientDisconnected { client_id: ClientId::from_raw(client_id), reason: DisconnectReason::DisconnectedByClient });
+            }
+        }
+    }
+
+    // Each frame is only played once, so its inputs can be moved out
+    for (client_id, event) in frame.inputs.drain(..)
+    {
+        inputs.send(FromClient { client_id: ClientId::from_raw(client_id), event });
+    }
+    for (client_id, event) in frame.movements.drain(..)
+    {
+        movements.send(FromClient { client_id: ClientId::from_raw(client_id), event });
+    }
+}
+
+/// Space pauses, Right steps one frame while paused, +/- change the speed, and the end of the recording is checked for divergence
+///
+/// Recorded frames are only ever played with their recorded time step, playing slower just leaves time stopped
+/// on the updates in between. That's also why there's no faster than real time, or stepping back.
+fn playback_control_system(
+    mut playback: ResMut<ReplayPlayback>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+    players: Query<(&Player, &Position, &Health)>,
+    input: Res<Input<KeyCode>>,
+) {
+    let frame_count = playback.recording.frames.len();
+    if playback.playing_frame && playback.next_frame == frame_count && !playback.finished
+    {
+        playback.finished = true;
+        let checksum = world_checksum(&players);
+        if checksum == playback.recording.checksum
+        {
+            info!("Replay: Finished {frame_count} frames, the final state matches the recording");
+        }
+        else
+        {
+            error!("Replay: Finished {frame_count} frames, but the final state diverged from the recording (checksum {checksum:x}, recorded {:x})", playback.recording.checksum);
+        }
+    }
+
+    if input.just_pressed(KeyCode::Space)
+    {
+        playback.paused = !playback.paused;
+        info!("Replay: {} at frame {}/{frame_count}", if playback.paused { "Paused" } else { "Resumed" }, playback.next_frame);
+    }
+    if input.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd])
+    {
+        playback.speed = (playback.speed * 2.0).min(1.0);
+        info!("Replay: Speed {}x", playback.speed);
+    }
+    if input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract])
+    {
+        playback.speed = (playback.speed / 2.0).max(MIN_SPEED);
+        info!("Replay: Speed {}x", playback.speed);
+    }
+    if input.just_pressed(KeyCode::Left)
+    {
+        warn!("Replay: Can't step backwards, the simulation can't be rewound");
+    }
+
+    let stepped = playback.paused && input.just_pressed(KeyCode::Right);
+    let mut play_next = stepped;
+    if !playback.paused
+    {
+        playback.progress += playback.speed;
+        play_next = playback.progress >= 1.0;
+        if play_next
+        {
+            playback.progress -= 1.0;
+        }
+    }
+    play_next &= playback.next_frame < frame_count;
+
+    playback.playing_frame = play_next;
+    let delta = if play_next { playback.recording.frames[playback.next_frame].delta } else { Duration::ZERO };
+    *time_update_strategy = TimeUpdateStrategy::ManualDuration(delta);
+}
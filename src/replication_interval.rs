@@ -0,0 +1,220 @@
+use std::{io::Cursor, marker::PhantomData, sync::atomic::{AtomicBool, AtomicU32, Ordering}};
+
+use bevy::{prelude::*, ptr::Ptr, utils::HashSet};
+use bevy_replicon::{prelude::*, renet::ServerEvent, replicon_core::replication_rules::remove_component, server::ServerSet};
+use bincode::{DefaultOptions, Options};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{replication_mask::deserialize_masked, send_rate::send_rate_system};
+
+/// The tick being sent, as replicon's serialize functions see it, they're plain `fn`s with no access to resources
+static SEND_TICK: AtomicU32 = AtomicU32::new(0);
+/// Set for a send where every throttled component goes out whatever its interval
+static SEND_ALL: AtomicBool = AtomicBool::new(false);
+
+/// Components that rarely change and only need replicating every `INTERVAL` ticks, registered with `replicate_throttled`
+pub trait Throttled: Component + Serialize + DeserializeOwned
+{
+    const INTERVAL: u32;
+}
+
+/// Shared state for every throttled component, the per-type systems are added by `replicate_throttled`
+pub struct ReplicationIntervalPlugin;
+
+impl Plugin for ReplicationIntervalPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SendAllNext>()
+            .configure_sets(PostUpdate,
+                (ThrottleSet::Collect, ThrottleSet::Decide, ThrottleSet::Resend)
+                    .chain()
+                    .after(send_rate_system)
+                    .before(ServerSet::Send)
+                    .run_if(resource_exists::<RenetServer>())
+            )
+            .add_systems(PostUpdate, flush_on_connect_system.in_set(ThrottleSet::Collect))
+            .add_systems(PostUpdate, decide_send_system.in_set(ThrottleSet::Decide));
+    }
+}
+
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+enum ThrottleSet
+{
+    Collect,
+    Decide,
+    Resend,
+}
+
+pub trait ReplicateThrottledExt
+{
+    /// Like `replicate`, but changes are only sent on ticks that are a multiple of `T::INTERVAL`
+    ///
+    /// New components and new clients don't wait, the next send includes every throttled component.
+    fn replicate_throttled<T: Throttled>(&mut self) -> &mut Self;
+}
+
+impl ReplicateThrottledExt for App
+{
+    fn replicate_throttled<T: Throttled>(&mut self) -> &mut Self {
+        self
+            .init_resource::<PendingChanges<T>>()
+            .replicate_with::<T>(serialize_throttled::<T>, deserialize_masked::<T>, remove_component::<T>)
+            .add_systems(PostUpdate, collect_changes_system::<T>.in_set(ThrottleSet::Collect))
+            .add_systems(PostUpdate, resend_pending_system::<T>.in_set(ThrottleSet::Resend))
+    }
+}
+
+/// Whether the next send should include every throttled component, because one was added or a client connected
+#[derive(Resource, Default)]
+struct SendAllNext(bool);
+
+/// Entities whose `T` changed since it was last sent, held back until its next interval
+#[derive(Resource)]
+struct PendingChanges<T>
+{
+    entities: HashSet<Entity>,
+    marker: PhantomData<T>,
+}
+
+impl<T> Default for PendingChanges<T>
+{
+    fn default() -> Self {
+        Self { entities: default(), marker: PhantomData }
+    }
+}
+
+fn is_send_tick<T: Throttled>(tick: u32) -> bool {
+    SEND_ALL.load(Ordering::Relaxed) || tick % T::INTERVAL.max(1) == 0
+}
+
+/// Writes the same one byte marker as `serialize_masked`, followed by the value only on this component's ticks
+fn serialize_throttled<T: Throttled>(component: Ptr, cursor: &mut Cursor<Vec<u8>>) -> bincode::Result<()> {
+    let send = is_send_tick::<T>(SEND_TICK.load(Ordering::Relaxed));
+    DefaultOptions::new().serialize_into(&mut *cursor, &send)?;
+    if !send
+    {
+        return Ok(());
+    }
+
+    // SAFETY: replicon only calls this with a pointer to the registered component type
+    let component = unsafe { component.deref::<T>() };
+    DefaultOptions::new().serialize_into(cursor, component)
+}
+
+fn flush_on_connect_system(
+    mut server_events: EventReader<ServerEvent>,
+    mut send_all: ResMut<SendAllNext>,
+) {
+    if server_events.read().any(|event| matches!(event, ServerEvent::ClientConnected { .. }))
+    {
+        send_all.0 = true;
+    }
+}
+
+fn collect_changes_system<T: Throttled>(
+    mut pending: ResMut<PendingChanges<T>>,
+    mut send_all: ResMut<SendAllNext>,
+    changed: Query<(Entity, Ref<T>), Changed<T>>,
+) {
+    for (entity, component) in &changed
+    {
+        if component.is_added()
+        {
+            send_all.0 = true;
+        }
+        pending.entities.insert(entity);
+    }
+}
+
+/// Publishes what the serialize functions need to know about the tick about to be sent
+fn decide_send_system(
+    mut send_all: ResMut<SendAllNext>,
+    mut last_tick: Local<Option<RepliconTick>>,
+    tick: Res<RepliconTick>,
+) {
+    // Replicon only sends on frames where `send_rate_system` moved the tick on
+    if *last_tick == Some(*tick)
+    {
+        return;
+    }
+    *last_tick = Some(*tick);
+
+    SEND_TICK.store(tick.get(), Ordering::Relaxed);
+    SEND_ALL.store(send_all.0, Ordering::Relaxed);
+    send_all.0 = false;
+}
+
+/// On this component's ticks, marks everything that changed since it was last sent as changed again
+///
+/// Replicon saw those changes on the ticks in between, but only sent the marker, so they have to be sent again.
+fn resend_pending_system<T: Throttled>(
+    mut pending: ResMut<PendingChanges<T>>,
+    mut components: Query<&mut T>,
+    mut last_tick: Local<Option<RepliconTick>>,
+    tick: Res<RepliconTick>,
+) {
+    if *last_tick == Some(*tick) || !is_send_tick::<T>(tick.get())
+    {
+        return;
+    }
+    *last_tick = Some(*tick);
+
+    for entity in pending.entities.drain()
+    {
+        if let Ok(mut component) = components.get_mut(entity)
+        {
+            component.set_changed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{Player, bench::{step_session, tests::connected_session}, server_info::ServerInfo, stats::PlayerStats};
+
+    /// Frames in which the client's copy of `T` changed, given the value it had last frame
+    fn changed<T: Component + Clone + PartialEq>(client_app: &mut App, last: &mut Option<T>) -> u32 {
+        let current = client_app.world.query::<&T>().get_single(&client_app.world).ok().cloned();
+        let changed = current.is_some() && *last != current;
+        *last = current;
+        changed as u32
+    }
+
+    #[test]
+    fn each_throttled_component_goes_out_at_its_own_interval_and_catches_up() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let start_tick = server_app.world.resource::<RepliconTick>().get();
+
+        // Both change every frame, far more often than either is sent
+        let (mut stats_changes, mut info_changes) = (0, 0);
+        let (mut last_stats, mut last_info) = (None, None);
+        for frame in 0..240
+        {
+            server_app.world.query_filtered::<&mut PlayerStats, With<Player>>().single_mut(&mut server_app.world).hits += 1;
+            server_app.world.query::<&mut ServerInfo>().single_mut(&mut server_app.world).map = format!("map {frame}");
+            step_session(&mut server_app, &mut client_apps);
+            stats_changes += changed::<PlayerStats>(&mut client_apps[0], &mut last_stats);
+            info_changes += changed::<ServerInfo>(&mut client_apps[0], &mut last_info);
+        }
+
+        let ticks = server_app.world.resource::<RepliconTick>().get() - start_tick;
+        let expected_stats = ticks / PlayerStats::INTERVAL;
+        let expected_info = ticks / ServerInfo::INTERVAL;
+        assert!(expected_info >= 3, "only {ticks} ticks were sent");
+        assert!(stats_changes.abs_diff(expected_stats) <= 1, "stats changed {stats_changes} times in {ticks} ticks");
+        assert!(info_changes.abs_diff(expected_info) <= 1, "server info changed {info_changes} times in {ticks} ticks");
+
+        // The last changes were held back, and go out on each component's next interval
+        for _ in 0..ServerInfo::INTERVAL * 3
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        let server_stats = *server_app.world.query_filtered::<&PlayerStats, With<Player>>().single(&server_app.world);
+        let server_info = server_app.world.query::<&ServerInfo>().single(&server_app.world).clone();
+        assert_eq!(client_apps[0].world.query::<&PlayerStats>().single(&client_apps[0].world), &server_stats);
+        assert_eq!(client_apps[0].world.query::<&ServerInfo>().single(&client_apps[0].world), &server_info);
+    }
+}
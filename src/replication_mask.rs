@@ -0,0 +1,193 @@
+use std::{io::Cursor, sync::atomic::{AtomicU8, Ordering}};
+
+use bevy::{prelude::*, ptr::Ptr};
+use bevy_replicon::prelude::*;
+use bincode::{DefaultOptions, Options};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{MoveDirection, PlayerSpawnedComponent, Position, console::ConsoleCommand};
+
+/// The mask as replicon's serialize functions see it, they're plain `fn`s with no access to resources
+static SUPPRESSED: AtomicU8 = AtomicU8::new(0);
+
+/// Server side switch for measuring what each component costs, `repl off position` in the console stops sending it
+pub struct ReplicationMaskPlugin;
+
+impl Plugin for ReplicationMaskPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ReplicationMask>()
+            .add_systems(Update,
+                (
+                    replication_mask_console_system,
+                    resend_on_enable_system::<Position>,
+                    resend_on_enable_system::<MoveDirection>,
+                    resend_on_enable_system::<PlayerSpawnedComponent>,
+                ).chain().run_if(resource_exists::<RenetServer>())
+            );
+    }
+}
+
+/// Replicated components that can be switched off at runtime
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MaskedComponent
+{
+    Position,
+    MoveDirection,
+    PlayerSpawned,
+}
+
+impl MaskedComponent
+{
+    const ALL: [MaskedComponent; 3] = [MaskedComponent::Position, MaskedComponent::MoveDirection, MaskedComponent::PlayerSpawned];
+
+    fn bit(self) -> u8 {
+        match self
+        {
+            MaskedComponent::Position => 1 << 0,
+            MaskedComponent::MoveDirection => 1 << 1,
+            MaskedComponent::PlayerSpawned => 1 << 2,
+        }
+    }
+
+    fn console_name(self) -> &'static str {
+        match self
+        {
+            MaskedComponent::Position => "position",
+            MaskedComponent::MoveDirection => "movedirection",
+            MaskedComponent::PlayerSpawned => "playerspawned",
+        }
+    }
+}
+
+/// Components whose replication goes through `serialize_masked`
+pub trait Masked: Component
+{
+    const MASK: MaskedComponent;
+}
+
+impl Masked for Position
+{
+    const MASK: MaskedComponent = MaskedComponent::Position;
+}
+
+impl Masked for MoveDirection
+{
+    const MASK: MaskedComponent = MaskedComponent::MoveDirection;
+}
+
+impl Masked for PlayerSpawnedComponent
+{
+    const MASK: MaskedComponent = MaskedComponent::PlayerSpawned;
+}
+
+/// Which components are currently suppressed
+#[derive(Resource, Default)]
+pub struct ReplicationMask(u8);
+
+impl ReplicationMask
+{
+    pub fn set_suppressed(&mut self, component: MaskedComponent, suppressed: bool) {
+        if suppressed
+        {
+            self.0 |= component.bit();
+        }
+        else
+        {
+            self.0 &= !component.bit();
+        }
+        SUPPRESSED.store(self.0, Ordering::Relaxed);
+    }
+
+    pub fn is_suppressed(&self, component: MaskedComponent) -> bool {
+        self.0 & component.bit() != 0
+    }
+}
+
+/// For custom serializers of masked components, whether to write the suppressed marker instead of the value
+pub fn is_suppressed(component: MaskedComponent) -> bool {
+    SUPPRESSED.load(Ordering::Relaxed) & component.bit() != 0
+}
+
+/// Writes a one byte marker, followed by the value only if the component isn't suppressed
+pub fn serialize_masked<T: Masked + Serialize>(component: Ptr, cursor: &mut Cursor<Vec<u8>>) -> bincode::Result<()> {
+    let suppressed = is_suppressed(T::MASK);
+    DefaultOptions::new().serialize_into(&mut *cursor, &!suppressed)?;
+    if suppressed
+    {
+        return Ok(());
+    }
+
+    // SAFETY: replicon only calls this with a pointer to the registered component type
+    let component = unsafe { component.deref::<T>() };
+    DefaultOptions::new().serialize_into(cursor, component)
+}
+
+/// Reads what `serialize_masked` wrote, a suppressed update leaves the previous value in place
+pub fn deserialize_masked<T: Component + DeserializeOwned>(
+    entity: &mut EntityWorldMut,
+    _entity_map: &mut ServerEntityMap,
+    cursor: &mut Cursor<&[u8]>,
+    _tick: RepliconTick,
+) -> bincode::Result<()> {
+    let present: bool = DefaultOptions::new().deserialize_from(&mut *cursor)?;
+    if present
+    {
+        let component: T = DefaultOptions::new().deserialize_from(cursor)?;
+        entity.insert(component);
+    }
+
+    Ok(())
+}
+
+/// Handles `repl on <component>` and `repl off <component>`
+fn replication_mask_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut mask: ResMut<ReplicationMask>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "repl"
+        {
+            continue;
+        }
+
+        let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+        let (suppressed, name) = match args.as_slice()
+        {
+            [_, "off", name] => (true, *name),
+            [_, "on", name] => (false, *name),
+            _ =>
+            {
+                warn!("Server: Usage: repl on|off position|movedirection|playerspawned");
+                continue;
+            }
+        };
+
+        let Some(component) = MaskedComponent::ALL.into_iter().find(|masked| masked.console_name() == name) else {
+            warn!("Server: Unknown component '{name}', expected position, movedirection or playerspawned");
+            continue;
+        };
+
+        mask.set_suppressed(component, suppressed);
+        info!("Server: Replication of {component:?} {}", if suppressed { "suppressed" } else { "resumed" });
+    }
+}
+
+/// Marks every `T` changed when it's switched back on, clients missed every change while it was off
+fn resend_on_enable_system<T: Masked>(
+    mask: Res<ReplicationMask>,
+    mut was_suppressed: Local<bool>,
+    mut components: Query<&mut T>,
+) {
+    let suppressed = mask.is_suppressed(T::MASK);
+    if *was_suppressed && !suppressed
+    {
+        for mut component in &mut components
+        {
+            component.set_changed();
+        }
+    }
+    *was_suppressed = suppressed;
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::{ClientId, SendType, transport::NetcodeClientTransport}};
+use serde::{Serialize, Deserialize};
+
+use crate::{Cli, Player, PlayerInput, audit::AuditFailures, input_map::{Action, InputBindings}, reconnect::{self, ClientIdentity}};
+
+/// Seconds between resyncs the server grants any one client
+const RESYNC_COOLDOWN: f32 = 30.0;
+
+/// Lets a client whose view has drifted (a missed despawn, an audit failure) ask for the whole world again,
+/// with F12 or automatically when its `--audit` finds a problem
+///
+/// Replicon has no way to reset one client's acknowledged ticks, so a granted resync is a new session: the client
+/// reconnects with its identity and gets its player back, `reset_on_new_session_system` clears its replicated
+/// entities and counters, and the server sends the full world as it would to any new client.
+pub struct ResyncPlugin;
+
+impl Plugin for ResyncPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ResyncRequests>()
+            .add_server_event::<ResyncGranted>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_systems(Update, receive_resync_requests_system.run_if(resource_exists::<RenetServer>()))
+            .add_systems(Update,
+                (
+                    request_resync_system,
+                    start_resync_system,
+                ).run_if(resource_exists::<RenetClient>())
+            );
+    }
+}
+
+/// Sent to a client whose `PlayerInput::RequestResync` was accepted, it reconnects on receiving it
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ResyncGranted;
+
+/// Server side, for each client (by identity, which survives the reconnect) the elapsed seconds of its last
+/// granted resync and how many it has asked for
+#[derive(Resource, Default)]
+pub struct ResyncRequests(HashMap<u64, (f32, u32)>);
+
+impl ResyncRequests
+{
+    /// Counts a resync for the client with `key`, granting it unless the last one was under `RESYNC_COOLDOWN` ago
+    ///
+    /// Returns how many it has asked for, or when refused, the seconds since the last one granted.
+    pub fn try_grant(&mut self, key: u64, now: f32) -> Result<u32, f32> {
+        let (last_granted, count) = self.0.entry(key).or_insert((f32::NEG_INFINITY, 0));
+        *count += 1;
+        if now - *last_granted < RESYNC_COOLDOWN
+        {
+            return Err(now - *last_granted);
+        }
+
+        *last_granted = now;
+        Ok(*count)
+    }
+}
+
+/// Sends a resync request on the resync key (F12), or when the audit finds new failures, at most once per `RESYNC_COOLDOWN`
+fn request_resync_system(
+    mut inputs: EventWriter<PlayerInput>,
+    mut last_failures: Local<u64>,
+    mut last_request: Local<Option<f32>>,
+    client: Res<RenetClient>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    failures: Res<AuditFailures>,
+    time: Res<Time>,
+) {
+    if !client.is_connected()
+    {
+        return;
+    }
+
+    let new_failures = failures.0 > *last_failures;
+    *last_failures = failures.0;
+
+    let reason = if bindings.just_pressed(Action::Resync, &keys) { "resync key pressed" } else if new_failures { "audit failed" } else { return; };
+
+    // The server would refuse it anyway
+    let now = time.elapsed_seconds();
+    if last_request.is_some_and(|last| now - last < RESYNC_COOLDOWN)
+    {
+        info!("Client: Not requesting a resync ({reason}), the last one was under {RESYNC_COOLDOWN} seconds ago");
+        return;
+    }
+    *last_request = Some(now);
+
+    info!("Client: Requesting a resync, {reason}");
+    inputs.send(PlayerInput::RequestResync);
+}
+
+fn receive_resync_requests_system(
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    mut grants: EventWriter<ToClients<ResyncGranted>>,
+    mut requests: ResMut<ResyncRequests>,
+    players: Query<(&Player, Option<&ClientIdentity>)>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for FromClient { client_id, event } in input_reader.read()
+    {
+        if !matches!(event, PlayerInput::RequestResync) || *client_id == SERVER_ID
+        {
+            continue;
+        }
+
+        match requests.try_grant(resync_key(&players, *client_id), now)
+        {
+            Ok(count) =>
+            {
+                info!("Server: Client '{client_id}' requested resync {count}, it will reconnect for the full world");
+                grants.send(ToClients { mode: SendMode::Direct(*client_id), event: ResyncGranted });
+            }
+            Err(since) => warn!("Server: Refused a resync request from client '{client_id}', the last one was {since:.1} seconds ago"),
+        }
+    }
+}
+
+/// What `ResyncRequests` knows a client by, its identity if it has one
+pub fn resync_key(
+    players: &Query<(&Player, Option<&ClientIdentity>)>,
+    client_id: ClientId,
+) -> u64 {
+    let identity = players.iter()
+        .find(|(player, _)| ClientId::from_raw(player.0) == client_id)
+        .and_then(|(_, identity)| identity.copied());
+    identity.map_or(client_id.raw(), |identity| identity.0)
+}
+
+/// Drops the connection and opens a new one straight away, the new session starts from a cleared world
+fn start_resync_system(
+    mut commands: Commands,
+    mut grants: EventReader<ResyncGranted>,
+    transport: Option<ResMut<NetcodeClientTransport>>,
+    cli: Res<Cli>,
+    network_channels: Res<NetworkChannels>,
+) {
+    if grants.read().count() == 0
+    {
+        return;
+    }
+
+    info!("Client: Resync granted, reconnecting");
+    // Tells the server now, instead of leaving it to time the old connection out
+    if let Some(mut transport) = transport
+    {
+        transport.disconnect();
+    }
+    reconnect::connect_again(&mut commands, &cli, &network_channels);
+}
@@ -0,0 +1,95 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
+
+/// Half-size of the square area players are respawned in
+const SPAWN_HALF_EXTENT: f32 = 200.0;
+
+/// Randomness the server and clients can reproduce, seeded from the replicated `RngSeed`
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate::<RngSeed>()
+            .add_systems(PreUpdate, reseed_game_rng_system.after(ClientSet::Receive));
+    }
+}
+
+/// Lives on the game-state singleton entity, changing it (e.g. on round restart) reseeds every `GameRng`
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct RngSeed(pub u64);
+
+impl RngSeed
+{
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+#[derive(Resource)]
+pub struct GameRng(ChaCha8Rng);
+
+impl GameRng
+{
+    pub fn from_seed(seed: RngSeed) -> Self {
+        Self(ChaCha8Rng::seed_from_u64(seed.0))
+    }
+
+    pub fn next_f32_in(&mut self, range: Range<f32>) -> f32 {
+        self.0.gen_range(range)
+    }
+
+    pub fn next_spawn_point(&mut self) -> Vec2 {
+        let x = self.next_f32_in(-SPAWN_HALF_EXTENT..SPAWN_HALF_EXTENT);
+        let y = self.next_f32_in(-SPAWN_HALF_EXTENT..SPAWN_HALF_EXTENT);
+        Vec2::new(x, y)
+    }
+}
+
+/// Rebuilds `GameRng` whenever the seed arrives or changes, identically on the server and every client
+fn reseed_game_rng_system(
+    mut commands: Commands,
+    seeds: Query<&RngSeed, Changed<RngSeed>>,
+) {
+    let Ok(seed) = seeds.get_single() else { return; };
+
+    info!("Seeding GameRng with {}", seed.0);
+    commands.insert_resource(GameRng::from_seed(*seed));
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::bench::{step_session, tests::connected_session};
+
+    fn draw(app: &mut App) -> Vec<Vec2> {
+        let mut rng = app.world.resource_mut::<GameRng>();
+        (0..100).map(|_| rng.next_spawn_point()).collect()
+    }
+
+    #[test]
+    fn server_and_clients_draw_the_same_sequence_from_a_seed() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        // A new seed, as a round restart would set, reseeds everyone from the same point
+        *server_app.world.query::<&mut RngSeed>().single_mut(&mut server_app.world) = RngSeed(0x5eed);
+        for _ in 0..10
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let expected = draw(&mut server_app);
+        let mut fresh = GameRng::from_seed(RngSeed(0x5eed));
+        assert_eq!(expected, (0..100).map(|_| fresh.next_spawn_point()).collect::<Vec<_>>(), "the server's GameRng wasn't reseeded");
+        for client_app in &mut client_apps
+        {
+            assert_eq!(draw(client_app), expected);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::{prelude::*, network_event::client_event, renet::{ClientId, SendType, ServerEvent}, server::ClientsInfo};
+use serde::{Serialize, Deserialize};
+
+use crate::{Cli, LocalPlayerId, Player, console::ConsoleCommand, event_limits::{self, MAX_ROOM_REQUEST_BYTES}, server_info::sanitize};
+
+/// The room every player starts in, and the one the ball is in. It's never cleaned up
+pub const DEFAULT_ROOM: &str = "default";
+/// Longest room name kept, in characters
+pub const MAX_ROOM_NAME_CHARS: usize = 32;
+
+/// Several games on one server: players pick a room with `--room` or `room <name>`, and only see, hit and grab
+/// what's in theirs
+///
+/// The server hides every entity in another room from each client through replicon's per-client visibility, so a
+/// client is never sent them, and scopes hits, hitscan and the ball by `Room`. Entities without a `Room`, like the game
+/// state, are in every room. A listen-server host has every room in its own world, so there whatever is in another
+/// room than the host's player is marked `OtherRoom`, which hides it and keeps it off the minimap, scoreboard, labels
+/// and health bars. A room's leftovers are despawned once its last player leaves.
+pub struct RoomPlugin;
+
+impl Plugin for RoomPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Rooms>()
+            .replicate::<Room>()
+            .add_client_event_with::<RoomRequest, _, _>(
+                SendType::ReliableOrdered { resend_time: Duration::from_millis(300) },
+                client_event::sending_system::<RoomRequest>,
+                event_limits::limited_receiving_system::<RoomRequest, MAX_ROOM_REQUEST_BYTES>,
+            )
+            .add_systems(Update,
+                (
+                    receive_room_requests_system,
+                    move_players_system,
+                    room_visibility_system,
+                    clean_up_empty_rooms_system,
+                    list_rooms_console_system,
+                ).chain().run_if(resource_exists::<RenetServer>())
+            )
+            .add_systems(Update, request_room_system.run_if(resource_exists::<RenetClient>()))
+            .add_systems(Update, mark_other_rooms_system.run_if(has_authority()));
+    }
+}
+
+/// Which room an entity is in, an index into the server's `Rooms`
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Room(pub u32);
+
+/// What an entity's `Room` is for the purpose of who it meets, entities without one are in the default room
+pub fn room_of(room: Option<&Room>) -> Room {
+    room.copied().unwrap_or_default()
+}
+
+/// Sent by a client as it connects, and whenever it switches rooms
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct RoomRequest
+{
+    pub name: String,
+}
+
+/// Listen-server side, marks an entity in a room other than the host's player's
+#[derive(Component)]
+pub struct OtherRoom;
+
+/// Server side, every room anyone has asked for, by `Room` index, and which rooms each client wants to be in
+#[derive(Resource)]
+pub struct Rooms
+{
+    names: Vec<String>,
+    requested: HashMap<ClientId, Room>,
+    /// Rooms that had a player last frame
+    occupied: HashSet<Room>,
+}
+
+impl Default for Rooms
+{
+    fn default() -> Self {
+        Self { names: vec![DEFAULT_ROOM.to_string()], requested: HashMap::default(), occupied: HashSet::default() }
+    }
+}
+
+impl Rooms
+{
+    /// The room called `name` once sanitized, made on first use, an empty name is the default room
+    pub fn room(&mut self, name: &str) -> Room {
+        let name = room_name(name);
+        let index = self.names.iter().position(|existing| *existing == name).unwrap_or_else(|| {
+            self.names.push(name);
+            self.names.len() - 1
+        });
+        Room(index as u32)
+    }
+
+    pub fn name(&self, room: Room) -> &str {
+        self.names.get(room.0 as usize).map_or(DEFAULT_ROOM, String::as_str)
+    }
+}
+
+/// Drops control characters, lowercases, like console arguments, and truncates, an empty name being the default room
+pub fn room_name(name: &str) -> String {
+    let name = sanitize(name, MAX_ROOM_NAME_CHARS).to_lowercase();
+    if name.is_empty() { DEFAULT_ROOM.to_string() } else { name }
+}
+
+fn receive_room_requests_system(
+    mut requests: EventReader<FromClient<RoomRequest>>,
+    mut server_events: EventReader<ServerEvent>,
+    mut rooms: ResMut<Rooms>,
+) {
+    for FromClient { client_id, event } in requests.read()
+    {
+        let room = rooms.room(&event.name);
+        info!("Server: Client '{client_id}' asked for room '{}'", rooms.name(room));
+        rooms.requested.insert(*client_id, room);
+    }
+
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            rooms.requested.remove(client_id);
+        }
+    }
+}
+
+/// Puts each player in the room its client asked for, whenever its player exists, so a queued or reconnecting client
+/// ends up there too
+fn move_players_system(
+    mut players: Query<(&Player, &mut Room)>,
+    rooms: Res<Rooms>,
+) {
+    for (player, mut room) in &mut players
+    {
+        let Some(requested) = rooms.requested.get(&ClientId::from_raw(player.0)) else { continue; };
+        if *room != *requested
+        {
+            info!("Server: Player '{}' moved from room '{}' to '{}'", player.0, rooms.name(*room), rooms.name(*requested));
+            *room = *requested;
+        }
+    }
+}
+
+/// Shows each client only what's in the room it asked for, anything without a `Room` is left visible to everyone
+///
+/// Replicon despawns an entity on a client once it's hidden from it, and spawns it afresh once it's shown again.
+fn room_visibility_system(
+    mut clients: ResMut<ClientsInfo>,
+    rooms: Res<Rooms>,
+    entities: Query<(Entity, &Room)>,
+) {
+    for client in clients.iter_mut()
+    {
+        let client_room = rooms.requested.get(&client.id()).copied().unwrap_or_default();
+        let visibility = client.visibility_mut();
+        for (entity, room) in &entities
+        {
+            let visible = *room == client_room;
+            if visibility.is_visible(entity) != visible
+            {
+                visibility.set_visibility(entity, visible);
+            }
+        }
+    }
+}
+
+/// Despawns what's left in a room once its last player has left it, the default room is never emptied
+fn clean_up_empty_rooms_system(
+    mut commands: Commands,
+    mut rooms: ResMut<Rooms>,
+    players: Query<&Room, With<Player>>,
+    leftovers: Query<(Entity, &Room), Without<Player>>,
+) {
+    let occupied: HashSet<Room> = players.iter().copied().collect();
+    let emptied: Vec<Room> = rooms.occupied.difference(&occupied).copied().filter(|room| *room != Room::default()).collect();
+    rooms.occupied = occupied;
+
+    for room in emptied
+    {
+        let mut despawned = 0;
+        for (entity, _) in leftovers.iter().filter(|(_, entity_room)| **entity_room == room)
+        {
+            commands.entity(entity).despawn();
+            despawned += 1;
+        }
+        info!("Server: Room '{}' is empty, despawned its {despawned} remaining entities", rooms.name(room));
+    }
+}
+
+/// `room` on the server lists the rooms with players in them
+fn list_rooms_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    rooms: Res<Rooms>,
+    players: Query<&Room, With<Player>>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "room"
+        {
+            continue;
+        }
+
+        let mut counts: HashMap<Room, usize> = HashMap::default();
+        for room in &players
+        {
+            *counts.entry(*room).or_default() += 1;
+        }
+        let mut listed: Vec<String> = counts.into_iter().map(|(room, count)| format!("'{}' ({count})", rooms.name(room))).collect();
+        listed.sort();
+        info!("Server: Rooms with players: {}", listed.join(", "));
+    }
+}
+
+/// Asks for the chosen room on connecting, and again on every reconnect since the server forgets a client's room when
+/// it disconnects. `room <name>` switches to another, `--room` being the first choice
+fn request_room_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut requests: EventWriter<RoomRequest>,
+    mut chosen: Local<Option<String>>,
+    mut was_connected: Local<bool>,
+    client: Res<RenetClient>,
+    cli: Res<Cli>,
+) {
+    let connected = client.is_connected();
+    if connected && !*was_connected
+    {
+        if chosen.is_none()
+        {
+            *chosen = cli.room().map(room_name);
+        }
+        if let Some(name) = chosen.clone()
+        {
+            requests.send(RoomRequest { name });
+        }
+    }
+    *was_connected = connected;
+
+    for command in console.read()
+    {
+        if command.name() != "room"
+        {
+            continue;
+        }
+        let Some(name) = command.args.get(1) else {
+            warn!("Usage: room <name>");
+            continue;
+        };
+
+        let name = room_name(name);
+        info!("Client: Switching to room '{name}'");
+        *chosen = Some(name.clone());
+        if connected
+        {
+            requests.send(RoomRequest { name });
+        }
+    }
+}
+
+/// Marks and hides everything in another room than the host's player, and unmarks it when they share a room again
+fn mark_other_rooms_system(
+    mut commands: Commands,
+    mut entities: Query<(Entity, &Room, Has<OtherRoom>, Option<&mut Visibility>)>,
+    players: Query<(&Player, &Room)>,
+    local_player: Option<Res<LocalPlayerId>>,
+) {
+    // A dedicated server has no player of its own, and shows every room
+    let Some(local_player) = local_player else { return; };
+    let Some(local_room) = players.iter().find_map(|(player, room)| (player.0 == local_player.0).then_some(*room)) else { return; };
+
+    for (entity, room, marked, visibility) in &mut entities
+    {
+        let other = *room != local_room;
+        if other && !marked
+        {
+            commands.entity(entity).insert(OtherRoom);
+        }
+        else if !other && marked
+        {
+            commands.entity(entity).remove::<OtherRoom>();
+            if let Some(mut visibility) = visibility
+            {
+                *visibility = Visibility::Inherited;
+            }
+            continue;
+        }
+
+        // Sprites are attached a frame or so after the entity arrives, so this keeps hiding them until they are
+        if let Some(mut visibility) = visibility.filter(|_| other)
+        {
+            visibility.set_if_neq(Visibility::Hidden);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{EntityKind, bench::{step_session, tests::connected_session}};
+
+    fn step(server_app: &mut App, client_apps: &mut [App], frames: usize) {
+        for _ in 0..frames
+        {
+            step_session(server_app, client_apps);
+        }
+    }
+
+    /// The players a client has been sent
+    fn seen_players(client_app: &mut App) -> Vec<u64> {
+        let mut seen: Vec<u64> = client_app.world.query::<&Player>().iter(&client_app.world).map(|player| player.0).collect();
+        seen.sort();
+        seen
+    }
+
+    #[test]
+    fn room_names_are_cleaned_up() {
+        assert_eq!(room_name("  Red\n"), "red");
+        assert_eq!(room_name(""), DEFAULT_ROOM);
+        assert_eq!(room_name(&"x".repeat(40)).len(), MAX_ROOM_NAME_CHARS);
+
+        let mut rooms = Rooms::default();
+        assert_eq!(rooms.room("default"), Room::default());
+        let red = rooms.room("red");
+        assert_eq!(rooms.room("RED"), red);
+        assert_eq!(rooms.name(red), "red");
+    }
+
+    #[test]
+    fn clients_in_different_rooms_dont_see_each_other() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        client_apps[0].world.send_event(RoomRequest { name: "red".to_string() });
+        client_apps[1].world.send_event(RoomRequest { name: "blue".to_string() });
+        step(&mut server_app, &mut client_apps, 20);
+
+        let server_rooms: HashMap<u64, Room> = server_app.world.query::<(&Player, &Room)>().iter(&server_app.world)
+            .map(|(player, room)| (player.0, *room))
+            .collect();
+        assert_ne!(server_rooms[&1], server_rooms[&2]);
+        // The server never sent either of them the other's player
+        assert_eq!(seen_players(&mut client_apps[0]), [1]);
+        assert_eq!(seen_players(&mut client_apps[1]), [2]);
+
+        // Switching rooms brings them together
+        client_apps[1].world.send_event(RoomRequest { name: "red".to_string() });
+        step(&mut server_app, &mut client_apps, 20);
+        assert_eq!(seen_players(&mut client_apps[0]), [1, 2]);
+        assert_eq!(seen_players(&mut client_apps[1]), [1, 2]);
+    }
+
+    #[test]
+    fn an_emptied_room_is_cleaned_up() {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        client_apps[0].world.send_event(RoomRequest { name: "red".to_string() });
+        step(&mut server_app, &mut client_apps, 20);
+
+        let red = server_app.world.resource_mut::<Rooms>().room("red");
+        let leftover = server_app.world.spawn((EntityKind::Effect, red, Replication)).id();
+        let shared = server_app.world.spawn((EntityKind::Effect, Room::default(), Replication)).id();
+        step(&mut server_app, &mut client_apps, 2);
+        assert!(server_app.world.get_entity(leftover).is_some());
+
+        client_apps[0].world.send_event(RoomRequest { name: DEFAULT_ROOM.to_string() });
+        step(&mut server_app, &mut client_apps, 20);
+        assert!(server_app.world.get_entity(leftover).is_none());
+        assert!(server_app.world.get_entity(shared).is_some());
+    }
+}
@@ -0,0 +1,119 @@
+use std::cmp::Reverse;
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{LocalPlayerId, Player, UiReady, input_map::{Action, InputBindings}, is_headless, reliable_replication::ReplicateReliableExt, room::OtherRoom, stats::PlayerStats};
+
+/// Kills and deaths per player, listed while Tab is held
+pub struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate_reliable::<Score>()
+            .add_systems(Startup, spawn_scoreboard_system.run_if(not(is_headless)))
+            .add_systems(Update, (toggle_scoreboard_system, update_scoreboard_system).run_if(not(is_headless)).run_if(resource_exists::<UiReady>()));
+    }
+}
+
+/// Kills this player has scored, only ever changed by the server
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Score(pub u32);
+
+#[derive(Component)]
+pub struct ScoreboardText;
+
+/// Highest score first, ties broken by player id
+///
+/// Query iteration order changes as entities are spawned and despawned, so without the id
+/// players on equal scores would swap places between rebuilds.
+pub fn sort_scoreboard(entries: &mut [(u64, Score)]) {
+    entries.sort_by_key(|(id, score)| (Reverse(score.0), *id));
+}
+
+fn spawn_scoreboard_system(
+    mut commands: Commands,
+) {
+    let mut scoreboard = TextBundle::default().with_style(Style {
+        position_type: PositionType::Absolute, right: Val::Percent(40.0), top: Val::Percent(30.0), ..default()
+    });
+    scoreboard.visibility = Visibility::Hidden;
+    commands.spawn((scoreboard, ScoreboardText));
+}
+
+fn toggle_scoreboard_system(
+    mut scoreboards: Query<&mut Visibility, With<ScoreboardText>>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    let Ok(mut visibility) = scoreboards.get_single_mut() else { return; };
+    let shown = if bindings.pressed(Action::Scoreboard, &input) { Visibility::Inherited } else { Visibility::Hidden };
+    if *visibility != shown
+    {
+        *visibility = shown;
+    }
+}
+
+/// Rebuilds the list of this room's players whenever a score changes, a player joins or leaves or someone switches
+/// rooms, with this machine's player in yellow
+fn update_scoreboard_system(
+    mut scoreboards: Query<&mut Text, With<ScoreboardText>>,
+    mut removed: RemovedComponents<Score>,
+    players: Query<(&Player, &Score, Option<&PlayerStats>), Without<OtherRoom>>,
+    changed: Query<(), Or<(Changed<Score>, Changed<PlayerStats>, Added<OtherRoom>)>>,
+    mut rejoined: RemovedComponents<OtherRoom>,
+    local_player: Option<Res<LocalPlayerId>>,
+    mut drawn: Local<bool>,
+) {
+    // Scores that changed before the UI was ready are picked up by the first draw
+    let removed_any = removed.read().count() + rejoined.read().count() > 0;
+    if *drawn && changed.is_empty() && !removed_any
+    {
+        return;
+    }
+    let Ok(mut text) = scoreboards.get_single_mut() else { return; };
+    *drawn = true;
+
+    let mut entries: Vec<(u64, Score)> = players.iter().map(|(player, score, _)| (player.0, *score)).collect();
+    sort_scoreboard(&mut entries);
+    let deaths = |id: u64| players.iter().find(|(player, ..)| player.0 == id).and_then(|(_, _, stats)| stats).map_or(0, |stats| stats.deaths);
+
+    // Highlighting only changes the colour, the local player keeps their place in the order
+    let local_id = local_player.map(|local_player| local_player.0);
+    text.sections = entries.into_iter()
+        .map(|(id, score)| {
+            let color = if Some(id) == local_id { Color::YELLOW } else { Color::WHITE };
+            TextSection::new(format!("{id}: {} / {}\n", score.0, deaths(id)), TextStyle { font_size: 24.0, color, ..default() })
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn equal_scores_keep_one_order_whatever_order_they_arrive_in() {
+        let entries = [(7, Score(2)), (3, Score(2)), (5, Score(4)), (1, Score(0))];
+        let mut expected = entries;
+        sort_scoreboard(&mut expected);
+        assert_eq!(expected, [(5, Score(4)), (3, Score(2)), (7, Score(2)), (1, Score(0))]);
+
+        // Every rotation and its reverse, standing in for query iteration order changing between rebuilds
+        for rotation in 0..entries.len()
+        {
+            let mut rotated = entries;
+            rotated.rotate_left(rotation);
+            sort_scoreboard(&mut rotated);
+            assert_eq!(rotated, expected);
+
+            rotated.reverse();
+            sort_scoreboard(&mut rotated);
+            assert_eq!(rotated, expected);
+        }
+    }
+}
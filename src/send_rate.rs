@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use bevy_replicon::{prelude::*, server::ServerSet};
+
+use crate::{player_state::PlayerState, settings::GameSettings};
+
+/// Sends replication at `GameSettings::replication_rate` instead of on every simulation step
+///
+/// Replicon is configured with `TickPolicy::Manual`, so the server only sends when `send_rate_system` increments the `RepliconTick`.
+pub struct SendRatePlugin;
+
+impl Plugin for SendRatePlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SendAccumulator>()
+            .add_systems(PostUpdate,
+                send_rate_system
+                    .before(ServerSet::Send)
+                    .run_if(resource_exists::<RenetServer>())
+            );
+    }
+}
+
+/// Seconds since the last replication send
+#[derive(Resource, Default)]
+pub struct SendAccumulator(pub f32);
+
+pub fn send_rate_system(
+    mut accumulator: ResMut<SendAccumulator>,
+    mut tick: ResMut<RepliconTick>,
+    time: Res<Time>,
+    settings: Query<&GameSettings>,
+    spawned: Query<(), Added<Replication>>,
+    state_changes: Query<(), Changed<PlayerState>>,
+) {
+    let replication_rate = settings.get_single().copied().unwrap_or_default().replication_rate;
+    let interval = 1.0 / replication_rate;
+
+    accumulator.0 += time.delta_seconds();
+
+    // Spawns and deaths go out right away rather than waiting for the next scheduled send
+    let one_shot_pending = !spawned.is_empty() || !state_changes.is_empty();
+    if accumulator.0 < interval && !one_shot_pending
+    {
+        return;
+    }
+
+    // Carry the remainder so the average rate stays on target, but don't let a long stall turn into a burst
+    accumulator.0 = (accumulator.0 - interval).clamp(0.0, interval);
+    tick.increment();
+}
+
+#[cfg(test)]
+mod tests
+{
+    use clap::Parser;
+
+    use super::*;
+    use crate::{Cli, SIMULATION_RATE, bench::{step_session, tests::connected_session_with}, tick_timer::SimulationTick};
+
+    #[test]
+    fn replication_goes_out_at_its_own_rate_while_the_simulation_steps_faster() {
+        let cli = Cli::parse_from(["replicon_test_1", "bench", "--replication-rate", "20"]);
+        let (mut server_app, mut client_apps) = connected_session_with(&cli, 1);
+        let sends_before = server_app.world.resource::<RepliconTick>().get();
+        let steps_before = server_app.world.resource::<SimulationTick>().0;
+
+        // Nobody spawns or dies meanwhile, so nothing is sent early
+        for _ in 0..2 * SIMULATION_RATE as u32
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let sends = server_app.world.resource::<RepliconTick>().get() - sends_before;
+        let steps = server_app.world.resource::<SimulationTick>().since(steps_before);
+        assert!(steps >= 2 * SIMULATION_RATE as u32 - 1, "only {steps} simulation steps in 2 seconds");
+        assert!((39..=41).contains(&sends), "{sends} sends in 2 seconds at 20 a second");
+    }
+}
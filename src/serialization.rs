@@ -0,0 +1,290 @@
+use std::{io::Cursor, sync::{Mutex, PoisonError, atomic::{AtomicU16, Ordering}}};
+
+use bevy::{prelude::*, ptr::Ptr};
+use bevy_replicon::{prelude::*, replicon_core::replication_rules::remove_component};
+use bincode::{DefaultOptions, Options};
+
+use crate::{PlayerSpawnedComponent, Position, replication_mask::{self, MaskedComponent}};
+
+/// Version of every custom component encoding in this module, written as the first byte of each payload
+///
+/// Bump this whenever an encoding changes. It also feeds the netcode protocol id,
+/// so peers with different encoders are rejected at handshake instead of misparsing each other.
+pub const FORMAT_VERSION: u8 = 4;
+
+/// Default for `--position-scale`, sixteenths of a world unit reach about 2000 units from the origin
+pub const DEFAULT_POSITION_SCALE: u16 = 16;
+
+/// Steps per world unit `Position` is quantized to, a static because replicon's serialize functions are plain `fn`s
+///
+/// Shared by every app in the process, see `set_position_scale`.
+static POSITION_SCALE: AtomicU16 = AtomicU16::new(DEFAULT_POSITION_SCALE);
+
+/// The scale the servers `run_server` has running use, so a second one can't change it under the first
+static SERVER_SCALES: Mutex<ScaleClaims> = Mutex::new(ScaleClaims { scale: DEFAULT_POSITION_SCALE, holders: 0 });
+
+/// Custom wire formats for components that don't suit the default bincode encoding
+pub struct SerializationPlugin;
+
+impl Plugin for SerializationPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate_with::<Position>(
+                serialize_position,
+                deserialize_position,
+                remove_component::<Position>,
+            )
+            .replicate_with::<PlayerSpawnedComponent>(
+                serialize_player_spawned,
+                deserialize_player_spawned,
+                remove_component::<PlayerSpawnedComponent>,
+            );
+    }
+}
+
+pub fn write_version(cursor: &mut Cursor<Vec<u8>>) -> bincode::Result<()> {
+    DefaultOptions::new().serialize_into(cursor, &FORMAT_VERSION)
+}
+
+/// Reads the version byte, rejecting payloads from an encoder this build doesn't know
+pub fn read_version(cursor: &mut Cursor<&[u8]>) -> bincode::Result<()> {
+    let version: u8 = DefaultOptions::new().deserialize_from(&mut *cursor)?;
+    if version != FORMAT_VERSION
+    {
+        error!("Received component encoded with format version {version}, this build only understands version {FORMAT_VERSION}");
+        return Err(Box::new(bincode::ErrorKind::Custom(format!("unknown component format version {version}"))));
+    }
+
+    Ok(())
+}
+
+/// Sets the scale both ends quantize `Position` with, they must agree so it's part of the protocol id
+///
+/// This is for the whole process, so apps built side by side in it can't use different scales.
+pub fn set_position_scale(scale: u16) {
+    POSITION_SCALE.store(scale, Ordering::Relaxed);
+}
+
+struct ScaleClaims
+{
+    scale: u16,
+    holders: usize,
+}
+
+impl ScaleClaims
+{
+    /// Fails with the scale in use when anyone still holds a different one
+    fn claim(&mut self, scale: u16) -> Result<(), u16> {
+        if self.holders > 0 && self.scale != scale
+        {
+            return Err(self.scale);
+        }
+
+        self.scale = scale;
+        self.holders += 1;
+        Ok(())
+    }
+
+    fn release(&mut self) {
+        self.holders -= 1;
+    }
+}
+
+/// Held by an embedded server for as long as it runs, see `claim_position_scale`
+pub struct PositionScaleClaim;
+
+impl Drop for PositionScaleClaim
+{
+    fn drop(&mut self) {
+        SERVER_SCALES.lock().unwrap_or_else(PoisonError::into_inner).release();
+    }
+}
+
+/// Reserves `scale` for a server about to be built in this process, failing with the scale in use if another one
+/// still running was started with a different one
+pub fn claim_position_scale(scale: u16) -> Result<PositionScaleClaim, u16> {
+    SERVER_SCALES.lock().unwrap_or_else(PoisonError::into_inner).claim(scale)?;
+    Ok(PositionScaleClaim)
+}
+
+/// `Position` as it's sent, so both ends can compare what the client was actually given
+pub fn wire_position(position: Vec2) -> [i16; 2] {
+    quantize_position(position, POSITION_SCALE.load(Ordering::Relaxed))
+}
+
+/// Fixed-point coordinates, positions further out than `i16` reaches are clamped to the edge
+fn quantize_position(position: Vec2, scale: u16) -> [i16; 2] {
+    let quantize = |value: f32| (value * scale as f32).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    [quantize(position.x), quantize(position.y)]
+}
+
+fn dequantize_position(quantized: [i16; 2], scale: u16) -> Vec2 {
+    Vec2::new(quantized[0] as f32, quantized[1] as f32) / scale as f32
+}
+
+/// Mostly-zero arrays are sent as (index, value) pairs of their non-zero entries
+fn write_sparse(cursor: &mut Cursor<Vec<u8>>, values: &[u64]) -> bincode::Result<()> {
+    let non_zero: Vec<(u8, u64)> = values.iter()
+        .enumerate()
+        .filter(|(_, value)| **value != 0)
+        .map(|(index, value)| (index as u8, *value))
+        .collect();
+    DefaultOptions::new().serialize_into(cursor, &non_zero)
+}
+
+fn read_sparse(cursor: &mut Cursor<&[u8]>, values: &mut [u64]) -> bincode::Result<()> {
+    let non_zero: Vec<(u8, u64)> = DefaultOptions::new().deserialize_from(&mut *cursor)?;
+    for (index, value) in non_zero
+    {
+        let Some(slot) = values.get_mut(index as usize) else {
+            return Err(Box::new(bincode::ErrorKind::Custom(format!("sparse index {index} out of range"))));
+        };
+        *slot = value;
+    }
+
+    Ok(())
+}
+
+/// Sends `Position` as two `i16`s instead of two `f32`s, off by at most half a step
+///
+/// The server keeps simulating at full precision, only what goes on the wire is rounded.
+fn serialize_position(component: Ptr, cursor: &mut Cursor<Vec<u8>>) -> bincode::Result<()> {
+    // SAFETY: replicon only calls this with a pointer to the registered component type
+    let component = unsafe { component.deref::<Position>() };
+    write_version(cursor)?;
+
+    let suppressed = replication_mask::is_suppressed(MaskedComponent::Position);
+    DefaultOptions::new().serialize_into(&mut *cursor, &!suppressed)?;
+    if suppressed
+    {
+        return Ok(());
+    }
+
+    let quantized = quantize_position(component.0, POSITION_SCALE.load(Ordering::Relaxed));
+    DefaultOptions::new().serialize_into(cursor, &quantized)
+}
+
+/// Replaces the client's `Position` outright, so its own prediction snaps to each authoritative value
+/// and rounding error is never carried from one update into the next
+fn deserialize_position(
+    entity: &mut EntityWorldMut,
+    _entity_map: &mut ServerEntityMap,
+    cursor: &mut Cursor<&[u8]>,
+    _tick: RepliconTick,
+) -> bincode::Result<()> {
+    read_version(cursor)?;
+    let present: bool = DefaultOptions::new().deserialize_from(&mut *cursor)?;
+    if !present
+    {
+        return Ok(());
+    }
+
+    let quantized: [i16; 2] = DefaultOptions::new().deserialize_from(cursor)?;
+    entity.insert(Position(dequantize_position(quantized, POSITION_SCALE.load(Ordering::Relaxed))));
+
+    Ok(())
+}
+
+fn serialize_player_spawned(component: Ptr, cursor: &mut Cursor<Vec<u8>>) -> bincode::Result<()> {
+    // SAFETY: replicon only calls this with a pointer to the registered component type
+    let component = unsafe { component.deref::<PlayerSpawnedComponent>() };
+    write_version(cursor)?;
+
+    // Suppressed by the replication mask, only the marker goes out and clients keep what they had
+    let suppressed = replication_mask::is_suppressed(MaskedComponent::PlayerSpawned);
+    DefaultOptions::new().serialize_into(&mut *cursor, &!suppressed)?;
+    if suppressed
+    {
+        return Ok(());
+    }
+
+    write_sparse(cursor, &component.random_stuff)?;
+    write_sparse(cursor, &component.other_random_stuff)
+}
+
+fn deserialize_player_spawned(
+    entity: &mut EntityWorldMut,
+    _entity_map: &mut ServerEntityMap,
+    cursor: &mut Cursor<&[u8]>,
+    _tick: RepliconTick,
+) -> bincode::Result<()> {
+    read_version(cursor)?;
+    let present: bool = DefaultOptions::new().deserialize_from(&mut *cursor)?;
+    if !present
+    {
+        return Ok(());
+    }
+
+    let mut component = PlayerSpawnedComponent::default();
+    read_sparse(cursor, &mut component.random_stuff)?;
+    read_sparse(cursor, &mut component.other_random_stuff)?;
+    entity.insert(component);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::settings::GameSettings;
+
+    /// Points checked along each axis between the world's edges
+    const SAMPLES: u32 = 10_000;
+
+    #[test]
+    fn round_trip_error_is_at_most_half_a_step_across_the_world() {
+        let half_extent = GameSettings::default().world_half_extent;
+        for scale in [1, DEFAULT_POSITION_SCALE, 64]
+        {
+            let max_error = 0.5 / scale as f32 + f32::EPSILON * half_extent;
+            for step in 0..=SAMPLES
+            {
+                let value = -half_extent + 2.0 * half_extent * step as f32 / SAMPLES as f32;
+                let position = Vec2::new(value, -value * 0.37);
+                let round_trip = dequantize_position(quantize_position(position, scale), scale);
+                let error = (round_trip - position).abs().max_element();
+                assert!(error <= max_error, "{position} came back as {round_trip} at scale {scale}");
+            }
+        }
+    }
+
+    #[test]
+    fn scale_is_only_changed_once_nobody_holds_it() {
+        let mut claims = ScaleClaims { scale: DEFAULT_POSITION_SCALE, holders: 0 };
+        assert_eq!(claims.claim(8), Ok(()));
+        assert_eq!(claims.claim(8), Ok(()));
+        assert_eq!(claims.claim(32), Err(8));
+
+        claims.release();
+        assert_eq!(claims.claim(32), Err(8), "one server was still running at 8");
+        claims.release();
+        assert_eq!(claims.claim(32), Ok(()));
+    }
+
+    #[test]
+    fn positions_past_i16_are_clamped_to_the_edge() {
+        let quantized = quantize_position(Vec2::new(1.0e6, -1.0e6), DEFAULT_POSITION_SCALE);
+        assert_eq!(quantized, [i16::MAX, i16::MIN]);
+    }
+
+    #[test]
+    fn payload_from_a_future_version_is_rejected_without_touching_the_entity() {
+        let mut cursor = Cursor::new(Vec::new());
+        serialize_position(Ptr::from(&Position(Vec2::new(3.0, -2.0))), &mut cursor).unwrap();
+        let mut payload = cursor.into_inner();
+
+        let mut world = World::new();
+        let mut entity_map = ServerEntityMap::default();
+        let current = world.spawn_empty().id();
+        deserialize_position(&mut world.entity_mut(current), &mut entity_map, &mut Cursor::new(&payload[..]), RepliconTick::default()).unwrap();
+        assert_eq!(world.get::<Position>(current).unwrap().0, Vec2::new(3.0, -2.0));
+
+        payload[0] = FORMAT_VERSION + 1;
+        let future = world.spawn_empty().id();
+        let result = deserialize_position(&mut world.entity_mut(future), &mut entity_map, &mut Cursor::new(&payload[..]), RepliconTick::default());
+        assert!(result.is_err());
+        assert!(world.get::<Position>(future).is_none());
+    }
+}
@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{ConnectionStatusText, LocalPlayerId, UiReady, console::ConsoleCommand, replication_interval::{ReplicateThrottledExt, Throttled}};
+
+/// Longest name kept, in characters
+pub const MAX_NAME_CHARS: usize = 32;
+/// Longest description or map name kept, in characters
+pub const MAX_DESCRIPTION_CHARS: usize = 96;
+
+/// The server's name, description and map, replicated so clients can show what they joined
+pub struct ServerInfoPlugin;
+
+impl Plugin for ServerInfoPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate_throttled::<ServerInfo>()
+            .add_systems(Update, rename_console_system.run_if(resource_exists::<RenetServer>()))
+            .add_systems(Update, show_server_name_system.run_if(resource_exists::<RenetClient>()).run_if(resource_exists::<UiReady>()));
+    }
+}
+
+/// Lives on the game-state singleton entity
+#[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ServerInfo
+{
+    pub name: String,
+    pub description: String,
+    pub map: String,
+}
+
+/// Only changes on `rename`, a second's delay at the default replication rate doesn't matter
+impl Throttled for ServerInfo
+{
+    const INTERVAL: u32 = 30;
+}
+
+impl ServerInfo
+{
+    pub fn new(name: &str, description: &str, map: &str) -> Self {
+        Self {
+            name: sanitize(name, MAX_NAME_CHARS),
+            description: sanitize(description, MAX_DESCRIPTION_CHARS),
+            map: sanitize(map, MAX_DESCRIPTION_CHARS),
+        }
+    }
+}
+
+/// Drops control characters (newlines included, they'd break the labels) and truncates to `max_chars`
+///
+/// Applied on both ends, the server's copy might come from a build that didn't.
+pub fn sanitize(text: &str, max_chars: usize) -> String {
+    text.chars().filter(|c| !c.is_control()).take(max_chars).collect::<String>().trim().to_string()
+}
+
+/// `name <new name>` renames the server for everyone
+fn rename_console_system(
+    mut console: EventReader<ConsoleCommand>,
+    mut infos: Query<&mut ServerInfo>,
+) {
+    for command in console.read()
+    {
+        if command.name() != "name"
+        {
+            continue;
+        }
+
+        let Ok(mut info) = infos.get_single_mut() else { continue; };
+        let name = sanitize(&command.args[1..].join(" "), MAX_NAME_CHARS);
+        if name.is_empty()
+        {
+            warn!("Server: Usage: name <new name>");
+            continue;
+        }
+
+        info!("Server: Renamed from '{}' to '{name}'", info.name);
+        info.name = name;
+    }
+}
+
+/// Client side, adds the server's name to the corner label once it arrives or changes
+fn show_server_name_system(
+    infos: Query<Ref<ServerInfo>>,
+    mut text_query: Query<&mut Text, With<ConnectionStatusText>>,
+    local_player: Res<LocalPlayerId>,
+    mut shown: Local<bool>,
+) {
+    let Ok(info) = infos.get_single() else { return; };
+    // A name that arrived before the status text existed is shown as soon as it does
+    if (*shown && !info.is_changed()) || text_query.is_empty()
+    {
+        return;
+    }
+    *shown = true;
+
+    info!("Client: Joined '{}' ({}) on map '{}'", info.name, info.description, info.map);
+    let name = sanitize(&info.name, MAX_NAME_CHARS);
+    for mut text in &mut text_query
+    {
+        text.sections[0].value = format!("Client: {} on {name}", local_player.0);
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::weapon::FireMode;
+
+/// Server settings that clients need to know about, replicated on a singleton entity
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WorldBounds>()
+            .init_resource::<GameTuning>()
+            .replicate::<GameSettings>()
+            .add_systems(PreUpdate, sync_world_bounds_system.after(ClientSet::Receive));
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy)]
+pub struct GameSettings
+{
+    /// How many times per second the server sends replication updates
+    pub replication_rate: f32,
+    /// Fixed for the whole session, for anything purely visual that every machine should lay out the same way
+    pub world_seed: u64,
+    /// Half the width and height of the playable area, mirrored into `WorldBounds`
+    pub world_half_extent: f32,
+    /// Live projectiles the server allows before despawning the oldest
+    pub max_projectiles: usize,
+    /// Mirrored from `GameTuning`, clients predict their own movement with it
+    pub move_speed: f32,
+    /// Mirrored from `GameTuning`, clients launch predicted projectiles with it
+    pub projectile_speed: f32,
+    /// Mirrored from `GameTuning`, so predicted projectiles bounce like the server's
+    pub projectile_bounces: u8,
+    /// Mirrored from `GameTuning`, clients only send shots as fast as the server will take them
+    pub fire_mode: FireMode,
+    /// Player movement runs in fixed-point integers (`--deterministic`), clients predict the same way
+    pub deterministic: bool,
+}
+
+impl Default for GameSettings
+{
+    fn default() -> Self {
+        let tuning = GameTuning::default();
+        Self {
+            replication_rate: 30.0,
+            world_seed: 0,
+            world_half_extent: 200.0,
+            max_projectiles: 500,
+            move_speed: tuning.move_speed,
+            projectile_speed: tuning.projectile_speed,
+            projectile_bounces: tuning.projectile_bounces,
+            fire_mode: tuning.fire_mode(),
+            deterministic: false,
+        }
+    }
+}
+
+/// Server side gameplay numbers, the `[tuning]` table of a `--config` file, reloaded whenever the file changes
+///
+/// The ones clients predict with are mirrored into `GameSettings` so they follow a reload too.
+#[derive(Resource, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameTuning
+{
+    /// World units per second a player moves
+    pub move_speed: f32,
+    /// World units per second a projectile flies
+    pub projectile_speed: f32,
+    /// Seconds before a projectile that hit nothing is despawned
+    pub projectile_lifetime: f32,
+    /// Walls a new projectile reflects off before the next one despawns it
+    pub projectile_bounces: u8,
+    /// Shots per second while the fire key is held, 0 for one shot per press
+    pub fire_rate: f32,
+    /// Seconds without taking damage before a player starts regenerating
+    pub regen_delay: f32,
+    /// Health per second regenerated after `regen_delay`, 0 for none
+    pub regen_rate: f32,
+    /// Seconds a player can't be damaged after spawning or respawning, 0 for none
+    pub spawn_protection: f32,
+    /// Whether firing gives up the rest of a player's spawn protection
+    pub protection_ends_on_fire: bool,
+    /// Seconds a projectile or player plays its death animation for, 0 to despawn projectiles straight away
+    pub despawn_delay: f32,
+    /// Whether grabs, releases and movement sent during a pause are applied after it instead of dropped
+    pub queue_paused_inputs: bool,
+}
+
+impl Default for GameTuning
+{
+    fn default() -> Self {
+        Self { move_speed: 50.0, projectile_speed: 150.0, projectile_lifetime: 2.0, projectile_bounces: 0, fire_rate: 0.0, regen_delay: 4.0, regen_rate: 5.0, spawn_protection: 3.0, protection_ends_on_fire: true, despawn_delay: 0.3, queue_paused_inputs: false }
+    }
+}
+
+impl GameTuning
+{
+    pub fn validate(&self) -> Result<(), String> {
+        if self.move_speed <= 0.0
+        {
+            return Err("`tuning.move_speed` must be greater than zero".to_string());
+        }
+        if self.projectile_speed <= 0.0
+        {
+            return Err("`tuning.projectile_speed` must be greater than zero".to_string());
+        }
+        if self.projectile_lifetime <= 0.0
+        {
+            return Err("`tuning.projectile_lifetime` must be greater than zero".to_string());
+        }
+        if !(self.fire_rate >= 0.0 && self.fire_rate.is_finite())
+        {
+            return Err("`tuning.fire_rate` must be zero or more".to_string());
+        }
+        if !(self.regen_delay >= 0.0 && self.regen_delay.is_finite())
+        {
+            return Err("`tuning.regen_delay` must be zero or more".to_string());
+        }
+        if !(self.regen_rate >= 0.0 && self.regen_rate.is_finite())
+        {
+            return Err("`tuning.regen_rate` must be zero or more".to_string());
+        }
+        if !(self.spawn_protection >= 0.0 && self.spawn_protection.is_finite())
+        {
+            return Err("`tuning.spawn_protection` must be zero or more".to_string());
+        }
+        if !(self.despawn_delay >= 0.0 && self.despawn_delay.is_finite())
+        {
+            return Err("`tuning.despawn_delay` must be zero or more".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Sets one value by its `[tuning]` name, for the `set` console command
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let number = || value.parse::<f32>().map_err(|_| format!("`{value}` isn't a number"));
+        match field
+        {
+            "move_speed" => self.move_speed = number()?,
+            "projectile_speed" => self.projectile_speed = number()?,
+            "projectile_lifetime" => self.projectile_lifetime = number()?,
+            "fire_rate" => self.fire_rate = number()?,
+            "regen_delay" => self.regen_delay = number()?,
+            "regen_rate" => self.regen_rate = number()?,
+            "spawn_protection" => self.spawn_protection = number()?,
+            "despawn_delay" => self.despawn_delay = number()?,
+            "protection_ends_on_fire" => self.protection_ends_on_fire = value.parse().map_err(|_| format!("`{value}` isn't `true` or `false`"))?,
+            "queue_paused_inputs" => self.queue_paused_inputs = value.parse().map_err(|_| format!("`{value}` isn't `true` or `false`"))?,
+            "projectile_bounces" => self.projectile_bounces = value.parse().map_err(|_| format!("`{value}` isn't a whole number from 0 to 255"))?,
+            _ => return Err(format!("there's no tuning value called `{field}`")),
+        }
+
+        Ok(())
+    }
+
+    pub fn fire_mode(&self) -> FireMode {
+        FireMode::from_rate(self.fire_rate)
+    }
+
+    /// Copies the values clients need into the replicated settings
+    pub fn mirror_into(&self, settings: &mut GameSettings) {
+        settings.move_speed = self.move_speed;
+        settings.projectile_speed = self.projectile_speed;
+        settings.projectile_bounces = self.projectile_bounces;
+        settings.fire_mode = self.fire_mode();
+    }
+}
+
+/// The playable area, centered on the origin
+#[derive(Resource, Clone, Copy)]
+pub struct WorldBounds
+{
+    pub half_extents: Vec2,
+}
+
+impl Default for WorldBounds
+{
+    fn default() -> Self {
+        Self { half_extents: Vec2::splat(GameSettings::default().world_half_extent) }
+    }
+}
+
+/// Keeps `WorldBounds` matching the server's settings, on the server and every client
+fn sync_world_bounds_system(
+    mut bounds: ResMut<WorldBounds>,
+    settings: Query<&GameSettings, Changed<GameSettings>>,
+) {
+    let Ok(settings) = settings.get_single() else { return; };
+
+    bounds.half_extents = Vec2::splat(settings.world_half_extent);
+}
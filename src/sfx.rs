@@ -0,0 +1,309 @@
+use std::path::Path;
+
+use bevy::{prelude::*, audio::Volume, utils::HashMap};
+use bevy_replicon::prelude::*;
+
+use crate::{Cli, Player, has_local_player, input_map::{Action, InputBindings}};
+
+const SFX_DIR: &str = "assets/sfx";
+/// Looped in the background if it's there, from `SFX_DIR` like the effects
+const MUSIC_FILE: &str = "music.ogg";
+/// Default for `--music-volume`, quieter than the effects so it stays in the background
+pub const DEFAULT_MUSIC_VOLUME: f32 = 0.3;
+/// Seconds before the same sound can play again, a shotgun blast's pellets all spawn together and would otherwise stack up
+const MIN_REPEAT: f32 = 0.05;
+
+/// Client-side sound effects, played from replication and server events, and looping background music
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<PlaySfx>()
+            .init_resource::<SfxSettings>()
+            .init_resource::<WorldStateReceived>()
+            .add_systems(Startup, load_sfx_system.run_if(has_local_player))
+            .add_systems(PostUpdate, track_world_state_received)
+            .add_systems(Update,
+                (
+                    player_join_leave_sfx_system,
+                    sfx_mute_hotkey_system,
+                    mute_music_system,
+                    play_sfx_system,
+                ).chain().run_if(resource_exists::<SfxAssets>())
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Sfx
+{
+    Pew,
+    Hit,
+    HitMarker,
+    Join,
+    Leave,
+}
+
+impl Sfx
+{
+    fn file_name(&self) -> &'static str {
+        match self
+        {
+            Sfx::Pew => "pew.ogg",
+            Sfx::Hit => "hit.ogg",
+            Sfx::HitMarker => "hitmarker.ogg",
+            Sfx::Join => "join.ogg",
+            Sfx::Leave => "leave.ogg",
+        }
+    }
+}
+
+/// Request to play a sound effect on this machine
+#[derive(Event)]
+pub struct PlaySfx(pub Sfx);
+
+#[derive(Resource)]
+pub struct SfxSettings
+{
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub muted: bool,
+}
+
+impl Default for SfxSettings
+{
+    fn default() -> Self {
+        Self { master_volume: 1.0, music_volume: DEFAULT_MUSIC_VOLUME, muted: false }
+    }
+}
+
+/// Marker for the background music's entity
+#[derive(Component)]
+struct Music;
+
+/// Loaded sound handles, `None` for any file missing from `assets/sfx/`
+#[derive(Resource)]
+pub struct SfxAssets
+{
+    pew: Option<Handle<AudioSource>>,
+    hit: Option<Handle<AudioSource>>,
+    hit_marker: Option<Handle<AudioSource>>,
+    join: Option<Handle<AudioSource>>,
+    leave: Option<Handle<AudioSource>>,
+}
+
+impl SfxAssets
+{
+    fn get(&self, sfx: Sfx) -> Option<&Handle<AudioSource>> {
+        match sfx
+        {
+            Sfx::Pew => self.pew.as_ref(),
+            Sfx::Hit => self.hit.as_ref(),
+            Sfx::HitMarker => self.hit_marker.as_ref(),
+            Sfx::Join => self.join.as_ref(),
+            Sfx::Leave => self.leave.as_ref(),
+        }
+    }
+}
+
+/// Whether this app already has the server's initial world state
+///
+/// Entities arriving with the initial state were already present before a late join, so they must not play spawn sounds.
+#[derive(Resource, Default)]
+pub struct WorldStateReceived(pub bool);
+
+/// Marks the initial world state as received, runs in `PostUpdate` so every `Update` system sees the same value for that frame
+fn track_world_state_received(
+    mut received: ResMut<WorldStateReceived>,
+    tick: Res<RepliconTick>,
+    client: Option<Res<RenetClient>>,
+) {
+    if received.0
+    {
+        return;
+    }
+
+    // The server (and host) never receive a world state, clients get theirs the first time the tick is updated
+    if client.is_none() || tick.is_changed() && tick.get() > 0
+    {
+        received.0 = true;
+    }
+}
+
+/// `--sfx-volume` and `--music-volume`, from the modes that have a local player
+fn volumes(cli: &Cli) -> (f32, f32) {
+    match *cli
+    {
+        Cli::Client { sfx_volume, music_volume, .. } | Cli::Host { sfx_volume, music_volume, .. } => (sfx_volume.max(0.0), music_volume.max(0.0)),
+        _ => (1.0, DEFAULT_MUSIC_VOLUME),
+    }
+}
+
+fn load_sfx_system(
+    mut commands: Commands,
+    mut settings: ResMut<SfxSettings>,
+    asset_server: Res<AssetServer>,
+    cli: Res<Cli>,
+) {
+    (settings.master_volume, settings.music_volume) = volumes(&cli);
+
+    let mut load = |sfx: Sfx| {
+        let file_name = sfx.file_name();
+        if !Path::new(SFX_DIR).join(file_name).exists()
+        {
+            warn!("Sound effect '{SFX_DIR}/{file_name}' is missing, {sfx:?} will be silent");
+            return None;
+        }
+        Some(asset_server.load(format!("sfx/{file_name}")))
+    };
+
+    commands.insert_resource(SfxAssets {
+        pew: load(Sfx::Pew),
+        hit: load(Sfx::Hit),
+        hit_marker: load(Sfx::HitMarker),
+        join: load(Sfx::Join),
+        leave: load(Sfx::Leave),
+    });
+
+    if settings.music_volume == 0.0
+    {
+        return;
+    }
+    if !Path::new(SFX_DIR).join(MUSIC_FILE).exists()
+    {
+        info!("No background music at '{SFX_DIR}/{MUSIC_FILE}'");
+        return;
+    }
+    commands.spawn((AudioBundle {
+        source: asset_server.load(format!("sfx/{MUSIC_FILE}")),
+        settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(settings.music_volume)),
+    }, Music));
+}
+
+fn player_join_leave_sfx_system(
+    mut sfx_events: EventWriter<PlaySfx>,
+    mut removed_players: RemovedComponents<Player>,
+    new_players: Query<(), Added<Player>>,
+    received: Res<WorldStateReceived>,
+) {
+    if received.0 && !new_players.is_empty()
+    {
+        sfx_events.send(PlaySfx(Sfx::Join));
+    }
+
+    if removed_players.read().count() > 0
+    {
+        sfx_events.send(PlaySfx(Sfx::Leave));
+    }
+}
+
+fn sfx_mute_hotkey_system(
+    mut settings: ResMut<SfxSettings>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    if !bindings.just_pressed(Action::Mute, &input)
+    {
+        return;
+    }
+
+    settings.muted = !settings.muted;
+    info!("Sound {}", if settings.muted { "muted" } else { "unmuted" });
+}
+
+/// Pauses the music while muted rather than stopping it, so unmuting carries on where it was
+fn mute_music_system(
+    settings: Res<SfxSettings>,
+    music: Query<&AudioSink, With<Music>>,
+) {
+    if !settings.is_changed()
+    {
+        return;
+    }
+
+    for sink in &music
+    {
+        if settings.muted { sink.pause() } else { sink.play() }
+    }
+}
+
+fn play_sfx_system(
+    mut commands: Commands,
+    mut sfx_events: EventReader<PlaySfx>,
+    mut last_played: Local<HashMap<Sfx, f32>>,
+    assets: Res<SfxAssets>,
+    settings: Res<SfxSettings>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for PlaySfx(sfx) in sfx_events.read()
+    {
+        if settings.muted
+        {
+            continue;
+        }
+
+        // The same sound several times at once is just louder, so only the first plays
+        if last_played.get(sfx).is_some_and(|played_at| now - played_at < MIN_REPEAT)
+        {
+            continue;
+        }
+        last_played.insert(*sfx, now);
+
+        let Some(source) = assets.get(*sfx) else { continue; };
+        commands.spawn(AudioBundle {
+            source: source.clone(),
+            settings: PlaybackSettings::DESPAWN.with_volume(Volume::new_relative(settings.master_volume)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+
+    use super::*;
+
+    fn sfx_app() -> App {
+        let sound = || Some(Handle::<AudioSource>::default());
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<SfxSettings>()
+            .insert_resource(SfxAssets { pew: sound(), hit: sound(), hit_marker: sound(), join: sound(), leave: sound() })
+            .add_event::<PlaySfx>()
+            .add_systems(Update, play_sfx_system);
+        app
+    }
+
+    fn playing(app: &mut App) -> usize {
+        app.world.query::<&Handle<AudioSource>>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn a_shot_plays_once_however_many_pellets_it_has() {
+        let mut app = sfx_app();
+        app.world.send_event(PlaySfx(Sfx::Pew));
+        app.world.send_event(PlaySfx(Sfx::Pew));
+        app.world.send_event(PlaySfx(Sfx::Hit));
+        app.update();
+        assert_eq!(playing(&mut app), 2);
+
+        app.world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(MIN_REPEAT * 2.0));
+        app.world.send_event(PlaySfx(Sfx::Pew));
+        app.update();
+        assert_eq!(playing(&mut app), 3);
+    }
+
+    #[test]
+    fn muted_plays_nothing() {
+        let mut app = sfx_app();
+        app.world.resource_mut::<SfxSettings>().muted = true;
+        app.world.send_event(PlaySfx(Sfx::Pew));
+        app.update();
+        assert_eq!(playing(&mut app), 0);
+    }
+}
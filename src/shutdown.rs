@@ -0,0 +1,263 @@
+use std::{sync::{Arc, atomic::{AtomicU32, Ordering}}, time::Duration};
+
+use bevy::{prelude::*, app::AppExit, window::WindowCloseRequested};
+use bevy_replicon::{prelude::*, renet::{SendType, transport::NetcodeServerTransport}};
+use serde::{Serialize, Deserialize};
+
+use crate::{input_map::{Action, InputBindings}, is_headless};
+#[cfg(feature = "native")]
+use crate::embed::EmbeddedServer;
+
+/// Seconds between announcing the shutdown and disconnecting everyone, so the announcement reaches clients first
+const DRAIN_SECONDS: f32 = 0.5;
+
+/// Tears the connection down properly before exiting, on SIGINT/SIGTERM, a confirmed Escape or the window closing,
+/// so the other side hears about it instead of timing out
+pub struct ShutdownPlugin;
+
+impl Plugin for ShutdownPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_server_event::<ServerAnnouncement>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_systems(Update, watch_signal_system.run_if(resource_exists::<ShutdownSignal>()))
+            .add_systems(Update, shutdown_system.run_if(resource_exists::<ShutdownRequested>()))
+            .add_systems(Startup, (spawn_announcement_text, spawn_quit_prompt).run_if(not(is_headless)))
+            .add_systems(Update, (show_announcement_system, quit_prompt_system).run_if(not(is_headless)));
+
+        // Browsers have no signals, and only native builds can host
+        #[cfg(feature = "native")]
+        app.add_systems(PostStartup, install_signal_handler_system.run_if(resource_exists::<RenetServer>()).run_if(not(resource_exists::<EmbeddedServer>())));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Severity
+{
+    Info,
+    Critical,
+}
+
+/// A message from the server shown to every player
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct ServerAnnouncement
+{
+    pub severity: Severity,
+    pub text: String,
+}
+
+/// How many shutdown signals have arrived, set from the signal handler thread
+#[derive(Resource, Clone)]
+pub struct ShutdownSignal(Arc<AtomicU32>);
+
+/// Present once the app has started shutting down, whatever asked for it
+#[derive(Resource)]
+pub struct ShutdownRequested;
+
+#[derive(Component)]
+pub struct AnnouncementText;
+
+/// The "Quit? (Y/N)" text, hidden until Escape is pressed
+#[derive(Component)]
+pub struct QuitPrompt;
+
+#[cfg(feature = "native")]
+fn install_signal_handler_system(
+    mut commands: Commands,
+) {
+    let signals = Arc::new(AtomicU32::new(0));
+    let handler_signals = signals.clone();
+    let result = ctrlc::set_handler(move || {
+        // The first signal starts the graceful shutdown, a second one means don't wait for it
+        if handler_signals.fetch_add(1, Ordering::SeqCst) >= 1
+        {
+            std::process::exit(130);
+        }
+    });
+
+    match result
+    {
+        Ok(()) => commands.insert_resource(ShutdownSignal(signals)),
+        Err(e) => warn!("Server: Couldn't install the shutdown signal handler, clients will time out instead: {e}"),
+    }
+}
+
+fn watch_signal_system(
+    mut commands: Commands,
+    signal: Res<ShutdownSignal>,
+) {
+    if signal.0.load(Ordering::SeqCst) > 0
+    {
+        commands.insert_resource(ShutdownRequested);
+    }
+}
+
+/// Announces the shutdown (server) or disconnects (client), waits `DRAIN_SECONDS` for the packets to go out, then exits
+fn shutdown_system(
+    mut draining: Local<Option<f32>>,
+    mut announcements: EventWriter<ToClients<ServerAnnouncement>>,
+    mut exit: EventWriter<AppExit>,
+    server: Option<ResMut<RenetServer>>,
+    transport: Option<ResMut<NetcodeServerTransport>>,
+    client: Option<ResMut<RenetClient>>,
+    time: Res<Time>,
+) {
+    let Some(elapsed) = draining.as_mut() else {
+        if server.is_some()
+        {
+            info!("Server: Shutting down, disconnecting clients in {DRAIN_SECONDS}s");
+            announcements.send(ToClients {
+                mode: SendMode::Broadcast,
+                event: ServerAnnouncement { severity: Severity::Critical, text: "Server shutting down".to_string() },
+            });
+        }
+        else if let Some(mut client) = client
+        {
+            // The client transport sends its disconnect packets on the next update
+            info!("Client: Disconnecting before quitting");
+            client.disconnect();
+        }
+        *draining = Some(0.0);
+        return;
+    };
+
+    *elapsed += time.delta_seconds();
+    if *elapsed < DRAIN_SECONDS
+    {
+        return;
+    }
+
+    // The transport sends disconnect packets right away, the server alone would only drop the connections
+    if let Some(mut server) = server
+    {
+        match transport
+        {
+            Some(mut transport) => transport.disconnect_all(&mut server),
+            None => server.disconnect_all(),
+        }
+    }
+    exit.send(AppExit);
+}
+
+fn spawn_announcement_text(
+    mut commands: Commands,
+) {
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle { font_size: 24.0, color: Color::WHITE, ..default() }
+    ).with_style(Style {
+        position_type: PositionType::Absolute, top: Val::Px(40.0), left: Val::Percent(40.0), ..default()
+    }), AnnouncementText));
+}
+
+fn show_announcement_system(
+    mut announcements: EventReader<ServerAnnouncement>,
+    mut texts: Query<&mut Text, With<AnnouncementText>>,
+) {
+    for announcement in announcements.read()
+    {
+        info!("Client: Server announcement ({:?}): {}", announcement.severity, announcement.text);
+        let color = match announcement.severity
+        {
+            Severity::Info => Color::WHITE,
+            Severity::Critical => Color::RED,
+        };
+
+        for mut text in &mut texts
+        {
+            text.sections[0].value = announcement.text.clone();
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+fn spawn_quit_prompt(
+    mut commands: Commands,
+) {
+    let mut prompt = TextBundle::from_section(
+        "Quit? (Y/N)",
+        TextStyle { font_size: 32.0, color: Color::WHITE, ..default() }
+    ).with_style(Style {
+        position_type: PositionType::Absolute, top: Val::Percent(45.0), left: Val::Percent(45.0), ..default()
+    }).with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.7));
+    prompt.visibility = Visibility::Hidden;
+
+    commands.spawn((prompt, QuitPrompt));
+}
+
+/// Escape asks to quit, Y confirms and N or Escape again dismisses, closing the window skips the question
+fn quit_prompt_system(
+    mut commands: Commands,
+    mut close_requests: EventReader<WindowCloseRequested>,
+    mut prompts: Query<&mut Visibility, With<QuitPrompt>>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    shutting_down: Option<Res<ShutdownRequested>>,
+) {
+    let close_requested = close_requests.read().count() > 0;
+    if shutting_down.is_some()
+    {
+        return;
+    }
+
+    let Ok(mut prompt) = prompts.get_single_mut() else { return; };
+    let showing = *prompt == Visibility::Visible;
+
+    if close_requested || (showing && bindings.just_pressed(Action::ConfirmQuit, &input))
+    {
+        *prompt = Visibility::Hidden;
+        commands.insert_resource(ShutdownRequested);
+    }
+    else if bindings.just_pressed(Action::Quit, &input) || (showing && bindings.just_pressed(Action::CancelQuit, &input))
+    {
+        *prompt = if showing { Visibility::Hidden } else { Visibility::Visible };
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::ecs::event::ManualEventReader;
+    use bevy_replicon::renet::ClientId;
+
+    use super::*;
+    use crate::{SIMULATION_RATE, bench::{step_session, tests::connected_session}};
+
+    #[test]
+    fn server_announces_then_disconnects_everyone_before_exiting() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        server_app.world.insert_resource(ShutdownRequested);
+
+        let mut exits = ManualEventReader::<AppExit>::default();
+        let mut announcement_readers: Vec<ManualEventReader<ServerAnnouncement>> = client_apps.iter().map(|_| default()).collect();
+        let mut announced_at = vec![None; client_apps.len()];
+        let mut exited_at = None;
+        for frame in 0..120
+        {
+            step_session(&mut server_app, &mut client_apps);
+            for ((client_app, reader), announced_at) in client_apps.iter().zip(&mut announcement_readers).zip(&mut announced_at)
+            {
+                let announcements = client_app.world.resource::<Events<ServerAnnouncement>>();
+                if reader.read(announcements).any(|announcement| announcement.severity == Severity::Critical)
+                {
+                    announced_at.get_or_insert(frame);
+                }
+            }
+
+            if exits.read(server_app.world.resource::<Events<AppExit>>()).next().is_some()
+            {
+                let server = server_app.world.resource::<RenetServer>();
+                assert!((1..=2).all(|id| !server.is_connected(ClientId::from_raw(id))), "exited with clients still connected");
+                exited_at = Some(frame);
+                break;
+            }
+        }
+
+        let exited_at = exited_at.expect("the server never exited");
+        assert!(exited_at as f32 >= DRAIN_SECONDS * SIMULATION_RATE as f32 - 1.0, "exited after only {exited_at} frames");
+        for announced_at in announced_at
+        {
+            assert!(announced_at.is_some_and(|announced_at| announced_at < exited_at), "a client heard of the shutdown at {announced_at:?}, the server exited at {exited_at}");
+        }
+    }
+}
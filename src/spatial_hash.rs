@@ -0,0 +1,124 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{Player, Position, hitscan::PLAYER_RADIUS, player_state::PlayerState};
+
+/// Side of a grid cell, the largest collider's diameter, so anything within reach of a point is in its cell or a neighbour
+pub const CELL_SIZE: f32 = PLAYER_RADIUS * 2.0;
+
+/// Server side broadphase, live players bucketed by `Position` so collision checks only look at nearby ones
+/// instead of every player for every projectile
+pub struct SpatialHashPlugin;
+
+impl Plugin for SpatialHashPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SpatialHash>()
+            .add_systems(Update, rebuild_spatial_hash_system.in_set(SpatialHashSet).run_if(has_authority()));
+    }
+}
+
+/// Collision systems run after this, so they see this frame's positions, spawns and respawns
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SpatialHashSet;
+
+#[derive(Resource, Default)]
+pub struct SpatialHash
+{
+    cells: HashMap<IVec2, Vec<Entity>>,
+}
+
+impl SpatialHash
+{
+    fn cell(position: Vec2) -> IVec2 {
+        (position / CELL_SIZE).floor().as_ivec2()
+    }
+
+    /// Everything in the cell `position` is in and the eight around it, a superset of what's within `CELL_SIZE`
+    pub fn near(&self, position: Vec2) -> impl Iterator<Item = Entity> + '_ {
+        let center = Self::cell(position);
+        (-1..=1)
+            .flat_map(move |dx| (-1..=1).map(move |dy| center + IVec2::new(dx, dy)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+/// Rebuilt from scratch every frame, cells that stay occupied keep their `Vec` so players standing still don't allocate
+fn rebuild_spatial_hash_system(
+    mut spatial_hash: ResMut<SpatialHash>,
+    players: Query<(Entity, &Position, &PlayerState), With<Player>>,
+) {
+    for entities in spatial_hash.cells.values_mut()
+    {
+        entities.clear();
+    }
+
+    for (entity, position, state) in &players
+    {
+        if state.is_alive()
+        {
+            spatial_hash.cells.entry(SpatialHash::cell(position.0)).or_default().push(entity);
+        }
+    }
+
+    // Cells nobody has been in for a frame would otherwise pile up as players move around
+    spatial_hash.cells.retain(|_, entities| !entities.is_empty());
+}
+
+#[cfg(test)]
+mod tests
+{
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const PLAYERS: usize = 500;
+    const PROJECTILES: usize = 500;
+    const HALF_EXTENT: f32 = 200.0;
+
+    #[test]
+    fn grid_finds_the_same_pairs_as_brute_force_from_far_fewer() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut point = || Vec2::new(rng.gen_range(-HALF_EXTENT..HALF_EXTENT), rng.gen_range(-HALF_EXTENT..HALF_EXTENT));
+
+        let mut app = App::new();
+        app
+            .init_resource::<SpatialHash>()
+            .add_systems(Update, rebuild_spatial_hash_system);
+        let players: Vec<(Entity, Vec2)> = (0..PLAYERS)
+            .map(|index| {
+                let position = point();
+                (app.world.spawn((Player(index as u64), Position(position), PlayerState::Alive)).id(), position)
+            })
+            .collect();
+        let projectiles: Vec<Vec2> = (0..PROJECTILES).map(|_| point()).collect();
+        app.update();
+
+        let position_of = |entity: Entity| players.iter().find(|(player, _)| *player == entity).unwrap().1;
+        let spatial_hash = app.world.resource::<SpatialHash>();
+        let mut brute_force = Vec::new();
+        let mut grid = Vec::new();
+        let mut grid_checked = 0;
+        for (index, projectile) in projectiles.iter().enumerate()
+        {
+            brute_force.extend(players.iter().filter(|(_, player)| player.distance(*projectile) < PLAYER_RADIUS).map(|(entity, _)| (index, *entity)));
+            for entity in spatial_hash.near(*projectile)
+            {
+                grid_checked += 1;
+                if position_of(entity).distance(*projectile) < PLAYER_RADIUS
+                {
+                    grid.push((index, entity));
+                }
+            }
+        }
+        brute_force.sort_unstable();
+        grid.sort_unstable();
+
+        assert!(!brute_force.is_empty(), "too sparse to test anything");
+        assert_eq!(grid, brute_force);
+        assert!(grid_checked * 10 < PLAYERS * PROJECTILES, "the grid checked {grid_checked} pairs");
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, network_event::client_event, renet::{ClientId, SendType}};
+use serde::{Serialize, Deserialize};
+
+use crate::{Cli, PlayerSpawnedComponent, PredictedSpawn, event_limits::{self, MAX_ACK_SPAWN_BYTES}};
+
+/// Clients started with `--ack-spawns` tell the server about every spawn they receive, so "did the client get it?"
+/// shows up in the server log instead of being inferred
+pub struct SpawnAckPlugin;
+
+impl Plugin for SpawnAckPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SpawnAcks>()
+            // Registered even when acks are off, so every build agrees on the channel layout
+            .add_client_event_with::<AckSpawn, _, _>(
+                SendType::ReliableUnordered { resend_time: Duration::from_millis(300) },
+                client_event::sending_system::<AckSpawn>,
+                event_limits::limited_receiving_system::<AckSpawn, MAX_ACK_SPAWN_BYTES>,
+            )
+            .add_systems(Update, ack_spawns_system.run_if(resource_exists::<RenetClient>()).run_if(acks_enabled))
+            .add_systems(Update, receive_spawn_acks_system.run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// Sent by a client the first time it sees a replicated `PlayerSpawnedComponent` entity
+#[derive(Event, Serialize, Deserialize, Debug)]
+pub struct AckSpawn
+{
+    /// Already translated to the server's entity, the client's own id for it means nothing there
+    pub server_entity: Entity,
+}
+
+/// Server side, which clients have acknowledged each replicated spawn that still exists
+#[derive(Resource, Default)]
+pub struct SpawnAcks(HashMap<Entity, Vec<ClientId>>);
+
+fn acks_enabled(cli: Res<Cli>) -> bool {
+    matches!(*cli, Cli::Client { ack_spawns: true, .. })
+}
+
+fn ack_spawns_system(
+    mut ack_writer: EventWriter<AckSpawn>,
+    new_spawns: Query<Entity, (With<PlayerSpawnedComponent>, Added<Replication>, Without<PredictedSpawn>)>,
+    entity_map: Res<ServerEntityMap>,
+) {
+    for entity in &new_spawns
+    {
+        let Some(server_entity) = entity_map.to_server().get(&entity) else { continue; };
+        ack_writer.send(AckSpawn { server_entity: *server_entity });
+    }
+}
+
+fn receive_spawn_acks_system(
+    mut ack_reader: EventReader<FromClient<AckSpawn>>,
+    mut removed: RemovedComponents<Replication>,
+    mut acks: ResMut<SpawnAcks>,
+    spawns: Query<(), With<PlayerSpawnedComponent>>,
+) {
+    for entity in removed.read()
+    {
+        acks.0.remove(&entity);
+    }
+
+    for FromClient { client_id, event } in ack_reader.read()
+    {
+        // Clients only know the server entity from replication, anything else is stale or made up
+        if spawns.get(event.server_entity).is_err()
+        {
+            continue;
+        }
+
+        let clients = acks.0.entry(event.server_entity).or_default();
+        if !clients.contains(client_id)
+        {
+            clients.push(*client_id);
+        }
+        info!("Server: Client '{client_id}' acknowledged {:?}, acknowledged by {clients:?}", event.server_entity);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::bench::{step_session, tests::connected_session};
+
+    #[test]
+    fn server_records_an_ack_from_each_acking_client_that_sees_a_spawn() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        // Bench clients have no `--ack-spawns`, so only client 1 acks
+        client_apps[0].add_systems(Update, ack_spawns_system.run_if(resource_exists::<RenetClient>()));
+
+        let spawned = server_app.world.spawn((PlayerSpawnedComponent::default(), Replication)).id();
+        for _ in 0..30
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let acks = server_app.world.resource::<SpawnAcks>();
+        assert_eq!(acks.0.get(&spawned), Some(&vec![ClientId::from_raw(1)]));
+
+        server_app.world.despawn(spawned);
+        step_session(&mut server_app, &mut client_apps);
+        assert!(server_app.world.resource::<SpawnAcks>().0.is_empty(), "acks outlived their spawn");
+    }
+}
@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use bevy_replicon::{prelude::*, renet::ClientId};
+use serde::{Serialize, Deserialize};
+
+use crate::{LocalPlayerId, Player, PlayerInput, SIMULATION_RATE, has_local_player, is_headless, pause::SimulationSet, player_state::{self, PlayerState}, settings::GameTuning, tick_timer::{SimulationTick, seconds_to_ticks}};
+
+/// Alpha protected players are drawn at, pulsing by `PULSE_ALPHA` either way
+const PROTECTED_ALPHA: f32 = 0.5;
+const PULSE_ALPHA: f32 = 0.15;
+const PULSE_RATE: f32 = 2.0;
+
+/// Players who just spawned can't be damaged for `GameTuning::spawn_protection` seconds, so camping the spawn
+/// point doesn't win, and firing gives the protection up early unless `protection_ends_on_fire` is off
+pub struct SpawnProtectionPlugin;
+
+impl Plugin for SpawnProtectionPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .replicate::<Invulnerable>()
+            .add_systems(Update,
+                (
+                    grant_protection_system,
+                    end_protection_on_shoot_system,
+                    expire_protection_system.in_set(SimulationSet),
+                ).chain().run_if(has_authority())
+            )
+            .add_systems(Update, show_protection_system.after(player_state::grey_out_inactive_players).run_if(not(is_headless)))
+            .add_systems(Startup, spawn_protection_text.run_if(has_local_player).run_if(not(is_headless)))
+            .add_systems(Update, update_protection_text_system.run_if(has_local_player).run_if(not(is_headless)));
+    }
+}
+
+/// Damage doesn't apply to this player until the simulation reaches `until_tick`, removed when it does
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Invulnerable
+{
+    pub until_tick: u32,
+}
+
+impl Invulnerable
+{
+    /// Zero once `until_tick` has been reached
+    pub fn remaining_ticks(&self, tick: SimulationTick) -> u32 {
+        let remaining = self.until_tick.wrapping_sub(tick.0);
+        // Wrapped around, so the tick is already past it
+        if remaining > u32::MAX / 2 { 0 } else { remaining }
+    }
+}
+
+#[derive(Component)]
+pub struct ProtectionText;
+
+/// Server side, protects players as they're first spawned and as they come back to life
+fn grant_protection_system(
+    mut commands: Commands,
+    players: Query<(Entity, &PlayerState), Or<(Added<Player>, Changed<PlayerState>)>>,
+    tuning: Res<GameTuning>,
+    tick: Res<SimulationTick>,
+) {
+    let protection_ticks = seconds_to_ticks(tuning.spawn_protection);
+    if protection_ticks == 0
+    {
+        return;
+    }
+
+    for (player_entity, state) in &players
+    {
+        // Only `Respawning` changes to `Alive`, dying changes it the other way
+        if state.is_alive()
+        {
+            commands.entity(player_entity).insert(Invulnerable { until_tick: tick.0.wrapping_add(protection_ticks) });
+        }
+    }
+}
+
+/// Server side, a protected player who fires loses the protection, so it can't be used to attack safely
+///
+/// Inputs are still read with `protection_ends_on_fire` off, so turning it back on doesn't act on old shots.
+fn end_protection_on_shoot_system(
+    mut commands: Commands,
+    mut input_reader: EventReader<FromClient<PlayerInput>>,
+    players: Query<(Entity, &Player), With<Invulnerable>>,
+    tuning: Res<GameTuning>,
+) {
+    for FromClient { client_id, event } in input_reader.read()
+    {
+        if !tuning.protection_ends_on_fire || !matches!(event, PlayerInput::Shoot { .. } | PlayerInput::HitscanShot { .. })
+        {
+            continue;
+        }
+
+        if let Some((player_entity, _)) = players.iter().find(|(_, player)| ClientId::from_raw(player.0) == *client_id)
+        {
+            info!("Server: Client '{client_id}' fired, ending its spawn protection");
+            commands.entity(player_entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+/// Server side, removing the component is what tells clients the protection is over
+fn expire_protection_system(
+    mut commands: Commands,
+    players: Query<(Entity, &Invulnerable)>,
+    tick: Res<SimulationTick>,
+) {
+    for (player_entity, invulnerable) in &players
+    {
+        if invulnerable.remaining_ticks(*tick) == 0
+        {
+            commands.entity(player_entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+/// Draws protected players see-through and pulsing, `grey_out_inactive_players` makes them opaque again
+fn show_protection_system(
+    mut players: Query<&mut Sprite, With<Invulnerable>>,
+    time: Res<Time>,
+) {
+    let alpha = PROTECTED_ALPHA + PULSE_ALPHA * (time.elapsed_seconds() * PULSE_RATE * std::f32::consts::TAU).sin();
+    for mut sprite in &mut players
+    {
+        sprite.color.set_a(alpha);
+    }
+}
+
+fn spawn_protection_text(
+    mut commands: Commands,
+) {
+    commands.spawn((TextBundle::from_section(
+        "",
+        TextStyle { font_size: 24.0, color: Color::CYAN, ..default() }
+    ).with_style(Style {
+        position_type: PositionType::Absolute, bottom: Val::Px(40.0), left: Val::Percent(45.0), ..default()
+    }), ProtectionText));
+}
+
+/// Seconds of protection the local player has left, blank once it's gone
+fn update_protection_text_system(
+    mut texts: Query<&mut Text, With<ProtectionText>>,
+    players: Query<(&Player, Option<&Invulnerable>)>,
+    local_player: Option<Res<LocalPlayerId>>,
+    tick: Res<SimulationTick>,
+) {
+    let Some(local_player) = local_player else { return; };
+    let remaining = players.iter()
+        .find(|(player, _)| player.0 == local_player.0)
+        .and_then(|(_, invulnerable)| invulnerable)
+        .map(|invulnerable| invulnerable.remaining_ticks(*tick) as f32 / SIMULATION_RATE as f32);
+
+    let value = remaining.map_or(String::new(), |remaining| format!("Spawn protection {remaining:.1}s"));
+    for mut text in &mut texts
+    {
+        if text.sections[0].value != value
+        {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{bench::{step_session, tests::connected_session}, damage::{ApplyDamage, Health}};
+
+    /// Player 2's entity, health and whether it's protected, as `app` sees it
+    fn player_two(app: &mut App) -> (Entity, u32, bool) {
+        app.world.query::<(Entity, &Player, &Health, Has<Invulnerable>)>().iter(&app.world)
+            .find_map(|(entity, player, health, invulnerable)| (player.0 == 2).then_some((entity, health.current, invulnerable)))
+            .unwrap()
+    }
+
+    /// Hits player 2 for 10 and lets the damage apply and replicate
+    fn hit(server_app: &mut App, client_apps: &mut [App]) {
+        let (target, ..) = player_two(server_app);
+        server_app.world.send_event(ApplyDamage { target, amount: 10, source: Some(1) });
+        for _ in 0..10
+        {
+            step_session(server_app, client_apps);
+        }
+    }
+
+    #[test]
+    fn fresh_player_takes_no_damage_until_protection_runs_out() {
+        let (mut server_app, mut client_apps) = connected_session(2);
+        let (_, full_health, protected) = player_two(&mut server_app);
+        assert!(protected);
+
+        hit(&mut server_app, &mut client_apps);
+        assert_eq!(player_two(&mut server_app).1, full_health);
+        assert_eq!(player_two(&mut client_apps[0]).1, full_health);
+        assert!(player_two(&mut client_apps[0]).2, "the client wasn't shown the protection");
+
+        let protection_frames = (GameTuning::default().spawn_protection * SIMULATION_RATE as f32).ceil() as usize;
+        for _ in 0..protection_frames
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        assert!(!player_two(&mut server_app).2, "still protected after {protection_frames} frames");
+        assert!(!player_two(&mut client_apps[0]).2, "the client never saw the protection end");
+
+        hit(&mut server_app, &mut client_apps);
+        assert_eq!(player_two(&mut server_app).1, full_health - 10);
+        assert_eq!(player_two(&mut client_apps[0]).1, full_health - 10);
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::{prelude::*, renet::{ClientId, SendType, ServerEvent}};
+use serde::{Serialize, Deserialize};
+
+use crate::{PredictedSpawn, ORPHAN_TIMEOUT};
+
+/// Stops a client from mapping one of its pre-spawned entities to more than one server entity
+///
+/// Every client entity the server maps is remembered until its server entity despawns, a `Shoot` naming one
+/// again (or naming the same one twice) is refused whole and the client told, so it can drop its prediction.
+/// The despawn is passed on to the client too, since one in the same tick as the mapping leaves it nothing to see.
+pub struct SpawnValidationPlugin;
+
+impl Plugin for SpawnValidationPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MappedSpawns>()
+            .add_server_event::<SpawnRejected>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_server_event::<MappedSpawnsDespawned>(SendType::ReliableOrdered { resend_time: Duration::from_millis(300) })
+            .add_systems(Update, prune_mapped_spawns_system.run_if(resource_exists::<RenetServer>()))
+            .add_systems(Update, receive_spawn_rejections_system.run_if(resource_exists::<RenetClient>()));
+    }
+}
+
+/// Sent only to the client whose shot was refused, with the client's own entities from it
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct SpawnRejected
+{
+    pub client_entities: Vec<Entity>,
+}
+
+/// Sent only to the client that pre-spawned them, with the client's own entities whose server entities despawned
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct MappedSpawnsDespawned
+{
+    pub client_entities: Vec<Entity>,
+}
+
+/// Server side, client entities mapped to a server entity that still exists, per client and by server entity
+#[derive(Resource, Default)]
+pub struct MappedSpawns
+{
+    by_client: HashMap<ClientId, HashSet<Entity>>,
+    by_server_entity: HashMap<Entity, (ClientId, Entity)>,
+}
+
+impl MappedSpawns
+{
+    /// Client entities in `client_entities` that are already mapped, or that appear more than once
+    pub fn reused(&self, client_id: ClientId, client_entities: &[Entity]) -> Vec<Entity> {
+        let mapped = self.by_client.get(&client_id);
+        let mut seen = HashSet::new();
+        client_entities.iter()
+            .filter(|entity| !seen.insert(**entity) || mapped.is_some_and(|mapped| mapped.contains(*entity)))
+            .copied()
+            .collect()
+    }
+
+    pub fn record(&mut self, client_id: ClientId, client_entity: Entity, server_entity: Entity) {
+        self.by_client.entry(client_id).or_default().insert(client_entity);
+        self.by_server_entity.insert(server_entity, (client_id, client_entity));
+    }
+}
+
+/// Forgets everything of a client that has disconnected, and mappings whose server entity has despawned, telling
+/// their client
+fn prune_mapped_spawns_system(
+    mut mapped_spawns: ResMut<MappedSpawns>,
+    mut removed: RemovedComponents<Replication>,
+    mut server_events: EventReader<ServerEvent>,
+    mut despawn_events: EventWriter<ToClients<MappedSpawnsDespawned>>,
+) {
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            mapped_spawns.by_client.remove(client_id);
+            mapped_spawns.by_server_entity.retain(|_, (owner, _)| owner != client_id);
+        }
+    }
+
+    let mut despawned: HashMap<ClientId, Vec<Entity>> = HashMap::new();
+    for server_entity in removed.read()
+    {
+        let Some((client_id, client_entity)) = mapped_spawns.by_server_entity.remove(&server_entity) else { continue; };
+        if let Some(mapped) = mapped_spawns.by_client.get_mut(&client_id)
+        {
+            mapped.remove(&client_entity);
+        }
+        despawned.entry(client_id).or_default().push(client_entity);
+    }
+
+    for (client_id, client_entities) in despawned
+    {
+        despawn_events.send(ToClients { mode: SendMode::Direct(client_id), event: MappedSpawnsDespawned { client_entities } });
+    }
+}
+
+/// Client side, pre-spawns the server refused are orphaned straight away instead of waiting out `ORPHAN_TIMEOUT`
+///
+/// Entities that were already confirmed aren't touched, they're the server's.
+fn receive_spawn_rejections_system(
+    mut rejections: EventReader<SpawnRejected>,
+    mut predicted: Query<&mut PredictedSpawn>,
+) {
+    for rejection in rejections.read()
+    {
+        warn!("Client: Server rejected pre-spawned {:?}", rejection.client_entities);
+        for entity in &rejection.client_entities
+        {
+            if let Ok(mut spawn) = predicted.get_mut(*entity)
+            {
+                spawn.0 = ORPHAN_TIMEOUT;
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use bevy::prelude::*;
+
+use crate::{Player, has_local_player, input_map::{Action, InputBindings}, is_headless, player_state::PlayerState};
+
+/// World units per second the free-fly camera moves
+const FREE_FLY_SPEED: f32 = 300.0;
+
+/// Camera control: the spectate key (V) toggles between following a live player and a free-fly camera moved with the arrow keys,
+/// Left/Right switch to the previous/next player while following
+///
+/// Starts in free-fly, so the camera stays where it always was until V picks a player.
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SpectatorTarget>()
+            .add_systems(Update,
+                (
+                    spectator_input_system,
+                    drop_missing_target_system,
+                    spectator_camera_system,
+                ).chain().run_if(has_local_player).run_if(not(is_headless))
+            );
+    }
+}
+
+/// The player the camera follows, or none while free-flying
+#[derive(Resource)]
+pub struct SpectatorTarget
+{
+    pub entity: Option<Entity>,
+    /// Id of the followed player, kept after they leave so cycling carries on from where they were
+    pub player_id: Option<u64>,
+    pub free_fly: bool,
+}
+
+impl Default for SpectatorTarget
+{
+    fn default() -> Self {
+        Self { entity: None, player_id: None, free_fly: true }
+    }
+}
+
+impl SpectatorTarget
+{
+    /// Follows the live player after (or before) the current one in id order, wrapping around at either end
+    ///
+    /// With nobody alive to follow, switches to free-fly.
+    pub fn cycle(&mut self, live_players: &[(Entity, u64)], forward: bool) {
+        let mut ordered = live_players.to_vec();
+        ordered.sort_by_key(|(_, id)| *id);
+
+        let next = match self.player_id
+        {
+            Some(current) if forward => ordered.iter().find(|(_, id)| *id > current).or(ordered.first()),
+            Some(current) => ordered.iter().rev().find(|(_, id)| *id < current).or(ordered.last()),
+            None if forward => ordered.first(),
+            None => ordered.last(),
+        };
+
+        match next
+        {
+            Some(&(entity, id)) =>
+            {
+                self.entity = Some(entity);
+                self.player_id = Some(id);
+                self.free_fly = false;
+            }
+            None =>
+            {
+                self.entity = None;
+                self.free_fly = true;
+            }
+        }
+    }
+}
+
+fn spectator_input_system(
+    mut target: ResMut<SpectatorTarget>,
+    players: Query<(Entity, &Player, &PlayerState)>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    if bindings.just_pressed(Action::Spectate, &input)
+    {
+        target.free_fly = !target.free_fly;
+        if !target.free_fly && target.entity.is_none()
+        {
+            let live_players = live_players(&players);
+            target.cycle(&live_players, true);
+        }
+        return;
+    }
+
+    // In free-fly the arrows move the camera instead
+    if target.free_fly
+    {
+        return;
+    }
+
+    let forward = bindings.just_pressed(Action::SpectateNext, &input);
+    if forward || bindings.just_pressed(Action::SpectatePrevious, &input)
+    {
+        let live_players = live_players(&players);
+        target.cycle(&live_players, forward);
+    }
+}
+
+fn live_players(players: &Query<(Entity, &Player, &PlayerState)>) -> Vec<(Entity, u64)> {
+    players.iter()
+        .filter(|(_, _, state)| state.is_alive())
+        .map(|(entity, player, _)| (entity, player.0))
+        .collect()
+}
+
+/// Moves on to the next player when the followed one disconnects and their entity is despawned
+fn drop_missing_target_system(
+    mut target: ResMut<SpectatorTarget>,
+    players: Query<(Entity, &Player, &PlayerState)>,
+) {
+    let Some(entity) = target.entity else { return; };
+    if players.contains(entity)
+    {
+        return;
+    }
+
+    target.entity = None;
+    if target.free_fly
+    {
+        return;
+    }
+
+    info!("Client: Followed player left, following the next one");
+    let live_players = live_players(&players);
+    target.cycle(&live_players, true);
+}
+
+/// Keeps the camera on the followed player's sprite, or flies it with the arrow keys
+fn spectator_camera_system(
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+    players: Query<&Transform, (With<Player>, Without<Camera2d>)>,
+    target: Res<SpectatorTarget>,
+    input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    time: Res<Time>,
+) {
+    let Ok(mut camera) = cameras.get_single_mut() else { return; };
+
+    if target.free_fly || target.entity.is_none()
+    {
+        let mut direction = Vec2::ZERO;
+        if bindings.pressed(Action::CameraRight, &input)
+        {
+            direction.x += 1.0;
+        }
+        if bindings.pressed(Action::CameraLeft, &input)
+        {
+            direction.x -= 1.0;
+        }
+        if bindings.pressed(Action::CameraUp, &input)
+        {
+            direction.y += 1.0;
+        }
+        if bindings.pressed(Action::CameraDown, &input)
+        {
+            direction.y -= 1.0;
+        }
+
+        camera.translation += (direction.normalize_or_zero() * FREE_FLY_SPEED * time.delta_seconds()).extend(0.0);
+        return;
+    }
+
+    // The sprite's transform is already interpolated toward the replicated `Position`, so following it doesn't stutter
+    let Some(followed) = target.entity.and_then(|entity| players.get(entity).ok()) else { return; };
+    camera.translation = followed.translation.truncate().extend(camera.translation.z);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn spectator_app() -> App {
+        let mut app = App::new();
+        app
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<InputBindings>()
+            .init_resource::<SpectatorTarget>()
+            .add_systems(Update, (spectator_input_system, drop_missing_target_system).chain());
+        app
+    }
+
+    /// Presses and releases the key bound to `action` over one update, returning the followed player after it
+    fn tap(app: &mut App, action: Action) -> Option<u64> {
+        let key = app.world.resource::<InputBindings>().get(action).key;
+        app.world.resource_mut::<Input<KeyCode>>().press(key);
+        app.update();
+        let mut input = app.world.resource_mut::<Input<KeyCode>>();
+        input.release(key);
+        input.clear();
+        app.world.resource::<SpectatorTarget>().player_id
+    }
+
+    #[test]
+    fn cycling_wraps_around_and_moves_on_when_the_target_leaves() {
+        let mut app = spectator_app();
+        let players: Vec<Entity> = [3, 1, 2].into_iter().map(|id| app.world.spawn((Player(id), PlayerState::Alive)).id()).collect();
+
+        assert_eq!(tap(&mut app, Action::Spectate), Some(1));
+        assert_eq!(tap(&mut app, Action::SpectateNext), Some(2));
+        assert_eq!(tap(&mut app, Action::SpectateNext), Some(3));
+        assert_eq!(tap(&mut app, Action::SpectateNext), Some(1));
+        assert_eq!(tap(&mut app, Action::SpectatePrevious), Some(3));
+
+        // Player 3 leaves while followed, so the camera moves on past them and wraps to player 1
+        app.world.despawn(players[0]);
+        app.update();
+        let target = app.world.resource::<SpectatorTarget>();
+        assert_eq!((target.entity, target.player_id), (Some(players[1]), Some(1)));
+
+        // The dead are skipped
+        *app.world.get_mut::<PlayerState>(players[2]).unwrap() = PlayerState::Respawning;
+        assert_eq!(tap(&mut app, Action::SpectateNext), Some(1));
+
+        // Switching to free-fly remembers who was followed
+        assert_eq!(tap(&mut app, Action::Spectate), Some(1));
+        assert!(app.world.resource::<SpectatorTarget>().free_fly);
+    }
+}
@@ -0,0 +1,205 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::ServerEvent};
+use serde::{Serialize, Deserialize};
+
+use crate::{Player, Position, game_phase::{GamePhase, RoundEnded}, player_state::PlayerState, reconnect::ClientIdentity, replication_interval::{ReplicateThrottledExt, Throttled}};
+
+const CSV_HEADER: &str = "player,round_shots,round_hits,round_deaths,round_distance,lifetime_shots,lifetime_hits,lifetime_deaths,lifetime_distance";
+
+/// Server side per-player statistics, replicated for the scoreboard, with a line per player appended to
+/// `--stats-file` at the end of every round
+///
+/// Lifetime stats outlive the player entity for the rest of the server run, a client that comes back with the
+/// same identity gets them back even after its parked player has expired.
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LifetimeStats>()
+            .replicate_throttled::<PlayerStats>()
+            .add_systems(Update,
+                (
+                    attach_stats_system,
+                    count_deaths_system,
+                    store_disconnected_stats_system,
+                    start_round_stats_system,
+                    write_round_stats_system.run_if(resource_exists::<StatsFile>()),
+                ).chain().run_if(resource_exists::<RenetServer>())
+            )
+            .add_systems(FixedUpdate, track_distance_system.run_if(resource_exists::<RenetServer>()));
+    }
+}
+
+/// Lifetime totals for one player, counted by the server
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct PlayerStats
+{
+    /// Shots, a shotgun blast counts once
+    pub shots_fired: u32,
+    /// Damage dealt to other players
+    pub hits: u32,
+    pub deaths: u32,
+    /// World units moved while alive
+    pub distance: f32,
+}
+
+/// Distance changes every step, the scoreboard only needs it now and then
+impl Throttled for PlayerStats
+{
+    const INTERVAL: u32 = 15;
+}
+
+impl PlayerStats
+{
+    fn since(&self, baseline: &PlayerStats) -> PlayerStats {
+        PlayerStats {
+            shots_fired: self.shots_fired - baseline.shots_fired,
+            hits: self.hits - baseline.hits,
+            deaths: self.deaths - baseline.deaths,
+            distance: self.distance - baseline.distance,
+        }
+    }
+}
+
+/// Where round summaries are appended, from `--stats-file`
+#[derive(Resource)]
+pub struct StatsFile(pub PathBuf);
+
+/// Server side, the player's stats when the current round started, their round stats are the difference
+#[derive(Component)]
+struct RoundBaseline(PlayerStats);
+
+/// Server side, where this player was at the last fixed step
+#[derive(Component)]
+struct LastPosition(Vec2);
+
+/// Server side, the last known stats of every player who has left, by identity or by client id without one
+#[derive(Resource, Default)]
+struct LifetimeStats(HashMap<u64, PlayerStats>);
+
+/// Identity if the client sent one, as it's the same across connections, otherwise the player id
+fn stats_key(player: &Player, identity: Option<&ClientIdentity>) -> u64 {
+    identity.map_or(player.0, |identity| identity.0)
+}
+
+/// New players start from their lifetime stats if they've played on this server before
+///
+/// A reclaimed player already has `PlayerStats`, so it isn't touched.
+fn attach_stats_system(
+    mut commands: Commands,
+    lifetime: Res<LifetimeStats>,
+    new_players: Query<(Entity, &Player, &Position, Option<&ClientIdentity>), (Added<Player>, Without<PlayerStats>)>,
+) {
+    for (player_entity, player, position, identity) in &new_players
+    {
+        let stats = lifetime.0.get(&stats_key(player, identity)).copied().unwrap_or_default();
+        if stats != PlayerStats::default()
+        {
+            info!("Server: Restored lifetime stats for player {}", player.0);
+        }
+        commands.entity(player_entity).insert((stats, RoundBaseline(stats), LastPosition(position.0)));
+    }
+}
+
+fn count_deaths_system(
+    mut players: Query<(&PlayerState, &mut PlayerStats), Changed<PlayerState>>,
+) {
+    for (state, mut stats) in &mut players
+    {
+        if matches!(state, PlayerState::Dead { .. })
+        {
+            stats.deaths += 1;
+        }
+    }
+}
+
+fn store_disconnected_stats_system(
+    mut server_events: EventReader<ServerEvent>,
+    mut lifetime: ResMut<LifetimeStats>,
+    players: Query<(&Player, &PlayerStats, Option<&ClientIdentity>)>,
+) {
+    for event in server_events.read()
+    {
+        let ServerEvent::ClientDisconnected { client_id, .. } = event else { continue; };
+        if let Some((player, stats, identity)) = players.iter().find(|(player, ..)| player.0 == client_id.raw())
+        {
+            lifetime.0.insert(stats_key(player, identity), *stats);
+        }
+    }
+}
+
+fn start_round_stats_system(
+    mut commands: Commands,
+    phases: Query<&GamePhase, Changed<GamePhase>>,
+    players: Query<(Entity, &PlayerStats)>,
+) {
+    if !phases.iter().any(|phase| *phase == GamePhase::Playing)
+    {
+        return;
+    }
+
+    for (player_entity, stats) in &players
+    {
+        commands.entity(player_entity).insert(RoundBaseline(*stats));
+    }
+}
+
+/// Appends each player's stats for the round just ended and their lifetime totals, writing the header into a new file
+fn write_round_stats_system(
+    mut round_ended: EventReader<RoundEnded>,
+    mut lifetime: ResMut<LifetimeStats>,
+    players: Query<(&Player, &PlayerStats, &RoundBaseline, Option<&ClientIdentity>)>,
+    stats_file: Res<StatsFile>,
+) {
+    if round_ended.read().count() == 0
+    {
+        return;
+    }
+
+    let mut lines = Vec::new();
+    for (player, stats, baseline, identity) in &players
+    {
+        let key = stats_key(player, identity);
+        let round = stats.since(&baseline.0);
+        lines.push(format!(
+            "{key:016x},{},{},{},{:.1},{},{},{},{:.1}",
+            round.shots_fired, round.hits, round.deaths, round.distance,
+            stats.shots_fired, stats.hits, stats.deaths, stats.distance,
+        ));
+        lifetime.0.insert(key, *stats);
+    }
+
+    let path = &stats_file.0;
+    let result = OpenOptions::new().create(true).append(true).open(path).and_then(|mut file| {
+        if file.metadata()?.len() == 0
+        {
+            writeln!(file, "{CSV_HEADER}")?;
+        }
+        lines.iter().try_for_each(|line| writeln!(file, "{line}"))
+    });
+    match result
+    {
+        Ok(()) => info!("Server: Wrote round stats for {} players to '{}'", lines.len(), path.display()),
+        Err(e) => warn!("Server: Couldn't write round stats to '{}': {e}", path.display()),
+    }
+}
+
+/// In the fixed step so the distance doesn't depend on frame rate, a respawn's jump isn't counted since
+/// the player isn't alive for it
+fn track_distance_system(
+    mut players: Query<(&Position, &PlayerState, &mut PlayerStats, &mut LastPosition)>,
+) {
+    for (position, state, mut stats, mut last_position) in &mut players
+    {
+        let moved = position.0.distance(last_position.0);
+        last_position.0 = position.0;
+        if state.is_alive() && moved > 0.0
+        {
+            stats.distance += moved;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::{fs::{File, OpenOptions}, io::{BufWriter, Write}};
+
+use bevy::{prelude::*, app::AppExit};
+use bevy_replicon::prelude::*;
+
+use crate::{Cli, diagnostics::NetworkDiagnostics, idle::ServerLoopMode};
+
+const CSV_HEADER: &str = "elapsed_seconds,clients,entities,bytes_sent_per_second,bytes_received_per_second,tick_rate,prediction_error,idle";
+
+/// With `--stats-csv`, appends a row of network numbers every second, for graphing how settings affect replication
+///
+/// On a server `clients` is how many are connected and the byte rates are summed over them, on a client it's 1
+/// while connected. Prediction error is only measured by clients, servers write 0. `idle` is 1 while a headless
+/// server is idling with nobody connected.
+pub struct StatsCsvPlugin;
+
+impl Plugin for StatsCsvPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, open_stats_csv_system)
+            .add_systems(Update, write_stats_row_system.run_if(resource_exists::<StatsCsv>()))
+            .add_systems(Last, flush_stats_csv_system.run_if(resource_exists::<StatsCsv>()));
+    }
+}
+
+#[derive(Resource)]
+pub struct StatsCsv
+{
+    writer: BufWriter<File>,
+    since_row: f32,
+    /// The tick at the last row, for the tick rate over the second since
+    last_tick: Option<u32>,
+}
+
+fn open_stats_csv_system(
+    mut commands: Commands,
+    cli: Res<Cli>,
+) {
+    let Some(path) = cli.stats_csv() else { return; };
+
+    let opened = OpenOptions::new().create(true).append(true).open(path).and_then(|file| {
+        let empty = file.metadata()?.len() == 0;
+        let mut writer = BufWriter::new(file);
+        if empty
+        {
+            writeln!(writer, "{CSV_HEADER}")?;
+        }
+        Ok(writer)
+    });
+    match opened
+    {
+        Ok(writer) =>
+        {
+            info!("Writing network stats to '{}' every second", path.display());
+            commands.insert_resource(StatsCsv { writer, since_row: 0.0, last_tick: None });
+        }
+        Err(e) => error!("Couldn't open '{}' for network stats: {e}", path.display()),
+    }
+}
+
+fn write_stats_row_system(
+    mut csv: ResMut<StatsCsv>,
+    server: Option<Res<RenetServer>>,
+    client: Option<Res<RenetClient>>,
+    diagnostics: Res<NetworkDiagnostics>,
+    loop_mode: Res<ServerLoopMode>,
+    replicated: Query<(), With<Replication>>,
+    tick: Res<RepliconTick>,
+    time: Res<Time>,
+) {
+    csv.since_row += time.delta_seconds();
+    if csv.since_row < 1.0
+    {
+        return;
+    }
+
+    let (clients, bytes_sent, bytes_received) = match (server, client)
+    {
+        (Some(server), _) =>
+        {
+            let infos: Vec<_> = server.clients_id().into_iter().filter_map(|client_id| server.network_info(client_id).ok()).collect();
+            (infos.len(), infos.iter().map(|info| info.bytes_sent_per_second).sum::<f64>(), infos.iter().map(|info| info.bytes_received_per_second).sum::<f64>())
+        }
+        (None, Some(client)) if client.is_connected() =>
+        {
+            let info = client.network_info();
+            (1, info.bytes_sent_per_second, info.bytes_received_per_second)
+        }
+        _ => (0, 0.0, 0.0),
+    };
+    // A client's first tick is wherever the server was when it joined
+    let tick_rate = csv.last_tick.map_or(0.0, |last_tick| tick.get().wrapping_sub(last_tick) as f32 / csv.since_row);
+
+    let row = format!(
+        "{:.1},{clients},{},{bytes_sent:.0},{bytes_received:.0},{tick_rate:.1},{:.3},{}",
+        time.elapsed_seconds(), replicated.iter().count(), diagnostics.prediction_error, u8::from(*loop_mode == ServerLoopMode::Idle),
+    );
+    if let Err(e) = writeln!(csv.writer, "{row}")
+    {
+        warn!("Couldn't write a network stats row: {e}");
+    }
+
+    csv.since_row = 0.0;
+    csv.last_tick = Some(tick.get());
+}
+
+/// Buffered rows would otherwise be lost if the process exits before the writer is dropped
+fn flush_stats_csv_system(
+    mut exits: EventReader<AppExit>,
+    mut csv: ResMut<StatsCsv>,
+) {
+    if exits.read().count() == 0
+    {
+        return;
+    }
+
+    if let Err(e) = csv.writer.flush()
+    {
+        error!("Couldn't flush network stats: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn a_row_a_second_with_every_column_and_flushed_on_exit() {
+        let path = std::env::temp_dir().join(format!("replicon_test_stats_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut app = App::new();
+        app
+            .insert_resource(Cli::parse_from(["replicon_test_1", "bench", "--stats-csv", path.to_str().unwrap()]))
+            .init_resource::<Time>()
+            .init_resource::<RepliconTick>()
+            .init_resource::<NetworkDiagnostics>()
+            .init_resource::<ServerLoopMode>()
+            .add_event::<AppExit>()
+            .add_plugins(StatsCsvPlugin);
+
+        for _ in 0..3 * 60
+        {
+            app.world.resource_mut::<Time>().advance_by(Duration::from_secs_f64(1.0 / 60.0));
+            app.update();
+        }
+        app.world.send_event(AppExit);
+        app.update();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        let columns = CSV_HEADER.split(',').count();
+        assert!((2..=3).contains(&(lines.len() - 1)), "{} rows in 3 seconds", lines.len() - 1);
+        assert!(lines[1..].iter().all(|row| row.split(',').count() == columns), "{csv}");
+    }
+}
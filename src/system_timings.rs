@@ -0,0 +1,127 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::Cli;
+
+/// Frames the overlay's rolling max looks back over, about two seconds at 60 fps
+const ROLLING_FRAMES: usize = 120;
+/// Default for `--system-budget-ms`
+pub const DEFAULT_SYSTEM_BUDGET_MS: f32 = 2.0;
+
+/// Wall-clock cost of the systems that react to replicated spawns, to tell their hitches apart from replicon's own
+pub struct SystemTimingsPlugin;
+
+impl Plugin for SystemTimingsPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SystemTimings>()
+            .add_systems(Startup, configure_budget_system)
+            .add_systems(Last, finish_frame_system);
+    }
+}
+
+/// Systems that time themselves into `SystemTimings`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimedSystem
+{
+    EntityTracker,
+    ClientTracker,
+    AttachExtrasToPlayers,
+    Interpolation,
+}
+
+impl TimedSystem
+{
+    pub const ALL: [TimedSystem; 4] = [TimedSystem::EntityTracker, TimedSystem::ClientTracker, TimedSystem::AttachExtrasToPlayers, TimedSystem::Interpolation];
+
+    pub fn name(self) -> &'static str {
+        match self
+        {
+            TimedSystem::EntityTracker => "entity_tracker_system",
+            TimedSystem::ClientTracker => "client_tracker_system",
+            TimedSystem::AttachExtrasToPlayers => "attach_extras_to_players",
+            TimedSystem::Interpolation => "interpolate_position_transform_system",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Timing
+{
+    /// This frame's time and entities processed, summed if the system ran more than once
+    frame: Duration,
+    entities: usize,
+    recent: VecDeque<Duration>,
+    /// Slowest frame since `take_sample_max` last read it
+    sample_max: Duration,
+}
+
+/// Per-frame durations of every `TimedSystem`, with a rolling max for the debug overlay
+#[derive(Resource)]
+pub struct SystemTimings
+{
+    /// Frames where a system takes longer than this are logged
+    pub budget: Duration,
+    timings: [Timing; 4],
+}
+
+impl Default for SystemTimings
+{
+    fn default() -> Self {
+        Self { budget: Duration::from_secs_f32(DEFAULT_SYSTEM_BUDGET_MS / 1000.0), timings: default() }
+    }
+}
+
+impl SystemTimings
+{
+    pub fn record(&mut self, system: TimedSystem, elapsed: Duration, entities: usize) {
+        let timing = &mut self.timings[system as usize];
+        timing.frame += elapsed;
+        timing.entities += entities;
+    }
+
+    pub fn rolling_max(&self, system: TimedSystem) -> Duration {
+        self.timings[system as usize].recent.iter().copied().max().unwrap_or_default()
+    }
+
+    /// The slowest frame since the last call, for sampling at a fixed interval like the benchmark does
+    pub fn take_sample_max(&mut self, system: TimedSystem) -> Duration {
+        std::mem::take(&mut self.timings[system as usize].sample_max)
+    }
+}
+
+fn configure_budget_system(
+    mut timings: ResMut<SystemTimings>,
+    cli: Res<Cli>,
+) {
+    if let Cli::Client { system_budget_ms, .. } = *cli
+    {
+        timings.budget = Duration::from_secs_f32(system_budget_ms.max(0.0) / 1000.0);
+    }
+}
+
+/// Closes the frame's timings, warning about any system that went over budget
+fn finish_frame_system(
+    mut timings: ResMut<SystemTimings>,
+) {
+    let budget = timings.budget;
+    for (system, timing) in TimedSystem::ALL.into_iter().zip(&mut timings.timings)
+    {
+        let frame = std::mem::take(&mut timing.frame);
+        let entities = std::mem::take(&mut timing.entities);
+        if frame > budget
+        {
+            warn!("{} took {:.2}ms, over the {:.2}ms budget, processing {entities} entities",
+                system.name(), frame.as_secs_f32() * 1000.0, budget.as_secs_f32() * 1000.0);
+        }
+
+        if timing.recent.len() == ROLLING_FRAMES
+        {
+            timing.recent.pop_front();
+        }
+        timing.recent.push_back(frame);
+        timing.sample_max = timing.sample_max.max(frame);
+    }
+}
@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::{SIMULATION_RATE, pause::SimulationSet};
+
+/// Counts the fixed simulation steps every gameplay timer is measured in, and keeps clients' count in step with the server's
+pub struct TickTimerPlugin;
+
+impl Plugin for TickTimerPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SimulationTick>()
+            .replicate::<SimulationClock>()
+            .add_systems(FixedUpdate, advance_simulation_tick_system.in_set(SimulationSet).run_if(has_authority()))
+            .add_systems(PreUpdate, follow_simulation_clock_system.after(ClientSet::Receive).run_if(resource_exists::<RenetClient>()));
+    }
+}
+
+/// Fixed simulation steps the server has run, the clock gameplay timers (respawns, countdowns, protection, regeneration) count in
+///
+/// `RepliconTick` can't be used for those, `send_rate_system` moves it on early whenever something spawns or dies so
+/// the change goes out straight away, which would cut a timer short. This only moves on once per `FixedUpdate` step,
+/// and not at all while the simulation is paused.
+#[derive(Resource, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct SimulationTick(pub u32);
+
+impl SimulationTick
+{
+    /// Steps from `earlier` to now
+    pub fn since(&self, earlier: u32) -> u32 {
+        self.0.wrapping_sub(earlier)
+    }
+}
+
+/// Lives on the game-state singleton entity, the server's `SimulationTick` as clients last heard it
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct SimulationClock(pub u32);
+
+/// Counts down `SimulationTick`s instead of seconds, so a timer lasts the same number of steps on every machine
+/// no matter the frame rate or how often replication is sent
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TickTimer
+{
+    pub remaining_ticks: u32,
+}
+
+impl TickTimer
+{
+    pub fn new(ticks: u32) -> Self {
+        Self { remaining_ticks: ticks }
+    }
+
+    /// Rounds up, so a timer never finishes before the given time has passed at the simulation rate
+    pub fn from_seconds(seconds: f32) -> Self {
+        Self::new(seconds_to_ticks(seconds))
+    }
+
+    pub fn tick(&mut self, ticks: u32) {
+        self.remaining_ticks = self.remaining_ticks.saturating_sub(ticks);
+    }
+
+    pub fn finished(&self) -> bool {
+        self.remaining_ticks == 0
+    }
+}
+
+/// Components that own a `TickTimer`, add `tick_down_system::<T>` to count it down
+pub trait TickTimed: Component
+{
+    fn timer_mut(&mut self) -> &mut TickTimer;
+}
+
+/// Simulation steps in `seconds`, rounded up
+pub fn seconds_to_ticks(seconds: f32) -> u32 {
+    (seconds * SIMULATION_RATE as f32).ceil() as u32
+}
+
+/// Counts every `T`'s timer down by however many simulation steps passed since this system last ran
+///
+/// A frame can pass several steps or none at all, only the steps matter.
+pub fn tick_down_system<T: TickTimed>(
+    mut timers: Query<&mut T>,
+    mut last_tick: Local<Option<SimulationTick>>,
+    tick: Res<SimulationTick>,
+) {
+    let elapsed = last_tick.map_or(0, |last_tick| tick.since(last_tick.0));
+    *last_tick = Some(*tick);
+    if elapsed == 0
+    {
+        return;
+    }
+
+    for mut timed in &mut timers
+    {
+        timed.timer_mut().tick(elapsed);
+    }
+}
+
+/// Server side, one step of the simulation, published on the singleton for clients
+fn advance_simulation_tick_system(
+    mut tick: ResMut<SimulationTick>,
+    mut clocks: Query<&mut SimulationClock>,
+) {
+    tick.0 = tick.0.wrapping_add(1);
+    for mut clock in &mut clocks
+    {
+        clock.0 = tick.0;
+    }
+}
+
+/// Client side, takes the server's count whenever the singleton brings a new one
+fn follow_simulation_clock_system(
+    mut tick: ResMut<SimulationTick>,
+    clocks: Query<&SimulationClock, Changed<SimulationClock>>,
+) {
+    if let Ok(clock) = clocks.get_single()
+    {
+        tick.set_if_neq(SimulationTick(clock.0));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[derive(Component)]
+    struct TestTimer(TickTimer);
+
+    impl TickTimed for TestTimer
+    {
+        fn timer_mut(&mut self) -> &mut TickTimer {
+            &mut self.0
+        }
+    }
+
+    #[test]
+    fn expires_after_exactly_n_ticks_regardless_of_real_time() {
+        const TICKS: u32 = 5;
+
+        let mut app = App::new();
+        app
+            .init_resource::<SimulationTick>()
+            .add_systems(Update, tick_down_system::<TestTimer>);
+        let timed = app.world.spawn(TestTimer(TickTimer::new(TICKS))).id();
+        let remaining = |app: &App| app.world.get::<TestTimer>(timed).unwrap().0.remaining_ticks;
+
+        // Frames keep coming with no simulation step, as when rendering outpaces the fixed step or replication is sent early
+        for _ in 0..100
+        {
+            app.update();
+        }
+        assert_eq!(remaining(&app), TICKS);
+
+        for step in 1..=TICKS
+        {
+            app.world.resource_mut::<SimulationTick>().0 += 1;
+            app.update();
+            app.update();
+            assert_eq!(remaining(&app), TICKS - step);
+        }
+        assert!(app.world.get::<TestTimer>(timed).unwrap().0.finished());
+    }
+
+    #[test]
+    fn catches_up_several_steps_in_one_frame() {
+        let mut app = App::new();
+        app
+            .init_resource::<SimulationTick>()
+            .add_systems(Update, tick_down_system::<TestTimer>);
+        let timed = app.world.spawn(TestTimer(TickTimer::new(10))).id();
+        app.update();
+
+        app.world.resource_mut::<SimulationTick>().0 += 4;
+        app.update();
+        assert_eq!(app.world.get::<TestTimer>(timed).unwrap().0.remaining_ticks, 6);
+    }
+
+    #[test]
+    fn from_seconds_rounds_up() {
+        assert_eq!(TickTimer::from_seconds(1.0).remaining_ticks, SIMULATION_RATE as u32);
+        assert_eq!(TickTimer::from_seconds(0.001).remaining_ticks, 1);
+        assert_eq!(TickTimer::from_seconds(0.0).remaining_ticks, 0);
+    }
+}
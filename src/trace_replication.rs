@@ -0,0 +1,118 @@
+use std::{any::type_name, fmt::Debug, marker::PhantomData};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::prelude::*;
+
+use crate::{Cli, MoveDirection, Player, Position, damage::Health, player_state::PlayerState};
+
+/// Logs every replicated component value a client receives, enabled with `--trace-replication`
+///
+/// Gives an exact timeline of what the server sent, with the value before and after and the tick it arrived on.
+pub struct TraceReplicationPlugin;
+
+impl Plugin for TraceReplicationPlugin
+{
+    fn build(&self, app: &mut App) {
+        trace_component::<Player>(app);
+        trace_component::<Position>(app);
+        trace_component::<MoveDirection>(app);
+        trace_component::<PlayerState>(app);
+        trace_component::<Health>(app);
+    }
+}
+
+fn trace_component<T: Component + Debug>(app: &mut App) {
+    app
+        .init_resource::<TracedValues<T>>()
+        .add_systems(PreUpdate,
+            trace_changes_system::<T>.after(ClientSet::Receive).run_if(trace_replication_enabled)
+        )
+        .add_systems(Last, snapshot_values_system::<T>.run_if(trace_replication_enabled));
+}
+
+fn trace_replication_enabled(cli: Res<Cli>) -> bool {
+    matches!(*cli, Cli::Client { trace_replication: true, .. })
+}
+
+/// Each entity's value of `T` as of the end of the last frame, formatted for the log
+#[derive(Resource)]
+struct TracedValues<T>
+{
+    values: HashMap<Entity, String>,
+    marker: PhantomData<T>,
+}
+
+impl<T> Default for TracedValues<T>
+{
+    fn default() -> Self {
+        Self { values: HashMap::default(), marker: PhantomData }
+    }
+}
+
+/// Runs right after replication is applied, anything that differs from last frame's snapshot came from the server
+fn trace_changes_system<T: Component + Debug>(
+    traced: Res<TracedValues<T>>,
+    changed: Query<(Entity, Ref<T>), Changed<T>>,
+    tick: Res<RepliconTick>,
+) {
+    let component = type_name::<T>().rsplit("::").next().unwrap_or_default();
+    for (entity, value) in &changed
+    {
+        let new = format!("{:?}", *value);
+        if let Some(record) = change_record(component, entity, tick.get(), traced.values.get(&entity), &new, value.is_added())
+        {
+            info!("Client: {record}");
+        }
+    }
+}
+
+/// The log line for a value going from `old` to `new`, none if it didn't actually change
+fn change_record(component: &str, entity: Entity, tick: u32, old: Option<&String>, new: &str, added: bool) -> Option<String> {
+    match old
+    {
+        Some(old) if old == new => None,
+        Some(old) => Some(format!("tick {tick}: {entity:?} {component} {old} -> {new}")),
+        None if added => Some(format!("tick {tick}: {entity:?} {component} inserted {new}")),
+        None => Some(format!("tick {tick}: {entity:?} {component} -> {new}")),
+    }
+}
+
+/// Records values at the end of the frame, so local changes (like prediction) aren't reported as replication
+fn snapshot_values_system<T: Component + Debug>(
+    mut traced: ResMut<TracedValues<T>>,
+    changed: Query<(Entity, &T), Changed<T>>,
+    mut removed: RemovedComponents<T>,
+) {
+    for entity in removed.read()
+    {
+        traced.values.remove(&entity);
+    }
+    for (entity, value) in &changed
+    {
+        traced.values.insert(entity, format!("{value:?}"));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn a_changed_value_is_recorded_with_its_old_and_new_value() {
+        let mut app = App::new();
+        app
+            .init_resource::<TracedValues<Position>>()
+            .add_systems(Last, snapshot_values_system::<Position>);
+        let entity = app.world.spawn(Position(Vec2::new(1.0, 2.0))).id();
+        app.update();
+
+        app.world.get_mut::<Position>(entity).unwrap().0 = Vec2::new(3.0, 4.0);
+        let new = format!("{:?}", app.world.get::<Position>(entity).unwrap());
+        let traced = app.world.resource::<TracedValues<Position>>();
+        let record = change_record("Position", entity, 7, traced.values.get(&entity), &new, false).unwrap();
+
+        assert_eq!(record, format!("tick 7: {entity:?} Position {:?} -> {:?}", Position(Vec2::new(1.0, 2.0)), Position(Vec2::new(3.0, 4.0))));
+        assert_eq!(change_record("Position", entity, 7, Some(&new), &new, false), None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::{error::Error, ffi::OsString, net::IpAddr};
+#[cfg(feature = "native")]
+use std::{net::{Ipv4Addr, SocketAddr, UdpSocket}, time::SystemTime};
+
+use bevy::prelude::*;
+use bevy_replicon::renet::transport::NetcodeServerTransport;
+#[cfg(feature = "native")]
+use bevy_replicon::renet::transport::{ClientAuthentication, NetcodeClientTransport, ServerAuthentication, ServerConfig};
+
+use crate::{ServerArgs, reconnect::ClientIdentity};
+#[cfg(feature = "native")]
+use crate::{join_queue::QUEUE_CAPACITY, reconnect};
+#[cfg(not(feature = "native"))]
+use crate::web_transport::WebClientTransport;
+
+// Everything that touches sockets, the system clock or the command line lives here, so only this file (and the
+// client half of `web_transport`) differs between the `native` build and the browser (`wasm`) build
+
+/// The `--web-port` a browser client dials when its page doesn't ask for another
+pub const DEFAULT_WEB_PORT: u16 = 5004;
+
+/// The process's arguments, program name first
+#[cfg(feature = "native")]
+pub fn command_line() -> Vec<OsString> {
+    std::env::args_os().collect()
+}
+
+/// A browser has no command line, so a client's is made from the page's query string: `?ip=127.0.0.1&port=5004`
+/// becomes `client --ip 127.0.0.1 --port 5004`, and a bare `?headless` becomes `--headless`
+///
+/// The port defaults to `DEFAULT_WEB_PORT` rather than the server's UDP port, browsers can only reach `--web-port`.
+#[cfg(not(feature = "native"))]
+pub fn command_line() -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["replicon_test_1".into(), "client".into()];
+    let query = web_sys::window().and_then(|window| window.location().search().ok()).unwrap_or_default();
+    let mut has_port = false;
+    for pair in query.trim_start_matches('?').split('&').filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        has_port |= key == "port";
+        args.push(format!("--{key}").into());
+        if !value.is_empty()
+        {
+            args.push(value.into());
+        }
+    }
+    if !has_port
+    {
+        args.extend(["--port".into(), DEFAULT_WEB_PORT.to_string().into()]);
+    }
+    args
+}
+
+/// Binds the server's UDP socket on localhost, returning the transport and the port it's bound to
+///
+/// Port 0 lets the OS pick a free one.
+#[cfg(feature = "native")]
+pub fn server_transport(
+    port: u16,
+    protocol_id: u64,
+    server_args: &ServerArgs,
+) -> Result<(NetcodeServerTransport, u16), Box<dyn Error>> {
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+    let socket = apply_socket_buffers(UdpSocket::bind((Ipv4Addr::LOCALHOST, port))?, server_args)?;
+    // Clients' connect tokens name the address they dial, so it has to be the bound one rather than port 0
+    let public_addr = socket.local_addr()?;
+    let server_config = ServerConfig {
+        current_time,
+        max_clients: server_args.max_players as usize + if server_args.queue { QUEUE_CAPACITY } else { 0 },
+        protocol_id,
+        public_addresses: vec![public_addr],
+        authentication: ServerAuthentication::Unsecure
+    };
+
+    Ok((NetcodeServerTransport::new(server_config, socket)?, public_addr.port()))
+}
+
+/// Connects to the server over UDP, returning the transport and the client id it authenticated with
+#[cfg(feature = "native")]
+pub fn client_transport(
+    ip: IpAddr,
+    port: u16,
+    identity: ClientIdentity,
+    protocol_id: u64,
+) -> Result<(NetcodeClientTransport, u64), Box<dyn Error>> {
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+    let client_id = current_time.as_millis() as u64;
+    let server_addr = SocketAddr::new(ip, port);
+    let socket = UdpSocket::bind((ip, 0))?;
+    let authentication = ClientAuthentication::Unsecure {
+        client_id,
+        protocol_id,
+        server_addr,
+        user_data: Some(reconnect::identity_to_user_data(identity)),
+    };
+
+    Ok((NetcodeClientTransport::new(current_time, authentication, socket)?, client_id))
+}
+
+/// Applies the requested socket buffer sizes, the default OS buffers can overflow and drop packets under many clients
+#[cfg(feature = "native")]
+fn apply_socket_buffers(
+    socket: UdpSocket,
+    server_args: &ServerArgs,
+) -> std::io::Result<UdpSocket> {
+    let socket = socket2::Socket::from(socket);
+    if let Some(size) = server_args.recv_buffer
+    {
+        socket.set_recv_buffer_size(size)?;
+    }
+    if let Some(size) = server_args.send_buffer
+    {
+        socket.set_send_buffer_size(size)?;
+    }
+
+    // The OS may clamp (or on Linux, double) the requested sizes, so log what was actually applied
+    let recv_size = socket.recv_buffer_size()?;
+    let send_size = socket.send_buffer_size()?;
+    info!("Server socket buffers: receive {recv_size} bytes, send {send_size} bytes");
+
+    Ok(socket.into())
+}
+
+/// A browser can't host, servers are always native
+#[cfg(not(feature = "native"))]
+pub fn server_transport(
+    _port: u16,
+    _protocol_id: u64,
+    _server_args: &ServerArgs,
+) -> Result<(NetcodeServerTransport, u16), Box<dyn Error>> {
+    Err("servers need the `native` feature".into())
+}
+
+/// Connects to the server's `--web-port` over WebSocket, browsers can't send UDP
+///
+/// The client id is the page's clock in milliseconds, like the native client's.
+#[cfg(not(feature = "native"))]
+pub fn client_transport(
+    ip: IpAddr,
+    port: u16,
+    identity: ClientIdentity,
+    protocol_id: u64,
+) -> Result<(WebClientTransport, u64), Box<dyn Error>> {
+    let client_id = js_sys::Date::now() as u64;
+    Ok((WebClientTransport::connect(ip, port, protocol_id, client_id, identity)?, client_id))
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests
+{
+    use clap::Parser;
+
+    use super::*;
+    use crate::Cli;
+
+    #[test]
+    fn requested_socket_buffers_are_applied() {
+        const REQUESTED: usize = 256 * 1024;
+        let mut cli = Cli::parse_from(["replicon_test_1", "server", "--recv-buffer", "262144", "--send-buffer", "262144"]);
+        let server_args = cli.server_args_mut().unwrap().clone();
+        assert_eq!((server_args.recv_buffer, server_args.send_buffer), (Some(REQUESTED), Some(REQUESTED)));
+
+        let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).unwrap();
+        let socket = socket2::Socket::from(apply_socket_buffers(socket, &server_args).unwrap());
+        // The OS decides what it actually gives, Linux doubles the request and caps it at `rmem_max`
+        assert!(socket.recv_buffer_size().unwrap() > 0);
+        assert!(socket.send_buffer_size().unwrap() > 0);
+        assert!(socket.local_addr().is_ok(), "the socket came back unusable");
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Instant;
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+
+use crate::Cli;
+
+/// Reports throughput while a headless server runs with `--turbo`
+///
+/// Turbo advances the simulation by one fixed step per frame as fast as the machine allows, so it is only
+/// meaningful for synthetic load, real clients connected to it see the world running faster than real time.
+pub struct TurboPlugin;
+
+impl Plugin for TurboPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.add_systems(Last, turbo_report_system.run_if(is_turbo));
+    }
+}
+
+pub fn is_turbo(cli: Res<Cli>) -> bool {
+    cli.is_turbo()
+}
+
+/// Logs once per wall-clock second, since simulated seconds pass much faster than that
+fn turbo_report_system(
+    mut last_report: Local<Option<(Instant, u32)>>,
+    replicated: Query<(), With<Replication>>,
+    server: Option<Res<RenetServer>>,
+    tick: Res<RepliconTick>,
+) {
+    let now = Instant::now();
+    let Some((reported_at, reported_tick)) = *last_report else {
+        *last_report = Some((now, tick.get()));
+        return;
+    };
+
+    let elapsed = now.duration_since(reported_at).as_secs_f64();
+    if elapsed < 1.0
+    {
+        return;
+    }
+
+    let ticks_per_sec = tick.get().wrapping_sub(reported_tick) as f64 / elapsed;
+    let entities = replicated.iter().count();
+    // Renet measures over simulated time, so this is bytes per simulated second
+    let bytes_per_sec: f64 = server.map_or(0.0, |server| server.clients_id().into_iter()
+        .filter_map(|client_id| server.network_info(client_id).ok())
+        .map(|info| info.bytes_sent_per_second)
+        .sum());
+
+    info!("Server: turbo {ticks_per_sec:.0} ticks/s, {entities} replicated entities, {:.0} entities/s, {bytes_per_sec:.0} bytes/s sent",
+        entities as f64 * ticks_per_sec);
+    *last_report = Some((now, tick.get()));
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{sync::{Arc, Mutex}, time::Duration};
+
+    use bevy::app::AppExit;
+    use clap::Parser;
+
+    use super::*;
+    use crate::{SIMULATION_RATE, build_app, tick_timer::SimulationTick};
+
+    /// Simulated seconds the turbo server is run for
+    const SIMULATED_SECONDS: u32 = 10;
+
+    /// Frames run and simulation steps taken, read after `run` has taken the app
+    #[derive(Resource, Clone, Default)]
+    struct Progress(Arc<Mutex<(u32, u32)>>);
+
+    fn stop_after_system(
+        mut exit: EventWriter<AppExit>,
+        progress: Res<Progress>,
+        tick: Res<SimulationTick>,
+    ) {
+        let mut progress = progress.0.lock().unwrap();
+        *progress = (progress.0 + 1, tick.0);
+        if tick.0 >= SIMULATED_SECONDS * SIMULATION_RATE as u32
+        {
+            exit.send(AppExit);
+        }
+    }
+
+    #[test]
+    fn turbo_runs_its_steps_without_waiting_for_the_clock() {
+        let cli = Cli::parse_from(["replicon_test_1", "server", "--headless", "--turbo", "--port", "0"]);
+        let progress = Progress::default();
+        let mut app = build_app(cli);
+        app.insert_resource(progress.clone()).add_systems(Last, stop_after_system);
+
+        let started = Instant::now();
+        app.run();
+        let elapsed = started.elapsed();
+
+        // One fixed step a frame however long the frame took, bar the first frame, which has no time step
+        let (frames, steps) = *progress.0.lock().unwrap();
+        assert_eq!(steps, SIMULATED_SECONDS * SIMULATION_RATE as u32);
+        assert!(frames - steps <= 1, "{steps} steps took {frames} frames");
+        assert!(elapsed < Duration::from_secs(SIMULATED_SECONDS as u64 / 2), "{SIMULATED_SECONDS} simulated seconds took {elapsed:?}");
+    }
+}
@@ -0,0 +1,257 @@
+use std::f32::consts::PI;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::{ClientId, ServerEvent}};
+use serde::{Serialize, Deserialize};
+
+use crate::{has_local_player, input_map::{Action, InputBindings}, projectile::ProjectileMotion};
+
+/// Full-auto shots the server accepts back to back, so shots the network bunched together aren't dropped
+const BURST_SHOTS: f32 = 2.0;
+/// Fraction of a weapon's cooldown the server insists on, the rest is slack for shots arriving unevenly
+const COOLDOWN_SLACK: f32 = 0.8;
+const SHOTGUN_PELLETS: usize = 5;
+/// Radians between neighbouring shotgun pellets
+const SHOTGUN_SPREAD: f32 = PI / 24.0;
+/// Lobbed projectiles fly at this fraction of `projectile_speed`
+const LOB_SPEED_FACTOR: f32 = 0.5;
+
+/// Which weapons players have and how often they may fire them, predicted by clients and enforced by the server
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ShotBudgets>()
+            .init_resource::<SelectedWeapon>()
+            .replicate::<ProjectileKind>()
+            .add_systems(Update, forget_disconnected_budgets_system.run_if(resource_exists::<RenetServer>()))
+            .add_systems(Update, select_weapon_system.run_if(has_local_player));
+    }
+}
+
+/// Picked with the number keys, sent with every shot
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum WeaponKind
+{
+    /// A single fast projectile
+    #[default]
+    Pistol,
+    /// `SHOTGUN_PELLETS` weaker projectiles in a fan
+    Shotgun,
+    /// A slow projectile that arcs over players and only hits where it lands
+    Lob,
+}
+
+impl WeaponKind
+{
+    /// Entities a `Shoot` with this weapon must carry, one per projectile
+    pub fn projectile_count(self) -> usize {
+        match self
+        {
+            WeaponKind::Pistol | WeaponKind::Lob => 1,
+            WeaponKind::Shotgun => SHOTGUN_PELLETS,
+        }
+    }
+
+    /// Seconds before the same weapon can fire again, on top of the fire rate
+    pub fn cooldown(self) -> f32 {
+        match self
+        {
+            WeaponKind::Pistol => 0.0,
+            WeaponKind::Shotgun => 0.8,
+            WeaponKind::Lob => 1.0,
+        }
+    }
+
+    /// Per projectile
+    pub fn damage(self) -> u32 {
+        match self
+        {
+            WeaponKind::Pistol => 10,
+            WeaponKind::Shotgun => 4,
+            WeaponKind::Lob => 25,
+        }
+    }
+
+    /// Every projectile's launch, in the same order as the entities in `Shoot` so both ends pair them up the same way
+    pub fn launches(self, owner: u64, origin: Vec2, direction: Vec2, speed: f32) -> Vec<ProjectileMotion> {
+        let speed = if self == WeaponKind::Lob { speed * LOB_SPEED_FACTOR } else { speed };
+        let aim = ProjectileMotion::new(owner, origin, direction, speed).velocity.normalize();
+
+        let count = self.projectile_count();
+        (0..count)
+            .map(|index| {
+                let angle = (index as f32 - (count - 1) as f32 / 2.0) * SHOTGUN_SPREAD;
+                ProjectileMotion::new(owner, origin, Vec2::from_angle(angle).rotate(aim), speed)
+            })
+            .collect()
+    }
+}
+
+/// Which weapon fired a projectile, replicated so clients draw and move each kind its own way
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ProjectileKind(pub WeaponKind);
+
+/// The local player's weapon
+#[derive(Resource, Default)]
+pub struct SelectedWeapon(pub WeaponKind);
+
+/// Whether holding the fire key keeps firing
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum FireMode
+{
+    /// One shot per press
+    #[default]
+    SemiAuto,
+    /// Shots per second while held
+    FullAuto { rate: f32 },
+}
+
+impl FireMode
+{
+    /// `GameTuning::fire_rate`, where 0 means one shot per press
+    pub fn from_rate(rate: f32) -> Self {
+        if rate > 0.0 { FireMode::FullAuto { rate } } else { FireMode::SemiAuto }
+    }
+
+    /// Seconds between shots, none for semi-auto beyond how fast the key can be pressed
+    pub fn cooldown(&self) -> f32 {
+        match *self
+        {
+            FireMode::SemiAuto => 0.0,
+            FireMode::FullAuto { rate } => 1.0 / rate,
+        }
+    }
+}
+
+/// Server side, each client's full-auto shots left and the elapsed seconds they were last topped up,
+/// and when it last fired each weapon
+///
+/// A bucket refilling at the fire rate rather than a fixed gap between arrivals, since shots sent evenly
+/// don't arrive evenly.
+#[derive(Resource, Default)]
+pub struct ShotBudgets
+{
+    shots: HashMap<ClientId, (f32, f32)>,
+    last_fired: HashMap<(ClientId, WeaponKind), f32>,
+}
+
+impl ShotBudgets
+{
+    /// Spends one shot if the weapon has cooled down and the client has one left, semi-auto shots only wait for the cooldown
+    pub fn try_fire(&mut self, client_id: ClientId, weapon: WeaponKind, fire_mode: FireMode, now: f32) -> bool {
+        let cooled_down = self.last_fired.get(&(client_id, weapon)).map_or(true, |last| now - last >= weapon.cooldown() * COOLDOWN_SLACK);
+        if !cooled_down
+        {
+            return false;
+        }
+
+        if let FireMode::FullAuto { rate } = fire_mode
+        {
+            let (shots, topped_up_at) = self.shots.entry(client_id).or_insert((BURST_SHOTS, now));
+            *shots = (*shots + (now - *topped_up_at) * rate).min(BURST_SHOTS);
+            *topped_up_at = now;
+            if *shots < 1.0
+            {
+                return false;
+            }
+            *shots -= 1.0;
+        }
+
+        self.last_fired.insert((client_id, weapon), now);
+        true
+    }
+}
+
+/// The weapon keys, 1 to 3 by default, pick the pistol, shotgun and lob
+fn select_weapon_system(
+    mut selected: ResMut<SelectedWeapon>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+) {
+    let weapon = if bindings.just_pressed(Action::Pistol, &keys)
+    {
+        WeaponKind::Pistol
+    }
+    else if bindings.just_pressed(Action::Shotgun, &keys)
+    {
+        WeaponKind::Shotgun
+    }
+    else if bindings.just_pressed(Action::Lob, &keys)
+    {
+        WeaponKind::Lob
+    }
+    else
+    {
+        return;
+    };
+
+    if selected.0 != weapon
+    {
+        info!("Client: Switched to {weapon:?}");
+        selected.0 = weapon;
+    }
+}
+
+fn forget_disconnected_budgets_system(
+    mut server_events: EventReader<ServerEvent>,
+    mut budgets: ResMut<ShotBudgets>,
+) {
+    for event in server_events.read()
+    {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = event
+        {
+            budgets.shots.remove(client_id);
+            budgets.last_fired.retain(|(fired_by, _), _| fired_by != client_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use bevy::input::{ButtonState, keyboard::KeyboardInput};
+
+    use super::*;
+    use crate::{Player, bench::{step_session, tests::connected_session}, settings::{GameSettings, GameTuning}, stats::PlayerStats};
+
+    /// Shots the server accepted from player 1 holding the fire key for `frames` frames at `fire_rate`
+    fn shots_while_held(fire_rate: f32, frames: usize) -> u32 {
+        let (mut server_app, mut client_apps) = connected_session(1);
+        let tuning = GameTuning { fire_rate, ..*server_app.world.resource::<GameTuning>() };
+        server_app.world.insert_resource(tuning);
+        tuning.mirror_into(&mut server_app.world.query::<&mut GameSettings>().single_mut(&mut server_app.world));
+        // Long enough for the client to have the replicated fire mode
+        for _ in 0..30
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        let key = client_apps[0].world.resource::<InputBindings>().get(Action::Shoot).key;
+        client_apps[0].world.send_event(KeyboardInput { scan_code: 0, key_code: Some(key), state: ButtonState::Pressed, window: Entity::PLACEHOLDER });
+        for _ in 0..frames
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+        client_apps[0].world.send_event(KeyboardInput { scan_code: 0, key_code: Some(key), state: ButtonState::Released, window: Entity::PLACEHOLDER });
+        // Shots still on their way
+        for _ in 0..30
+        {
+            step_session(&mut server_app, &mut client_apps);
+        }
+
+        server_app.world.query::<(&Player, &PlayerStats)>().iter(&server_app.world)
+            .find_map(|(player, stats)| (player.0 == 1).then_some(stats.shots_fired))
+            .unwrap()
+    }
+
+    #[test]
+    fn held_trigger_fires_at_the_fire_rate_only_in_full_auto() {
+        // Two seconds at 10 shots a second, the first going out as soon as the key is down
+        let full_auto = shots_while_held(10.0, 120);
+        assert!((20..=21).contains(&full_auto), "{full_auto} shots in two seconds at 10 a second");
+        assert_eq!(shots_while_held(0.0, 120), 1);
+    }
+}
@@ -0,0 +1,369 @@
+use std::{error::Error, sync::{Mutex, mpsc::{Receiver, Sender, TryRecvError}}};
+#[cfg(feature = "native")]
+use std::{io::ErrorKind, net::{Ipv4Addr, TcpListener, TcpStream}, sync::mpsc, thread, time::Duration};
+#[cfg(not(feature = "native"))]
+use std::{cell::{Cell, RefCell}, collections::VecDeque, net::{IpAddr, SocketAddr}, rc::Rc};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{prelude::*, renet::ClientId, server::ServerSet, RenetReceive, RenetSend};
+#[cfg(not(feature = "native"))]
+use wasm_bindgen::{JsCast, JsValue, closure::Closure};
+#[cfg(not(feature = "native"))]
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+use crate::reconnect::ClientIdentity;
+
+/// Bytes of the hello a browser client opens with: protocol id, client id and identity, each a little-endian `u64`
+const HELLO_BYTES: usize = 24;
+
+/// How long a browser client's socket thread waits for a packet before checking for ones to send it
+#[cfg(feature = "native")]
+const SOCKET_POLL: Duration = Duration::from_millis(2);
+
+/// Renet over WebSocket, for browser clients that can't open UDP sockets
+///
+/// A server started with `--web-port` listens for them alongside its netcode clients, a `wasm` build connects with
+/// it. Packets are renet's, one per binary message, with no netcode around them. WebSocket already delivers them in
+/// order over TCP, so there's nothing to gain from renet's own encryption or connection handshake, only the
+/// `HELLO_BYTES` hello, which lets the server turn away a client built with a different protocol id.
+pub struct WebTransportPlugin;
+
+impl Plugin for WebTransportPlugin
+{
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(PreUpdate, web_server_receive_system.in_set(RenetReceive).run_if(resource_exists::<WebServerTransport>()))
+            // Before netcode's send, which would otherwise take these clients' packets and fail to encrypt them
+            .add_systems(PostUpdate, web_server_send_system.after(ServerSet::Send).before(RenetSend).run_if(resource_exists::<WebServerTransport>()));
+
+        #[cfg(not(feature = "native"))]
+        app
+            .add_systems(PreUpdate, web_client_receive_system.in_set(RenetReceive).run_if(resource_exists::<WebClientTransport>()))
+            .add_systems(PostUpdate, web_client_send_system.in_set(RenetSend).run_if(resource_exists::<WebClientTransport>()));
+    }
+}
+
+/// One browser client's socket, as seen from the server
+struct WebLink
+{
+    client_id: ClientId,
+    identity: ClientIdentity,
+    outgoing: Sender<Vec<u8>>,
+    incoming: Mutex<Receiver<Vec<u8>>>,
+}
+
+/// Server side, the browser clients connected over `--web-port`, each served by its own socket thread
+#[derive(Resource)]
+pub struct WebServerTransport
+{
+    /// Clients whose hello checked out, still to be added to the `RenetServer`
+    accepted: Mutex<Receiver<WebLink>>,
+    links: HashMap<ClientId, WebLink>,
+}
+
+impl WebServerTransport
+{
+    /// Listens for browser clients on `port` on localhost, like the UDP socket, turning away any whose hello doesn't
+    /// carry `protocol_id`
+    #[cfg(feature = "native")]
+    pub fn listen(port: u16, protocol_id: u64) -> Result<Self, Box<dyn Error>> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        info!("Server: Listening for browser clients on port {}", listener.local_addr()?.port());
+
+        let (accept, accepted) = mpsc::channel();
+        thread::Builder::new().name("web listener".to_string()).spawn(move || {
+            for stream in listener.incoming().flatten()
+            {
+                let accept = accept.clone();
+                let spawned = thread::Builder::new().name("web client".to_string()).spawn(move || {
+                    if let Err(e) = serve_socket(stream, protocol_id, accept)
+                    {
+                        warn!("Server: Browser client dropped: {e}");
+                    }
+                });
+                if let Err(e) = spawned
+                {
+                    warn!("Server: Couldn't start a thread for a browser client: {e}");
+                }
+            }
+        })?;
+
+        Ok(Self { accepted: Mutex::new(accepted), links: HashMap::default() })
+    }
+
+    /// Browsers can't listen for connections, servers are always native
+    #[cfg(not(feature = "native"))]
+    pub fn listen(_port: u16, _protocol_id: u64) -> Result<Self, Box<dyn Error>> {
+        Err("--web-port needs the `native` feature".into())
+    }
+
+    /// The identity a browser client sent with its hello, the netcode user data has it for everyone else
+    pub fn identity(&self, client_id: ClientId) -> Option<ClientIdentity> {
+        self.links.get(&client_id).map(|link| link.identity)
+    }
+}
+
+#[cfg(feature = "native")]
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[at..at + 8]);
+    u64::from_le_bytes(word)
+}
+
+/// Serves one browser client until either side hangs up: its hello, then packets both ways
+#[cfg(feature = "native")]
+fn serve_socket(
+    stream: TcpStream,
+    protocol_id: u64,
+    accept: Sender<WebLink>,
+) -> Result<(), Box<dyn Error>> {
+    use tungstenite::{Message, error::Error as SocketError};
+
+    let mut socket = tungstenite::accept(stream).map_err(|e| format!("WebSocket handshake failed: {e}"))?;
+    let hello = match socket.read()?
+    {
+        Message::Binary(hello) if hello.len() == HELLO_BYTES => hello,
+        _ => return Err("it didn't start with a hello".into()),
+    };
+    if read_u64(&hello, 0) != protocol_id
+    {
+        let _ = socket.close(None);
+        return Err(format!("protocol id {:x} isn't the server's {protocol_id:x}, it's a different build", read_u64(&hello, 0)).into());
+    }
+
+    let (to_server, incoming) = mpsc::channel();
+    let (outgoing, from_server) = mpsc::channel();
+    let link = WebLink {
+        client_id: ClientId::from_raw(read_u64(&hello, 8)),
+        identity: ClientIdentity(read_u64(&hello, 16)),
+        outgoing,
+        incoming: Mutex::new(incoming),
+    };
+    accept.send(link).map_err(|_| "the server has stopped")?;
+
+    socket.get_mut().set_read_timeout(Some(SOCKET_POLL))?;
+    loop
+    {
+        match socket.read()
+        {
+            Ok(Message::Binary(packet)) =>
+            {
+                if to_server.send(packet).is_err()
+                {
+                    break;
+                }
+            }
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(SocketError::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        loop
+        {
+            match from_server.try_recv()
+            {
+                Ok(packet) => socket.send(Message::Binary(packet))?,
+                Err(TryRecvError::Empty) => break,
+                // The server dropped the link, it disconnected this client or is shutting down
+                Err(TryRecvError::Disconnected) =>
+                {
+                    let _ = socket.close(None);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let _ = socket.close(None);
+    Ok(())
+}
+
+fn web_server_receive_system(
+    mut transport: ResMut<WebServerTransport>,
+    mut server: ResMut<RenetServer>,
+) {
+    let transport = &mut *transport;
+    if let Ok(accepted) = transport.accepted.get_mut()
+    {
+        for link in accepted.try_iter()
+        {
+            // Dropping the link closes its socket
+            if server.clients_id().contains(&link.client_id)
+            {
+                warn!("Server: Turned away a browser client claiming id '{}', which is already connected", link.client_id);
+                continue;
+            }
+
+            info!("Server: Browser client '{}' connected", link.client_id);
+            server.add_connection(link.client_id);
+            transport.links.insert(link.client_id, link);
+        }
+    }
+
+    transport.links.retain(|client_id, link| {
+        let Ok(incoming) = link.incoming.get_mut() else { return false; };
+        loop
+        {
+            match incoming.try_recv()
+            {
+                Ok(packet) =>
+                {
+                    if server.process_packet_from(&packet, *client_id).is_err()
+                    {
+                        warn!("Server: Dropped a packet from unknown browser client '{client_id}'");
+                    }
+                }
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) =>
+                {
+                    info!("Server: Browser client '{client_id}' closed its socket");
+                    server.disconnect(*client_id);
+                    return false;
+                }
+            }
+        }
+    });
+}
+
+pub fn web_server_send_system(
+    mut transport: ResMut<WebServerTransport>,
+    mut server: ResMut<RenetServer>,
+) {
+    transport.links.retain(|client_id, link| {
+        let Ok(packets) = server.get_packets_to_send(*client_id) else { return false; };
+        packets.into_iter().all(|packet| link.outgoing.send(packet).is_ok())
+    });
+}
+
+/// Client side, a browser's WebSocket to the server's `--web-port`
+///
+/// The socket's callbacks only queue what they're given, the systems hand it to renet each frame.
+#[cfg(not(feature = "native"))]
+#[derive(Resource)]
+pub struct WebClientTransport
+{
+    socket: WebSocket,
+    opened: Rc<Cell<bool>>,
+    closed: Rc<Cell<bool>>,
+    received: Rc<RefCell<VecDeque<Vec<u8>>>>,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+}
+
+// SAFETY: wasm32 without threads runs everything on one thread, so the socket and its callbacks never leave it
+#[cfg(not(feature = "native"))]
+unsafe impl Send for WebClientTransport {}
+#[cfg(not(feature = "native"))]
+unsafe impl Sync for WebClientTransport {}
+
+#[cfg(not(feature = "native"))]
+impl WebClientTransport
+{
+    /// Opens the socket, the hello goes out as soon as it's open
+    pub fn connect(
+        ip: IpAddr,
+        port: u16,
+        protocol_id: u64,
+        client_id: u64,
+        identity: ClientIdentity,
+    ) -> Result<Self, Box<dyn Error>> {
+        let socket = WebSocket::new(&format!("ws://{}", SocketAddr::new(ip, port))).map_err(|e| format!("couldn't open a WebSocket: {e:?}"))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let hello: Vec<u8> = [protocol_id, client_id, identity.0].into_iter().flat_map(u64::to_le_bytes).collect();
+        debug_assert_eq!(hello.len(), HELLO_BYTES);
+        let opened = Rc::new(Cell::new(false));
+        let closed = Rc::new(Cell::new(false));
+        let received = Rc::new(RefCell::new(VecDeque::new()));
+
+        let on_open = {
+            let (socket, opened) = (socket.clone(), opened.clone());
+            Closure::<dyn FnMut(JsValue)>::new(move |_| {
+                if socket.send_with_u8_array(&hello).is_ok()
+                {
+                    opened.set(true);
+                }
+            })
+        };
+        let on_message = {
+            let received = received.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                if let Ok(buffer) = event.data().dyn_into::<js_sys::ArrayBuffer>()
+                {
+                    received.borrow_mut().push_back(js_sys::Uint8Array::new(&buffer).to_vec());
+                }
+            })
+        };
+        let on_close = {
+            let closed = closed.clone();
+            Closure::<dyn FnMut(JsValue)>::new(move |_| closed.set(true))
+        };
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(Self { socket, opened, closed, received, _on_open: on_open, _on_message: on_message, _on_close: on_close })
+    }
+}
+
+#[cfg(not(feature = "native"))]
+impl Drop for WebClientTransport
+{
+    /// A reconnect replaces the transport, the old socket is closed with it instead of lingering
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        self.socket.set_onerror(None);
+        let _ = self.socket.close();
+    }
+}
+
+#[cfg(not(feature = "native"))]
+fn web_client_receive_system(
+    transport: Res<WebClientTransport>,
+    mut client: ResMut<RenetClient>,
+) {
+    if transport.closed.get()
+    {
+        if !client.is_disconnected()
+        {
+            warn!("Client: The server closed the WebSocket");
+            client.disconnect();
+        }
+        return;
+    }
+    if !transport.opened.get()
+    {
+        return;
+    }
+
+    client.set_connected();
+    for packet in transport.received.borrow_mut().drain(..)
+    {
+        client.process_packet(&packet);
+    }
+}
+
+#[cfg(not(feature = "native"))]
+fn web_client_send_system(
+    transport: Res<WebClientTransport>,
+    mut client: ResMut<RenetClient>,
+) {
+    if !transport.opened.get() || transport.closed.get()
+    {
+        return;
+    }
+
+    for packet in client.get_packets_to_send()
+    {
+        if transport.socket.send_with_u8_array(&packet).is_err()
+        {
+            client.disconnect();
+            return;
+        }
+    }
+}